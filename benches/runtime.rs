@@ -0,0 +1,113 @@
+//! 运行时热路径基准测试
+//!
+//! 运行：`cargo bench`，可叠加 `--features index-dispatch,cow-state` 对比优化效果
+
+use std::hint::black_box;
+use std::sync::Arc;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use state_zen::{
+    EventDef, RuntimeStateMachine, State, StateInRange, StateMachineBlueprint, StateObserver, Transfer,
+    Transition,
+};
+
+const COUNTER: u64 = 1;
+const EVENTS: u64 = 100;
+
+fn counter(state: &State) -> u64 {
+    state
+        .get(&COUNTER)
+        .and_then(|v| v.downcast_ref::<u64>())
+        .copied()
+        .unwrap_or(0)
+}
+
+fn increment() -> Transfer {
+    Transfer::new(|s| {
+        let mut next = s.clone();
+        next.insert(COUNTER, Arc::new(counter(s) + 1));
+        next
+    })
+}
+
+fn initial_state() -> State {
+    let mut state = State::new();
+    state.insert(COUNTER, Arc::new(0u64));
+    state
+}
+
+/// `n` 个转换均匀分布在 `EVENTS` 个事件上，每个转换都可触发
+fn blueprint_with_transitions(n: u64) -> StateMachineBlueprint {
+    let mut blueprint = StateMachineBlueprint::new();
+    for event_id in 0..EVENTS {
+        blueprint
+            .add_event(EventDef::new(event_id))
+            .unwrap();
+    }
+    for id in 0..n {
+        blueprint.add_transition(Transition {
+            priority: (id % 7) as i32,
+            ..Transition::new(id, id % EVENTS, StateInRange::new(|_| true), increment())
+        }).unwrap();
+    }
+    blueprint
+}
+
+fn bench_dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    for n in [10u64, 1_000, 100_000] {
+        let blueprint = blueprint_with_transitions(n);
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state());
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            let mut event = 0;
+            b.iter(|| {
+                runtime.handle_event(black_box(event % EVENTS), None);
+                event += 1;
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_observers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transform_observers");
+    for n in [10u64, 1_000, 10_000] {
+        let mut blueprint = blueprint_with_transitions(1);
+        for id in 0..n {
+            blueprint.add_observer(StateObserver {
+                on_enter: Some(Arc::new(|s| {
+                    black_box(s);
+                })),
+                ..StateObserver::new(
+                    id,
+                    StateInRange::new(move |s| counter(s).is_multiple_of(id + 2)),
+                )
+            }).unwrap();
+        }
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state());
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            b.iter(|| runtime.handle_event(0, None));
+        });
+    }
+    group.finish();
+}
+
+fn bench_pool(c: &mut Criterion) {
+    let mut group = c.benchmark_group("machine_pool");
+    for n in [100usize, 10_000] {
+        let blueprint = blueprint_with_transitions(10);
+        let mut pool: Vec<_> = (0..n)
+            .map(|_| RuntimeStateMachine::new(blueprint.clone(), initial_state()))
+            .collect();
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            b.iter(|| {
+                for runtime in &mut pool {
+                    runtime.handle_event(0, None);
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_dispatch, bench_observers, bench_pool);
+criterion_main!(benches);
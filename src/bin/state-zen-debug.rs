@@ -0,0 +1,355 @@
+//! state-zen 交互式调试器
+//!
+//! 用法：`state-zen-debug <blueprint.json>`，输入 `help` 查看命令。
+//! 蓝图中的 `*_call` 可引用 `Registry::with_builtins` 提供的内置工厂。
+//! 用 `save` 导出的 `.szsession` 会话文件可以用 `state-zen-debug <file.szsession>` 离线查看
+
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use state_zen::core::History;
+use state_zen::loader::Registry;
+use state_zen::loader::json::{self, LoadedBlueprint};
+use state_zen::loader::session::{Session, SESSION_EXTENSION};
+use state_zen::core::BreakAction;
+use state_zen::{EventId, RuntimeStateMachine, StateInRange, TransitionId};
+
+const HELP: &str = "\
+命令：
+  state                    打印当前状态
+  events                   列出事件
+  send <事件名|ID>         分发事件
+  continue                 执行被断点拦下的转换
+  skip                     丢弃被断点拦下的转换
+  back                     回退一步
+  history                  列出历史
+  break t <转换ID>         在转换上设置断点
+  break r <区域JSON>       在进入区域时中断，如 break r {\"action\": \"Walk\"}
+  breaks                   列出断点
+  factories                列出可用的工厂
+  save <路径>              把蓝图与历史导出为 .szsession 会话文件
+  delete <序号>            删除断点
+  quit                     退出";
+
+const SESSION_HELP: &str = "\
+命令：
+  history                  列出历史
+  show <序号>              打印一次提交前后的状态
+  quit                     退出";
+
+enum Breakpoint {
+    Transition(TransitionId),
+    Region { source: String, region: StateInRange },
+}
+
+struct Debugger {
+    loaded: LoadedBlueprint,
+    runtime: RuntimeStateMachine,
+    history: Arc<History>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    fn new(loaded: LoadedBlueprint) -> Result<Self, String> {
+        let mut runtime = loaded.instantiate().map_err(|e| e.to_string())?;
+        let history = History::new(None);
+        runtime.add_tracer(history.clone());
+        Ok(Self {
+            loaded,
+            runtime,
+            history,
+            breakpoints: Vec::new(),
+        })
+    }
+
+    fn print_state(&self) {
+        println!("{}", self.loaded.registry.format_state(&self.runtime.current_state));
+    }
+
+    fn event_label(&self, id: EventId) -> String {
+        match self.loaded.event_name(id) {
+            Some(name) => format!("{name}({id})"),
+            None => id.to_string(),
+        }
+    }
+
+    fn send(&mut self, arg: &str) {
+        if self.runtime.pending_transition().is_some() {
+            println!("有被断点拦下的转换，先 continue 或 skip");
+            return;
+        }
+        let Some(event_id) = self.loaded.event_id(arg).or_else(|| arg.parse().ok()) else {
+            println!("未知事件 `{arg}`");
+            return;
+        };
+
+        // 经 handle_event 分发，中间件、负载变换与暂停照常生效
+        let committed = self.history.entries().len();
+        self.runtime.handle_event(event_id, None);
+        if self.history.entries().len() == committed && self.runtime.pending_transition().is_none() {
+            println!("{} 被忽略", self.event_label(event_id));
+            return;
+        }
+        self.report(committed);
+    }
+
+    /// 继续执行被断点拦下的转换（`skip` 时丢弃），随后处理期间缓冲的事件
+    fn resume(&mut self, skip: bool) {
+        let Some(transition) = self.runtime.pending_transition().map(|t| t.id) else {
+            println!("没有待执行的转换");
+            return;
+        };
+        if skip {
+            self.runtime.discard_pending();
+            println!("跳过转换 {transition}");
+        }
+        let committed = self.history.entries().len();
+        self.runtime.resume();
+        self.report(committed);
+    }
+
+    /// 打印第 `committed` 条之后新提交的转换，以及拦下下一个转换的断点
+    fn report(&self, committed: usize) {
+        let entries = self.history.entries();
+        for entry in entries.iter().skip(committed) {
+            let transition = entry.transition.map_or("-".to_string(), |t| t.to_string());
+            println!("执行转换 {transition}");
+        }
+        if entries.len() > committed {
+            self.print_state();
+        }
+
+        let Some(pending) = self.runtime.pending_transition() else {
+            return;
+        };
+        let state = &self.runtime.current_state;
+        let next = pending.transfer.apply(state);
+        for breakpoint in &self.breakpoints {
+            match breakpoint {
+                Breakpoint::Transition(id) if *id == pending.id => {
+                    println!("断点：将执行转换 {id}（continue / skip）");
+                }
+                Breakpoint::Region { source, region } if !region.contains(state) && region.contains(&next) => {
+                    println!("断点：转换 {} 将进入区域 {source}（continue / skip）", pending.id);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// 把断点同步为运行时的转换断点：转换断点直接拦下，区域断点按转换函数的结果预判，
+    /// 在进入区域的转换提交之前拦下
+    fn install_breakpoints(&mut self) {
+        let regions: Vec<StateInRange> = self
+            .breakpoints
+            .iter()
+            .filter_map(|b| match b {
+                Breakpoint::Region { region, .. } => Some(region.clone()),
+                Breakpoint::Transition(_) => None,
+            })
+            .collect();
+        let ids: Vec<TransitionId> = self.runtime.blueprint.transitions().map(|t| t.id).collect();
+        for id in ids {
+            self.runtime.clear_breakpoint(id);
+            let stop = self.breakpoints.iter().any(|b| matches!(b, Breakpoint::Transition(t) if *t == id));
+            if !stop && regions.is_empty() {
+                continue;
+            }
+            let regions = regions.clone();
+            self.runtime.set_breakpoint(
+                id,
+                Box::new(move |context| {
+                    let enters = || {
+                        let next = context.transition.transfer.apply(context.state);
+                        regions.iter().any(|r| !r.contains(context.state) && r.contains(&next))
+                    };
+                    if stop || enters() { BreakAction::Pause } else { BreakAction::Continue }
+                }),
+            );
+        }
+    }
+
+    fn back(&mut self) {
+        match self.history.step_back(&mut self.runtime) {
+            Some(entry) => {
+                let transition = entry.transition.map_or("-".to_string(), |t| t.to_string());
+                println!("撤销转换 {transition}");
+                self.print_state();
+            }
+            None => println!("没有可回退的历史"),
+        }
+    }
+
+    fn print_history(&self) {
+        for (i, entry) in self.history.entries().iter().enumerate() {
+            let event = entry.event_id.map_or("tick".to_string(), |e| self.event_label(e));
+            let transition = entry.transition.map_or("-".to_string(), |t| t.to_string());
+            println!("#{i} {event} -> {transition}: {}", self.loaded.registry.format_state(&entry.after));
+        }
+    }
+
+    fn add_breakpoint(&mut self, arg: &str) {
+        let (kind, rest) = arg.split_once(' ').unwrap_or((arg, ""));
+        let rest = rest.trim();
+        match kind {
+            "t" => match rest.parse() {
+                Ok(id) => self.breakpoints.push(Breakpoint::Transition(id)),
+                Err(_) => println!("无效的转换ID `{rest}`"),
+            },
+            "r" => match self.loaded.parse_region(rest) {
+                Ok(region) => self.breakpoints.push(Breakpoint::Region {
+                    source: rest.to_string(),
+                    region,
+                }),
+                Err(e) => println!("{e}"),
+            },
+            _ => println!("用法：break t <转换ID> | break r <区域JSON>"),
+        }
+        self.install_breakpoints();
+    }
+
+    fn save(&self, path: &str) {
+        let session = Session::capture(&self.runtime.blueprint, &self.loaded.registry, &self.history)
+            .with_event_names(self.loaded.events.iter().cloned());
+        match session.save(path) {
+            Ok(()) => println!("已导出 {} 条历史到 {path}", session.entries.len()),
+            Err(e) => println!("{e}"),
+        }
+    }
+
+    fn print_breakpoints(&self) {
+        for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+            match breakpoint {
+                Breakpoint::Transition(id) => println!("#{i} 转换 {id}"),
+                Breakpoint::Region { source, .. } => println!("#{i} 区域 {source}"),
+            }
+        }
+    }
+
+    /// 执行一条命令，返回 `false` 表示退出
+    fn execute(&mut self, line: &str) -> bool {
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        match command {
+            "" => {}
+            "help" | "h" => println!("{HELP}"),
+            "state" | "s" => self.print_state(),
+            "events" => {
+                for (id, name) in &self.loaded.events {
+                    println!("{id}\t{name}");
+                }
+            }
+            "send" => self.send(arg),
+            "continue" | "c" => self.resume(false),
+            "skip" => self.resume(true),
+            "back" | "b" => self.back(),
+            "history" => self.print_history(),
+            "break" => self.add_breakpoint(arg),
+            "breaks" => self.print_breakpoints(),
+            "factories" => {
+                let factories = Registry::with_builtins();
+                println!("守卫：{}", factories.guard_names().join(", "));
+                println!("转换：{}", factories.transfer_names().join(", "));
+            }
+            "save" if !arg.is_empty() => self.save(arg),
+            "save" => println!("用法：save <路径>"),
+            "delete" => match arg.parse::<usize>() {
+                Ok(i) if i < self.breakpoints.len() => {
+                    self.breakpoints.remove(i);
+                    self.install_breakpoints();
+                }
+                _ => println!("无效的断点序号 `{arg}`"),
+            },
+            "quit" | "q" => return false,
+            _ => println!("未知命令 `{command}`，输入 help 查看命令"),
+        }
+        true
+    }
+}
+
+/// 离线查看会话文件
+struct SessionViewer {
+    session: Session,
+}
+
+impl SessionViewer {
+    fn print_history(&self) {
+        for (i, entry) in self.session.entries.iter().enumerate() {
+            let event = entry.event_id.map_or("tick".to_string(), |e| self.session.event_name(e));
+            let transition = entry.transition.map_or("-".to_string(), |t| t.to_string());
+            println!("#{i} {event} -> {transition}: {}", self.session.format_state(&entry.after));
+        }
+    }
+
+    fn show(&self, arg: &str) {
+        match arg.parse::<usize>().ok().and_then(|i| self.session.entries.get(i)) {
+            Some(entry) => {
+                println!("提交前：{}", self.session.format_state(&entry.before));
+                println!("提交后：{}", self.session.format_state(&entry.after));
+            }
+            None => println!("无效的历史序号 `{arg}`"),
+        }
+    }
+
+    /// 执行一条命令，返回 `false` 表示退出
+    fn execute(&mut self, line: &str) -> bool {
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "" => {}
+            "help" | "h" => println!("{SESSION_HELP}"),
+            "history" => self.print_history(),
+            "show" => self.show(arg.trim()),
+            "quit" | "q" => return false,
+            _ => println!("未知命令 `{command}`，输入 help 查看命令"),
+        }
+        true
+    }
+}
+
+fn repl(mut execute: impl FnMut(&str) -> bool) {
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if !execute(line.trim()) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("用法：state-zen-debug <blueprint.json | session.{SESSION_EXTENSION}>");
+        std::process::exit(2);
+    };
+    if path.ends_with(&format!(".{SESSION_EXTENSION}")) {
+        let mut viewer = match Session::load(&path) {
+            Ok(session) => SessionViewer { session },
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        };
+        println!("会话共 {} 条历史，输入 help 查看命令", viewer.session.entries.len());
+        repl(|line| viewer.execute(line));
+        return;
+    }
+    let debugger = json::load_file_with(&path, &Registry::with_builtins())
+        .map_err(|e| e.to_string())
+        .and_then(Debugger::new);
+    let mut debugger = match debugger {
+        Ok(debugger) => debugger,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    debugger.print_state();
+    repl(|line| debugger.execute(line));
+}
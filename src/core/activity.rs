@@ -0,0 +1,85 @@
+//! 近期活动环形缓冲区
+
+use std::collections::VecDeque;
+use std::fmt;
+use super::types::{StateAspectId, EventId, TransitionId};
+use super::runtime::{State, changed_aspects};
+
+/// 一条近期活动
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Activity {
+    /// 事件完成转换选择；`seq` 为事件在运行时处理过的全部事件中的序号（从 0 开始），
+    /// 采样跳过的事件不记录，序号因此出现间隔
+    Event { seq: u64, event_id: EventId, selected: Option<TransitionId> },
+    /// 转换被执行，`written` 为写入的方面（升序）
+    Transition { transition_id: TransitionId, written: Vec<StateAspectId> },
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Event { seq, event_id, selected: Some(t) } => write!(f, "#{seq} 事件 {event_id} -> 转换 {t}"),
+            Self::Event { seq, event_id, selected: None } => write!(f, "#{seq} 事件 {event_id} 被忽略"),
+            Self::Transition { transition_id, written } => write!(f, "转换 {transition_id} 写入 {written:?}"),
+        }
+    }
+}
+
+/// 运行时内部的近期活动记录
+///
+/// 只保留最近 `capacity` 条，不复制状态，开销远小于 `History`；
+/// 事件按 `sample_every` 采样，转换总是被记录
+pub(crate) struct ActivityLog {
+    capacity: usize,
+    sample_every: u64,
+    seen: u64,
+    records: VecDeque<Activity>,
+}
+
+impl Default for ActivityLog {
+    fn default() -> Self {
+        Self::new(32, 1)
+    }
+}
+
+impl ActivityLog {
+    pub(crate) fn new(capacity: usize, sample_every: u64) -> Self {
+        assert!(sample_every > 0, "采样间隔必须大于零");
+        Self {
+            capacity,
+            sample_every,
+            seen: 0,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn push(&mut self, activity: Activity) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(activity);
+    }
+
+    pub(crate) fn on_event(&mut self, event_id: EventId, selected: Option<TransitionId>) {
+        let seq = self.seen;
+        self.seen += 1;
+        if seq.is_multiple_of(self.sample_every) {
+            self.push(Activity::Event { seq, event_id, selected });
+        }
+    }
+
+    pub(crate) fn on_transition(&mut self, transition_id: TransitionId, prev: &State, next: &State) {
+        if self.capacity > 0 {
+            let mut written = changed_aspects(prev, next);
+            written.sort_unstable();
+            self.push(Activity::Transition { transition_id, written });
+        }
+    }
+
+    pub(crate) fn records(&self) -> Vec<Activity> {
+        self.records.iter().cloned().collect()
+    }
+}
@@ -0,0 +1,63 @@
+//! 事件别名：合并独立构建的蓝图时统一事件ID
+
+use std::collections::HashMap;
+use super::types::EventId;
+use super::blueprint::StateMachineBlueprint;
+
+/// 事件别名表
+/// 把别名事件ID映射到规范事件ID，例如一个蓝图中的 "Jump" 是 7，另一个是 42
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventAliasMap {
+    aliases: HashMap<EventId, EventId>,
+}
+
+impl EventAliasMap {
+    /// 创建一个空别名表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 把 `alias` 映射到规范事件 `canonical`
+    pub fn alias(mut self, alias: EventId, canonical: EventId) -> Self {
+        self.aliases.insert(alias, canonical);
+        self
+    }
+
+    /// 事件的规范ID，未设置别名时原样返回
+    pub fn canonical(&self, event_id: EventId) -> EventId {
+        self.aliases.get(&event_id).copied().unwrap_or(event_id)
+    }
+
+    /// 改写蓝图中的事件ID：事件定义、转换监听的事件以及转换发出的事件
+    ///
+    /// 规范事件已有定义时保留已有定义
+    pub fn apply(&self, blueprint: &StateMachineBlueprint) -> StateMachineBlueprint {
+        let mut rewritten = blueprint.clone();
+        rewritten.events_mut().clear();
+        let mut events: Vec<_> = blueprint.events().collect();
+        // 先放入规范事件自身的定义，使其优先于别名的定义
+        events.sort_by_key(|e| (self.aliases.contains_key(&e.id), e.id));
+        for event in events {
+            let id = self.canonical(event.id);
+            rewritten.events_mut().entry(id).or_insert_with(|| {
+                let mut event = event.clone();
+                event.id = id;
+                event
+            });
+        }
+        for transition in rewritten.transitions_mut() {
+            transition.event_id = self.canonical(transition.event_id);
+            for template in &mut transition.emits {
+                template.event_id = self.canonical(template.event_id);
+            }
+        }
+        rewritten
+    }
+}
+
+impl StateMachineBlueprint {
+    /// 用别名表改写双方的事件ID后合并
+    pub fn merge_aliased(&self, other: &Self, aliases: &EventAliasMap) -> Self {
+        aliases.apply(self).merge(&aliases.apply(other))
+    }
+}
@@ -0,0 +1,27 @@
+//! 方面结构体：普通结构体与动态状态之间的映射
+
+use std::any::TypeId;
+use super::types::StateAspectId;
+use super::blueprint::StateMachineBlueprint;
+use super::runtime::State;
+use super::error::StateZenError;
+
+/// 方面结构体
+/// 结构体的每个字段对应一个状态方面。
+/// 启用 `derive` 特性后可用 `#[derive(Aspects)]` 自动生成实现
+pub trait Aspects: Sized {
+    /// 把所有字段对应的方面注册到蓝图
+    fn register_aspects(blueprint: &mut StateMachineBlueprint) -> Result<(), StateZenError>;
+
+    /// 转换为动态状态
+    fn to_state(&self) -> State;
+
+    /// 从动态状态读取；任一方面缺失或类型不符时返回 `None`
+    fn from_state(state: &State) -> Option<Self>;
+
+    /// 各字段对应的方面ID与取值类型（按字段顺序），供 `StateBridge` 给出具体的错误；
+    /// 默认为空，此时读取失败只能报告 `MissingAspect(0)`
+    fn aspect_types() -> Vec<(StateAspectId, TypeId)> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,61 @@
+//! 确定性审计：同一事件执行两次并比较结果
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::runtime::{State, AspectValue, changed_aspects};
+
+/// 方面取值的相等比较
+type ValueEq = Arc<dyn Fn(&AspectValue, &AspectValue) -> bool + Send + Sync>;
+
+/// 确定性审计配置
+///
+/// 开启后运行时对每个事件从同一状态出发再选择一次转换、再执行一次转换函数，
+/// 两次结果不一致时记录 `StateZenError::NonDeterministic`，用于发现读取了
+/// 隐藏的可变捕获或随机数的守卫与转换函数。
+/// 两次写入的方面集合总是被比较；取值只对登记了比较方式的方面比较
+#[derive(Clone, Default)]
+pub struct DeterminismAudit {
+    comparators: HashMap<StateAspectId, ValueEq>,
+}
+
+impl DeterminismAudit {
+    /// 只比较写入方面集合的审计配置
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按 `T` 的 `PartialEq` 比较方面的取值
+    pub fn compare<T>(mut self, aspect_id: StateAspectId) -> Self
+    where
+        T: PartialEq + Send + Sync + 'static,
+    {
+        self.comparators.insert(
+            aspect_id,
+            Arc::new(|a, b| match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            }),
+        );
+        self
+    }
+
+    /// 从 `base` 出发的两次执行结果 `a`、`b` 中ID最小的不一致方面
+    pub(crate) fn first_difference(&self, base: &State, a: &State, b: &State) -> Option<StateAspectId> {
+        let mut written = changed_aspects(base, a);
+        written.extend(changed_aspects(base, b));
+        written.sort_unstable();
+        written.dedup();
+        written.into_iter().find(|id| match (a.get(id), b.get(id)) {
+            (None, None) => false,
+            (Some(x), Some(y)) => {
+                let (x_written, y_written) = (
+                    base.get(id).is_none_or(|old| !Arc::ptr_eq(old, x)),
+                    base.get(id).is_none_or(|old| !Arc::ptr_eq(old, y)),
+                );
+                x_written != y_written || self.comparators.get(id).is_some_and(|eq| !eq(x, y))
+            }
+            _ => true,
+        })
+    }
+}
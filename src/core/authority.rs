@@ -0,0 +1,45 @@
+//! 联网时方面的权威归属
+
+/// 方面由哪一端决定取值
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Authority {
+    /// 两端都可以在本地写入
+    #[default]
+    Shared,
+    /// 只能由服务器写入，客户端只接收同步过来的取值
+    Server,
+    /// 只能由客户端写入（如本地输入），服务器只接收同步过来的取值
+    Client,
+}
+
+/// 运行时所在的一端
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum NetworkRole {
+    /// 不联网，忽略方面的权威归属
+    #[default]
+    Standalone,
+    /// 服务器，本地转换不能写入客户端权威的方面
+    Server,
+    /// 客户端，本地转换不能写入服务器权威的方面
+    Client,
+}
+
+impl NetworkRole {
+    /// 该端能否在本地写入给定归属的方面
+    pub fn may_write(self, authority: Authority) -> bool {
+        !matches!(
+            (self, authority),
+            (Self::Server, Authority::Client) | (Self::Client, Authority::Server)
+        )
+    }
+}
+
+/// 本地转换写入对端权威的方面时的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuthorityPolicy {
+    /// 放弃本次转换，并记录一个 `StateZenError::AuthorityViolation`
+    #[default]
+    Reject,
+    /// 照常提交，只记录一个 `StateZenError::AuthorityViolation`
+    Warn,
+}
@@ -1,60 +1,876 @@
 //! 状态机蓝图
 
-use std::collections::HashMap;
-use super::types::{StateAspectId, EventId};
+use std::any::TypeId;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use super::types::{StateAspectId, EventId, TransitionId, ObserverId};
 use super::state_aspect::StateAspect;
+use super::authority::Authority;
 use super::event::EventDef;
 use super::transition::Transition;
 use super::state_observer::StateObserver;
+use super::edge_observer::EdgeObserver;
+use super::transition_observer::TransitionObserver;
+use super::continuous::ContinuousTransfer;
+use super::derived::DerivedAspect;
+use super::transfer::{Transfer, restrict_writes};
+use super::intern::PredicateInterner;
+use super::state_in_range::StateInRange;
+use super::error::StateZenError;
+use super::runtime::{State, AspectValue};
 
 /// 状态机蓝图
 /// 包含状态机的完整定义：方面、事件、转换和观察者
+///
+/// 推荐通过 `add_*` 方法构建蓝图，它们会检查ID唯一性与类型一致性。
+/// 公开字段均已弃用：请使用 `add_*`、`remove_*`、`*_mut` 与只读查询方法，直接访问字段将在后续版本移除
 #[derive(Clone)]
 pub struct StateMachineBlueprint {
     /// 状态方面定义
+    #[deprecated]
     pub aspects: HashMap<StateAspectId, StateAspect>,
     /// 事件定义
+    #[deprecated]
     pub events: HashMap<EventId, EventDef>,
     /// 状态转换定义
+    #[deprecated]
     pub transitions: Vec<Transition>,
     /// 状态观察者定义
+    #[deprecated]
     pub observers: Vec<StateObserver>,
+    /// 条件边沿观察者定义
+    #[deprecated]
+    pub edge_observers: Vec<EdgeObserver>,
+    /// 连续转换定义，由 `tick` 驱动
+    #[deprecated]
+    pub continuous_transfers: Vec<ContinuousTransfer>,
+    /// 终止区域，状态进入该区域即视为状态机已完成
+    #[deprecated]
+    pub final_region: Option<StateInRange>,
+    /// 蓝图版本，用于持久化状态的迁移
+    version: u32,
+    /// 受保护（对其他片段只读）的方面
+    protected: BTreeSet<StateAspectId>,
+    /// 联网时各方面的权威归属，未列出的为 `Authority::Shared`
+    authority: BTreeMap<StateAspectId, Authority>,
+    /// 启用的标签
+    enabled_tags: BTreeSet<String>,
+    /// 规范化转换，在每次转换写入对应方面后执行
+    normalizers: Vec<(StateAspectId, Transfer)>,
+    /// 派生方面（按添加顺序计算）
+    derived: Vec<DerivedAspect>,
+    /// 观察者区域的包含关系（外层, 内层）
+    containment: Vec<(ObserverId, ObserverId)>,
+    /// 各观察者区域的嵌套深度，包含关系变化时重新计算；未列出的为 0
+    depths: HashMap<ObserverId, usize>,
+    /// 转换观察者
+    transition_observers: Vec<TransitionObserver>,
+    /// 修改代数，转换或观察者经方法修改后加一
+    generation: u64,
 }
 
 impl StateMachineBlueprint {
     /// 创建一个新的空蓝图
+    #[allow(deprecated)]
     pub fn new() -> Self {
         Self {
             aspects: HashMap::new(),
             events: HashMap::new(),
             transitions: Vec::new(),
             observers: Vec::new(),
+            edge_observers: Vec::new(),
+            continuous_transfers: Vec::new(),
+            final_region: None,
+            version: 0,
+            protected: BTreeSet::new(),
+            authority: BTreeMap::new(),
+            enabled_tags: BTreeSet::new(),
+            normalizers: Vec::new(),
+            derived: Vec::new(),
+            containment: Vec::new(),
+            depths: HashMap::new(),
+            transition_observers: Vec::new(),
+            generation: 0,
         }
     }
 
+    /// 修改代数：每次经 `add_*`、`retain_enabled`、`intern_predicates` 等方法修改转换或观察者后加一，
+    /// 运行时据此判断分发索引、规范状态位集等缓存是否失效
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// 直接修改（已弃用的）字段后调用，使依赖修改代数的缓存失效
+    pub fn mark_changed(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// 蓝图版本
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// 设置蓝图版本
+    pub fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    /// 检查状态是否与蓝图声明一致：每个声明的方面都有取值、类型匹配且通过取值校验
+    /// 派生方面由运行时计算，可以缺省
+    pub fn validate_state(&self, state: &State) -> Result<(), StateZenError> {
+        for (id, aspect) in self.aspect_map() {
+            match state.get(id) {
+                None if self.derived.iter().any(|d| d.id == *id) => {}
+                None => return Err(StateZenError::MissingAspect(*id)),
+                Some(value) if (**value).type_id() != aspect.value_type_id => {
+                    return Err(StateZenError::AspectTypeMismatch(*id));
+                }
+                Some(value) if aspect.validator.as_ref().is_some_and(|valid| !valid(&**value)) => {
+                    return Err(StateZenError::InvalidAspectValue { aspect: *id, transition: None });
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// 用各方面的默认值构造初始状态，派生方面随后计算
+    ///
+    /// 没有默认值的方面返回 `MissingAspect`；结果需通过 `validate_state` 校验。
+    /// 个别方面可用 `State::with_overrides` 覆盖
+    pub fn default_initial_state(&self) -> Result<State, StateZenError> {
+        let mut state = State::new();
+        for (id, aspect) in self.aspect_map() {
+            if self.is_derived(*id) {
+                continue;
+            }
+            let default = aspect.default.as_ref().ok_or(StateZenError::MissingAspect(*id))?;
+            state.insert(*id, default());
+        }
+        let state = self.derive(state);
+        self.validate_state(&state)?;
+        Ok(state)
+    }
+
+    /// 设置终止区域
+    pub fn set_final_region(&mut self, region: StateInRange) {
+        *self.final_region_mut() = Some(region);
+    }
+
     /// 合并两个蓝图
     /// 返回一个新的蓝图，包含两个蓝图的所有定义
+    ///
+    /// 不检查受保护的方面：任一方的转换都可以改写对方受保护的方面，需要保护时使用 `try_merge`
     pub fn merge(&self, other: &Self) -> Self {
-        let mut aspects = self.aspects.clone();
-        let mut events = self.events.clone();
-        let mut transitions = self.transitions.clone();
-        let mut observers = self.observers.clone();
+        let mut merged = Self {
+            version: self.version.max(other.version),
+            protected: self.protected.union(&other.protected).copied().collect(),
+            authority: self.authority.iter().chain(&other.authority).map(|(id, a)| (*id, *a)).collect(),
+            enabled_tags: self.enabled_tags.union(&other.enabled_tags).cloned().collect(),
+            normalizers: self.normalizers.iter().chain(&other.normalizers).cloned().collect(),
+            derived: self
+                .derived
+                .iter()
+                .filter(|d| !other.derived.iter().any(|o| o.id == d.id))
+                .chain(&other.derived)
+                .cloned()
+                .collect(),
+            containment: self.containment.clone(),
+            transition_observers: self
+                .transition_observers
+                .iter()
+                .chain(&other.transition_observers)
+                .cloned()
+                .collect(),
+            ..Self::new()
+        };
+        merged.aspects_mut().extend(self.aspects().chain(other.aspects()).map(|a| (a.id, a.clone())));
+        merged.events_mut().extend(self.events().chain(other.events()).map(|e| (e.id, e.clone())));
+        merged.transitions_mut().extend(self.transitions().chain(other.transitions()).cloned());
+        merged.observers_mut().extend(self.observers().chain(other.observers()).cloned());
+        merged.edge_observers_mut().extend(self.edge_observers().chain(other.edge_observers()).cloned());
+        merged
+            .continuous_transfers_mut()
+            .extend(self.continuous_transfers().chain(other.continuous_transfers()).cloned());
+
+        // 并行组合：两个蓝图都完成才算完成
+        *merged.final_region_mut() = match (self.final_region(), other.final_region()) {
+            (Some(a), Some(b)) => Some(a.clone().and(b.clone())),
+            (a, b) => a.or(b).cloned(),
+        };
+        // 与已有关系成环的包含关系被忽略
+        for &(outer, inner) in &other.containment {
+            if outer != inner && !merged.region_contains(inner, outer) && !merged.containment.contains(&(outer, inner)) {
+                merged.containment.push((outer, inner));
+            }
+        }
+        merged.recompute_depths();
+        merged
+    }
 
-        for (k, v) in &other.aspects {
-            aspects.insert(*k, v.clone());
+    /// 已知某个方面取值恒定时化简整个蓝图
+    ///
+    /// 所有守卫、观察区域、连续转换区域与终止区域都被化简，守卫恒假的转换被移除；
+    /// `value` 的类型与方面声明或条件中的取值类型不符时返回 `AspectTypeMismatch`
+    pub fn specialize<T>(&self, aspect_id: StateAspectId, value: T) -> Result<Self, StateZenError>
+    where
+        T: Send + Sync + 'static,
+    {
+        if self.aspect(aspect_id).is_some_and(|a| a.value_type_id != TypeId::of::<T>()) {
+            return Err(StateZenError::AspectTypeMismatch(aspect_id));
         }
-        for (k, v) in &other.events {
-            events.insert(*k, v.clone());
+        let value: AspectValue = Arc::new(value);
+        let mut specialized = self.clone();
+        let transitions = specialized.transitions_mut();
+        transitions.clear();
+        for t in self.transitions() {
+            let guard = t.guard.specialize_value(aspect_id, &value)?;
+            if guard.constant() != Some(false) {
+                transitions.push(Transition { guard, ..t.clone() });
+            }
         }
-        transitions.extend(other.transitions.iter().cloned());
-        observers.extend(other.observers.iter().cloned());
+        for observer in specialized.observers_mut() {
+            observer.region = observer.region.specialize_value(aspect_id, &value)?;
+            if let Some(active) = &mut observer.active_when {
+                *active = active.specialize_value(aspect_id, &value)?;
+            }
+        }
+        for edge in specialized.edge_observers_mut() {
+            edge.condition = edge.condition.specialize_value(aspect_id, &value)?;
+        }
+        for continuous in specialized.continuous_transfers_mut() {
+            continuous.region = continuous.region.specialize_value(aspect_id, &value)?;
+        }
+        *specialized.final_region_mut() = self
+            .final_region()
+            .map(|r| r.specialize_value(aspect_id, &value))
+            .transpose()?;
+        Ok(specialized)
+    }
 
-        Self {
-            aspects,
-            events,
-            transitions,
-            observers,
+    /// 把蓝图投影到方面子集上，得到只涉及这些方面的更小的蓝图
+    ///
+    /// 只保留守卫读集合与转换函数写集合都已知且落在子集内的转换（后置条件超出子集时被去掉），
+    /// 以及读集合落在子集内的观察者、边沿观察者与终止区域；读写集合未知的部分一律视为超出子集。
+    /// 连续转换的写集合未知，全部被移除。事件只保留仍被转换监听或发出的。
+    /// 适合用于分析，或在客户端运行轻量的预测副本
+    pub fn project(&self, aspects: &[StateAspectId]) -> Self {
+        let subset: BTreeSet<StateAspectId> = aspects.iter().copied().collect();
+        let within = |ids: Option<&[StateAspectId]>| ids.is_some_and(|ids| ids.iter().all(|id| subset.contains(id)));
+        let region_within = |region: &StateInRange| within(region.reads());
+
+        let mut projected = self.clone();
+        projected.aspects_mut().retain(|id, _| subset.contains(id));
+        *projected.transitions_mut() = self
+            .transitions()
+            .filter(|t| region_within(&t.guard) && within(t.transfer.writes()))
+            .map(|t| Transition {
+                ensures: t.ensures.clone().filter(region_within),
+                ..t.clone()
+            })
+            .collect();
+        let used: BTreeSet<EventId> = projected
+            .transitions()
+            .flat_map(|t| std::iter::once(t.event_id).chain(t.emits.iter().map(|e| e.event_id)))
+            .collect();
+        projected.events_mut().retain(|id, _| used.contains(id));
+        projected
+            .observers_mut()
+            .retain(|o| region_within(&o.region) && o.active_when.as_ref().is_none_or(region_within));
+        projected.edge_observers_mut().retain(|e| region_within(&e.condition));
+        projected.continuous_transfers_mut().clear();
+        *projected.final_region_mut() = self.final_region().cloned().filter(region_within);
+        projected.protected.retain(|id| subset.contains(id));
+        projected.authority.retain(|id, _| subset.contains(id));
+        projected
+            .normalizers
+            .retain(|(id, transfer)| subset.contains(id) && within(transfer.writes()));
+        projected
+            .derived
+            .retain(|d| subset.contains(&d.id) && !d.inputs.is_empty() && within(Some(&d.inputs)));
+        let kept: BTreeSet<ObserverId> = projected.observers().map(|o| o.id).collect();
+        projected
+            .containment
+            .retain(|(outer, inner)| kept.contains(outer) && kept.contains(inner));
+        projected.recompute_depths();
+        projected
+    }
+
+    /// 启用标签，带这些标签的转换与观察者在构造运行时时生效
+    pub fn enable_tags(&mut self, tags: &[&str]) {
+        self.enabled_tags.extend(tags.iter().map(|t| t.to_string()));
+    }
+
+    /// 标签是否生效：未打标签的条目始终生效
+    pub fn is_tag_enabled(&self, tag: Option<&str>) -> bool {
+        tag.is_none_or(|t| self.enabled_tags.contains(t))
+    }
+
+    /// 只保留生效的转换与观察者，运行时构造时自动调用
+    pub fn retain_enabled(&mut self) {
+        let enabled = std::mem::take(&mut self.enabled_tags);
+        let active = |tag: &Option<String>| tag.as_ref().is_none_or(|t| enabled.contains(t));
+        self.transitions_mut().retain(|t| active(&t.tag));
+        self.observers_mut().retain(|o| active(&o.tag));
+        self.enabled_tags = enabled;
+        self.mark_changed();
+    }
+
+    /// 把方面标记为对其他片段只读
+    /// 通过 `try_merge` 合并进来的其他蓝图的转换不能写入该方面
+    pub fn protect_aspect(&mut self, aspect_id: StateAspectId) {
+        self.protected.insert(aspect_id);
+    }
+
+    /// 受保护的方面（按ID升序）
+    pub fn protected_aspects(&self) -> impl Iterator<Item = StateAspectId> + '_ {
+        self.protected.iter().copied()
+    }
+
+    /// 设置方面的权威归属，联网运行时本地转换只能写入本端有权写入的方面
+    pub fn set_authority(&mut self, aspect_id: StateAspectId, authority: Authority) {
+        if authority == Authority::Shared {
+            self.authority.remove(&aspect_id);
+        } else {
+            self.authority.insert(aspect_id, authority);
+        }
+    }
+
+    /// 方面的权威归属
+    pub fn authority(&self, aspect_id: StateAspectId) -> Authority {
+        self.authority.get(&aspect_id).copied().unwrap_or_default()
+    }
+
+    /// 合并蓝图，并检查双方的转换是否写入对方受保护的方面
+    ///
+    /// 声明了写集合的转换若写入对方受保护的方面，直接返回 `ProtectedAspectWrite`；
+    /// 写集合声明不约束函数的实际行为，因此所有转换都被包装为运行时检查：改写受保护方面时整个转换被拒绝
+    pub fn try_merge(&self, other: &Self) -> Result<Self, StateZenError> {
+        let ours = guard_foreign(self.transition_slice(), &other.protected)?;
+        let theirs = guard_foreign(other.transition_slice(), &self.protected)?;
+        let mut merged = self.merge(other);
+        *merged.transitions_mut() = ours.into_iter().chain(theirs).collect();
+        Ok(merged)
+    }
+}
+
+/// 检查一个片段的转换是否声明写入另一片段受保护的方面，并把全部转换包装为运行时检查
+fn guard_foreign(
+    transitions: &[Transition],
+    protected: &BTreeSet<StateAspectId>,
+) -> Result<Vec<Transition>, StateZenError> {
+    if protected.is_empty() {
+        return Ok(transitions.to_vec());
+    }
+    let protected_ids: std::sync::Arc<[StateAspectId]> = protected.iter().copied().collect();
+    transitions
+        .iter()
+        .map(|t| {
+            let declared = t.transfer.writes().unwrap_or_default();
+            if let Some(aspect) = declared.iter().find(|id| protected.contains(id)) {
+                return Err(StateZenError::ProtectedAspectWrite { transition: t.id, aspect: *aspect });
+            }
+            let mut guarded = t.clone();
+            guarded.transfer = t.transfer.guarded(protected_ids.clone());
+            Ok(guarded)
+        })
+        .collect()
+}
+
+/// 带校验的修改
+impl StateMachineBlueprint {
+    /// 声明一个方面
+    /// 重复声明同一类型的方面是允许的（无操作），类型不同则返回错误
+    pub fn add_aspect(&mut self, aspect: StateAspect) -> Result<(), StateZenError> {
+        match self.aspect(aspect.id) {
+            Some(existing) if existing.value_type_id != aspect.value_type_id => {
+                Err(StateZenError::DuplicateAspect(aspect.id))
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.aspects_mut().insert(aspect.id, aspect);
+                Ok(())
+            }
+        }
+    }
+
+    /// 声明一个事件
+    /// 重复声明同一payload类型的事件是允许的（无操作），类型不同则返回错误
+    pub fn add_event(&mut self, event: EventDef) -> Result<(), StateZenError> {
+        match self.event(event.id) {
+            Some(existing) if existing.payload_type_id != event.payload_type_id => {
+                Err(StateZenError::DuplicateEvent(event.id))
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.events_mut().insert(event.id, event);
+                Ok(())
+            }
+        }
+    }
+
+    /// 添加一个转换
+    /// 转换ID必须唯一，监听的事件必须已声明
+    pub fn add_transition(&mut self, transition: Transition) -> Result<(), StateZenError> {
+        if self.transition(transition.id).is_some() {
+            return Err(StateZenError::DuplicateTransition(transition.id));
+        }
+        if self.event(transition.event_id).is_none() {
+            return Err(StateZenError::UnknownEvent(transition.event_id));
+        }
+        self.transitions_mut().push(transition);
+        Ok(())
+    }
+
+    /// 添加一个状态观察者，观察者ID必须唯一
+    ///
+    /// 区域与已有观察者结构相同（声明式谓词）时改为共享已有的谓词，
+    /// 运行时据此把它们视为同一区域（只求值一次，`Handled::Stop` 作用于整个区域）
+    pub fn add_observer(&mut self, mut observer: StateObserver) -> Result<(), StateZenError> {
+        if self.observer(observer.id).is_some() {
+            return Err(StateZenError::DuplicateObserver(observer.id));
+        }
+        if let Some(existing) = self.observers().find(|o| o.region.structurally_eq(&observer.region)) {
+            observer.region = existing.region.clone();
+        }
+        self.observers_mut().push(observer);
+        Ok(())
+    }
+
+    /// 声明观察者 `outer` 的区域包含观察者 `inner` 的区域
+    /// 运行时按嵌套顺序执行回调：先进入外层再进入内层，先退出内层再退出外层。
+    /// 声明不做检查，可用 `verify_containment` 在样本状态上验证
+    pub fn declare_containment(&mut self, outer: ObserverId, inner: ObserverId) -> Result<(), StateZenError> {
+        for id in [outer, inner] {
+            if self.observer(id).is_none() {
+                return Err(StateZenError::UnknownObserver(id));
+            }
+        }
+        if outer == inner || self.region_contains(inner, outer) {
+            return Err(StateZenError::InvalidContainment { outer, inner });
+        }
+        if !self.containment.contains(&(outer, inner)) {
+            self.containment.push((outer, inner));
+            self.recompute_depths();
+            self.mark_changed();
+        }
+        Ok(())
+    }
+
+    /// 在样本状态上验证后声明包含关系
+    /// 任一样本位于内层区域而不在外层区域时返回 `InvalidContainment`，不做声明
+    pub fn declare_containment_verified(
+        &mut self,
+        outer: ObserverId,
+        inner: ObserverId,
+        samples: &[State],
+    ) -> Result<(), StateZenError> {
+        let (Some(o), Some(i)) = (self.observer(outer), self.observer(inner)) else {
+            let missing = if self.observer(outer).is_none() { outer } else { inner };
+            return Err(StateZenError::UnknownObserver(missing));
+        };
+        if samples.iter().any(|s| i.region.contains(s) && !o.region.contains(s)) {
+            return Err(StateZenError::InvalidContainment { outer, inner });
+        }
+        self.declare_containment(outer, inner)
+    }
+
+    /// 在样本状态上验证全部已声明的包含关系，返回第一个被违反的关系
+    pub fn verify_containment(&self, samples: &[State]) -> Result<(), StateZenError> {
+        for &(outer, inner) in &self.containment {
+            let (Some(o), Some(i)) = (self.observer(outer), self.observer(inner)) else {
+                continue;
+            };
+            if samples.iter().any(|s| i.region.contains(s) && !o.region.contains(s)) {
+                return Err(StateZenError::InvalidContainment { outer, inner });
+            }
+        }
+        Ok(())
+    }
+
+    /// 添加一个条件边沿观察者，ID必须在边沿观察者中唯一
+    pub fn add_edge_observer(&mut self, observer: EdgeObserver) -> Result<(), StateZenError> {
+        if self.edge_observers().any(|o| o.id == observer.id) {
+            return Err(StateZenError::DuplicateObserver(observer.id));
+        }
+        self.edge_observers_mut().push(observer);
+        Ok(())
+    }
+
+    /// 添加一个规范化转换
+    /// 任何转换（含连续转换）写入 `aspect_id` 后按添加顺序执行，如把位置对齐到网格、把属性限制在范围内；
+    /// 规范化转换只能改写 `aspect_id`，对其他方面的写入被丢弃
+    pub fn add_normalizer(&mut self, aspect_id: StateAspectId, transfer: Transfer) {
+        self.normalizers.push((aspect_id, transfer));
+    }
+
+    /// 只保留满足条件的规范化转换
+    pub(crate) fn retain_normalizers<F>(&mut self, f: F)
+    where
+        F: FnMut(&(StateAspectId, Transfer)) -> bool,
+    {
+        self.normalizers.retain(f);
+    }
+
+    /// 只保留满足条件的权威归属
+    pub(crate) fn retain_authority<F>(&mut self, mut f: F)
+    where
+        F: FnMut(StateAspectId, Authority) -> bool,
+    {
+        self.authority.retain(|id, authority| f(*id, *authority));
+    }
+
+    /// 移除终止区域
+    pub(crate) fn clear_final_region(&mut self) {
+        *self.final_region_mut() = None;
+    }
+
+    /// 添加一个派生方面，同时以其取值类型声明该方面
+    /// 派生方面按添加顺序计算，可以依赖先添加的派生方面
+    pub fn add_derived_aspect(&mut self, derived: DerivedAspect) -> Result<(), StateZenError> {
+        if self.derived.iter().any(|d| d.id == derived.id) {
+            return Err(StateZenError::DuplicateAspect(derived.id));
+        }
+        self.add_aspect(StateAspect::new(derived.id, derived.value_type_id()))?;
+        self.derived.push(derived);
+        Ok(())
+    }
+
+    /// 只保留满足条件的派生方面
+    pub(crate) fn retain_derived<F>(&mut self, f: F)
+    where
+        F: FnMut(&DerivedAspect) -> bool,
+    {
+        self.derived.retain(f);
+    }
+
+    /// 添加一个连续转换
+    pub fn add_continuous_transfer(&mut self, continuous: ContinuousTransfer) {
+        self.continuous_transfers_mut().push(continuous);
+    }
+
+    /// 按ID取得方面定义的可变引用，如替换取值校验
+    pub fn aspect_mut(&mut self, id: StateAspectId) -> Option<&mut StateAspect> {
+        self.aspects_mut().get_mut(&id)
+    }
+
+    /// 按ID取得事件定义的可变引用，如追加载荷转换器
+    pub fn event_mut(&mut self, id: EventId) -> Option<&mut EventDef> {
+        self.events_mut().get_mut(&id)
+    }
+
+    /// 按ID取得转换的可变引用，修改代数随之更新
+    pub fn transition_mut(&mut self, id: TransitionId) -> Option<&mut Transition> {
+        self.transitions_mut().iter_mut().find(|t| t.id == id)
+    }
+
+    /// 移除一个转换，返回被移除的转换
+    pub fn remove_transition(&mut self, id: TransitionId) -> Option<Transition> {
+        let index = self.transition_slice().iter().position(|t| t.id == id)?;
+        Some(self.transitions_mut().remove(index))
+    }
+
+    /// 按ID取得状态观察者的可变引用，修改代数随之更新
+    pub fn observer_mut(&mut self, id: ObserverId) -> Option<&mut StateObserver> {
+        self.observers_mut().iter_mut().find(|o| o.id == id)
+    }
+
+    /// 移除一个状态观察者及涉及它的包含关系，返回被移除的观察者
+    pub fn remove_observer(&mut self, id: ObserverId) -> Option<StateObserver> {
+        let index = self.observer_slice().iter().position(|o| o.id == id)?;
+        self.containment.retain(|&(outer, inner)| outer != id && inner != id);
+        self.recompute_depths();
+        Some(self.observers_mut().remove(index))
+    }
+}
+
+/// 只读查询
+impl StateMachineBlueprint {
+    /// 全部方面定义
+    pub fn aspects(&self) -> impl Iterator<Item = &StateAspect> {
+        self.aspect_map().values()
+    }
+
+    /// 按ID查找方面定义
+    pub fn aspect(&self, id: StateAspectId) -> Option<&StateAspect> {
+        self.aspect_map().get(&id)
+    }
+
+    /// 全部事件定义
+    pub fn events(&self) -> impl Iterator<Item = &EventDef> {
+        self.event_map().values()
+    }
+
+    /// 按ID查找事件定义
+    pub fn event(&self, id: EventId) -> Option<&EventDef> {
+        self.event_map().get(&id)
+    }
+
+    /// 全部转换（按蓝图中的顺序）
+    pub fn transitions(&self) -> impl Iterator<Item = &Transition> {
+        self.transition_slice().iter()
+    }
+
+    /// 按ID查找转换
+    pub fn transition(&self, id: TransitionId) -> Option<&Transition> {
+        self.transition_slice().iter().find(|t| t.id == id)
+    }
+
+    /// 监听指定事件的转换（按蓝图中的顺序）
+    pub fn transitions_for_event(&self, event_id: EventId) -> impl Iterator<Item = &Transition> {
+        self.transition_slice().iter().filter(move |t| t.event_id == event_id)
+    }
+
+    /// 全部状态观察者
+    pub fn observers(&self) -> impl Iterator<Item = &StateObserver> {
+        self.observer_slice().iter()
+    }
+
+    /// 按ID查找状态观察者
+    pub fn observer(&self, id: ObserverId) -> Option<&StateObserver> {
+        self.observer_slice().iter().find(|o| o.id == id)
+    }
+
+    /// 区域可能依赖指定方面的观察者
+    /// 依据区域与激活区域谓词声明的读集合；未声明读集合的观察者保守地视为依赖所有方面
+    pub fn observers_watching(&self, aspect_id: StateAspectId) -> impl Iterator<Item = &StateObserver> {
+        let watches = move |p: &StateInRange| p.reads().is_none_or(|r| r.contains(&aspect_id));
+        self.observer_slice()
+            .iter()
+            .filter(move |o| watches(&o.region) || o.active_when.as_ref().is_some_and(watches))
+    }
+
+    /// 已声明的区域包含关系（外层, 内层），按声明顺序
+    pub fn containments(&self) -> impl Iterator<Item = (ObserverId, ObserverId)> + '_ {
+        self.containment.iter().copied()
+    }
+
+    /// 观察者 `outer` 的区域是否（直接或间接）声明为包含 `inner` 的区域
+    pub fn region_contains(&self, outer: ObserverId, inner: ObserverId) -> bool {
+        let mut stack = vec![outer];
+        let mut seen = BTreeSet::new();
+        while let Some(id) = stack.pop() {
+            for &(o, i) in &self.containment {
+                if o == id && seen.insert(i) {
+                    if i == inner {
+                        return true;
+                    }
+                    stack.push(i);
+                }
+            }
+        }
+        false
+    }
+
+    /// 观察者区域的嵌套深度：最长的外层链长度，没有外层时为 0
+    pub fn nesting_depth(&self, id: ObserverId) -> usize {
+        self.depths.get(&id).copied().unwrap_or(0)
+    }
+
+    /// 按包含关系重新计算嵌套深度（包含关系无环，见 `declare_containment`）
+    fn recompute_depths(&mut self) {
+        fn depth(
+            id: ObserverId,
+            containment: &[(ObserverId, ObserverId)],
+            depths: &mut HashMap<ObserverId, usize>,
+        ) -> usize {
+            if let Some(&d) = depths.get(&id) {
+                return d;
+            }
+            let d = containment
+                .iter()
+                .filter(|(_, inner)| *inner == id)
+                .map(|&(outer, _)| depth(outer, containment, depths) + 1)
+                .max()
+                .unwrap_or(0);
+            depths.insert(id, d);
+            d
+        }
+
+        let mut depths = HashMap::new();
+        for &(_, inner) in &self.containment {
+            depth(inner, &self.containment, &mut depths);
+        }
+        depths.retain(|_, d| *d > 0);
+        self.depths = depths;
+    }
+
+    /// 规范化转换（按添加顺序）
+    pub fn normalizers(&self) -> impl Iterator<Item = &(StateAspectId, Transfer)> {
+        self.normalizers.iter()
+    }
+
+    /// 全部派生方面（按添加顺序）
+    pub fn derived_aspects(&self) -> impl Iterator<Item = &DerivedAspect> {
+        self.derived.iter()
+    }
+
+    /// 是否为派生方面
+    pub fn is_derived(&self, id: StateAspectId) -> bool {
+        self.derived.iter().any(|d| d.id == id)
+    }
+
+    /// 按添加顺序重新计算全部派生方面，覆盖 `state` 中已有的取值
+    pub fn derive(&self, mut state: State) -> State {
+        for derived in &self.derived {
+            let value = derived.compute(&state);
+            state.insert(derived.id, value);
+        }
+        state
+    }
+
+    /// 对 `next` 中相对 `prev` 被写入的方面执行规范化转换，随后重新计算派生方面
+    ///
+    /// 派生方面按输入记忆：只有输入在本次写入中变化（或未声明输入）的派生方面才重新计算，
+    /// 其余派生方面保持 `prev` 中的取值，转换对它们的写入同样被丢弃
+    pub fn normalize(&self, prev: &State, mut next: State) -> State {
+        for (aspect_id, transfer) in &self.normalizers {
+            if next.contains_key(aspect_id) && written(prev, &next, *aspect_id) {
+                next = restrict_writes(&next, &transfer.apply(&next), &[*aspect_id]);
+            }
+        }
+        for derived in &self.derived {
+            if derived.inputs.is_empty() || derived.inputs.iter().any(|id| written(prev, &next, *id)) {
+                let value = derived.compute(&next);
+                next.insert(derived.id, value);
+            } else {
+                next = restrict_writes(&next, prev, &[derived.id]);
+            }
+        }
+        next
+    }
+
+    /// 全部条件边沿观察者
+    pub fn edge_observers(&self) -> impl Iterator<Item = &EdgeObserver> {
+        self.edge_observer_slice().iter()
+    }
+
+    /// 全部连续转换
+    pub fn continuous_transfers(&self) -> impl Iterator<Item = &ContinuousTransfer> {
+        self.continuous_slice().iter()
+    }
+
+    /// 添加一个转换观察者，由该蓝图构造的每个运行时都会调用
+    pub fn add_transition_observer(&mut self, observer: TransitionObserver) {
+        self.transition_observers.push(observer);
+    }
+
+    /// 全部转换观察者
+    pub fn transition_observers(&self) -> impl Iterator<Item = &TransitionObserver> {
+        self.transition_observers.iter()
+    }
+}
+
+/// 已弃用字段的唯一访问入口：按下标访问与整体改写，取得可变引用时修改代数随之更新
+#[allow(deprecated)]
+impl StateMachineBlueprint {
+    /// 终止区域
+    pub fn final_region(&self) -> Option<&StateInRange> {
+        self.final_region.as_ref()
+    }
+
+    pub(crate) fn aspect_map(&self) -> &HashMap<StateAspectId, StateAspect> {
+        &self.aspects
+    }
+
+    pub(crate) fn event_map(&self) -> &HashMap<EventId, EventDef> {
+        &self.events
+    }
+
+    pub(crate) fn transition_slice(&self) -> &[Transition] {
+        &self.transitions
+    }
+
+    pub(crate) fn observer_slice(&self) -> &[StateObserver] {
+        &self.observers
+    }
+
+    fn edge_observer_slice(&self) -> &[EdgeObserver] {
+        &self.edge_observers
+    }
+
+    fn continuous_slice(&self) -> &[ContinuousTransfer] {
+        &self.continuous_transfers
+    }
+
+    pub(crate) fn aspects_mut(&mut self) -> &mut HashMap<StateAspectId, StateAspect> {
+        self.mark_changed();
+        &mut self.aspects
+    }
+
+    pub(crate) fn events_mut(&mut self) -> &mut HashMap<EventId, EventDef> {
+        self.mark_changed();
+        &mut self.events
+    }
+
+    pub(crate) fn transitions_mut(&mut self) -> &mut Vec<Transition> {
+        self.mark_changed();
+        &mut self.transitions
+    }
+
+    pub(crate) fn observers_mut(&mut self) -> &mut Vec<StateObserver> {
+        self.mark_changed();
+        &mut self.observers
+    }
+
+    fn edge_observers_mut(&mut self) -> &mut Vec<EdgeObserver> {
+        self.mark_changed();
+        &mut self.edge_observers
+    }
+
+    pub(crate) fn continuous_transfers_mut(&mut self) -> &mut Vec<ContinuousTransfer> {
+        self.mark_changed();
+        &mut self.continuous_transfers
+    }
+
+    fn final_region_mut(&mut self) -> &mut Option<StateInRange> {
+        self.mark_changed();
+        &mut self.final_region
+    }
+}
+
+/// `next` 相对 `prev` 是否写入（或移除）了方面；未被触及的方面共享同一个取值
+fn written(prev: &State, next: &State, id: StateAspectId) -> bool {
+    match (prev.get(&id), next.get(&id)) {
+        (Some(a), Some(b)) => !Arc::ptr_eq(a, b),
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+impl StateMachineBlueprint {
+    /// 驻留蓝图中的全部谓词
+    /// 结构等价的声明式守卫、区域共享同一个闭包，返回被替换为共享谓词的数量
+    pub fn intern_predicates(&mut self) -> usize {
+        let mut interner = PredicateInterner::new();
+        let mut shared = 0;
+        let mut intern = |p: &mut StateInRange| {
+            let interned = interner.intern(p.clone());
+            if !StateInRange::ptr_eq(&interned, p) {
+                *p = interned;
+                shared += 1;
+            }
+        };
+
+        for t in self.transitions_mut() {
+            intern(&mut t.guard);
+        }
+        for o in self.observers_mut() {
+            intern(&mut o.region);
+            if let Some(active) = &mut o.active_when {
+                intern(active);
+            }
+        }
+        for e in self.edge_observers_mut() {
+            intern(&mut e.condition);
+        }
+        for c in self.continuous_transfers_mut() {
+            intern(&mut c.region);
+        }
+        if let Some(r) = self.final_region_mut() {
+            intern(r);
         }
+        shared
     }
 }
 
@@ -0,0 +1,29 @@
+//! 转换断点
+
+use super::types::EventId;
+use super::transition::Transition;
+use super::runtime::State;
+
+/// 断点触发时的上下文
+pub struct BreakContext<'a> {
+    /// 触发转换的事件，直接调用 `transform` 时为 `None`
+    pub event_id: Option<EventId>,
+    /// 即将执行的转换
+    pub transition: &'a Transition,
+    /// 转换前的状态
+    pub state: &'a State,
+}
+
+/// 断点钩子的决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakAction {
+    /// 照常执行转换
+    Continue,
+    /// 丢弃本次转换
+    Skip,
+    /// 暂停状态机并挂起转换；`resume` 时执行，`discard_pending` 可丢弃
+    Pause,
+}
+
+/// 断点钩子
+pub type BreakHook = Box<dyn Fn(&BreakContext) -> BreakAction + Send + Sync>;
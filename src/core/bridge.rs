@@ -0,0 +1,169 @@
+//! 动态状态与普通结构体之间的桥接
+
+use std::any::TypeId;
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::state_aspect::StateAspect;
+use super::blueprint::StateMachineBlueprint;
+use super::runtime::{State, AspectValue};
+use super::aspects::Aspects;
+use super::error::StateZenError;
+
+/// 读取字段为方面取值
+type FieldReader<T> = Arc<dyn Fn(&T) -> AspectValue + Send + Sync>;
+
+/// 把方面取值写入字段，取值类型不符时返回 `false`
+type FieldWriter<T> = Arc<dyn Fn(&mut T, &AspectValue) -> bool + Send + Sync>;
+
+/// 结构体的一个字段与方面的对应
+struct BridgeField<T> {
+    id: StateAspectId,
+    type_id: TypeId,
+    read: FieldReader<T>,
+    write: FieldWriter<T>,
+}
+
+impl<T> Clone for BridgeField<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            type_id: self.type_id,
+            read: self.read.clone(),
+            write: self.write.clone(),
+        }
+    }
+}
+
+enum Mapping<T> {
+    /// 逐个字段注册，读取时从基础值出发逐个写入字段
+    Fields { base: Arc<dyn Fn() -> T + Send + Sync>, fields: Vec<BridgeField<T>> },
+    /// 使用 `Aspects` 实现
+    Derived { to: fn(&T) -> State, from: fn(&State) -> Option<T>, types: Vec<(StateAspectId, TypeId)> },
+}
+
+impl<T> Clone for Mapping<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Fields { base, fields } => Self::Fields { base: base.clone(), fields: fields.clone() },
+            Self::Derived { to, from, types } => Self::Derived { to: *to, from: *from, types: types.clone() },
+        }
+    }
+}
+
+/// 动态 `State` 与结构体 `T` 之间的转换
+///
+/// 可以逐个字段注册（`new` + `field`），也可以直接使用 `#[derive(Aspects)]` 的实现（`derived`）。
+/// 读取失败时给出具体的方面：缺失为 `MissingAspect`，类型不符为 `AspectTypeMismatch`
+#[derive(Clone)]
+pub struct StateBridge<T> {
+    mapping: Mapping<T>,
+}
+
+impl<T: Default + 'static> Default for StateBridge<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: 'static> StateBridge<T> {
+    /// 逐个字段注册的桥接，`from_state` 从 `T::default()` 出发写入各字段
+    pub fn new() -> Self
+    where
+        T: Default,
+    {
+        Self::with_base(T::default)
+    }
+
+    /// 逐个字段注册的桥接，`from_state` 从 `base()` 出发写入各字段，未注册的字段保持基础值
+    pub fn with_base<B>(base: B) -> Self
+    where
+        B: Fn() -> T + Send + Sync + 'static,
+    {
+        Self {
+            mapping: Mapping::Fields { base: Arc::new(base), fields: Vec::new() },
+        }
+    }
+
+    /// 由 `Aspects` 实现构造
+    pub fn derived() -> Self
+    where
+        T: Aspects,
+    {
+        Self {
+            mapping: Mapping::Derived { to: T::to_state, from: T::from_state, types: T::aspect_types() },
+        }
+    }
+
+    /// 注册一个字段：`get` 读取字段，`set` 写入字段；对 `derived` 构造的桥接无效
+    pub fn field<V, G, S>(mut self, id: StateAspectId, get: G, set: S) -> Self
+    where
+        V: Clone + Send + Sync + 'static,
+        G: Fn(&T) -> &V + Send + Sync + 'static,
+        S: Fn(&mut T, V) + Send + Sync + 'static,
+    {
+        if let Mapping::Fields { fields, .. } = &mut self.mapping {
+            fields.push(BridgeField {
+                id,
+                type_id: TypeId::of::<V>(),
+                read: Arc::new(move |t| Arc::new(get(t).clone())),
+                write: Arc::new(move |t, value| match value.downcast_ref::<V>() {
+                    Some(v) => {
+                        set(t, v.clone());
+                        true
+                    }
+                    None => false,
+                }),
+            });
+        }
+        self
+    }
+
+    /// 桥接涉及的方面ID与取值类型
+    pub fn aspect_types(&self) -> Vec<(StateAspectId, TypeId)> {
+        match &self.mapping {
+            Mapping::Fields { fields, .. } => fields.iter().map(|f| (f.id, f.type_id)).collect(),
+            Mapping::Derived { types, .. } => types.clone(),
+        }
+    }
+
+    /// 把涉及的方面注册到蓝图
+    pub fn register_aspects(&self, blueprint: &mut StateMachineBlueprint) -> Result<(), StateZenError> {
+        for (id, value_type_id) in self.aspect_types() {
+            blueprint.add_aspect(StateAspect::new(id, value_type_id))?;
+        }
+        Ok(())
+    }
+
+    /// 转换为动态状态
+    pub fn to_state(&self, value: &T) -> State {
+        match &self.mapping {
+            Mapping::Fields { fields, .. } => fields.iter().map(|f| (f.id, (f.read)(value))).collect(),
+            Mapping::Derived { to, .. } => to(value),
+        }
+    }
+
+    /// 从动态状态读取，状态中多出的方面被忽略
+    pub fn from_state(&self, state: &State) -> Result<T, StateZenError> {
+        for (id, type_id) in self.aspect_types() {
+            match state.get(&id) {
+                None => return Err(StateZenError::MissingAspect(id)),
+                Some(value) if (**value).type_id() != type_id => return Err(StateZenError::AspectTypeMismatch(id)),
+                Some(_) => {}
+            }
+        }
+        match &self.mapping {
+            Mapping::Fields { base, fields } => {
+                let mut value = base();
+                for field in fields {
+                    if !(field.write)(&mut value, &state[&field.id]) {
+                        return Err(StateZenError::AspectTypeMismatch(field.id));
+                    }
+                }
+                Ok(value)
+            }
+            Mapping::Derived { from, types, .. } => {
+                from(state).ok_or(StateZenError::MissingAspect(types.first().map_or(0, |(id, _)| *id)))
+            }
+        }
+    }
+}
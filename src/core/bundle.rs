@@ -0,0 +1,113 @@
+//! 方面组：以单个方面存储的组件结构体
+
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::state_aspect::StateAspect;
+use super::state_in_range::StateInRange;
+use super::transfer::Transfer;
+use super::blueprint::StateMachineBlueprint;
+use super::runtime::State;
+use super::error::StateZenError;
+
+/// 方面组
+/// 把位置、速度、朝向这类总是一起读写的字段打包为一个结构体 `T`，整体存为一个方面，
+/// 通过字段投影构造守卫与转换。
+/// 需要每个字段各占一个方面时使用 `Aspects`
+pub struct AspectBundle<T> {
+    /// 方面ID
+    pub id: StateAspectId,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for AspectBundle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for AspectBundle<T> {}
+
+impl<T> AspectBundle<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// 以方面ID `id` 创建方面组
+    pub const fn new(id: StateAspectId) -> Self {
+        Self {
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 把方面组注册到蓝图
+    pub fn register(&self, blueprint: &mut StateMachineBlueprint) -> Result<(), StateZenError> {
+        blueprint.add_aspect(StateAspect::of::<T>(self.id))
+    }
+
+    /// 读取方面组的取值
+    pub fn get<'a>(&self, state: &'a State) -> Option<&'a T> {
+        state.get(&self.id)?.downcast_ref::<T>()
+    }
+
+    /// 写入方面组的取值
+    pub fn insert(&self, state: &mut State, value: T) {
+        state.insert(self.id, Arc::new(value));
+    }
+
+    /// 取值满足 `f` 的区域
+    pub fn when<F>(&self, f: F) -> StateInRange
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        let id = self.id;
+        StateInRange::new(move |s| s.get(&id).and_then(|v| v.downcast_ref::<T>()).is_some_and(&f))
+            .with_reads([id])
+    }
+
+    /// 字段等于 `value` 的区域，`field` 从结构体中投影出字段
+    pub fn field_eq<V, P>(&self, field: P, value: V) -> StateInRange
+    where
+        V: PartialEq + Send + Sync + 'static,
+        P: Fn(&T) -> V + Send + Sync + 'static,
+    {
+        self.when(move |t| field(t) == value)
+    }
+
+    /// 字段落在 `range` 内的区域
+    pub fn field_in<V, P, R>(&self, field: P, range: R) -> StateInRange
+    where
+        V: PartialOrd + Send + Sync + 'static,
+        P: Fn(&T) -> V + Send + Sync + 'static,
+        R: RangeBounds<V> + Send + Sync + 'static,
+    {
+        self.when(move |t| range.contains(&field(t)))
+    }
+
+    /// 整体替换取值的转换
+    pub fn set(&self, value: T) -> Transfer
+    where
+        T: PartialEq + std::fmt::Debug,
+    {
+        Transfer::set(self.id, value)
+    }
+
+    /// 原地修改取值的转换；方面缺失或类型不符时不做修改
+    pub fn update<F>(&self, f: F) -> Transfer
+    where
+        F: Fn(&mut T) + Send + Sync + 'static,
+    {
+        let id = self.id;
+        Transfer::new(move |s| {
+            let mut next = s.clone();
+            if let Some(value) = s.get(&id).and_then(|v| v.downcast_ref::<T>()) {
+                let mut value = value.clone();
+                f(&mut value);
+                next.insert(id, Arc::new(value));
+            }
+            next
+        })
+        .with_writes([id])
+    }
+}
@@ -0,0 +1,147 @@
+//! 规范状态集合上的位集区域归属
+
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::runtime::{State, AspectValue};
+use super::state_in_range::StateInRange;
+use super::blueprint::StateMachineBlueprint;
+
+/// 规范状态分类函数：返回与给定状态等价的规范状态下标，不属于任何规范状态时返回 `None`
+pub type CanonicalClassifier = Arc<dyn Fn(&State) -> Option<usize> + Send + Sync>;
+
+/// 有限的规范状态集合
+///
+/// 两个状态在 `aspects` 上取值相同即视为等价，分类函数把状态映射到等价的规范状态。
+/// 只读取 `aspects` 的观察者（读集合已知）可以预先算出在每个规范状态上的归属
+#[derive(Clone)]
+pub struct CanonicalStates {
+    aspects: Vec<StateAspectId>,
+    states: Vec<State>,
+    classify: CanonicalClassifier,
+}
+
+impl CanonicalStates {
+    /// 由规范状态与分类函数构造；`aspects` 为决定等价关系的方面
+    pub fn new<I, F>(aspects: I, states: Vec<State>, classify: F) -> Self
+    where
+        I: IntoIterator<Item = StateAspectId>,
+        F: Fn(&State) -> Option<usize> + Send + Sync + 'static,
+    {
+        Self {
+            aspects: aspects.into_iter().collect(),
+            states,
+            classify: Arc::new(classify),
+        }
+    }
+
+    /// 按单个方面的取值分类：状态的 `aspect_id` 与某个规范状态相等即等价于它
+    pub fn by_aspect<T>(aspect_id: StateAspectId, states: Vec<State>) -> Self
+    where
+        T: PartialEq + Send + Sync + 'static,
+    {
+        let keys: Vec<Option<AspectValue>> = states.iter().map(|s| s.get(&aspect_id).cloned()).collect();
+        Self::new([aspect_id], states, move |state| {
+            let value = state.get(&aspect_id)?.downcast_ref::<T>()?;
+            keys.iter()
+                .position(|k| k.as_ref().and_then(|k| k.downcast_ref::<T>()).is_some_and(|k| k == value))
+        })
+    }
+
+    /// 规范状态数量
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// 与给定状态等价的规范状态下标
+    pub fn classify(&self, state: &State) -> Option<usize> {
+        (self.classify)(state).filter(|i| *i < self.states.len())
+    }
+}
+
+/// 预先计算的观察者归属位集，位 `i` 对应蓝图中第 `i` 个观察者
+pub(crate) struct CanonicalMembership {
+    states: CanonicalStates,
+    /// 计算位集时的（蓝图修改代数, 观察者数量）
+    built_for: (u64, usize),
+    /// 可以查表的观察者（读集合落在规范方面内）
+    covered: Vec<u64>,
+    /// 是否全部观察者都可以查表
+    all_covered: bool,
+    /// 每个规范状态上的归属，未覆盖的观察者位为 0
+    bits: Vec<Vec<u64>>,
+}
+
+fn words(n: usize) -> usize {
+    n.div_ceil(64)
+}
+
+fn bit(set: &[u64], i: usize) -> bool {
+    set[i / 64] >> (i % 64) & 1 == 1
+}
+
+impl CanonicalMembership {
+    pub(crate) fn build(states: CanonicalStates, blueprint: &StateMachineBlueprint) -> Self {
+        let observers = blueprint.observer_slice();
+        let mut covered = vec![0u64; words(observers.len())];
+        for (i, observer) in observers.iter().enumerate() {
+            let covers = |p: &StateInRange| p.reads().is_some_and(|r| r.iter().all(|id| states.aspects.contains(id)));
+            if covers(&observer.region) && observer.active_when.as_ref().is_none_or(covers) {
+                covered[i / 64] |= 1 << (i % 64);
+            }
+        }
+        let bits = states
+            .states
+            .iter()
+            .map(|state| {
+                let mut set = vec![0u64; covered.len()];
+                for (i, observer) in observers.iter().enumerate() {
+                    if bit(&covered, i) && observer.contains(state) {
+                        set[i / 64] |= 1 << (i % 64);
+                    }
+                }
+                set
+            })
+            .collect();
+        let all_covered = (0..observers.len()).all(|i| bit(&covered, i));
+        Self { states, built_for: (blueprint.generation(), observers.len()), covered, all_covered, bits }
+    }
+
+    /// 蓝图修改代数或观察者数量变化后位集失效
+    pub(crate) fn is_stale(&self, blueprint: &StateMachineBlueprint) -> bool {
+        self.built_for != (blueprint.generation(), blueprint.observer_slice().len())
+    }
+
+    pub(crate) fn into_states(self) -> CanonicalStates {
+        self.states
+    }
+
+    pub(crate) fn index_of(&self, state: &State) -> Option<usize> {
+        self.states.classify(state)
+    }
+
+    /// 第 `observer` 个观察者在第 `index` 个规范状态上的归属，未覆盖时为 `None`
+    pub(crate) fn contains(&self, index: usize, observer: usize) -> Option<bool> {
+        bit(&self.covered, observer).then(|| bit(&self.bits[index], observer))
+    }
+
+    /// 从规范状态 `a` 到 `b` 归属发生变化的观察者（升序），只在全部观察者都被覆盖时可用
+    pub(crate) fn changed(&self, a: usize, b: usize) -> Option<Vec<usize>> {
+        if !self.all_covered {
+            return None;
+        }
+        let mut changed = Vec::new();
+        for (w, (x, y)) in self.bits[a].iter().zip(&self.bits[b]).enumerate() {
+            let mut diff = x ^ y;
+            while diff != 0 {
+                changed.push(w * 64 + diff.trailing_zeros() as usize);
+                diff &= diff - 1;
+            }
+        }
+        Some(changed)
+    }
+}
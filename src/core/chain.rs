@@ -0,0 +1,172 @@
+//! 转换链：一个事件依次执行多个转换函数
+
+use std::sync::Arc;
+use super::types::{TransitionId, EventId, StateAspectId};
+use super::state_in_range::StateInRange;
+use super::transfer::Transfer;
+use super::transition::{Transition, OnTranCallback};
+use super::event::EventTemplate;
+use super::runtime::State;
+
+/// 转换链中的一步
+#[derive(Clone)]
+struct ChainStep {
+    /// 执行该步之前中间状态必须满足的条件，`None` 表示无条件
+    requires: Option<StateInRange>,
+    transfer: Transfer,
+}
+
+/// 转换链
+///
+/// 把“消耗道具、施加增益、刷新属性”这类多步操作写成有序的转换函数列表，
+/// 构造为单个 `Transition`：各步依次作用于上一步的结果，整条链作为一次状态变更提交，
+/// 观察者只比较链开始前与结束后的状态。
+/// 任一步的前置条件不满足时整条链不生效，如同守卫不成立，事件可以选择其他转换
+#[derive(Clone)]
+pub struct TransitionChain {
+    id: TransitionId,
+    event_id: EventId,
+    guard: StateInRange,
+    steps: Vec<ChainStep>,
+    priority: i32,
+    on_tran: Option<OnTranCallback>,
+    emits: Vec<EventTemplate>,
+    ensures: Option<StateInRange>,
+}
+
+impl TransitionChain {
+    /// 创建一条空链，守卫为恒真
+    pub fn new(id: TransitionId, event_id: EventId) -> Self {
+        Self {
+            id,
+            event_id,
+            guard: StateInRange::always(),
+            steps: Vec::new(),
+            priority: 0,
+            on_tran: None,
+            emits: Vec::new(),
+            ensures: None,
+        }
+    }
+
+    /// 设置整条链的守卫，作用于链开始前的状态
+    pub fn with_guard(mut self, guard: StateInRange) -> Self {
+        self.guard = guard;
+        self
+    }
+
+    /// 设置优先级
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 设置链执行后的回调，参数为链开始前与结束后的状态
+    pub fn with_on_tran(mut self, on_tran: OnTranCallback) -> Self {
+        self.on_tran = Some(on_tran);
+        self
+    }
+
+    /// 追加链成功后发往事件汇的事件
+    pub fn with_emit(mut self, template: EventTemplate) -> Self {
+        self.emits.push(template);
+        self
+    }
+
+    /// 设置后置条件，作用于链结束后的状态
+    pub fn with_ensures(mut self, ensures: StateInRange) -> Self {
+        self.ensures = Some(ensures);
+        self
+    }
+
+    /// 追加一步
+    pub fn then(mut self, transfer: Transfer) -> Self {
+        self.steps.push(ChainStep {
+            requires: None,
+            transfer,
+        });
+        self
+    }
+
+    /// 追加一步，执行前中间状态必须位于 `requires` 内，否则整条链不生效
+    pub fn then_if(mut self, requires: StateInRange, transfer: Transfer) -> Self {
+        self.steps.push(ChainStep {
+            requires: Some(requires),
+            transfer,
+        });
+        self
+    }
+
+    /// 步数
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// 是否没有任何步骤
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// 从 `state` 开始依次执行各步，某步前置条件不满足时返回 `None`（不检查整条链的守卫）
+    pub fn run(&self, state: &State) -> Option<State> {
+        run_steps(&self.steps, state)
+    }
+
+    /// 构造为转换
+    ///
+    /// 没有前置条件时各步用 `Transfer::then` 串接，保留声明式结构；
+    /// 有前置条件时守卫会先试运行整条链，链因此执行两次
+    pub fn build(self) -> Transition {
+        let conditional = self.steps.iter().any(|s| s.requires.is_some());
+        let (guard, transfer) = if conditional {
+            let writes: Option<Vec<StateAspectId>> = self
+                .steps
+                .iter()
+                .map(|s| s.transfer.writes())
+                .try_fold(Vec::new(), |mut all, writes| {
+                    all.extend_from_slice(writes?);
+                    Some(all)
+                });
+            let steps: Arc<[ChainStep]> = self.steps.into();
+            let probe = steps.clone();
+            let guard = self.guard.and(StateInRange::new(move |s| run_steps(&probe, s).is_some()));
+            let mut transfer = Transfer::new(move |s| run_steps(&steps, s).unwrap_or_else(|| s.clone()));
+            if let Some(writes) = writes {
+                transfer = transfer.with_writes(writes);
+            }
+            (guard, transfer)
+        } else {
+            let transfer = self
+                .steps
+                .into_iter()
+                .map(|s| s.transfer)
+                .reduce(Transfer::then)
+                .unwrap_or_else(|| Transfer::new(State::clone).with_writes([]));
+            (self.guard, transfer)
+        };
+        Transition {
+            id: self.id,
+            event_id: self.event_id,
+            guard,
+            transfer,
+            priority: self.priority,
+            on_tran: self.on_tran,
+            emits: self.emits,
+            tag: None,
+            min_dwell: None,
+            ensures: self.ensures,
+            respond: None,
+        }
+    }
+}
+
+fn run_steps(steps: &[ChainStep], state: &State) -> Option<State> {
+    let mut current = state.clone();
+    for step in steps {
+        if step.requires.as_ref().is_some_and(|r| !r.contains(&current)) {
+            return None;
+        }
+        current = step.transfer.apply(&current);
+    }
+    Some(current)
+}
@@ -0,0 +1,124 @@
+//! 时钟：时间相关功能的统一时间来源
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 时钟
+/// 返回自时钟起点以来经过的时间。需要读取真实时间的功能都通过时钟获取，
+/// 测试与回放时换成 `ManualClock` 或 `RecordedClock` 即可得到确定的结果
+pub trait Clock: Send + Sync {
+    /// 当前时刻（自时钟起点以来经过的时间）
+    fn now(&self) -> Duration;
+}
+
+/// 系统时钟，起点为创建时刻
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    /// 以当前时刻为起点创建系统时钟
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// 手动时钟，只在调用 `advance` / `set` 时前进；克隆共享同一时刻
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// 创建一个停在零时刻的手动时钟
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 前进 `dt`
+    pub fn advance(&self, dt: Duration) {
+        *self.now.lock().unwrap() += dt;
+    }
+
+    /// 设为时刻 `now`
+    pub fn set(&self, now: Duration) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+enum RecordMode {
+    /// 读取内部时钟并记录
+    Record(Arc<dyn Clock>),
+    /// 按顺序回放记录的读数，下一个读数的下标
+    Replay(usize),
+}
+
+/// 可录制、可回放的时钟
+/// 录制时转发内部时钟并记下每次读数；回放时按相同顺序返回这些读数，读数用尽后停在最后一个
+pub struct RecordedClock {
+    mode: Mutex<RecordMode>,
+    readings: Mutex<Vec<Duration>>,
+}
+
+impl RecordedClock {
+    /// 录制 `inner` 的读数
+    pub fn recording(inner: Arc<dyn Clock>) -> Self {
+        Self {
+            mode: Mutex::new(RecordMode::Record(inner)),
+            readings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 回放录制的读数
+    pub fn replay(readings: Vec<Duration>) -> Self {
+        Self {
+            mode: Mutex::new(RecordMode::Replay(0)),
+            readings: Mutex::new(readings),
+        }
+    }
+
+    /// 全部读数（录制时为已记录的读数）
+    pub fn readings(&self) -> Vec<Duration> {
+        self.readings.lock().unwrap().clone()
+    }
+}
+
+impl Clock for RecordedClock {
+    fn now(&self) -> Duration {
+        let mut mode = self.mode.lock().unwrap();
+        let mut readings = self.readings.lock().unwrap();
+        match &mut *mode {
+            RecordMode::Record(inner) => {
+                let now = inner.now();
+                readings.push(now);
+                now
+            }
+            RecordMode::Replay(next) => {
+                let now = readings.get(*next).or(readings.last()).copied().unwrap_or_default();
+                *next += 1;
+                now
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! 方面取值的文本编解码
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::runtime::{State, AspectValue};
+use super::error::StateZenError;
+
+type EncodeFn = Arc<dyn Fn(&AspectValue) -> Option<String> + Send + Sync>;
+type DecodeFn = Arc<dyn Fn(&str) -> Option<AspectValue> + Send + Sync>;
+
+/// 状态编解码器
+/// 按方面ID注册取值与文本之间的转换，供持久化、同步等需要把状态写出进程的功能使用
+#[derive(Clone, Default)]
+pub struct StateCodec {
+    codecs: HashMap<StateAspectId, (EncodeFn, DecodeFn)>,
+}
+
+impl StateCodec {
+    /// 创建一个空编解码器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以 `Display` / `FromStr` 编解码取值类型为 `T` 的方面
+    pub fn register<T>(&mut self, id: StateAspectId) -> &mut Self
+    where
+        T: Display + FromStr + Send + Sync + 'static,
+    {
+        self.register_with::<T, _, _>(id, |v| v.to_string(), |s| s.parse().ok())
+    }
+
+    /// 以自定义函数编解码取值类型为 `T` 的方面
+    pub fn register_with<T, E, D>(&mut self, id: StateAspectId, encode: E, decode: D) -> &mut Self
+    where
+        T: Send + Sync + 'static,
+        E: Fn(&T) -> String + Send + Sync + 'static,
+        D: Fn(&str) -> Option<T> + Send + Sync + 'static,
+    {
+        let encode: EncodeFn = Arc::new(move |v| v.downcast_ref::<T>().map(&encode));
+        let decode: DecodeFn = Arc::new(move |s| decode(s).map(|v| Arc::new(v) as AspectValue));
+        self.codecs.insert(id, (encode, decode));
+        self
+    }
+
+    /// 是否注册了方面
+    pub fn contains(&self, id: StateAspectId) -> bool {
+        self.codecs.contains_key(&id)
+    }
+
+    /// 编码单个取值；未注册或类型不符时返回 `CodecFailed`
+    pub fn encode(&self, id: StateAspectId, value: &AspectValue) -> Result<String, StateZenError> {
+        self.codecs
+            .get(&id)
+            .and_then(|(encode, _)| encode(value))
+            .ok_or(StateZenError::CodecFailed(id))
+    }
+
+    /// 解码单个取值；未注册或解析失败时返回 `CodecFailed`
+    pub fn decode(&self, id: StateAspectId, text: &str) -> Result<AspectValue, StateZenError> {
+        self.codecs
+            .get(&id)
+            .and_then(|(_, decode)| decode(text))
+            .ok_or(StateZenError::CodecFailed(id))
+    }
+
+    /// 编码整个状态，结果按方面ID升序
+    pub fn encode_state(&self, state: &State) -> Result<Vec<(StateAspectId, String)>, StateZenError> {
+        let mut entries = state
+            .iter()
+            .map(|(id, value)| Ok((*id, self.encode(*id, value)?)))
+            .collect::<Result<Vec<_>, StateZenError>>()?;
+        entries.sort_unstable_by_key(|(id, _)| *id);
+        Ok(entries)
+    }
+
+    /// 解码整个状态
+    pub fn decode_state<'a, I>(&self, entries: I) -> Result<State, StateZenError>
+    where
+        I: IntoIterator<Item = (StateAspectId, &'a str)>,
+    {
+        entries
+            .into_iter()
+            .map(|(id, text)| Ok((id, self.decode(id, text)?)))
+            .collect()
+    }
+}
@@ -0,0 +1,238 @@
+//! 集合类型的方面：背包、增益列表等
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::state_aspect::StateAspect;
+use super::state_in_range::StateInRange;
+use super::transfer::Transfer;
+use super::blueprint::StateMachineBlueprint;
+use super::runtime::State;
+use super::error::StateZenError;
+
+/// 可作为集合方面取值的集合类型
+pub trait Collection: Clone + Default + Send + Sync + 'static {
+    /// 元素数量
+    fn len(&self) -> usize;
+
+    /// 是否为空
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 清空
+    fn clear(&mut self);
+}
+
+impl<T: Clone + Send + Sync + 'static> Collection for Vec<T> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+}
+
+impl<T: Eq + Hash + Clone + Send + Sync + 'static> Collection for HashSet<T> {
+    fn len(&self) -> usize {
+        HashSet::len(self)
+    }
+
+    fn clear(&mut self) {
+        HashSet::clear(self);
+    }
+}
+
+impl<K, V> Collection for HashMap<K, V>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn clear(&mut self) {
+        HashMap::clear(self);
+    }
+}
+
+/// 集合方面
+/// 取值类型为集合 `C` 的方面，提供常用的守卫与转换；守卫声明读集合、转换声明写集合。
+/// 转换在方面缺失时从空集合开始，类型不符时不做修改
+pub struct CollectionAspect<C> {
+    /// 方面ID
+    pub id: StateAspectId,
+    _marker: PhantomData<fn() -> C>,
+}
+
+/// 列表方面
+pub type VecAspect<T> = CollectionAspect<Vec<T>>;
+/// 集合方面
+pub type SetAspect<T> = CollectionAspect<HashSet<T>>;
+/// 映射方面
+pub type MapAspect<K, V> = CollectionAspect<HashMap<K, V>>;
+
+impl<C> Clone for CollectionAspect<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for CollectionAspect<C> {}
+
+impl<C: Collection> CollectionAspect<C> {
+    /// 以方面ID `id` 创建集合方面
+    pub const fn new(id: StateAspectId) -> Self {
+        Self {
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 把方面注册到蓝图，默认值为空集合
+    pub fn register(&self, blueprint: &mut StateMachineBlueprint) -> Result<(), StateZenError> {
+        blueprint.add_aspect(StateAspect::of::<C>(self.id).with_type_default::<C>())
+    }
+
+    /// 读取集合
+    pub fn get<'a>(&self, state: &'a State) -> Option<&'a C> {
+        state.get(&self.id)?.downcast_ref::<C>()
+    }
+
+    /// 集合满足 `f` 的区域
+    pub fn when<F>(&self, f: F) -> StateInRange
+    where
+        F: Fn(&C) -> bool + Send + Sync + 'static,
+    {
+        let id = self.id;
+        StateInRange::new(move |s| s.get(&id).and_then(|v| v.downcast_ref::<C>()).is_some_and(&f))
+            .with_reads([id])
+    }
+
+    /// 元素数量不少于 `n`
+    pub fn len_at_least(&self, n: usize) -> StateInRange {
+        self.when(move |c| c.len() >= n)
+    }
+
+    /// 元素数量不多于 `n`
+    pub fn len_at_most(&self, n: usize) -> StateInRange {
+        self.when(move |c| c.len() <= n)
+    }
+
+    /// 集合为空
+    pub fn is_empty(&self) -> StateInRange {
+        self.when(Collection::is_empty)
+    }
+
+    /// 原地修改集合的转换
+    pub fn modify<F>(&self, f: F) -> Transfer
+    where
+        F: Fn(&mut C) + Send + Sync + 'static,
+    {
+        let id = self.id;
+        Transfer::new(move |s| {
+            let mut collection = match s.get(&id) {
+                None => C::default(),
+                Some(value) => match value.downcast_ref::<C>() {
+                    Some(c) => c.clone(),
+                    None => return s.clone(),
+                },
+            };
+            f(&mut collection);
+            let mut next = s.clone();
+            next.insert(id, Arc::new(collection));
+            next
+        })
+        .with_writes([id])
+    }
+
+    /// 清空集合
+    pub fn clear(&self) -> Transfer {
+        self.modify(Collection::clear)
+    }
+}
+
+impl<T> CollectionAspect<Vec<T>>
+where
+    T: PartialEq + Clone + Send + Sync + 'static,
+{
+    /// 列表包含 `value`
+    pub fn contains(&self, value: T) -> StateInRange {
+        self.when(move |v| v.contains(&value))
+    }
+
+    /// 在末尾追加 `value`
+    pub fn push(&self, value: T) -> Transfer {
+        self.modify(move |v| v.push(value.clone()))
+    }
+
+    /// 移除第一个等于 `value` 的元素
+    pub fn remove(&self, value: T) -> Transfer {
+        self.modify(move |v| {
+            if let Some(i) = v.iter().position(|x| *x == value) {
+                v.remove(i);
+            }
+        })
+    }
+}
+
+impl<T> CollectionAspect<HashSet<T>>
+where
+    T: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    /// 集合包含 `value`
+    pub fn contains(&self, value: T) -> StateInRange {
+        self.when(move |s| s.contains(&value))
+    }
+
+    /// 加入 `value`
+    pub fn insert(&self, value: T) -> Transfer {
+        self.modify(move |s| {
+            s.insert(value.clone());
+        })
+    }
+
+    /// 移除 `value`
+    pub fn remove(&self, value: T) -> Transfer {
+        self.modify(move |s| {
+            s.remove(&value);
+        })
+    }
+}
+
+impl<K, V> CollectionAspect<HashMap<K, V>>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// 映射包含键 `key`
+    pub fn contains_key(&self, key: K) -> StateInRange {
+        self.when(move |m| m.contains_key(&key))
+    }
+
+    /// 键 `key` 对应的值满足 `f`；键不存在时为假
+    pub fn value_is<F>(&self, key: K, f: F) -> StateInRange
+    where
+        F: Fn(&V) -> bool + Send + Sync + 'static,
+    {
+        self.when(move |m| m.get(&key).is_some_and(&f))
+    }
+
+    /// 插入或替换键值
+    pub fn insert(&self, key: K, value: V) -> Transfer {
+        self.modify(move |m| {
+            m.insert(key.clone(), value.clone());
+        })
+    }
+
+    /// 移除键 `key`
+    pub fn remove(&self, key: K) -> Transfer {
+        self.modify(move |m| {
+            m.remove(&key);
+        })
+    }
+}
@@ -0,0 +1,224 @@
+//! 把蓝图编译为转换查找表
+
+use std::collections::{BTreeSet, HashMap};
+use super::types::{StateAspectId, EventId, TransitionId};
+use super::state_in_range::{GuardExpr, GuardValue};
+use super::transition::Transition;
+use super::blueprint::StateMachineBlueprint;
+use super::runtime::State;
+
+/// 单个事件查找表的格子数上限，超出时该事件的守卫全部在运行时求值
+const MAX_TABLE_CELLS: usize = 4096;
+
+/// 查找表格子中的候选转换（转换下标）
+#[derive(Clone, Copy, Debug)]
+enum Candidate {
+    /// 守卫在该格子上恒成立
+    Taken(usize),
+    /// 守卫无法查表，需要求值
+    Check(usize),
+}
+
+/// 一个事件的查找表
+struct EventTable {
+    /// 参与查表的枚举方面，第一个为最低位
+    aspects: Vec<StateAspectId>,
+    /// 每个格子的候选转换，按优先级从高到低、同优先级按蓝图顺序排列，
+    /// 遇到 `Taken` 即终止
+    cells: Vec<Vec<Candidate>>,
+    /// 表是否过大而全部退回运行时求值
+    oversized: bool,
+}
+
+/// 编译后的状态机
+///
+/// 只由等值比较（及其与、或、非）构成的声明式守卫被降为查找表：
+/// 守卫中只以等值出现的方面视为枚举方面，取值域为守卫中出现过的取值加上“其他”，
+/// 每个事件按相关枚举方面的取值组合预先算好选中的转换。
+/// 闭包、区间等无法查表的守卫照常求值，选择结果与运行时一致（不考虑最短停留时间）。
+///
+/// 只执行转换函数、规范化与派生方面，不含观察者与回调以外的运行时功能，适合热循环中的批量模拟
+pub struct CompiledMachine {
+    blueprint: StateMachineBlueprint,
+    transitions: Vec<Transition>,
+    domains: HashMap<StateAspectId, Vec<GuardValue>>,
+    tables: HashMap<EventId, EventTable>,
+    state: State,
+}
+
+/// 守卫能否查表：只含等值比较且比较的都是枚举方面
+fn lowerable(expr: &GuardExpr, domains: &HashMap<StateAspectId, Vec<GuardValue>>) -> bool {
+    match expr {
+        GuardExpr::Always | GuardExpr::Never => true,
+        GuardExpr::AspectEq { aspect, .. } => domains.contains_key(aspect),
+        GuardExpr::And(a, b) | GuardExpr::Or(a, b) => lowerable(a, domains) && lowerable(b, domains),
+        GuardExpr::Not(a) | GuardExpr::Named { inner: a, .. } => lowerable(a, domains),
+        GuardExpr::Range { .. } | GuardExpr::Opaque(_) => false,
+    }
+}
+
+/// 收集等值比较的方面与取值，以及以区间出现或出现在闭包中的方面
+fn collect(expr: &GuardExpr, eq: &mut HashMap<StateAspectId, Vec<GuardValue>>, other: &mut BTreeSet<StateAspectId>) {
+    match expr {
+        GuardExpr::Always | GuardExpr::Never | GuardExpr::Opaque(_) => {}
+        GuardExpr::AspectEq { aspect, value } => {
+            let values = eq.entry(*aspect).or_default();
+            if !values.contains(value) {
+                values.push(value.clone());
+            }
+        }
+        GuardExpr::Range { aspect, .. } => {
+            other.insert(*aspect);
+        }
+        GuardExpr::And(a, b) | GuardExpr::Or(a, b) => {
+            collect(a, eq, other);
+            collect(b, eq, other);
+        }
+        GuardExpr::Not(a) | GuardExpr::Named { inner: a, .. } => collect(a, eq, other),
+    }
+}
+
+/// 在查表格子上求值：`cell` 给出各枚举方面的取值下标，等于取值域长度表示“其他”
+fn eval_cell(expr: &GuardExpr, domains: &HashMap<StateAspectId, Vec<GuardValue>>, cell: &HashMap<StateAspectId, usize>) -> bool {
+    match expr {
+        GuardExpr::Always => true,
+        GuardExpr::AspectEq { aspect, value } => domains[aspect].get(cell[aspect]).is_some_and(|v| v == value),
+        GuardExpr::And(a, b) => eval_cell(a, domains, cell) && eval_cell(b, domains, cell),
+        GuardExpr::Or(a, b) => eval_cell(a, domains, cell) || eval_cell(b, domains, cell),
+        GuardExpr::Not(a) => !eval_cell(a, domains, cell),
+        GuardExpr::Named { inner, .. } => eval_cell(inner, domains, cell),
+        GuardExpr::Never | GuardExpr::Range { .. } | GuardExpr::Opaque(_) => false,
+    }
+}
+
+impl StateMachineBlueprint {
+    /// 编译为查找表驱动的状态机，`initial_state` 中的派生方面会被重新计算
+    pub fn compile(&self, initial_state: State) -> CompiledMachine {
+        let mut blueprint = self.clone();
+        blueprint.retain_enabled();
+
+        let mut domains = HashMap::new();
+        let mut excluded = BTreeSet::new();
+        for t in blueprint.transitions() {
+            collect(t.guard.expr(), &mut domains, &mut excluded);
+        }
+        domains.retain(|id, _| !excluded.contains(id));
+
+        let transitions: Vec<Transition> = blueprint.transitions().cloned().collect();
+        let mut by_event: HashMap<EventId, Vec<usize>> = HashMap::new();
+        for (i, t) in transitions.iter().enumerate() {
+            by_event.entry(t.event_id).or_default().push(i);
+        }
+        let tables = by_event
+            .into_iter()
+            .map(|(event_id, mut order)| {
+                // 稳定排序：同优先级保持蓝图顺序
+                order.sort_by_key(|i| std::cmp::Reverse(transitions[*i].priority));
+                (event_id, build_table(&transitions, order, &domains))
+            })
+            .collect();
+
+        let state = blueprint.derive(initial_state);
+        CompiledMachine { blueprint, transitions, domains, tables, state }
+    }
+}
+
+fn build_table(transitions: &[Transition], order: Vec<usize>, domains: &HashMap<StateAspectId, Vec<GuardValue>>) -> EventTable {
+    let lowered: Vec<bool> = order.iter().map(|i| lowerable(transitions[*i].guard.expr(), domains)).collect();
+    let mut aspects = BTreeSet::new();
+    for (i, _) in order.iter().zip(&lowered).filter(|(_, l)| **l) {
+        let mut eq = HashMap::new();
+        collect(transitions[*i].guard.expr(), &mut eq, &mut BTreeSet::new());
+        aspects.extend(eq.into_keys());
+    }
+    let aspects: Vec<StateAspectId> = aspects.into_iter().collect();
+    let size = aspects
+        .iter()
+        .try_fold(1usize, |n, id| n.checked_mul(domains[id].len() + 1))
+        .filter(|n| *n <= MAX_TABLE_CELLS);
+    let Some(size) = size else {
+        // 表太大，全部退回运行时求值
+        let cells = vec![order.iter().map(|i| Candidate::Check(*i)).collect()];
+        return EventTable { aspects: Vec::new(), cells, oversized: true };
+    };
+
+    let cells = (0..size)
+        .map(|mut index| {
+            let mut cell = HashMap::new();
+            for id in &aspects {
+                let radix = domains[id].len() + 1;
+                cell.insert(*id, index % radix);
+                index /= radix;
+            }
+            let mut candidates = Vec::new();
+            for (i, lowered) in order.iter().zip(&lowered) {
+                if !lowered {
+                    candidates.push(Candidate::Check(*i));
+                } else if eval_cell(transitions[*i].guard.expr(), domains, &cell) {
+                    candidates.push(Candidate::Taken(*i));
+                    break;
+                }
+            }
+            candidates
+        })
+        .collect();
+    EventTable { aspects, cells, oversized: false }
+}
+
+impl CompiledMachine {
+    /// 当前状态
+    pub fn current_state(&self) -> &State {
+        &self.state
+    }
+
+    /// 取出当前状态
+    pub fn into_state(self) -> State {
+        self.state
+    }
+
+    /// 能查表的守卫数量与需要运行时求值的守卫数量
+    pub fn lowered_guards(&self) -> (usize, usize) {
+        let lowered = self
+            .transitions
+            .iter()
+            .filter(|t| !self.tables[&t.event_id].oversized && lowerable(t.guard.expr(), &self.domains))
+            .count();
+        (lowered, self.transitions.len() - lowered)
+    }
+
+    /// 枚举方面取值在取值域中的下标，不在取值域中（或缺失）时为取值域长度
+    fn domain_index(&self, aspect: StateAspectId, state: &State) -> usize {
+        let domain = &self.domains[&aspect];
+        state
+            .get(&aspect)
+            .and_then(|value| domain.iter().position(|v| v.eq_value(value)))
+            .unwrap_or(domain.len())
+    }
+
+    /// 在给定状态下为事件选择转换
+    pub fn select(&self, event_id: EventId, state: &State) -> Option<&Transition> {
+        let table = self.tables.get(&event_id)?;
+        let mut index = 0;
+        for id in table.aspects.iter().rev() {
+            index = index * (self.domains[id].len() + 1) + self.domain_index(*id, state);
+        }
+        table.cells[index].iter().find_map(|candidate| match *candidate {
+            Candidate::Taken(i) => Some(&self.transitions[i]),
+            Candidate::Check(i) => Some(&self.transitions[i]).filter(|t| t.guard.contains(state)),
+        })
+    }
+
+    /// 处理事件：选中转换时执行转换函数与 OnTran 回调，返回执行的转换
+    pub fn handle_event(&mut self, event_id: EventId) -> Option<TransitionId> {
+        let transition = self.select(event_id, &self.state)?;
+        // 改写受保护方面而被拒绝的转换按未选中处理
+        let next = transition.transfer.try_apply(&self.state)?;
+        let next = self.blueprint.normalize(&self.state, next);
+        if let Some(on_tran) = &transition.on_tran {
+            on_tran(&self.state, &next);
+        }
+        let id = transition.id;
+        self.state = next;
+        Some(id)
+    }
+}
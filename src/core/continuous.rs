@@ -0,0 +1,37 @@
+//! 连续转换（随时间推进的状态变化）
+
+use std::sync::Arc;
+use std::time::Duration;
+use super::state_in_range::StateInRange;
+use super::runtime::State;
+
+/// 连续转换函数：（当前状态，时间步长）-> 新状态
+type ContinuousFn = dyn Fn(&State, Duration) -> State + 'static + Send + Sync;
+
+/// 连续转换
+/// 状态位于 `region` 内时，每次 `tick(dt)` 都会应用一次，
+/// 用于建模饱食度随时间衰减、位置按速度积分等连续动态
+#[derive(Clone)]
+pub struct ContinuousTransfer {
+    /// 生效区域
+    pub region: StateInRange,
+    func: Arc<ContinuousFn>,
+}
+
+impl ContinuousTransfer {
+    /// 创建一个新的连续转换
+    pub fn new<F>(region: StateInRange, f: F) -> Self
+    where
+        F: Fn(&State, Duration) -> State + 'static + Send + Sync,
+    {
+        Self {
+            region,
+            func: Arc::new(f),
+        }
+    }
+
+    /// 以时间步长 `dt` 应用连续转换
+    pub fn apply(&self, state: &State, dt: Duration) -> State {
+        (self.func)(state, dt)
+    }
+}
@@ -0,0 +1,73 @@
+//! 增量快照：只同步变化的方面
+
+use super::types::StateAspectId;
+use super::codec::StateCodec;
+use super::drive::StateSnapshot;
+use super::error::StateZenError;
+
+/// 两个快照之间的增量
+///
+/// 只含版本号变化的方面，取值按编解码器编码为文本，
+/// 适合服务器为大量状态机实例广播紧凑的状态更新
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateDelta {
+    /// 变化的方面：（方面ID，新版本号，编码后的取值），按方面ID升序
+    pub changed: Vec<(StateAspectId, u64, String)>,
+    /// 被移除的方面，按方面ID升序
+    pub removed: Vec<StateAspectId>,
+}
+
+impl StateDelta {
+    /// 增量是否为空
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl StateSnapshot {
+    /// 相对较早的快照 `older` 的增量
+    ///
+    /// 版本号比 `older` 新（或 `older` 中没有）的方面被编码进增量；
+    /// 变化的方面未在编解码器中注册时返回 `CodecFailed`
+    pub fn delta_since(&self, older: &StateSnapshot, codec: &StateCodec) -> Result<StateDelta, StateZenError> {
+        // `versions` 按方面ID有序，`changed` 因此也是有序的
+        let mut changed = Vec::new();
+        for (id, version) in &self.versions {
+            if older.versions.get(id).is_some_and(|v| v >= version) && older.state.contains_key(id) {
+                continue;
+            }
+            if let Some(value) = self.state.get(id) {
+                changed.push((*id, *version, codec.encode(*id, value)?));
+            }
+        }
+        let mut removed: Vec<StateAspectId> = older
+            .state
+            .keys()
+            .filter(|id| !self.state.contains_key(id))
+            .copied()
+            .collect();
+        removed.sort_unstable();
+        Ok(StateDelta { changed, removed })
+    }
+
+    /// 应用增量，得到新的快照
+    ///
+    /// 只覆盖版本号比本快照新的方面，重复或乱序到达的增量不会让状态倒退；
+    /// 被移除的方面保留最后的版本号作为墓碑，迟到的旧增量不会让它复活。
+    /// 取值无法解码时返回 `CodecFailed`，本快照不变
+    pub fn apply_delta(&self, delta: &StateDelta, codec: &StateCodec) -> Result<StateSnapshot, StateZenError> {
+        let mut next = self.clone();
+        for (id, version, text) in &delta.changed {
+            if next.versions.get(id).is_some_and(|v| v >= version) {
+                continue;
+            }
+            next.state.insert(*id, codec.decode(*id, text)?);
+            next.versions.insert(*id, *version);
+        }
+        for id in &delta.removed {
+            next.state.remove(id);
+        }
+        next.event_id = None;
+        Ok(next)
+    }
+}
@@ -0,0 +1,49 @@
+//! 派生方面
+
+use std::any::TypeId;
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::runtime::{State, AspectValue};
+
+/// 派生方面的计算函数
+type DeriveFn = dyn Fn(&State) -> AspectValue + Send + Sync;
+
+/// 派生方面
+/// 取值由其他方面计算得出，每次转换（含连续转换）后自动重新计算。
+/// 守卫与观察者可以像普通方面一样读取它，转换对它的写入会被计算结果覆盖
+#[derive(Clone)]
+pub struct DerivedAspect {
+    /// 方面ID
+    pub id: StateAspectId,
+    /// 计算依赖的方面，只有它们被写入时才重新计算；为空表示每次写入都重新计算
+    pub inputs: Vec<StateAspectId>,
+    /// 取值类型的TypeId
+    value_type_id: TypeId,
+    compute: Arc<DeriveFn>,
+}
+
+impl DerivedAspect {
+    /// 创建一个取值类型为 `T` 的派生方面
+    pub fn new<T, F>(id: StateAspectId, inputs: impl IntoIterator<Item = StateAspectId>, f: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&State) -> T + Send + Sync + 'static,
+    {
+        Self {
+            id,
+            inputs: inputs.into_iter().collect(),
+            value_type_id: TypeId::of::<T>(),
+            compute: Arc::new(move |state| Arc::new(f(state))),
+        }
+    }
+
+    /// 取值类型的TypeId
+    pub fn value_type_id(&self) -> TypeId {
+        self.value_type_id
+    }
+
+    /// 按当前状态计算取值
+    pub fn compute(&self, state: &State) -> AspectValue {
+        (self.compute)(state)
+    }
+}
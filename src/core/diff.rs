@@ -0,0 +1,218 @@
+//! 蓝图版本对比
+
+use std::collections::BTreeMap;
+use std::fmt;
+use super::types::{StateAspectId, EventId, TransitionId, ObserverId};
+use super::blueprint::StateMachineBlueprint;
+use super::transition::Transition;
+use super::state_observer::StateObserver;
+use super::registry::AspectRegistry;
+
+/// 一个字段的变化
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    /// 字段名
+    pub field: &'static str,
+    /// 旧值的描述
+    pub before: String,
+    /// 新值的描述
+    pub after: String,
+}
+
+/// 一类条目的增删改
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemDiff<Id> {
+    /// 新增的ID（升序）
+    pub added: Vec<Id>,
+    /// 删除的ID（升序）
+    pub removed: Vec<Id>,
+    /// 修改的ID及其字段变化（按ID升序）
+    pub changed: Vec<(Id, Vec<FieldChange>)>,
+}
+
+impl<Id> Default for ItemDiff<Id> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl<Id> ItemDiff<Id> {
+    /// 是否没有任何变化
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// 两个蓝图之间的差异
+///
+/// 守卫与转换函数按结构描述比较：由 `aspect_eq` / `set` 等构造的部分逐项比较，
+/// 闭包构造的部分无法比较内容，视为相同
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlueprintDiff {
+    /// 方面
+    pub aspects: ItemDiff<StateAspectId>,
+    /// 事件
+    pub events: ItemDiff<EventId>,
+    /// 转换
+    pub transitions: ItemDiff<TransitionId>,
+    /// 观察者
+    pub observers: ItemDiff<ObserverId>,
+    /// 方面名称，用于输出
+    names: BTreeMap<StateAspectId, String>,
+}
+
+impl BlueprintDiff {
+    /// 是否没有任何变化
+    pub fn is_empty(&self) -> bool {
+        self.aspects.is_empty() && self.events.is_empty() && self.transitions.is_empty() && self.observers.is_empty()
+    }
+}
+
+fn diff_items<Id, T, F>(before: BTreeMap<Id, &T>, after: BTreeMap<Id, &T>, compare: F) -> ItemDiff<Id>
+where
+    Id: Ord + Copy,
+    F: Fn(&T, &T) -> Vec<FieldChange>,
+{
+    let mut diff = ItemDiff::default();
+    for (id, old) in &before {
+        match after.get(id) {
+            Some(new) => {
+                let changes = compare(old, new);
+                if !changes.is_empty() {
+                    diff.changed.push((*id, changes));
+                }
+            }
+            None => diff.removed.push(*id),
+        }
+    }
+    diff.added = after.keys().filter(|id| !before.contains_key(id)).copied().collect();
+    diff
+}
+
+/// 描述不同时记录一个字段变化
+fn field(changes: &mut Vec<FieldChange>, field: &'static str, before: String, after: String) {
+    if before != after {
+        changes.push(FieldChange { field, before, after });
+    }
+}
+
+fn optional<T: fmt::Debug>(value: &Option<T>) -> String {
+    value.as_ref().map_or("-".to_string(), |v| format!("{v:?}"))
+}
+
+fn compare_transitions(registry: &AspectRegistry, a: &Transition, b: &Transition) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    field(&mut changes, "event", a.event_id.to_string(), b.event_id.to_string());
+    field(&mut changes, "priority", a.priority.to_string(), b.priority.to_string());
+    field(&mut changes, "guard", a.guard.describe_with(registry), b.guard.describe_with(registry));
+    field(&mut changes, "transfer", a.transfer.describe_with(registry), b.transfer.describe_with(registry));
+    let emits = |t: &Transition| format!("{:?}", t.emits.iter().map(|e| e.event_id).collect::<Vec<_>>());
+    field(&mut changes, "emits", emits(a), emits(b));
+    field(&mut changes, "tag", optional(&a.tag), optional(&b.tag));
+    field(&mut changes, "min_dwell", optional(&a.min_dwell), optional(&b.min_dwell));
+    let ensures = |t: &Transition| t.ensures.as_ref().map_or("-".to_string(), |e| e.describe_with(registry));
+    field(&mut changes, "ensures", ensures(a), ensures(b));
+    changes
+}
+
+fn compare_observers(registry: &AspectRegistry, a: &StateObserver, b: &StateObserver) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    field(&mut changes, "region", a.region.describe_with(registry), b.region.describe_with(registry));
+    let active_when = |o: &StateObserver| o.active_when.as_ref().map_or("-".to_string(), |r| r.describe_with(registry));
+    field(&mut changes, "active_when", active_when(a), active_when(b));
+    field(&mut changes, "priority", a.priority.to_string(), b.priority.to_string());
+    field(&mut changes, "tag", optional(&a.tag), optional(&b.tag));
+    changes
+}
+
+impl StateMachineBlueprint {
+    /// 对比当前蓝图（旧）与 `other`（新），方面以 `#<id>` 表示
+    pub fn diff(&self, other: &Self) -> BlueprintDiff {
+        self.diff_with(other, &AspectRegistry::new())
+    }
+
+    /// 对比当前蓝图（旧）与 `other`（新），方面名称取自注册表
+    pub fn diff_with(&self, other: &Self, registry: &AspectRegistry) -> BlueprintDiff {
+        let aspects = diff_items(
+            self.aspects().map(|a| (a.id, a)).collect(),
+            other.aspects().map(|a| (a.id, a)).collect(),
+            |a, b| {
+                let mut changes = Vec::new();
+                field(&mut changes, "type", format!("{:?}", a.value_type_id), format!("{:?}", b.value_type_id));
+                changes
+            },
+        );
+        let events = diff_items(
+            self.events().map(|e| (e.id, e)).collect(),
+            other.events().map(|e| (e.id, e)).collect(),
+            |a, b| {
+                let mut changes = Vec::new();
+                field(&mut changes, "payload", format!("{:?}", a.payload_type_id), format!("{:?}", b.payload_type_id));
+                changes
+            },
+        );
+        let transitions = diff_items(
+            self.transitions().map(|t| (t.id, t)).collect(),
+            other.transitions().map(|t| (t.id, t)).collect(),
+            |a, b| compare_transitions(registry, a, b),
+        );
+        let observers = diff_items(
+            self.observers().map(|o| (o.id, o)).collect(),
+            other.observers().map(|o| (o.id, o)).collect(),
+            |a, b| compare_observers(registry, a, b),
+        );
+
+        let names = aspects
+            .added
+            .iter()
+            .chain(&aspects.removed)
+            .chain(aspects.changed.iter().map(|(id, _)| id))
+            .filter(|id| registry.get(**id).is_some())
+            .map(|id| (*id, registry.name(*id)))
+            .collect();
+        BlueprintDiff {
+            aspects,
+            events,
+            transitions,
+            observers,
+            names,
+        }
+    }
+}
+
+/// 按 `+ 新增`、`- 删除`、`~ 修改` 逐行输出
+impl fmt::Display for BlueprintDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn section<Id: Copy + fmt::Display>(
+            f: &mut fmt::Formatter<'_>,
+            kind: &str,
+            diff: &ItemDiff<Id>,
+            label: impl Fn(Id) -> String,
+        ) -> fmt::Result {
+            for id in &diff.added {
+                writeln!(f, "+ {kind} {}", label(*id))?;
+            }
+            for id in &diff.removed {
+                writeln!(f, "- {kind} {}", label(*id))?;
+            }
+            for (id, changes) in &diff.changed {
+                for change in changes {
+                    writeln!(f, "~ {kind} {} {}: {} -> {}", label(*id), change.field, change.before, change.after)?;
+                }
+            }
+            Ok(())
+        }
+
+        section(f, "aspect", &self.aspects, |id| match self.names.get(&id) {
+            Some(name) => format!("{name}({id})"),
+            None => id.to_string(),
+        })?;
+        section(f, "event", &self.events, |id| id.to_string())?;
+        section(f, "transition", &self.transitions, |id| id.to_string())?;
+        section(f, "observer", &self.observers, |id| id.to_string())
+    }
+}
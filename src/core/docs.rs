@@ -0,0 +1,165 @@
+//! 蓝图文档生成
+
+use std::any::TypeId;
+use std::fmt::Write;
+use super::blueprint::StateMachineBlueprint;
+use super::registry::AspectRegistry;
+use super::state_in_range::StateInRange;
+use super::transition::Transition;
+
+/// 表格单元格中的 `|` 需要转义
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn code(text: &str) -> String {
+    format!("`{}`", cell(text))
+}
+
+fn region(region: &StateInRange, registry: &AspectRegistry) -> String {
+    code(&region.describe_with(registry))
+}
+
+impl StateMachineBlueprint {
+    /// 生成描述蓝图的 Markdown 文档
+    ///
+    /// 依次列出方面（类型、默认值、约束）、事件、转换（守卫、优先级、效果）与观察者区域，
+    /// 各部分按ID升序，适合把蓝图当作规格说明评审与存档。
+    /// 名称、类型与默认值取自注册表，未注册的方面以 `#<id>` 表示；闭包构造的守卫与效果显示为 `<closure>`
+    pub fn generate_docs(&self, registry: &AspectRegistry) -> String {
+        let mut doc = String::new();
+        let _ = self.write_docs(&mut doc, registry);
+        doc
+    }
+
+    fn write_docs(&self, doc: &mut String, registry: &AspectRegistry) -> std::fmt::Result {
+        writeln!(doc, "# 状态机蓝图")?;
+        writeln!(doc)?;
+        writeln!(doc, "版本：{}", self.version())?;
+
+        writeln!(doc)?;
+        writeln!(doc, "## 方面")?;
+        writeln!(doc)?;
+        writeln!(doc, "| ID | 名称 | 类型 | 默认值 | 约束 |")?;
+        writeln!(doc, "|---|---|---|---|---|")?;
+        let mut aspects: Vec<_> = self.aspects().collect();
+        aspects.sort_by_key(|a| a.id);
+        for aspect in aspects {
+            let info = registry.get(aspect.id);
+            let type_name = info.map_or("-".to_string(), |i| i.type_name());
+            let default = match (&aspect.default, info) {
+                (Some(default), Some(info)) => code(&info.format(&default())),
+                (Some(_), None) => "有".to_string(),
+                (None, _) => "-".to_string(),
+            };
+            let mut constraints = Vec::new();
+            if aspect.validator.is_some() {
+                constraints.push("取值校验");
+            }
+            if self.is_derived(aspect.id) {
+                constraints.push("派生");
+            }
+            if self.protected_aspects().any(|id| id == aspect.id) {
+                constraints.push("受保护");
+            }
+            let constraints = if constraints.is_empty() { "-".to_string() } else { constraints.join("、") };
+            writeln!(
+                doc,
+                "| {} | {} | {} | {default} | {constraints} |",
+                aspect.id,
+                cell(&registry.name(aspect.id)),
+                code(&type_name),
+            )?;
+        }
+
+        writeln!(doc)?;
+        writeln!(doc, "## 事件")?;
+        writeln!(doc)?;
+        writeln!(doc, "| ID | 负载 | 触发的转换 | 由转换发出 |")?;
+        writeln!(doc, "|---|---|---|---|")?;
+        let mut events: Vec<_> = self.events().collect();
+        events.sort_by_key(|e| e.id);
+        for event in events {
+            let payload = if event.payload_type_id == TypeId::of::<()>() { "无" } else { "有" };
+            let ids = |transitions: Vec<&Transition>| {
+                let mut ids: Vec<String> = transitions.iter().map(|t| t.id.to_string()).collect();
+                ids.sort();
+                if ids.is_empty() { "-".to_string() } else { ids.join(", ") }
+            };
+            let triggers = ids(self.transitions_for_event(event.id).collect());
+            let emitters = ids(self
+                .transitions()
+                .filter(|t| t.emits.iter().any(|e| e.event_id == event.id))
+                .collect());
+            writeln!(doc, "| {} | {payload} | {triggers} | {emitters} |", event.id)?;
+        }
+
+        writeln!(doc)?;
+        writeln!(doc, "## 转换")?;
+        let mut transitions: Vec<_> = self.transitions().collect();
+        transitions.sort_by_key(|t| t.id);
+        for t in transitions {
+            writeln!(doc)?;
+            writeln!(doc, "### 转换 {}", t.id)?;
+            writeln!(doc)?;
+            writeln!(doc, "- 事件：{}", t.event_id)?;
+            writeln!(doc, "- 优先级：{}", t.priority)?;
+            writeln!(doc, "- 守卫：{}", region(&t.guard, registry))?;
+            writeln!(doc, "- 效果：{}", code(&t.transfer.describe_with(registry)))?;
+            if let Some(ensures) = &t.ensures {
+                writeln!(doc, "- 后置条件：{}", region(ensures, registry))?;
+            }
+            if !t.emits.is_empty() {
+                let emits: Vec<String> = t.emits.iter().map(|e| e.event_id.to_string()).collect();
+                writeln!(doc, "- 发出事件：{}", emits.join(", "))?;
+            }
+            if let Some(dwell) = t.min_dwell {
+                writeln!(doc, "- 最短停留：{dwell:?}")?;
+            }
+            if let Some(tag) = &t.tag {
+                writeln!(doc, "- 标签：{tag}")?;
+            }
+        }
+
+        writeln!(doc)?;
+        writeln!(doc, "## 观察者")?;
+        writeln!(doc)?;
+        writeln!(doc, "| ID | 区域 | 优先级 | 回调 |")?;
+        writeln!(doc, "|---|---|---|---|")?;
+        let mut observers: Vec<_> = self.observers().collect();
+        observers.sort_by_key(|o| o.id);
+        for o in observers {
+            let callbacks: Vec<&str> = [
+                (o.on_enter.is_some(), "进入"),
+                (o.on_exit.is_some(), "退出"),
+                (o.on_enter_consume.is_some(), "消费"),
+            ]
+            .into_iter()
+            .filter_map(|(present, name)| present.then_some(name))
+            .collect();
+            let callbacks = if callbacks.is_empty() { "-".to_string() } else { callbacks.join("、") };
+            writeln!(doc, "| {} | {} | {} | {callbacks} |", o.id, region(&o.region, registry), o.priority)?;
+        }
+
+        let mut edges: Vec<_> = self.edge_observers().collect();
+        if !edges.is_empty() {
+            edges.sort_by_key(|e| e.id);
+            writeln!(doc)?;
+            writeln!(doc, "## 边沿观察者")?;
+            writeln!(doc)?;
+            writeln!(doc, "| ID | 条件 |")?;
+            writeln!(doc, "|---|---|")?;
+            for e in edges {
+                writeln!(doc, "| {} | {} |", e.id, region(&e.condition, registry))?;
+            }
+        }
+
+        if let Some(final_region) = self.final_region() {
+            writeln!(doc)?;
+            writeln!(doc, "## 终止区域")?;
+            writeln!(doc)?;
+            writeln!(doc, "{}", region(final_region, registry))?;
+        }
+        Ok(())
+    }
+}
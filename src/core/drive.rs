@@ -0,0 +1,83 @@
+//! 以迭代器 / 流的方式驱动状态机
+
+use std::collections::BTreeMap;
+use super::types::{StateAspectId, EventId};
+use super::event::EventInstance;
+use super::runtime::{RuntimeStateMachine, State};
+
+/// 处理完一个事件后的状态快照
+#[derive(Clone)]
+pub struct StateSnapshot {
+    /// 处理的事件，`None` 表示状态变化不是由事件引起（如 `tick` 推进的连续转换）
+    pub event_id: Option<EventId>,
+    /// 处理后的状态
+    pub state: State,
+    /// 状态中各方面的版本号，见 `RuntimeStateMachine::aspect_version`；
+    /// 经 `apply_delta` 得到的快照还保留已移除方面的版本号（墓碑）
+    pub versions: BTreeMap<StateAspectId, u64>,
+}
+
+/// `RuntimeStateMachine::drive` 返回的迭代器
+/// 每次迭代处理一个事件，产出处理后的状态快照
+pub struct Drive<'a, I> {
+    runtime: &'a mut RuntimeStateMachine,
+    events: I,
+}
+
+impl<I> Iterator for Drive<'_, I>
+where
+    I: Iterator<Item = EventInstance>,
+{
+    type Item = StateSnapshot;
+
+    fn next(&mut self) -> Option<StateSnapshot> {
+        let event = self.events.next()?;
+        Some(self.runtime.step(event))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.events.size_hint()
+    }
+}
+
+impl RuntimeStateMachine {
+    /// 依次处理 `events`，惰性地产出每个事件处理后的状态快照
+    /// 事件只在迭代时处理，未消费的部分不会影响状态机
+    pub fn drive<I>(&mut self, events: I) -> Drive<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = EventInstance>,
+    {
+        Drive {
+            runtime: self,
+            events: events.into_iter(),
+        }
+    }
+
+    /// `drive` 的异步版本：每从 `events` 取到一个事件就处理并产出状态快照
+    #[cfg(feature = "stream")]
+    pub fn drive_stream<S>(&mut self, events: S) -> impl futures::Stream<Item = StateSnapshot>
+    where
+        S: futures::Stream<Item = EventInstance>,
+    {
+        use futures::StreamExt;
+        events.map(move |event| self.step(event))
+    }
+
+    fn step(&mut self, event: EventInstance) -> StateSnapshot {
+        let event_id = event.event_id;
+        self.handle_event(event_id, event.payload);
+        StateSnapshot {
+            event_id: Some(event_id),
+            ..self.snapshot()
+        }
+    }
+
+    /// 当前状态及各方面版本号的快照，`event_id` 为 `None`
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            event_id: None,
+            state: self.current_state.clone(),
+            versions: self.current_state.keys().map(|id| (*id, self.aspect_version(*id))).collect(),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! 条件边沿观察者
+
+use std::sync::Arc;
+use super::types::ObserverId;
+use super::state_in_range::StateInRange;
+use super::runtime::State;
+
+/// 边沿回调函数，参数为（前一状态，后一状态）
+pub type EdgeCallback = Arc<dyn Fn(&State, &State) + Send + Sync>;
+
+/// 条件边沿观察者
+/// 在相邻两个状态之间，条件由假变真（上升沿）或由真变假（下降沿）时触发回调，
+/// 适用于“生命值跌破 20%”这类阈值告警
+#[derive(Clone)]
+pub struct EdgeObserver {
+    /// 观察者的唯一标识符
+    pub id: ObserverId,
+    /// 被观察的条件
+    pub condition: StateInRange,
+    /// 条件由假变真时的回调函数
+    pub on_rising: Option<EdgeCallback>,
+    /// 条件由真变假时的回调函数
+    pub on_falling: Option<EdgeCallback>,
+}
@@ -0,0 +1,113 @@
+//! 错误类型
+
+use std::fmt;
+use super::types::{StateAspectId, EventId, TransitionId, ObserverId, MachineId};
+use super::supervisor::ChildId;
+use super::label::MachineLabel;
+
+/// 状态机框架的错误类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateZenError {
+    /// 蓝图声明的方面在状态中没有取值
+    MissingAspect(StateAspectId),
+    /// 方面取值的类型与蓝图声明不一致
+    AspectTypeMismatch(StateAspectId),
+    /// 方面ID已被另一个类型的方面占用
+    DuplicateAspect(StateAspectId),
+    /// 事件ID已被另一个payload类型的事件占用
+    DuplicateEvent(EventId),
+    /// 转换ID重复
+    DuplicateTransition(TransitionId),
+    /// 观察者ID重复
+    DuplicateObserver(ObserverId),
+    /// 引用了蓝图中未声明的事件
+    UnknownEvent(EventId),
+    /// 迁移时方面取值转换失败（旧值缺失或类型不符）
+    MigrationFailed(StateAspectId),
+    /// 找不到从旧版本到新版本的迁移路径
+    NoMigrationPath { from: u32, to: u32 },
+    /// 引用了未注册的状态机模板
+    UnknownTemplate(String),
+    /// 实例化模板时没有提供模板要求的上下文方面
+    MissingContext(StateAspectId),
+    /// 子状态机出错且策略为上报
+    ChildFailed(ChildId),
+    /// 转换声明写入另一片段受保护的方面
+    ProtectedAspectWrite { transition: TransitionId, aspect: StateAspectId },
+    /// 方面取值未通过校验；`transition` 为 `None` 表示非转换引起（连续转换或初始状态）
+    InvalidAspectValue { aspect: StateAspectId, transition: Option<TransitionId> },
+    /// 本地写入了对端权威的方面；`transition` 为 `None` 表示连续转换写入
+    AuthorityViolation { aspect: StateAspectId, transition: Option<TransitionId> },
+    /// 引用了蓝图中不存在的观察者
+    UnknownObserver(ObserverId),
+    /// 区域包含关系成环，或在样本状态上不成立
+    InvalidContainment { outer: ObserverId, inner: ObserverId },
+    /// 方面取值无法编码或解码（未注册编解码器、类型不符或解析失败）
+    CodecFailed(StateAspectId),
+    /// 状态存储读写失败
+    StoreFailed { machine: MachineId, message: String },
+    /// 事件缓冲区已满，溢出策略为 `OverflowPolicy::Error`（或无法等待的 `Block`）的事件被拒绝
+    EventOverflow(EventId),
+    /// 路由的事件没有关联ID
+    MissingCorrelationId(EventId),
+    /// 广播分发中两个转换把同一方面写为不同的值，事件整体被拒绝
+    WriteConflict { aspect: StateAspectId, transitions: [TransitionId; 2] },
+    /// 确定性审计发现同一事件两次处理的结果不一致：
+    /// `aspect` 为 `None` 表示选中的转换不同（`transition` 为第一次选中的转换），否则为写入不一致的方面
+    NonDeterministic { event: EventId, transition: Option<TransitionId>, aspect: Option<StateAspectId> },
+    /// 附带所在状态机实例标签的错误，见 `RuntimeStateMachine::take_labeled_errors`
+    Labeled { label: Box<MachineLabel>, error: Box<StateZenError> },
+}
+
+impl fmt::Display for StateZenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingAspect(id) => write!(f, "方面 {id} 缺少取值"),
+            Self::AspectTypeMismatch(id) => write!(f, "方面 {id} 的取值类型与声明不一致"),
+            Self::DuplicateAspect(id) => write!(f, "方面 {id} 已以不同的类型声明"),
+            Self::DuplicateEvent(id) => write!(f, "事件 {id} 已以不同的payload类型声明"),
+            Self::DuplicateTransition(id) => write!(f, "转换 {id} 重复"),
+            Self::DuplicateObserver(id) => write!(f, "观察者 {id} 重复"),
+            Self::UnknownEvent(id) => write!(f, "事件 {id} 未在蓝图中声明"),
+            Self::MigrationFailed(id) => write!(f, "迁移方面 {id} 失败"),
+            Self::NoMigrationPath { from, to } => write!(f, "找不到从版本 {from} 到版本 {to} 的迁移路径"),
+            Self::UnknownTemplate(name) => write!(f, "状态机模板 `{name}` 未注册"),
+            Self::MissingContext(id) => write!(f, "实例化模板时缺少上下文方面 {id}"),
+            Self::ChildFailed(id) => write!(f, "子状态机 {id} 出错"),
+            Self::ProtectedAspectWrite { transition, aspect } => {
+                write!(f, "转换 {transition} 写入了受保护的方面 {aspect}")
+            }
+            Self::InvalidAspectValue { aspect, transition: Some(transition) } => {
+                write!(f, "转换 {transition} 写入的方面 {aspect} 取值未通过校验")
+            }
+            Self::InvalidAspectValue { aspect, transition: None } => write!(f, "方面 {aspect} 的取值未通过校验"),
+            Self::AuthorityViolation { aspect, transition: Some(transition) } => {
+                write!(f, "转换 {transition} 在本地写入了对端权威的方面 {aspect}")
+            }
+            Self::AuthorityViolation { aspect, transition: None } => {
+                write!(f, "连续转换在本地写入了对端权威的方面 {aspect}")
+            }
+            Self::UnknownObserver(id) => write!(f, "观察者 {id} 不存在"),
+            Self::InvalidContainment { outer, inner } => {
+                write!(f, "观察者 {outer} 的区域不包含观察者 {inner} 的区域")
+            }
+            Self::CodecFailed(id) => write!(f, "方面 {id} 的取值无法编解码"),
+            Self::StoreFailed { machine, message } => write!(f, "状态机 {machine} 的状态存储失败：{message}"),
+            Self::EventOverflow(id) => write!(f, "事件缓冲区已满，事件 {id} 被拒绝"),
+            Self::MissingCorrelationId(id) => write!(f, "事件 {id} 没有关联ID，无法路由"),
+            Self::WriteConflict { aspect, transitions: [a, b] } => {
+                write!(f, "转换 {a} 与转换 {b} 把方面 {aspect} 写为不同的值")
+            }
+            Self::NonDeterministic { event, aspect: None, .. } => {
+                write!(f, "事件 {event} 两次选择的转换不同")
+            }
+            Self::NonDeterministic { event, transition, aspect: Some(aspect) } => {
+                let transition = transition.map_or("-".to_string(), |t| t.to_string());
+                write!(f, "事件 {event} 触发的转换 {transition} 两次写入方面 {aspect} 的结果不同")
+            }
+            Self::Labeled { label, error } => write!(f, "{label}：{error}"),
+        }
+    }
+}
+
+impl std::error::Error for StateZenError {}
@@ -1,7 +1,15 @@
 //! 事件定义
 
 use std::any::TypeId;
-use super::types::EventId;
+use std::sync::Arc;
+use super::types::{EventId, CorrelationId};
+use super::runtime::State;
+
+/// 事件负载：类型擦除后的共享值
+pub type EventPayload = Arc<dyn std::any::Any + Send + Sync>;
+
+/// 负载变换：在转换选择之前整理原始负载
+pub type PayloadTransformer = Arc<dyn Fn(EventPayload) -> EventPayload + Send + Sync>;
 
 /// 事件定义
 /// 包含事件ID和payload类型信息
@@ -11,4 +19,111 @@ pub struct EventDef {
     pub id: EventId,
     /// payload类型的TypeId
     pub payload_type_id: TypeId,
-}
\ No newline at end of file
+    /// 负载变换管线，按顺序作用于带负载的事件实例
+    pub transformers: Vec<PayloadTransformer>,
+}
+
+impl EventDef {
+    /// 创建无负载的事件定义
+    pub fn new(id: EventId) -> Self {
+        Self::of::<()>(id)
+    }
+
+    /// 创建负载类型为 `T` 的事件定义
+    pub fn of<T: 'static>(id: EventId) -> Self {
+        Self {
+            id,
+            payload_type_id: TypeId::of::<T>(),
+            transformers: Vec::new(),
+        }
+    }
+
+    /// 追加一个负载变换：负载为 `R` 时替换为 `f` 的结果，其他负载原样保留
+    ///
+    /// 例如把原始摇杆轴值归一化为离散方向，使守卫与转换函数不必各自处理原始输入
+    pub fn with_transformer<R, P, F>(mut self, f: F) -> Self
+    where
+        R: std::any::Any + Send + Sync,
+        P: std::any::Any + Send + Sync,
+        F: Fn(&R) -> P + Send + Sync + 'static,
+    {
+        self.transformers.push(Arc::new(move |payload: EventPayload| match payload.downcast_ref::<R>() {
+            Some(raw) => Arc::new(f(raw)) as EventPayload,
+            None => payload,
+        }));
+        self
+    }
+
+    /// 依次执行负载变换
+    pub fn transform(&self, payload: Option<EventPayload>) -> Option<EventPayload> {
+        payload.map(|payload| self.transformers.iter().fold(payload, |payload, f| f(payload)))
+    }
+}
+
+/// 事件实例
+/// 一次具体发生的事件及其负载
+#[derive(Clone)]
+pub struct EventInstance {
+    /// 事件ID
+    pub event_id: EventId,
+    /// 事件负载
+    pub payload: Option<EventPayload>,
+    /// 关联ID，由 `MachineRouter` 用于选择状态机实例
+    pub correlation_id: Option<CorrelationId>,
+}
+
+impl EventInstance {
+    /// 创建一个新的事件实例
+    pub fn new(event_id: EventId, payload: Option<EventPayload>) -> Self {
+        Self {
+            event_id,
+            payload,
+            correlation_id: None,
+        }
+    }
+
+    /// 设置关联ID
+    pub fn with_correlation(mut self, correlation_id: impl Into<CorrelationId>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+}
+
+/// 事件负载渲染函数：根据新状态生成负载
+pub type PayloadRenderer = Arc<dyn Fn(&State) -> EventPayload + Send + Sync>;
+
+/// 事件模板
+/// 转换成功后用新状态渲染为事件实例，推送到事件汇
+#[derive(Clone)]
+pub struct EventTemplate {
+    /// 事件ID
+    pub event_id: EventId,
+    /// 负载渲染函数，`None` 表示无负载
+    pub payload: Option<PayloadRenderer>,
+}
+
+impl EventTemplate {
+    /// 创建一个无负载的事件模板
+    pub fn new(event_id: EventId) -> Self {
+        Self {
+            event_id,
+            payload: None,
+        }
+    }
+
+    /// 创建一个由新状态渲染负载的事件模板
+    pub fn with_payload<F>(event_id: EventId, f: F) -> Self
+    where
+        F: Fn(&State) -> EventPayload + Send + Sync + 'static,
+    {
+        Self {
+            event_id,
+            payload: Some(Arc::new(f)),
+        }
+    }
+
+    /// 用给定状态渲染事件实例
+    pub fn render(&self, state: &State) -> EventInstance {
+        EventInstance::new(self.event_id, self.payload.as_ref().map(|f| f(state)))
+    }
+}
@@ -0,0 +1,76 @@
+//! 按方面版本号缓存守卫的求值结果
+
+use std::collections::HashMap;
+use super::types::{StateAspectId, TransitionId};
+use super::state_in_range::StateInRange;
+use super::runtime::State;
+
+/// 守卫缓存的命中统计
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GuardMemoStats {
+    /// 直接复用上次结果的次数
+    pub hits: u64,
+    /// 重新求值的次数（含未声明读集合的守卫）
+    pub misses: u64,
+}
+
+/// 一个转换守卫上次的求值
+struct MemoEntry {
+    guard: StateInRange,
+    /// 求值时读集合中各方面的版本号，与读集合一一对应
+    versions: Vec<u64>,
+    result: bool,
+}
+
+/// 守卫缓存
+/// 声明了读集合的守卫在读集合中各方面的版本号都未变化时复用上次的结果；
+/// 未声明读集合的守卫每次都重新求值
+#[derive(Default)]
+pub(crate) struct GuardMemo {
+    entries: HashMap<TransitionId, MemoEntry>,
+    stats: GuardMemoStats,
+}
+
+impl GuardMemo {
+    /// 转换 `id` 的守卫在当前状态 `state` 下是否成立，`versions` 为当前的方面版本号
+    pub(crate) fn contains(
+        &mut self,
+        id: TransitionId,
+        guard: &StateInRange,
+        state: &State,
+        versions: &HashMap<StateAspectId, u64>,
+    ) -> bool {
+        let Some(reads) = guard.reads() else {
+            self.stats.misses += 1;
+            return guard.contains(state);
+        };
+        let current = reads.iter().map(|id| versions.get(id).copied().unwrap_or(0));
+        if let Some(entry) = self.entries.get(&id)
+            && StateInRange::ptr_eq(&entry.guard, guard)
+            && entry.versions.iter().copied().eq(current.clone())
+        {
+            self.stats.hits += 1;
+            return entry.result;
+        }
+        self.stats.misses += 1;
+        let result = guard.contains(state);
+        self.entries.insert(
+            id,
+            MemoEntry {
+                guard: guard.clone(),
+                versions: current.collect(),
+                result,
+            },
+        );
+        result
+    }
+
+    /// 清空缓存的结果，保留统计
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn stats(&self) -> GuardMemoStats {
+        self.stats
+    }
+}
@@ -0,0 +1,194 @@
+//! 运行历史：记录每次提交前后的状态，支持回退
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::sync::Arc;
+use super::types::{EventId, TransitionId};
+use super::runtime::{RuntimeStateMachine, State};
+use super::blueprint::StateMachineBlueprint;
+use super::transfer::Transfer;
+use super::trace::Tracer;
+
+/// 一次提交的历史记录
+#[derive(Clone)]
+pub struct HistoryEntry {
+    /// 触发提交的事件，连续转移提交时为 `None`
+    pub event_id: Option<EventId>,
+    /// 执行的转换，连续转移提交时为 `None`
+    pub transition: Option<TransitionId>,
+    /// 提交前的状态
+    pub before: State,
+    /// 提交后的状态
+    pub after: State,
+}
+
+/// 运行历史
+/// 作为 `Tracer` 挂载到运行时，按提交顺序记录状态快照
+pub struct History {
+    capacity: Option<usize>,
+    entries: Mutex<VecDeque<HistoryEntry>>,
+    pending: Mutex<Option<(EventId, TransitionId)>>,
+}
+
+impl History {
+    /// 创建历史记录器，`capacity` 为 `None` 时不限条数，否则只保留最近的记录
+    pub fn new(capacity: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            capacity,
+            entries: Mutex::new(VecDeque::new()),
+            pending: Mutex::new(None),
+        })
+    }
+
+    /// 记录条数
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// 是否没有记录
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 全部记录（从旧到新）
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// 最近一条记录
+    pub fn last(&self) -> Option<HistoryEntry> {
+        self.entries.lock().unwrap().back().cloned()
+    }
+
+    /// 清空记录
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// 回退一步：弹出最近一条记录并把运行时恢复到提交前的状态
+    ///
+    /// 恢复通过 `set_state` 完成，不会触发观察者回调
+    pub fn step_back(&self, runtime: &mut RuntimeStateMachine) -> Option<HistoryEntry> {
+        let entry = self.entries.lock().unwrap().pop_back()?;
+        runtime.set_state(entry.before.clone());
+        Some(entry)
+    }
+}
+
+impl Tracer for History {
+    fn on_event(&self, event_id: EventId, selected: Option<TransitionId>) {
+        *self.pending.lock().unwrap() = selected.map(|t| (event_id, t));
+    }
+
+    fn on_commit(&self, prev: &State, next: &State) {
+        let cause = self.pending.lock().unwrap().take();
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_back(HistoryEntry {
+            event_id: cause.map(|(e, _)| e),
+            transition: cause.map(|(_, t)| t),
+            before: prev.clone(),
+            after: next.clone(),
+        });
+        if let Some(capacity) = self.capacity
+            && entries.len() > capacity
+        {
+            entries.pop_front();
+        }
+    }
+}
+
+/// 一步撤销
+#[derive(Clone)]
+pub enum UndoStep {
+    /// 逆转换，对当前状态应用即可撤销
+    Inverse(Transfer),
+    /// 无法求逆时保存的提交前状态
+    Snapshot(State),
+}
+
+/// 撤销日志
+/// 可逆转换（见 `Transfer::invert`）只记录逆转换，其余提交才保存完整快照，
+/// 适合状态很大、逐步快照代价高的场景
+pub struct UndoLog {
+    transfers: HashMap<TransitionId, Transfer>,
+    capacity: Option<usize>,
+    steps: Mutex<VecDeque<UndoStep>>,
+    /// 本次提交期间执行的转换对应的撤销步骤
+    recorded: Mutex<Vec<UndoStep>>,
+}
+
+impl UndoLog {
+    /// 为蓝图创建撤销日志，`capacity` 为 `None` 时不限步数
+    pub fn new(blueprint: &StateMachineBlueprint, capacity: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            transfers: blueprint.transitions().map(|t| (t.id, t.transfer.clone())).collect(),
+            capacity,
+            steps: Mutex::new(VecDeque::new()),
+            recorded: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// 可撤销的步数
+    pub fn len(&self) -> usize {
+        self.steps.lock().unwrap().len()
+    }
+
+    /// 是否没有可撤销的步骤
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 以完整快照保存的步数
+    pub fn snapshot_count(&self) -> usize {
+        self.steps
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| matches!(s, UndoStep::Snapshot(_)))
+            .count()
+    }
+
+    /// 撤销最近一步，返回是否有可撤销的步骤
+    ///
+    /// 恢复通过 `set_state` 完成，不会触发观察者回调
+    pub fn undo(&self, runtime: &mut RuntimeStateMachine) -> bool {
+        let Some(step) = self.steps.lock().unwrap().pop_back() else {
+            return false;
+        };
+        let state = match step {
+            UndoStep::Inverse(inverse) => inverse.apply(&runtime.current_state),
+            UndoStep::Snapshot(state) => state,
+        };
+        runtime.set_state(state);
+        true
+    }
+
+    fn push(&self, step: UndoStep) {
+        let mut steps = self.steps.lock().unwrap();
+        steps.push_back(step);
+        if let Some(capacity) = self.capacity
+            && steps.len() > capacity
+        {
+            steps.pop_front();
+        }
+    }
+}
+
+impl Tracer for UndoLog {
+    fn on_transition(&self, id: TransitionId, prev: &State, _next: &State) {
+        let step = match self.transfers.get(&id).and_then(|t| t.invert(prev)) {
+            Some(inverse) => UndoStep::Inverse(inverse),
+            None => UndoStep::Snapshot(prev.clone()),
+        };
+        self.recorded.lock().unwrap().push(step);
+    }
+
+    fn on_commit(&self, prev: &State, _next: &State) {
+        // 连续转移的提交没有对应的转换、批处理的一次提交包含多个转换，都只能保存快照
+        let mut recorded = std::mem::take(&mut *self.recorded.lock().unwrap());
+        match recorded.pop() {
+            Some(step) if recorded.is_empty() => self.push(step),
+            _ => self.push(UndoStep::Snapshot(prev.clone())),
+        }
+    }
+}
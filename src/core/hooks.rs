@@ -0,0 +1,59 @@
+//! 全局的状态变更钩子
+
+use std::sync::Arc;
+use super::runtime::State;
+
+/// 状态变更钩子，参数为（变更前状态，变更后状态）
+pub type TransformHook = Arc<dyn Fn(&State, &State) + Send + Sync>;
+
+/// 钩子句柄，用于移除
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TransformHookHandle(u64);
+
+/// 运行时上注册的前置与后置钩子（按注册顺序执行）
+#[derive(Default)]
+pub(crate) struct TransformHooks {
+    before: Vec<(TransformHookHandle, TransformHook)>,
+    after: Vec<(TransformHookHandle, TransformHook)>,
+    next_handle: u64,
+}
+
+impl TransformHooks {
+    fn handle(&mut self) -> TransformHookHandle {
+        let handle = TransformHookHandle(self.next_handle);
+        self.next_handle += 1;
+        handle
+    }
+
+    pub(crate) fn add_before(&mut self, hook: TransformHook) -> TransformHookHandle {
+        let handle = self.handle();
+        self.before.push((handle, hook));
+        handle
+    }
+
+    pub(crate) fn add_after(&mut self, hook: TransformHook) -> TransformHookHandle {
+        let handle = self.handle();
+        self.after.push((handle, hook));
+        handle
+    }
+
+    /// 移除钩子，返回是否存在
+    pub(crate) fn remove(&mut self, handle: TransformHookHandle) -> bool {
+        let before = self.before.len() + self.after.len();
+        self.before.retain(|(h, _)| *h != handle);
+        self.after.retain(|(h, _)| *h != handle);
+        self.before.len() + self.after.len() != before
+    }
+
+    pub(crate) fn run_before(&self, prev: &State, next: &State) {
+        for (_, hook) in &self.before {
+            hook(prev, next);
+        }
+    }
+
+    pub(crate) fn run_after(&self, prev: &State, next: &State) {
+        for (_, hook) in &self.after {
+            hook(prev, next);
+        }
+    }
+}
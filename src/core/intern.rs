@@ -0,0 +1,43 @@
+//! 谓词结构化驻留
+
+use std::collections::HashMap;
+use super::state_in_range::StateInRange;
+
+/// 谓词驻留器
+/// 结构上等价的声明式谓词共享同一个闭包（同一个 `Arc`），
+/// 既节省内存，也让按谓词去重的缓存能合并求值。闭包构造的谓词原样返回
+#[derive(Default)]
+pub struct PredicateInterner {
+    /// 结构形状哈希 -> 已驻留的谓词
+    buckets: HashMap<u64, Vec<StateInRange>>,
+}
+
+impl PredicateInterner {
+    /// 创建一个空的驻留器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 驻留一个谓词，返回结构等价的共享谓词
+    pub fn intern(&mut self, predicate: StateInRange) -> StateInRange {
+        let Some(shape) = predicate.shape_hash() else {
+            return predicate;
+        };
+        let bucket = self.buckets.entry(shape).or_default();
+        if let Some(shared) = bucket.iter().find(|p| p.structurally_eq(&predicate)) {
+            return shared.clone();
+        }
+        bucket.push(predicate.clone());
+        predicate
+    }
+
+    /// 已驻留的不同谓词数量
+    pub fn len(&self) -> usize {
+        self.buckets.values().map(Vec::len).sum()
+    }
+
+    /// 是否还没有驻留任何谓词
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
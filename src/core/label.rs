@@ -0,0 +1,49 @@
+//! 状态机实例的标识与元数据
+
+use std::collections::BTreeMap;
+use std::fmt;
+use super::types::MachineId;
+
+/// 状态机实例标签
+/// 多实例部署中用来区分各个运行时：实例ID加上任意键值元数据（实体名、所属者、分片等），
+/// 会出现在追踪器、监控统计、带标签的错误与监督器的接口中
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineLabel {
+    /// 实例ID
+    pub id: MachineId,
+    /// 元数据（按键升序）
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl MachineLabel {
+    /// 创建没有元数据的标签
+    pub fn new(id: MachineId) -> Self {
+        Self {
+            id,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// 添加一项元数据，同名键被替换
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// 元数据取值
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+}
+
+/// 形如 `状态机 3 (name=goblin, shard=2)`，没有元数据时省略括号
+impl fmt::Display for MachineLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "状态机 {}", self.id)?;
+        if !self.metadata.is_empty() {
+            let pairs: Vec<String> = self.metadata.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            write!(f, " ({})", pairs.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! 可配置的蓝图合并
+
+use super::blueprint::StateMachineBlueprint;
+
+/// 合并时导入蓝图转换优先级的调整方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PriorityBias {
+    /// 保持原优先级
+    #[default]
+    Keep,
+    /// 统一加上一个偏移
+    Offset(i32),
+    /// 自动偏移，使导入蓝图的每个转换都高于原蓝图的全部转换
+    Override,
+}
+
+/// 导入蓝图中与原蓝图ID相同的转换、观察者如何处理
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// 追加到末尾（与 `merge` 相同，同ID的条目会重复）
+    #[default]
+    Append,
+    /// 原地替换原蓝图中同ID的条目，其余追加
+    ReplaceById,
+}
+
+/// 合并选项
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// 导入蓝图转换的优先级调整
+    pub priority: PriorityBias,
+    /// 同ID条目的处理方式
+    pub policy: MergePolicy,
+}
+
+impl MergeOptions {
+    /// 与 `merge` 行为相同的默认选项
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置优先级调整方式
+    pub fn priority(mut self, bias: PriorityBias) -> Self {
+        self.priority = bias;
+        self
+    }
+
+    /// 设置同ID条目的处理方式
+    pub fn policy(mut self, policy: MergePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl StateMachineBlueprint {
+    /// 所有转换的优先级加上 `delta`（饱和运算）
+    pub fn offset_priorities(&mut self, delta: i32) {
+        for transition in self.transitions_mut() {
+            transition.priority = transition.priority.saturating_add(delta);
+        }
+    }
+
+    /// 按选项合并另一个蓝图
+    /// 适合模组、DLC 一类的“覆盖”蓝图：`PriorityBias::Override` 让它们的转换
+    /// 在转换选择中稳定胜出，而无需逐个修改优先级；`MergePolicy::ReplaceById`
+    /// 按ID替换原有的转换与观察者，实现补丁式的分层。
+    /// 自动偏移按替换前的原蓝图计算
+    pub fn merge_with(&self, other: &Self, options: &MergeOptions) -> Self {
+        let delta = match options.priority {
+            PriorityBias::Keep => 0,
+            PriorityBias::Offset(delta) => delta,
+            PriorityBias::Override => {
+                let highest = self.transitions().map(|t| t.priority).max();
+                let lowest = other.transitions().map(|t| t.priority).min();
+                match (highest, lowest) {
+                    (Some(highest), Some(lowest)) if lowest <= highest => {
+                        i32::try_from(i64::from(highest) - i64::from(lowest) + 1).unwrap_or(i32::MAX)
+                    }
+                    _ => 0,
+                }
+            }
+        };
+
+        let mut imported = other.clone();
+        imported.offset_priorities(delta);
+        match options.policy {
+            MergePolicy::Append => self.merge(&imported),
+            MergePolicy::ReplaceById => {
+                let mut base = self.clone();
+                let transitions = base.transitions_mut();
+                imported.transitions_mut().retain(|t| {
+                    match transitions.iter_mut().find(|b| b.id == t.id) {
+                        Some(slot) => {
+                            *slot = t.clone();
+                            false
+                        }
+                        None => true,
+                    }
+                });
+                let observers = base.observers_mut();
+                imported.observers_mut().retain(|o| {
+                    match observers.iter_mut().find(|b| b.id == o.id) {
+                        Some(slot) => {
+                            *slot = o.clone();
+                            false
+                        }
+                        None => true,
+                    }
+                });
+                base.merge(&imported)
+            }
+        }
+    }
+}
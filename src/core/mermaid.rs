@@ -0,0 +1,99 @@
+//! Mermaid 状态图导出
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use super::types::StateAspectId;
+use super::blueprint::StateMachineBlueprint;
+use super::registry::AspectRegistry;
+use super::state_in_range::GuardExpr;
+
+/// 规范状态：方面到取值描述的映射，按方面ID升序
+type CanonicalState = BTreeMap<StateAspectId, String>;
+
+/// 收集守卫中以“与”连接的相等条件；其余条件无法确定具体取值，被忽略
+fn equalities(expr: &GuardExpr, out: &mut CanonicalState) {
+    match expr {
+        GuardExpr::AspectEq { aspect, value } => {
+            out.insert(*aspect, value.to_string());
+        }
+        GuardExpr::And(a, b) => {
+            equalities(a, out);
+            equalities(b, out);
+        }
+        GuardExpr::Named { inner, .. } => equalities(inner, out),
+        _ => {}
+    }
+}
+
+fn label(state: &CanonicalState, registry: &AspectRegistry) -> String {
+    if state.is_empty() {
+        return "任意状态".to_string();
+    }
+    state
+        .iter()
+        .map(|(id, value)| format!("{} = {value}", registry.name(*id)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl StateMachineBlueprint {
+    /// 导出 Mermaid `stateDiagram-v2` 状态图，可直接嵌入 Markdown 文档与 GitHub wiki
+    ///
+    /// 每个转换的起点是其守卫中以“与”连接的相等条件构成的规范状态，
+    /// 终点是起点叠加转换函数的写入：`Transfer::set` 写入的方面取常量，其余写入的方面记为 `?`；
+    /// 闭包构造、写集合未知的转换函数终点记为 `?`。没有相等条件的守卫起点为“任意状态”。
+    /// 终止区域恰为某个规范状态时，该状态连向 `[*]`。方面名称取自注册表
+    pub fn to_mermaid(&self, registry: &AspectRegistry) -> String {
+        let mut nodes: Vec<Option<CanonicalState>> = Vec::new();
+        let mut node = |state: Option<CanonicalState>| match nodes.iter().position(|n| *n == state) {
+            Some(i) => i,
+            None => {
+                nodes.push(state);
+                nodes.len() - 1
+            }
+        };
+
+        let mut transitions: Vec<_> = self.transitions().collect();
+        transitions.sort_by_key(|t| t.id);
+        let mut edges = Vec::new();
+        for t in transitions {
+            let mut source = CanonicalState::new();
+            equalities(t.guard.expr(), &mut source);
+            let target = t.transfer.writes().map(|writes| {
+                let mut target = source.clone();
+                for id in writes {
+                    let value = t.transfer.expr().final_set(*id).map_or("?".to_string(), |v| v.to_string());
+                    target.insert(*id, value);
+                }
+                target
+            });
+            let from = node(Some(source));
+            let to = node(target);
+            edges.push((from, to, format!("事件 {} (转换 {})", t.event_id, t.id)));
+        }
+        let finals: Vec<usize> = self
+            .final_region()
+            .map(|region| {
+                let mut state = CanonicalState::new();
+                equalities(region.expr(), &mut state);
+                state
+            })
+            .filter(|state| !state.is_empty())
+            .and_then(|state| nodes.iter().position(|n| n.as_ref() == Some(&state)))
+            .into_iter()
+            .collect();
+
+        let mut diagram = String::from("stateDiagram-v2\n");
+        for (i, state) in nodes.iter().enumerate() {
+            let text = state.as_ref().map_or("?".to_string(), |s| label(s, registry));
+            let _ = writeln!(diagram, "    s{i} : {text}");
+        }
+        for (from, to, text) in edges {
+            let _ = writeln!(diagram, "    s{from} --> s{to} : {text}");
+        }
+        for i in finals {
+            let _ = writeln!(diagram, "    s{i} --> [*]");
+        }
+        diagram
+    }
+}
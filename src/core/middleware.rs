@@ -0,0 +1,37 @@
+//! 事件中间件
+
+use std::sync::Arc;
+use super::types::EventId;
+use super::event::{EventInstance, EventPayload};
+
+/// 中间件的后继调用：把（可能被改写的）事件交给链上的下一个中间件
+pub type Next<'a> = &'a mut dyn FnMut(EventId, Option<EventPayload>);
+
+/// 事件中间件
+/// 在转换选择之前处理事件，可以改写、丢弃（不调用 next）或复制（多次调用 next）事件
+pub type Middleware = Arc<dyn Fn(EventId, Option<EventPayload>, Next) + Send + Sync>;
+
+/// 让事件依次穿过中间件链，返回最终需要分发的事件序列
+pub(crate) fn run_chain(
+    middlewares: &[Middleware],
+    event_id: EventId,
+    payload: Option<EventPayload>,
+) -> Vec<EventInstance> {
+    let mut out = Vec::new();
+    run_from(middlewares, event_id, payload, &mut out);
+    out
+}
+
+fn run_from(
+    middlewares: &[Middleware],
+    event_id: EventId,
+    payload: Option<EventPayload>,
+    out: &mut Vec<EventInstance>,
+) {
+    match middlewares.split_first() {
+        None => out.push(EventInstance::new(event_id, payload)),
+        Some((first, rest)) => {
+            first(event_id, payload, &mut |e, p| run_from(rest, e, p, out));
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! 蓝图版本迁移
+
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::blueprint::StateMachineBlueprint;
+use super::runtime::{State, AspectValue};
+use super::error::StateZenError;
+
+/// 方面取值转换函数，返回 `None` 表示旧值无法转换
+pub type AspectConverter = Arc<dyn Fn(&AspectValue) -> Option<AspectValue> + Send + Sync>;
+
+/// 单个方面的迁移步骤
+#[derive(Clone)]
+enum AspectMigration {
+    /// 转换取值并（可能）改换方面ID
+    Convert {
+        from: StateAspectId,
+        to: StateAspectId,
+        converter: AspectConverter,
+    },
+    /// 移除方面
+    Remove(StateAspectId),
+    /// 新增方面（已有取值时不覆盖）
+    Insert(StateAspectId, AspectValue),
+}
+
+/// 迁移计划
+/// 描述从 `from_version` 到 `to_version` 的方面变化，按声明顺序执行；
+/// 未提及的方面原样保留
+#[derive(Clone)]
+pub struct MigrationPlan {
+    /// 旧版本
+    pub from_version: u32,
+    /// 新版本
+    pub to_version: u32,
+    steps: Vec<AspectMigration>,
+}
+
+impl MigrationPlan {
+    /// 创建一个空的迁移计划
+    pub fn new(from_version: u32, to_version: u32) -> Self {
+        Self {
+            from_version,
+            to_version,
+            steps: Vec::new(),
+        }
+    }
+
+    /// 方面改换ID，取值不变
+    pub fn rename(self, from: StateAspectId, to: StateAspectId) -> Self {
+        self.convert_raw(from, to, Arc::new(|v| Some(v.clone())))
+    }
+
+    /// 用类型化转换函数把旧方面 `from`（类型 `Old`）迁移为新方面 `to`（类型 `New`）
+    pub fn convert<Old, New, F>(self, from: StateAspectId, to: StateAspectId, f: F) -> Self
+    where
+        Old: 'static,
+        New: Send + Sync + 'static,
+        F: Fn(&Old) -> New + Send + Sync + 'static,
+    {
+        self.convert_raw(
+            from,
+            to,
+            Arc::new(move |v| v.downcast_ref::<Old>().map(|old| Arc::new(f(old)) as AspectValue)),
+        )
+    }
+
+    /// 用类型擦除的转换函数迁移方面
+    pub fn convert_raw(mut self, from: StateAspectId, to: StateAspectId, converter: AspectConverter) -> Self {
+        self.steps.push(AspectMigration::Convert { from, to, converter });
+        self
+    }
+
+    /// 移除一个方面
+    pub fn remove(mut self, id: StateAspectId) -> Self {
+        self.steps.push(AspectMigration::Remove(id));
+        self
+    }
+
+    /// 新增一个方面，旧状态中没有该方面时使用 `value`
+    pub fn insert<T>(mut self, id: StateAspectId, value: T) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        self.steps.push(AspectMigration::Insert(id, Arc::new(value)));
+        self
+    }
+
+    /// 对状态执行迁移
+    pub fn apply(&self, state: &State) -> Result<State, StateZenError> {
+        let mut next = state.clone();
+        for step in &self.steps {
+            match step {
+                AspectMigration::Convert { from, to, converter } => {
+                    let old = next.remove(from).ok_or(StateZenError::MigrationFailed(*from))?;
+                    let new = converter(&old).ok_or(StateZenError::MigrationFailed(*from))?;
+                    next.insert(*to, new);
+                }
+                AspectMigration::Remove(id) => {
+                    next.remove(id);
+                }
+                AspectMigration::Insert(id, value) => {
+                    if !next.contains_key(id) {
+                        next.insert(*id, value.clone());
+                    }
+                }
+            }
+        }
+        Ok(next)
+    }
+}
+
+impl StateMachineBlueprint {
+    /// 把旧版本蓝图下持久化的状态迁移到当前蓝图
+    ///
+    /// 从 `from_version` 开始依次选取 `plans` 中首尾相接的计划直到当前版本，
+    /// 迁移结果需通过 `validate_state` 校验
+    pub fn migrate_state(
+        &self,
+        state: &State,
+        from_version: u32,
+        plans: &[MigrationPlan],
+    ) -> Result<State, StateZenError> {
+        let no_path = StateZenError::NoMigrationPath {
+            from: from_version,
+            to: self.version(),
+        };
+        let mut version = from_version;
+        let mut state = state.clone();
+        while version != self.version() {
+            let plan = plans
+                .iter()
+                .find(|p| p.from_version == version && p.to_version > version)
+                .ok_or_else(|| no_path.clone())?;
+            state = plan.apply(&state)?;
+            version = plan.to_version;
+            if version > self.version() {
+                return Err(no_path);
+            }
+        }
+        self.validate_state(&state)?;
+        Ok(state)
+    }
+}
@@ -2,22 +2,140 @@
 
 // 子模块
 pub mod types;
+pub mod state;
+pub(crate) mod pool;
 pub mod state_aspect;
+pub mod validation;
+pub mod authority;
+pub mod arg;
 pub mod state_in_range;
+pub mod intern;
+pub mod guard_memo;
+pub mod profile;
+pub mod audit;
 pub mod transfer;
+pub mod continuous;
+pub mod derived;
 pub mod event;
+pub mod middleware;
+pub mod queue;
+pub mod source;
+pub mod sink;
+pub mod outbox;
 pub mod transition;
+pub mod chain;
 pub mod state_observer;
+pub mod canonical;
+pub mod edge_observer;
+pub mod transition_observer;
+pub mod hooks;
+pub mod label;
 pub mod blueprint;
+pub mod compiled;
 pub mod runtime;
+pub mod drive;
+pub mod delta;
+#[cfg(feature = "async")]
+pub mod wait;
+#[cfg(feature = "tokio")]
+pub mod task;
+pub mod trace;
+pub mod registry;
+pub mod history;
+pub mod breakpoint;
+pub mod scheduler;
+pub mod watchdog;
+pub mod activity;
+pub mod virtual_time;
+pub mod predict;
+pub mod clock;
+pub mod codec;
+pub mod store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod supervisor;
+pub mod router;
+pub mod relation;
+pub mod scope;
+pub mod alias;
+pub mod merge;
+pub mod diff;
+pub mod docs;
+pub mod mermaid;
+pub mod stats;
+pub mod template;
+pub mod migration;
+pub mod typed;
+pub mod static_machine;
+pub mod aspects;
+pub mod bridge;
+pub mod bundle;
+pub mod collections;
+pub mod error;
 
 // 重新导出常用类型
 pub use types::*;
-pub use state_aspect::StateAspect;
-pub use state_in_range::StateInRange;
-pub use transfer::Transfer;
-pub use event::EventDef;
+pub use state_aspect::{StateAspect, AspectValidator, AspectDefault, AspectEq};
+pub use validation::{ValidationPolicy, AspectClamper};
+pub use authority::{Authority, NetworkRole, AuthorityPolicy};
+pub use arg::ArgValue;
+pub use state_in_range::{StateInRange, GuardExpr, GuardValue, GuardFn};
+pub use intern::PredicateInterner;
+pub use guard_memo::GuardMemoStats;
+pub use profile::{ProfileKey, ProfileEntry, ProfileReport};
+pub use audit::DeterminismAudit;
+pub use transfer::{Transfer, TransferExpr, UpdateOp, ArithValue};
+pub use continuous::ContinuousTransfer;
+pub use derived::DerivedAspect;
+pub use event::{EventDef, EventInstance, EventPayload, EventTemplate, PayloadTransformer};
+pub use middleware::Middleware;
+pub use queue::{EventBuffer, OverflowPolicy, CoalesceFn};
+pub use source::EventSource;
+pub use sink::EventSink;
+pub use outbox::{EffectOutbox, MemoryOutbox, OutboxEntry};
 pub use transition::Transition;
-pub use state_observer::StateObserver;
+pub use chain::TransitionChain;
+pub use state_observer::{StateObserver, Handled, ConsumeCallback};
+pub use edge_observer::EdgeObserver;
+pub use canonical::{CanonicalStates, CanonicalClassifier};
+pub use transition_observer::TransitionObserver;
+pub use hooks::{TransformHook, TransformHookHandle};
+pub use label::MachineLabel;
 pub use blueprint::StateMachineBlueprint;
-pub use runtime::{RuntimeStateMachine, State};
\ No newline at end of file
+pub use compiled::CompiledMachine;
+pub use runtime::{RuntimeStateMachine, BatchConflictPolicy, DispatchPolicy, State, AspectValue};
+pub use drive::{Drive, StateSnapshot};
+pub use delta::StateDelta;
+#[cfg(feature = "tokio")]
+pub use task::{spawn_machine_task, EventSender, StateReceiver, MachineTaskClosed, SendError};
+pub use trace::{Tracer, RegionEdge};
+pub use registry::{AspectRegistry, AspectInfo};
+pub use history::{History, HistoryEntry, UndoLog, UndoStep};
+pub use breakpoint::{BreakContext, BreakAction, BreakHook};
+pub use scheduler::RecurringHandle;
+pub use activity::Activity;
+pub use watchdog::{Watchdog, WatchdogHandle, WatchdogMode, Escalation};
+pub use virtual_time::VirtualTimeDriver;
+pub use predict::PredictedMachine;
+pub use clock::{Clock, SystemClock, ManualClock, RecordedClock};
+pub use codec::StateCodec;
+pub use store::{StateStore, MemoryStore, FileStore, AutosavePolicy};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteHistoryStore, SqliteRecorder, StoredTransform};
+pub use scope::Scope;
+pub use alias::EventAliasMap;
+pub use merge::{MergeOptions, MergePolicy, PriorityBias};
+pub use diff::{BlueprintDiff, ItemDiff, FieldChange};
+pub use stats::{BlueprintStats, ComplexityThresholds, ComplexityWarning};
+pub use relation::{MachineRef, MachineDirectory};
+pub use supervisor::{MachineSupervisor, Spawner, Child, ChildId, RestartPolicy, FailureCause, FailureNotice};
+pub use router::{MachineRouter, RouteInitializer};
+pub use template::{MachineTemplate, RequiredContext};
+pub use migration::MigrationPlan;
+pub use typed::{AspectTuple, TypedBlueprint};
+pub use static_machine::{StaticBlueprint, StaticMachine, StaticTransition, StaticObserver};
+pub use aspects::Aspects;
+pub use bridge::StateBridge;
+pub use bundle::AspectBundle;
+pub use collections::{Collection, CollectionAspect, VecAspect, SetAspect, MapAspect};
+pub use error::StateZenError;
\ No newline at end of file
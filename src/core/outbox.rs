@@ -0,0 +1,85 @@
+//! 输出事件发件箱：至少一次投递
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use super::event::EventInstance;
+use super::error::StateZenError;
+
+/// 发件箱中的一条待投递事件
+#[derive(Clone)]
+pub struct OutboxEntry {
+    /// 发件箱分配的序号，单调递增
+    pub seq: u64,
+    /// 待投递的事件
+    pub event: EventInstance,
+}
+
+/// 输出事件发件箱
+///
+/// 运行时在提交状态之前把本次转换产生的 `emits` 整体写入发件箱，写入失败时放弃本次转换；
+/// 投递到事件汇后逐条确认。进程在投递与确认之间退出时，未确认的事件会在下次
+/// `RuntimeStateMachine::relay_outbox` 时重新投递，因此接收方需要容忍重复
+pub trait EffectOutbox: Send + Sync {
+    /// 原子地写入一次提交产生的全部事件
+    fn commit(&self, events: &[EventInstance]) -> Result<(), StateZenError>;
+
+    /// 全部未确认的事件（按序号升序）
+    fn pending(&self) -> Vec<OutboxEntry>;
+
+    /// 确认事件已投递，返回该序号是否存在
+    fn ack(&self, seq: u64) -> bool;
+}
+
+/// 内存发件箱
+/// 通过 `Arc` 在运行时之间共享，用于测试或由外部负责持久化的场景
+#[derive(Default)]
+pub struct MemoryOutbox {
+    inner: Mutex<(u64, VecDeque<OutboxEntry>)>,
+}
+
+impl MemoryOutbox {
+    /// 创建一个空发件箱
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 未确认的事件数量
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().1.len()
+    }
+
+    /// 是否没有未确认的事件
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl EffectOutbox for MemoryOutbox {
+    fn commit(&self, events: &[EventInstance]) -> Result<(), StateZenError> {
+        let mut inner = self.inner.lock().unwrap();
+        for event in events {
+            let seq = inner.0;
+            inner.0 += 1;
+            inner.1.push_back(OutboxEntry {
+                seq,
+                event: event.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn pending(&self) -> Vec<OutboxEntry> {
+        self.inner.lock().unwrap().1.iter().cloned().collect()
+    }
+
+    fn ack(&self, seq: u64) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.1.iter().position(|e| e.seq == seq) {
+            Some(i) => {
+                inner.1.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! 转换过程中临时状态的回收池
+
+use super::runtime::State;
+
+/// 回收池最多保留的状态数量
+const POOL_CAPACITY: usize = 16;
+
+/// 临时状态回收池
+///
+/// 批量分发时每个事件都要复制一份转换前的状态，提交后这些中间状态随即被丢弃。
+/// 回收池保留它们已分配的存储（取值已清空，不会延长方面取值的生命周期），
+/// 下次复制时用 `clone_from` 复用，减少游戏循环中反复分配的开销
+#[derive(Default)]
+pub(crate) struct StatePool {
+    free: Vec<State>,
+}
+
+impl StatePool {
+    /// 复制一份状态，优先复用回收的存储
+    pub(crate) fn copy_of(&mut self, source: &State) -> State {
+        match self.free.pop() {
+            Some(mut state) => {
+                state.clone_from(source);
+                state
+            }
+            None => source.clone(),
+        }
+    }
+
+    /// 回收不再使用的状态
+    pub(crate) fn recycle(&mut self, mut state: State) {
+        if self.free.len() < POOL_CAPACITY {
+            state.clear();
+            self.free.push(state);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! 客户端预测与服务器状态校正
+
+use std::collections::VecDeque;
+use super::types::EventId;
+use super::event::{EventInstance, EventPayload};
+use super::runtime::{RuntimeStateMachine, State};
+
+/// 客户端预测的状态机
+///
+/// 本地事件立即乐观地应用并按序号缓冲；服务器的权威快照到达时，
+/// 回滚到快照状态，丢弃快照已确认的事件，再重放其余未确认的事件。
+/// 重放只重建状态，不会再次执行回调、发出事件或通知追踪器，这些副作用只在第一次应用时发生
+pub struct PredictedMachine {
+    runtime: RuntimeStateMachine,
+    next_seq: u64,
+    pending: VecDeque<(u64, EventInstance)>,
+}
+
+impl PredictedMachine {
+    /// 包装一个本地状态机
+    pub fn new(runtime: RuntimeStateMachine) -> Self {
+        Self {
+            runtime,
+            next_seq: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// 乐观地应用本地事件，返回事件的序号，随事件一起发给服务器用于确认
+    pub fn apply(&mut self, event_id: EventId, payload: Option<EventPayload>) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let event = EventInstance::new(event_id, payload);
+        self.runtime.handle_event(event.event_id, event.payload.clone());
+        self.pending.push_back((seq, event));
+        seq
+    }
+
+    /// 用服务器的权威状态校正
+    ///
+    /// `acknowledged` 为服务器处理 `authoritative` 时已包含的最后一个本地事件序号，
+    /// `None` 表示还没有处理任何本地事件。返回重放的事件数量
+    pub fn reconcile(&mut self, authoritative: State, acknowledged: Option<u64>) -> usize {
+        if let Some(acknowledged) = acknowledged {
+            while self.pending.front().is_some_and(|(seq, _)| *seq <= acknowledged) {
+                self.pending.pop_front();
+            }
+        }
+        self.runtime.set_state(authoritative);
+        self.runtime.replay_quietly(self.pending.iter().map(|(_, event)| event));
+        self.pending.len()
+    }
+
+    /// 尚未被服务器确认的本地事件数量
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// 当前（预测的）状态
+    pub fn current_state(&self) -> &State {
+        &self.runtime.current_state
+    }
+
+    /// 本地状态机
+    pub fn runtime(&self) -> &RuntimeStateMachine {
+        &self.runtime
+    }
+
+    /// 本地状态机的可变引用
+    pub fn runtime_mut(&mut self) -> &mut RuntimeStateMachine {
+        &mut self.runtime
+    }
+
+    /// 取回本地状态机
+    pub fn into_inner(self) -> RuntimeStateMachine {
+        self.runtime
+    }
+}
@@ -0,0 +1,117 @@
+//! 守卫、观察者区域与转换函数的耗时统计
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use super::types::{TransitionId, ObserverId};
+
+/// 被计时的闭包
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ProfileKey {
+    /// 转换的守卫
+    Guard(TransitionId),
+    /// 转换的转换函数
+    Transfer(TransitionId),
+    /// 观察者的区域
+    Observer(ObserverId),
+    /// 边沿观察者的条件
+    Edge(ObserverId),
+}
+
+impl fmt::Display for ProfileKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Guard(id) => write!(f, "转换 {id} 的守卫"),
+            Self::Transfer(id) => write!(f, "转换 {id} 的转换函数"),
+            Self::Observer(id) => write!(f, "观察者 {id} 的区域"),
+            Self::Edge(id) => write!(f, "边沿观察者 {id} 的条件"),
+        }
+    }
+}
+
+/// 一个闭包的累计耗时
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProfileEntry {
+    pub key: ProfileKey,
+    /// 调用次数
+    pub calls: u64,
+    /// 累计耗时
+    pub total: Duration,
+}
+
+impl ProfileEntry {
+    /// 平均每次调用的耗时
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(0) => Duration::ZERO,
+            Ok(calls) => self.total / calls,
+            Err(_) => self.total.div_f64(self.calls as f64),
+        }
+    }
+}
+
+/// 耗时报告，按累计耗时从高到低排列
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub entries: Vec<ProfileEntry>,
+}
+
+impl ProfileReport {
+    /// 给定闭包的统计
+    pub fn get(&self, key: ProfileKey) -> Option<&ProfileEntry> {
+        self.entries.iter().find(|e| e.key == key)
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}：{} 次，共 {:?}，平均 {:?}", entry.key, entry.calls, entry.total, entry.mean())?;
+        }
+        Ok(())
+    }
+}
+
+/// 运行时内部的计时器
+///
+/// 观察者区域可能被并行求值，计数放在锁后面
+#[derive(Default)]
+pub(crate) struct Profiler {
+    entries: Mutex<HashMap<ProfileKey, (u64, Duration)>>,
+}
+
+impl Profiler {
+    fn record(&self, key: ProfileKey, elapsed: Duration) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.entry(key).or_default();
+        entry.0 += 1;
+        entry.1 += elapsed;
+    }
+
+    pub(crate) fn report(&self) -> ProfileReport {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries: Vec<ProfileEntry> = entries
+            .iter()
+            .map(|(key, (calls, total))| ProfileEntry { key: *key, calls: *calls, total: *total })
+            .collect();
+        entries.sort_by(|a, b| b.total.cmp(&a.total).then(a.key.cmp(&b.key)));
+        ProfileReport { entries }
+    }
+}
+
+/// 开启计时时记录 `f` 的耗时
+pub(crate) fn profiled<R>(profiler: Option<&Profiler>, key: ProfileKey, f: impl FnOnce() -> R) -> R {
+    match profiler {
+        Some(profiler) => {
+            let start = Instant::now();
+            let result = f();
+            profiler.record(key, start.elapsed());
+            result
+        }
+        None => f(),
+    }
+}
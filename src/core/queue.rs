@@ -0,0 +1,128 @@
+//! 事件缓冲队列
+
+use std::collections::{HashMap, VecDeque};
+use super::types::EventId;
+use super::event::EventInstance;
+
+/// 合并函数：把新到达的事件并入缓冲中同一事件ID的最近一个事件
+pub type CoalesceFn = fn(&mut EventInstance, EventInstance);
+
+/// 缓冲区溢出策略
+#[derive(Clone, Copy, Debug)]
+pub enum OverflowPolicy {
+    /// 丢弃新到达的事件
+    DropNewest,
+    /// 丢弃最早缓冲的事件，为新事件腾出空间
+    DropOldest,
+    /// 并入缓冲中同一事件ID的最近一个事件；没有可合并的事件时丢弃新事件
+    Coalesce(CoalesceFn),
+    /// 等待空位：异步邮箱（`spawn_machine_task`）的 `send_async` 等到有空位再入队；
+    /// 同步压入无法等待，队列已满时与 `Error` 一样拒绝新事件，由调用方稍后重试
+    Block,
+    /// 拒绝新事件并报告错误
+    Error,
+}
+
+impl PartialEq for OverflowPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Coalesce(a), Self::Coalesce(b)) => std::ptr::fn_addr_eq(*a, *b),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for OverflowPolicy {}
+
+/// 事件缓冲区
+/// 有界（或无界）的 FIFO 队列，按溢出策略处理满队列时的新事件
+#[derive(Clone)]
+pub struct EventBuffer {
+    events: VecDeque<EventInstance>,
+    /// 容量上限，`None` 表示无界
+    capacity: Option<usize>,
+    /// 溢出策略
+    policy: OverflowPolicy,
+    /// 按事件ID覆盖的溢出策略
+    event_policies: HashMap<EventId, OverflowPolicy>,
+}
+
+impl EventBuffer {
+    /// 创建一个新的事件缓冲区
+    pub fn new(capacity: Option<usize>, policy: OverflowPolicy) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity,
+            policy,
+            event_policies: HashMap::new(),
+        }
+    }
+
+    /// 为指定事件设置溢出策略，覆盖缓冲区的默认策略
+    pub fn set_event_policy(&mut self, event_id: EventId, policy: OverflowPolicy) {
+        self.event_policies.insert(event_id, policy);
+    }
+
+    /// 指定事件生效的溢出策略
+    pub fn policy_for(&self, event_id: EventId) -> OverflowPolicy {
+        self.event_policies.get(&event_id).copied().unwrap_or(self.policy)
+    }
+
+    /// 压入一个事件
+    /// 返回被丢弃的事件（如果发生了溢出）；`Error` / `Block` 策略下被拒绝的新事件同样视为丢弃
+    pub fn push(&mut self, event: EventInstance) -> Option<EventInstance> {
+        self.try_push(event).unwrap_or_else(Some)
+    }
+
+    /// 压入一个事件，`Error` / `Block` 策略下队列已满时返回 `Err` 交还新事件
+    pub fn try_push(&mut self, event: EventInstance) -> Result<Option<EventInstance>, EventInstance> {
+        let full = self.capacity.is_some_and(|c| self.events.len() >= c);
+        if !full {
+            self.events.push_back(event);
+            return Ok(None);
+        }
+        match self.policy_for(event.event_id) {
+            OverflowPolicy::DropNewest => Ok(Some(event)),
+            OverflowPolicy::DropOldest => {
+                let dropped = self.events.pop_front();
+                if self.capacity != Some(0) {
+                    self.events.push_back(event);
+                    Ok(dropped)
+                } else {
+                    Ok(Some(event))
+                }
+            }
+            OverflowPolicy::Coalesce(merge) => {
+                match self.events.iter_mut().rev().find(|e| e.event_id == event.event_id) {
+                    Some(queued) => {
+                        merge(queued, event);
+                        Ok(None)
+                    }
+                    None => Ok(Some(event)),
+                }
+            }
+            OverflowPolicy::Block | OverflowPolicy::Error => Err(event),
+        }
+    }
+
+    /// 弹出最早的事件
+    pub fn pop(&mut self) -> Option<EventInstance> {
+        self.events.pop_front()
+    }
+
+    /// 缓冲的事件数量
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// 缓冲区是否为空
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl Default for EventBuffer {
+    fn default() -> Self {
+        Self::new(None, OverflowPolicy::DropNewest)
+    }
+}
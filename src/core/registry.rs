@@ -0,0 +1,128 @@
+//! 方面注册表：方面名称与取值格式化
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::runtime::{State, AspectValue};
+
+/// 取值格式化函数
+pub type AspectFormatter = Arc<dyn Fn(&AspectValue) -> String + Send + Sync>;
+
+/// 已注册方面的信息
+#[derive(Clone)]
+pub struct AspectInfo {
+    /// 方面ID
+    pub id: StateAspectId,
+    /// 方面名称
+    pub name: String,
+    /// 取值类型
+    pub value_type_id: TypeId,
+    type_name: &'static str,
+    formatter: AspectFormatter,
+}
+
+impl AspectInfo {
+    /// 格式化取值，类型不符时返回 `<类型不符>`
+    pub fn format(&self, value: &AspectValue) -> String {
+        (self.formatter)(value)
+    }
+
+    /// 取值类型名称（去掉模块路径），如 `Option<Action>`
+    pub fn type_name(&self) -> String {
+        let mut short = String::new();
+        let mut path = String::new();
+        for c in self.type_name.chars() {
+            if c.is_alphanumeric() || c == '_' || c == ':' {
+                path.push(c);
+            } else {
+                short.push_str(path.rsplit("::").next().unwrap_or_default());
+                path.clear();
+                short.push(c);
+            }
+        }
+        short.push_str(path.rsplit("::").next().unwrap_or_default());
+        short
+    }
+}
+
+/// 方面注册表
+/// 为类型擦除的方面提供可读名称和取值格式化，供追踪、调试、导出等工具使用
+#[derive(Clone, Default)]
+pub struct AspectRegistry {
+    aspects: HashMap<StateAspectId, AspectInfo>,
+}
+
+impl AspectRegistry {
+    /// 创建一个空注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册一个取值类型为 `T` 的方面
+    pub fn register<T>(&mut self, id: StateAspectId, name: impl Into<String>) -> &mut Self
+    where
+        T: Debug + Send + Sync + 'static,
+    {
+        let formatter: AspectFormatter = Arc::new(|v| match v.downcast_ref::<T>() {
+            Some(v) => format!("{v:?}"),
+            None => "<类型不符>".to_string(),
+        });
+        self.aspects.insert(
+            id,
+            AspectInfo {
+                id,
+                name: name.into(),
+                value_type_id: TypeId::of::<T>(),
+                type_name: std::any::type_name::<T>(),
+                formatter,
+            },
+        );
+        self
+    }
+
+    /// 按ID查找方面信息
+    pub fn get(&self, id: StateAspectId) -> Option<&AspectInfo> {
+        self.aspects.get(&id)
+    }
+
+    /// 按名称查找方面信息
+    pub fn by_name(&self, name: &str) -> Option<&AspectInfo> {
+        self.aspects.values().find(|a| a.name == name)
+    }
+
+    /// 全部已注册方面（按ID升序）
+    pub fn iter(&self) -> impl Iterator<Item = &AspectInfo> {
+        let mut infos: Vec<&AspectInfo> = self.aspects.values().collect();
+        infos.sort_by_key(|a| a.id);
+        infos.into_iter()
+    }
+
+    /// 方面的显示名称，未注册时为 `#<id>`
+    pub fn name(&self, id: StateAspectId) -> String {
+        match self.aspects.get(&id) {
+            Some(info) => info.name.clone(),
+            None => format!("#{id}"),
+        }
+    }
+
+    /// 格式化方面取值，未注册时为 `<?>`
+    pub fn format_value(&self, id: StateAspectId, value: &AspectValue) -> String {
+        match self.aspects.get(&id) {
+            Some(info) => info.format(value),
+            None => "<?>".to_string(),
+        }
+    }
+
+    /// 格式化整个状态，按方面ID升序：`name=value, ...`
+    pub fn format_state(&self, state: &State) -> String {
+        let mut entries: Vec<_> = state.iter().collect();
+        entries.sort_by_key(|(id, _)| **id);
+        entries
+            .into_iter()
+            .map(|(id, v)| format!("{}={}", self.name(*id), self.format_value(*id, v)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
@@ -0,0 +1,59 @@
+//! 引用其他状态机的方面
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use super::types::{StateAspectId, MachineId};
+use super::state_in_range::StateInRange;
+use super::runtime::State;
+
+/// 对另一个状态机实例的引用，作为方面取值使用
+/// 例如“攻击目标”方面保存目标的 `MachineRef`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MachineRef(pub MachineId);
+
+/// 状态机目录
+/// 保存各实例最近一次发布的状态，供守卫查询其他状态机；可克隆，克隆共享同一份目录。
+/// `MachineSupervisor` 在每个子状态机处理完事件后自动发布其状态
+#[derive(Clone, Default)]
+pub struct MachineDirectory {
+    states: Arc<RwLock<HashMap<MachineId, State>>>,
+}
+
+impl MachineDirectory {
+    /// 创建一个空目录
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 发布实例的最新状态
+    pub fn publish(&self, id: MachineId, state: State) {
+        self.states.write().unwrap().insert(id, state);
+    }
+
+    /// 移除实例
+    pub fn remove(&self, id: MachineId) {
+        self.states.write().unwrap().remove(&id);
+    }
+
+    /// 实例最近发布的状态
+    pub fn state(&self, id: MachineId) -> Option<State> {
+        self.states.read().unwrap().get(&id).cloned()
+    }
+
+    /// 实例最近发布的状态是否位于 `region` 内；实例不存在时为假
+    pub fn in_region(&self, id: MachineId, region: &StateInRange) -> bool {
+        self.states.read().unwrap().get(&id).is_some_and(|s| region.contains(s))
+    }
+
+    /// 守卫：方面 `ref_aspect` 引用的状态机位于 `region` 内
+    /// 方面缺失、取值不是 `MachineRef` 或被引用的实例不存在时为假。
+    /// 结果依赖目录而非自身状态，因此不声明读集合
+    pub fn other_in_region(&self, ref_aspect: StateAspectId, region: StateInRange) -> StateInRange {
+        let directory = self.clone();
+        StateInRange::new(move |s| {
+            s.get(&ref_aspect)
+                .and_then(|v| v.downcast_ref::<MachineRef>())
+                .is_some_and(|target| directory.in_region(target.0, &region))
+        })
+    }
+}
@@ -0,0 +1,118 @@
+//! 按关联ID把事件路由到状态机实例
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use super::types::CorrelationId;
+use super::event::EventInstance;
+use super::runtime::{RuntimeStateMachine, State};
+use super::template::MachineTemplate;
+use super::state_in_range::StateInRange;
+use super::error::StateZenError;
+
+/// 实例初始化函数：根据首个事件计算新实例的初始覆盖值
+pub type RouteInitializer = Arc<dyn Fn(&EventInstance) -> State + Send + Sync>;
+
+/// 状态机路由器
+///
+/// 每个关联ID对应一个状态机实例：事件按 `correlation_id` 送达对应实例，
+/// 遇到未知的关联ID时用模板创建新实例，适合作为审批、工单这类工作流的后端
+pub struct MachineRouter {
+    template: MachineTemplate,
+    initializer: Option<RouteInitializer>,
+    machines: BTreeMap<CorrelationId, RuntimeStateMachine>,
+}
+
+impl MachineRouter {
+    /// 基于模板创建路由器
+    pub fn new(template: MachineTemplate) -> Self {
+        Self {
+            template,
+            initializer: None,
+            machines: BTreeMap::new(),
+        }
+    }
+
+    /// 设置实例初始化函数；未设置时新实例使用模板的默认初始状态
+    pub fn set_initializer<F>(&mut self, f: F)
+    where
+        F: Fn(&EventInstance) -> State + Send + Sync + 'static,
+    {
+        self.initializer = Some(Arc::new(f));
+    }
+
+    /// 把事件路由到其关联ID对应的实例，返回是否新建了实例
+    ///
+    /// 事件没有关联ID时返回 `MissingCorrelationId`；新实例的初始状态无效时返回相应错误，且不创建实例
+    pub fn route(&mut self, event: EventInstance) -> Result<bool, StateZenError> {
+        let correlation_id = event
+            .correlation_id
+            .clone()
+            .ok_or(StateZenError::MissingCorrelationId(event.event_id))?;
+        let created = !self.machines.contains_key(&correlation_id);
+        if created {
+            let overrides = self.initializer.as_ref().map(|f| f(&event)).unwrap_or_default();
+            let runtime = self.template.instantiate(overrides)?;
+            self.machines.insert(correlation_id.clone(), runtime);
+        }
+        let runtime = self.machines.get_mut(&correlation_id).unwrap();
+        runtime.handle_event(event.event_id, event.payload);
+        Ok(created)
+    }
+
+    /// 关联ID对应的实例
+    pub fn get(&self, correlation_id: &str) -> Option<&RuntimeStateMachine> {
+        self.machines.get(correlation_id)
+    }
+
+    /// 关联ID对应的实例（可变）
+    pub fn get_mut(&mut self, correlation_id: &str) -> Option<&mut RuntimeStateMachine> {
+        self.machines.get_mut(correlation_id)
+    }
+
+    /// 是否存在关联ID对应的实例
+    pub fn contains(&self, correlation_id: &str) -> bool {
+        self.machines.contains_key(correlation_id)
+    }
+
+    /// 移除并返回实例；之后同一关联ID的事件会创建新实例
+    pub fn remove(&mut self, correlation_id: &str) -> Option<RuntimeStateMachine> {
+        self.machines.remove(correlation_id)
+    }
+
+    /// 全部实例（按关联ID升序）
+    pub fn instances(&self) -> impl Iterator<Item = (&str, &RuntimeStateMachine)> {
+        self.machines.iter().map(|(id, m)| (id.as_str(), m))
+    }
+
+    /// 当前状态位于 `region` 内的实例的关联ID（升序）
+    pub fn in_region(&self, region: &StateInRange) -> Vec<&str> {
+        self.instances()
+            .filter(|(_, m)| region.contains(&m.current_state))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// 移除已进入终止区域的实例，返回它们的关联ID
+    pub fn reap(&mut self) -> Vec<CorrelationId> {
+        let finished: Vec<CorrelationId> = self
+            .machines
+            .iter()
+            .filter(|(_, m)| m.is_finished())
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &finished {
+            self.machines.remove(id);
+        }
+        finished
+    }
+
+    /// 实例数量
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    /// 是否没有实例
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+}
@@ -1,13 +1,160 @@
 //! 运行时状态机
 
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use super::types::{StateAspectId, EventId};
+use std::time::Duration;
+use super::types::{StateAspectId, EventId, TransitionId, MachineId};
+use super::validation::{ValidationPolicy, AspectClamper};
+use super::authority::{NetworkRole, AuthorityPolicy};
+use super::error::StateZenError;
 use super::blueprint::StateMachineBlueprint;
 use super::transition::Transition;
+use super::trace::{Tracer, RegionEdge};
+use super::state_observer::{StateObserver, ObserverCallback, ConsumeCallback, Handled};
+use super::transition::OnTranCallback;
+use super::edge_observer::EdgeCallback;
+use super::transition_observer::{TransitionObserver, TransitionCallback};
+use super::event::{EventPayload, EventInstance};
+use super::queue::{EventBuffer, OverflowPolicy};
+use super::source::EventSource;
+use super::sink::EventSink;
+use super::outbox::EffectOutbox;
+use super::guard_memo::{GuardMemo, GuardMemoStats};
+use super::profile::{Profiler, ProfileKey, ProfileReport, profiled};
+use super::pool::StatePool;
+use super::canonical::{CanonicalStates, CanonicalMembership};
+use super::audit::DeterminismAudit;
+use super::middleware::{self, Middleware, Next};
+use super::hooks::{TransformHooks, TransformHookHandle};
+use super::label::MachineLabel;
+use super::breakpoint::{BreakContext, BreakAction, BreakHook};
+use super::clock::{Clock, SystemClock};
+use super::store::{Autosave, AutosavePolicy, StateStore};
+use super::scheduler::{Scheduler, RecurringHandle};
+use super::activity::{Activity, ActivityLog};
+use super::watchdog::{Watchdog, WatchdogHandle, Watchdogs, Escalation};
+#[cfg(feature = "async")]
+use super::wait::{self, Waiter};
+#[cfg(feature = "async")]
+use super::drive::StateSnapshot;
+#[cfg(feature = "async")]
+use super::state_in_range::StateInRange;
 
-/// 运行时状态：aspect_id -> Arc<dyn Any>
-pub type State = HashMap<StateAspectId, Arc<dyn std::any::Any + Send + Sync>>;
+pub use super::state::{State, AspectValue};
+
+/// 批量处理事件时的写冲突策略
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BatchConflictPolicy {
+    /// 依次执行，后一个事件基于前一个转换的结果选择转换
+    #[default]
+    Sequential,
+    /// 所有事件都基于批次开始时的状态选择并执行转换，同一方面取后执行的转换写入的值
+    LastWriteWins,
+    /// 同上，同一方面保留先执行的转换写入的值
+    FirstWriteWins,
+    /// 同上，写入了本批次已写过的方面的转换整体丢弃
+    Reject,
+}
+
+/// 单个事件的转换选择策略
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DispatchPolicy {
+    /// 只执行守卫满足者中优先级最高的一个转换
+    #[default]
+    HighestPriority,
+    /// 执行守卫满足的全部转换：都基于事件发生前的状态计算，写入合并后提交一次。
+    /// 两个转换把同一方面写为不同的值时整个事件被拒绝，并记录 `WriteConflict`。
+    /// 取值按方面声明的相等比较（`StateAspect::with_eq`）判断，未声明时按 `Arc` 同一性比较，
+    /// 最后一次写入为 `Transfer::set` 时按常量比较。
+    /// 断点按优先级顺序对每个转换生效：跳过的转换不参与合并；暂停时已执行的转换照常提交，
+    /// 命中断点的转换挂起等待 `resume`，其余转换不再执行
+    AllNonConflicting,
+}
+
+/// `request` 处理期间收集的结果
+#[derive(Default)]
+struct RequestOutcome {
+    /// 最后一个被执行的转换的响应
+    response: Option<Box<dyn Any + Send>>,
+    /// 第一个使选中的转换未能提交的错误
+    rejection: Option<StateZenError>,
+}
+
+/// 批处理中执行的一个转换
+struct BatchStep {
+    transition: Transition,
+    before: State,
+    after: State,
+}
+
+/// 一次提交由什么引起
+enum Fired<'a> {
+    /// 连续转移
+    Tick,
+    /// 单个转换
+    Transition(&'a Transition),
+    /// 批处理中的多个转换
+    Batch(&'a [BatchStep]),
+}
+
+/// 一次提交触发的回调
+enum Effect {
+    Exit(ObserverCallback),
+    Tran(OnTranCallback),
+    /// 批处理中的 OnTran，使用该转换自己的前后状态
+    TranBetween(OnTranCallback, Box<(State, State)>),
+    /// 进入回调，附带区域谓词的标识，用于消费语义
+    Enter(usize, ObserverCallback),
+    Consume(usize, ConsumeCallback),
+    Edge(EdgeCallback),
+    /// 转换观察者回调，批处理中附带该转换自己的前后状态
+    Observed(TransitionCallback, TransitionId, Option<Box<(State, State)>>),
+    Finished(ObserverCallback),
+}
+
+impl Effect {
+    fn run(&self, prev: &State, next: &State) {
+        match self {
+            Self::Exit(f) => f(prev),
+            Self::Tran(f) => f(prev, next),
+            Self::TranBetween(f, states) => f(&states.0, &states.1),
+            Self::Enter(_, f) | Self::Finished(f) => f(next),
+            Self::Consume(_, f) => {
+                f(next);
+            }
+            Self::Edge(f) => f(prev, next),
+            Self::Observed(f, id, None) => f(*id, prev, next),
+            Self::Observed(f, id, Some(states)) => f(*id, &states.0, &states.1),
+        }
+    }
+}
+
+/// 按顺序执行一次提交的回调；被消费区域的后续进入回调被跳过
+fn run_effects(effects: &[Effect], prev: &State, next: &State) {
+    let mut stopped = Vec::new();
+    for effect in effects {
+        match effect {
+            Effect::Enter(region, _) | Effect::Consume(region, _) if stopped.contains(region) => {}
+            Effect::Consume(region, f) => {
+                if f(next) == Handled::Stop {
+                    stopped.push(*region);
+                }
+            }
+            _ => effect.run(prev, next),
+        }
+    }
+}
+
+/// 延迟到 `flush_effects` 执行的一次提交的回调
+struct DeferredEffects {
+    effects: Vec<Effect>,
+    prev: State,
+    next: State,
+}
+
+/// 默认保留的错误条数
+const DEFAULT_ERROR_CAPACITY: usize = 256;
 
 /// 运行时状态机
 /// 管理状态机的当前状态和执行转换
@@ -16,76 +163,1635 @@ pub struct RuntimeStateMachine {
     pub blueprint: StateMachineBlueprint,
     /// 当前状态
     pub current_state: State,
+    /// 实例标签
+    label: Option<MachineLabel>,
     /// 待处理的转换
     pending_transition: Option<Transition>,
+    /// 选中待处理转换的事件
+    pending_event: Option<EventId>,
+    /// 转换断点
+    breakpoints: HashMap<TransitionId, BreakHook>,
+    /// 事件中间件链（按注册顺序执行）
+    middlewares: Vec<Middleware>,
+    /// 注册在运行时上的转换观察者，在蓝图的转换观察者之后调用
+    transition_observers: Vec<TransitionObserver>,
+    /// 每次状态变更前后的全局钩子
+    transform_hooks: TransformHooks,
+    /// `request` 处理期间为 `Some`，收集转换的响应与拒绝原因
+    request: Option<RequestOutcome>,
+    /// 是否处于暂停状态
+    paused: bool,
+    /// 暂停期间缓冲的事件
+    paused_events: EventBuffer,
+    /// 进入终止区域时的回调函数
+    on_finished: Option<ObserverCallback>,
+    /// 领域事件汇
+    event_sink: Option<Box<dyn EventSink + Send>>,
+    /// 输出事件发件箱，设置后 `emits` 先写入发件箱再投递
+    outbox: Option<Arc<dyn EffectOutbox>>,
+    /// 各观察者对当前状态的区域归属缓存，`None` 表示需要重新计算
+    observer_membership: Option<Vec<bool>>,
+    /// 规范状态上预先计算的观察者归属
+    canonical: Option<CanonicalMembership>,
+    /// 追踪器
+    tracers: Vec<Arc<dyn Tracer>>,
+    /// `handle_events` 的写冲突策略
+    batch_policy: BatchConflictPolicy,
+    /// 单个事件的转换选择策略
+    dispatch_policy: DispatchPolicy,
+    /// 是否把回调延迟到 `flush_effects`
+    defer_effects: bool,
+    /// 尚未执行的延迟回调（按提交顺序）
+    deferred_effects: Vec<DeferredEffects>,
+    /// 带最短停留时间的转换已在其守卫区域内停留的时间
+    dwell: HashMap<TransitionId, Duration>,
+    /// 周期事件调度器
+    scheduler: Scheduler,
+    /// 看门狗
+    watchdogs: Watchdogs,
+    /// 近期活动
+    activity: ActivityLog,
+    /// 方面取值校验失败时的处理方式
+    validation_policy: ValidationPolicy,
+    /// 运行时所在的一端
+    network_role: NetworkRole,
+    /// 本地写入对端权威方面时的处理方式
+    authority_policy: AuthorityPolicy,
+    /// 各方面的取值修正函数
+    clampers: HashMap<StateAspectId, AspectClamper>,
+    /// 运行时停用的转换
+    disabled_transitions: HashSet<TransitionId>,
+    /// 运行时停用的事件
+    disabled_events: HashSet<EventId>,
+    /// 运行中记录的错误，超出容量时丢弃最旧的
+    errors: VecDeque<StateZenError>,
+    /// 保留的错误条数上限
+    error_capacity: usize,
+    /// 因超出容量被丢弃的错误数
+    dropped_errors: u64,
+    /// 各方面的版本号，每次提交写入该方面时加一
+    versions: HashMap<StateAspectId, u64>,
+    /// 按方面版本号缓存的转换守卫结果，`None` 表示未开启
+    guard_memo: Option<GuardMemo>,
+    /// 耗时统计，`None` 表示未开启
+    profiler: Option<Profiler>,
+    /// 转换过程中临时状态的回收池
+    state_pool: StatePool,
+    /// 确定性审计，`None` 表示未开启
+    audit: Option<DeterminismAudit>,
+    /// `tick_clock` 使用的时钟及上一次读数
+    clock: Option<(Arc<dyn Clock>, Duration)>,
+    /// 自动保存
+    autosave: Option<Autosave>,
+    /// 观察者数量达到该阈值时并行计算区域归属
+    #[cfg(feature = "parallel")]
+    parallel_observer_threshold: Option<usize>,
+    /// 事件 -> 转换下标 的分发索引
+    #[cfg(feature = "index-dispatch")]
+    dispatch_index: DispatchIndex,
+    /// 等待进入区域的 Future
+    #[cfg(feature = "async")]
+    waiters: Vec<Waiter>,
+}
+
+/// 事件分发索引
+/// 蓝图修改代数或转换数量变化时自动重建；绕过蓝图方法原地修改已有转换的事件ID后需调用 `rebuild`
+#[cfg(feature = "index-dispatch")]
+#[derive(Default)]
+struct DispatchIndex {
+    by_event: std::collections::HashMap<EventId, Vec<usize>>,
+    /// 建立索引时的（蓝图修改代数, 转换数量）
+    indexed: Option<(u64, usize)>,
+}
+
+#[cfg(feature = "index-dispatch")]
+impl DispatchIndex {
+    fn refresh(&mut self, blueprint: &StateMachineBlueprint) {
+        if self.indexed != Some((blueprint.generation(), blueprint.transition_slice().len())) {
+            self.rebuild(blueprint);
+        }
+    }
+
+    fn rebuild(&mut self, blueprint: &StateMachineBlueprint) {
+        self.by_event.clear();
+        for (i, t) in blueprint.transitions().enumerate() {
+            self.by_event.entry(t.event_id).or_default().push(i);
+        }
+        self.indexed = Some((blueprint.generation(), blueprint.transition_slice().len()));
+    }
+
+    fn get(&self, event_id: EventId) -> &[usize] {
+        self.by_event.get(&event_id).map_or(&[], Vec::as_slice)
+    }
 }
 
 impl RuntimeStateMachine {
     /// 创建一个新的运行时状态机
-    pub fn new(blueprint: StateMachineBlueprint, initial_state: State) -> Self {
+    /// 蓝图中未启用标签的转换与观察者不会进入运行时
+    /// 初始状态中的派生方面会被重新计算
+    pub fn new(mut blueprint: StateMachineBlueprint, initial_state: State) -> Self {
+        blueprint.retain_enabled();
+        let current_state = blueprint.derive(initial_state);
         Self {
             blueprint,
-            current_state: initial_state,
+            current_state,
+            label: None,
             pending_transition: None,
+            pending_event: None,
+            breakpoints: HashMap::new(),
+            middlewares: Vec::new(),
+            transition_observers: Vec::new(),
+            transform_hooks: TransformHooks::default(),
+            request: None,
+            paused: false,
+            paused_events: EventBuffer::default(),
+            on_finished: None,
+            event_sink: None,
+            outbox: None,
+            observer_membership: None,
+            canonical: None,
+            tracers: Vec::new(),
+            batch_policy: BatchConflictPolicy::default(),
+            dispatch_policy: DispatchPolicy::default(),
+            defer_effects: false,
+            deferred_effects: Vec::new(),
+            dwell: HashMap::new(),
+            scheduler: Scheduler::default(),
+            watchdogs: Watchdogs::default(),
+            activity: ActivityLog::default(),
+            validation_policy: ValidationPolicy::default(),
+            network_role: NetworkRole::default(),
+            authority_policy: AuthorityPolicy::default(),
+            clampers: HashMap::new(),
+            disabled_transitions: HashSet::new(),
+            disabled_events: HashSet::new(),
+            errors: VecDeque::new(),
+            error_capacity: DEFAULT_ERROR_CAPACITY,
+            dropped_errors: 0,
+            versions: HashMap::new(),
+            guard_memo: None,
+            profiler: None,
+            state_pool: StatePool::default(),
+            audit: None,
+            clock: None,
+            autosave: None,
+            #[cfg(feature = "parallel")]
+            parallel_observer_threshold: None,
+            #[cfg(feature = "index-dispatch")]
+            dispatch_index: DispatchIndex::default(),
+            #[cfg(feature = "async")]
+            waiters: Vec::new(),
+        }
+    }
+
+    /// 重建事件分发索引
+    /// 在运行时创建后原地修改了转换的事件ID时调用
+    #[cfg(feature = "index-dispatch")]
+    pub fn rebuild_dispatch_index(&mut self) {
+        self.dispatch_index.rebuild(&self.blueprint);
+    }
+
+    /// 添加一个只作用于本运行时的转换观察者
+    pub fn add_transition_observer(&mut self, observer: TransitionObserver) {
+        self.transition_observers.push(observer);
+    }
+
+    /// 注册状态变更前的钩子，参数为（变更前状态，变更后状态）
+    ///
+    /// 每次提交（转换、批处理、连续转换）调用一次，早于任何回调；
+    /// 适合在一处实现日志、持久化、帧同步，而不必挂在每个转换上
+    pub fn on_before_transform<F>(&mut self, hook: F) -> TransformHookHandle
+    where
+        F: Fn(&State, &State) + Send + Sync + 'static,
+    {
+        self.transform_hooks.add_before(Arc::new(hook))
+    }
+
+    /// 注册状态变更后的钩子，参数同上
+    /// 在新状态生效、回调执行（或延迟）之后调用
+    pub fn on_after_transform<F>(&mut self, hook: F) -> TransformHookHandle
+    where
+        F: Fn(&State, &State) + Send + Sync + 'static,
+    {
+        self.transform_hooks.add_after(Arc::new(hook))
+    }
+
+    /// 移除状态变更钩子，返回是否存在
+    pub fn remove_transform_hook(&mut self, handle: TransformHookHandle) -> bool {
+        self.transform_hooks.remove(handle)
+    }
+
+    /// 返回一个在状态机下一次进入 `region` 时完成的 Future，结果为进入后的状态快照
+    ///
+    /// 只有从区域外进入区域内的提交才算进入，调用时已在区域内则等待下一次重新进入。
+    /// Future 不借用状态机，可交给其他任务等待，状态机照常在别处处理事件；
+    /// 丢弃 Future 即取消等待
+    #[cfg(feature = "async")]
+    pub fn wait_for(&mut self, region: StateInRange) -> impl Future<Output = StateSnapshot> + Send + 'static {
+        let (waiter, entry) = wait::wait_for(region);
+        self.waiters.push(waiter);
+        entry
+    }
+
+    /// 设置近期活动缓冲区：保留最近 `capacity` 条记录（为 0 时不记录），事件每 `sample_every` 个记录一个
+    /// 默认保留 32 条、记录每个事件；重新设置会清空已有记录
+    ///
+    /// # Panics
+    ///
+    /// `sample_every` 为零时 panic
+    pub fn set_activity_log(&mut self, capacity: usize, sample_every: u64) {
+        self.activity = ActivityLog::new(capacity, sample_every);
+    }
+
+    /// 近期的事件与转换（从旧到新），用于出错后的事后分析
+    pub fn recent_activity(&self) -> Vec<Activity> {
+        self.activity.records()
+    }
+
+    /// 添加一个追踪器
+    pub fn add_tracer(&mut self, tracer: Arc<dyn Tracer>) {
+        if let Some(label) = &self.label {
+            tracer.on_label(label);
+        }
+        self.tracers.push(tracer);
+    }
+
+    /// 设置实例标签，并通知已挂载的追踪器
+    pub fn set_label(&mut self, label: MachineLabel) {
+        for tracer in &self.tracers {
+            tracer.on_label(&label);
+        }
+        self.label = Some(label);
+    }
+
+    /// 实例标签
+    pub fn label(&self) -> Option<&MachineLabel> {
+        self.label.as_ref()
+    }
+
+    /// 实例ID，未设置标签时为 `None`
+    pub fn machine_id(&self) -> Option<MachineId> {
+        self.label.as_ref().map(|l| l.id)
+    }
+
+    /// 直接替换当前状态（不触发任何回调），派生方面会被重新计算
+    pub fn set_state(&mut self, state: State) {
+        let state = self.blueprint.derive(state);
+        for id in changed_aspects(&self.current_state, &state) {
+            *self.versions.entry(id).or_insert(0) += 1;
+        }
+        self.current_state = state;
+        self.invalidate_observer_cache();
+        self.refresh_dwell();
+        self.watchdogs.refresh(&self.current_state);
+    }
+
+    /// 使观察者区域归属缓存与守卫缓存失效
+    /// 绕过 `set_state` 直接修改 `current_state` 或观察者区域后需要调用
+    pub fn invalidate_observer_cache(&mut self) {
+        self.observer_membership = None;
+        if let Some(memo) = &mut self.guard_memo {
+            memo.clear();
+        }
+    }
+
+    /// 开启或关闭转换守卫缓存
+    /// 开启后，声明了读集合的守卫只在读集合中某个方面的版本号变化后才重新求值，
+    /// 适合守卫很多而状态大多不变的状态机。读集合必须完整，否则可能复用过期的结果
+    pub fn set_guard_memo(&mut self, enabled: bool) {
+        self.guard_memo = enabled.then(GuardMemo::default);
+    }
+
+    /// 开启或关闭耗时统计，重新开启时清空已有的统计
+    /// 开启后记录每个守卫、观察者区域、边沿条件与转换函数的调用次数与累计耗时，
+    /// 用于找出拖慢每次转换的闭包；计时本身有开销，只应在排查性能时开启
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler = enabled.then(Profiler::default);
+    }
+
+    /// 按累计耗时从高到低排列的统计，未开启时为 `None`
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        self.profiler.as_ref().map(Profiler::report)
+    }
+
+    /// 开启或关闭确定性审计
+    /// 开启后 `handle_event` 分发的每个事件都会再选择一次转换、再执行一次转换函数，
+    /// 结果不一致时记录 `NonDeterministic`，状态仍按第一次的结果提交。
+    /// 守卫与转换函数都会被多调用一次，只应在测试或排查问题时开启；批量与广播分发不受审计
+    pub fn set_determinism_audit(&mut self, audit: Option<DeterminismAudit>) {
+        self.audit = audit;
+    }
+
+    /// 守卫缓存的命中统计，未开启时为 `None`
+    pub fn guard_memo_stats(&self) -> Option<GuardMemoStats> {
+        self.guard_memo.as_ref().map(GuardMemo::stats)
+    }
+
+    /// 设置方面取值校验失败时的处理方式，默认为 `ValidationPolicy::Error`
+    pub fn set_validation_policy(&mut self, policy: ValidationPolicy) {
+        self.validation_policy = policy;
+    }
+
+    /// 设置运行时所在的一端及越权写入的处理方式，默认为 `NetworkRole::Standalone`
+    ///
+    /// 只约束本地的转换与连续转换；`set_state` 等直接替换状态的操作视为接收对端同步，不受约束
+    pub fn set_network_role(&mut self, role: NetworkRole, policy: AuthorityPolicy) {
+        self.network_role = role;
+        self.authority_policy = policy;
+    }
+
+    /// 运行时所在的一端
+    pub fn network_role(&self) -> NetworkRole {
+        self.network_role
+    }
+
+    /// 注册方面的取值修正函数，在 `ValidationPolicy::Clamp` 下修正非法取值
+    pub fn set_clamper<T, F>(&mut self, aspect_id: StateAspectId, f: F)
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) -> T + Send + Sync + 'static,
+    {
+        let clamper: AspectClamper = Arc::new(move |v| v.downcast_ref::<T>().map(|v| Arc::new(f(v)) as AspectValue));
+        self.clampers.insert(aspect_id, clamper);
+    }
+
+    /// 方面的版本号：初始为 0，之后每次写入（或移除）该方面时加一
+    /// 派生方面只在输入变化时重新计算，未重新计算的派生方面版本号不变
+    pub fn aspect_version(&self, aspect_id: StateAspectId) -> u64 {
+        self.versions.get(&aspect_id).copied().unwrap_or(0)
+    }
+
+    /// 取出运行中记录的错误（如 `ValidationPolicy::Error` 下的校验失败）
+    ///
+    /// 错误在取出前一直保留，超出容量（默认 256 条，见 `set_error_capacity`）时丢弃最旧的，
+    /// 长期运行的宿主应定期调用本方法
+    pub fn take_errors(&mut self) -> Vec<StateZenError> {
+        std::mem::take(&mut self.errors).into()
+    }
+
+    /// 设置保留的错误条数上限，已有错误超出时丢弃最旧的
+    pub fn set_error_capacity(&mut self, capacity: usize) {
+        self.error_capacity = capacity;
+        while self.errors.len() > capacity {
+            self.errors.pop_front();
+            self.dropped_errors += 1;
+        }
+    }
+
+    /// 因超出容量被丢弃的错误数
+    pub fn dropped_errors(&self) -> u64 {
+        self.dropped_errors
+    }
+
+    fn record_error(&mut self, error: StateZenError) {
+        if self.errors.len() >= self.error_capacity {
+            self.dropped_errors += 1;
+            if self.errors.pop_front().is_none() {
+                return;
+            }
+        }
+        self.errors.push_back(error);
+    }
+
+    fn record_errors(&mut self, errors: impl IntoIterator<Item = StateZenError>) {
+        for error in errors {
+            self.record_error(error);
+        }
+    }
+
+    /// 记录使选中的转换未能提交的错误，`request` 处理期间保留第一个作为请求的结果
+    fn record_rejection(&mut self, error: Option<StateZenError>) {
+        let Some(error) = error else {
+            return;
+        };
+        if let Some(request) = &mut self.request
+            && request.rejection.is_none()
+        {
+            request.rejection = Some(error.clone());
+        }
+        self.record_error(error);
+    }
+
+    /// 同 `take_errors`，设置了标签时每个错误包装为 `StateZenError::Labeled`，便于多实例日志区分来源
+    pub fn take_labeled_errors(&mut self) -> Vec<StateZenError> {
+        let errors = self.take_errors();
+        match &self.label {
+            Some(label) => errors
+                .into_iter()
+                .map(|error| StateZenError::Labeled {
+                    label: Box::new(label.clone()),
+                    error: Box::new(error),
+                })
+                .collect(),
+            None => errors,
+        }
+    }
+
+    /// 校验 `next` 中相对 `base` 被写入的方面取值，按策略修正或拒绝
+    /// `Err(None)` 表示静默拒绝
+    fn check(&self, base: &State, mut next: State, transition: Option<TransitionId>) -> Result<State, Option<StateZenError>> {
+        if self.authority_policy == AuthorityPolicy::Reject
+            && let Some(error) = self.authority_violation(base, &next, transition)
+        {
+            return Err(Some(error));
+        }
+        for aspect in self.blueprint.aspects() {
+            let Some(valid) = &aspect.validator else {
+                continue;
+            };
+            let Some(value) = next.get(&aspect.id).cloned() else {
+                continue;
+            };
+            if base.get(&aspect.id).is_some_and(|old| Arc::ptr_eq(old, &value)) || valid(&*value) {
+                continue;
+            }
+            let error = StateZenError::InvalidAspectValue { aspect: aspect.id, transition };
+            match self.validation_policy {
+                ValidationPolicy::Reject => return Err(None),
+                ValidationPolicy::Error => return Err(Some(error)),
+                ValidationPolicy::Clamp => {
+                    let clamped = self
+                        .clampers
+                        .get(&aspect.id)
+                        .and_then(|clamp| clamp(&value))
+                        .filter(|v| valid(&**v))
+                        .ok_or(Some(error))?;
+                    next.insert(aspect.id, clamped);
+                }
+            }
+        }
+        Ok(next)
+    }
+
+    /// `next` 相对 `base` 是否写入了本端无权写入的方面，只报告ID最小的一个
+    fn authority_violation(&self, base: &State, next: &State, transition: Option<TransitionId>) -> Option<StateZenError> {
+        if self.network_role == NetworkRole::Standalone {
+            return None;
+        }
+        changed_aspects(base, next)
+            .into_iter()
+            .filter(|id| !self.network_role.may_write(self.blueprint.authority(*id)))
+            .min()
+            .map(|aspect| StateZenError::AuthorityViolation { aspect, transition })
+    }
+
+    /// `AuthorityPolicy::Warn` 下越权写入只记录，不拒绝
+    fn authority_warning(&self, base: &State, next: &State, transition: Option<TransitionId>) -> Option<StateZenError> {
+        match self.authority_policy {
+            AuthorityPolicy::Warn => self.authority_violation(base, next, transition),
+            AuthorityPolicy::Reject => None,
+        }
+    }
+
+    /// 设置是否延迟执行回调
+    /// 开启后，提交触发的 OnExit / OnTran / OnEnter / 边沿 / OnFinished 回调不再立即执行，
+    /// 而是连同当时的前后状态一起排队，在 `flush_effects` 时按提交顺序统一执行，
+    /// 便于渲染、音频等系统在帧内的固定时刻看到一致的顺序。
+    /// 关闭时已排队的回调保留，仍需 `flush_effects`
+    pub fn set_defer_effects(&mut self, defer: bool) {
+        self.defer_effects = defer;
+    }
+
+    /// 排队等待 `flush_effects` 的回调数量
+    pub fn pending_effects(&self) -> usize {
+        self.deferred_effects.iter().map(|d| d.effects.len()).sum()
+    }
+
+    /// 按提交顺序执行全部排队的回调，返回执行的回调数量
+    pub fn flush_effects(&mut self) -> usize {
+        let deferred = std::mem::take(&mut self.deferred_effects);
+        let mut count = 0;
+        for commit in &deferred {
+            run_effects(&commit.effects, &commit.prev, &commit.next);
+            count += commit.effects.len();
+        }
+        count
+    }
+
+    /// 依次处理事件但不产生任何对外可见的副作用，用于回滚到快照后重放事件
+    ///
+    /// 重放期间回调、输出事件（事件汇与发件箱）、追踪器、活动记录、前后置钩子、断点、
+    /// 请求响应、自动保存与等待者都被暂时卸下；事件直接分发，不再经过中间件
+    pub(crate) fn replay_quietly<'a>(&mut self, events: impl IntoIterator<Item = &'a EventInstance>) {
+        let (defer, queued) = (self.defer_effects, self.deferred_effects.len());
+        self.defer_effects = true;
+        let event_sink = self.event_sink.take();
+        let outbox = self.outbox.take();
+        let tracers = std::mem::take(&mut self.tracers);
+        let activity = std::mem::replace(&mut self.activity, ActivityLog::new(0, 1));
+        let middlewares = std::mem::take(&mut self.middlewares);
+        let transform_hooks = std::mem::take(&mut self.transform_hooks);
+        let breakpoints = std::mem::take(&mut self.breakpoints);
+        let request = self.request.take();
+        let autosave = self.autosave.take();
+        #[cfg(feature = "async")]
+        let waiters = std::mem::take(&mut self.waiters);
+
+        for event in events {
+            self.handle_event(event.event_id, event.payload.clone());
+        }
+
+        self.deferred_effects.truncate(queued);
+        self.defer_effects = defer;
+        self.event_sink = event_sink;
+        self.outbox = outbox;
+        self.tracers = tracers;
+        self.activity = activity;
+        self.middlewares = middlewares;
+        self.transform_hooks = transform_hooks;
+        self.breakpoints = breakpoints;
+        self.request = request;
+        self.autosave = autosave;
+        #[cfg(feature = "async")]
+        {
+            self.waiters = waiters;
+        }
+    }
+
+    /// 设置并行计算观察者区域归属的阈值，`None` 表示始终串行
+    ///
+    /// 只有区域谓词的计算是并行的；回调仍按观察者顺序串行执行，结果是确定的
+    #[cfg(feature = "parallel")]
+    pub fn set_parallel_observer_threshold(&mut self, threshold: Option<usize>) {
+        self.parallel_observer_threshold = threshold;
+    }
+
+    /// 配置暂停期间的事件缓冲区
+    /// `capacity` 为 `None` 时不限容量；已缓冲的事件会被丢弃
+    pub fn set_pause_buffer(&mut self, capacity: Option<usize>, policy: OverflowPolicy) {
+        self.paused_events = EventBuffer::new(capacity, policy);
+    }
+
+    /// 为指定事件设置暂停缓冲区的溢出策略，重新配置缓冲区后需要重新设置
+    /// 例如输入类事件使用 `Coalesce` 合并，关键指令使用 `Error` 在缓冲区满时报告而不是悄悄丢弃
+    pub fn set_pause_event_policy(&mut self, event_id: EventId, policy: OverflowPolicy) {
+        self.paused_events.set_event_policy(event_id, policy);
+    }
+
+    /// 缓冲一个事件，`OverflowPolicy::Error` / `Block` 拒绝的事件记录为错误
+    fn buffer_event(&mut self, event: EventInstance) {
+        if let Err(event) = self.paused_events.try_push(event) {
+            self.record_error(StateZenError::EventOverflow(event.event_id));
+        }
+    }
+
+    /// 暂停状态机
+    /// 暂停期间 `handle_event` 只缓冲事件，不做处理
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// 恢复状态机，并按到达顺序处理暂停期间缓冲的事件
+    /// 被断点挂起的转换先于缓冲事件执行
+    pub fn resume(&mut self) {
+        self.paused = false;
+        if let Some(transition) = self.pending_transition.take() {
+            self.apply(transition);
+        }
+        while !self.paused {
+            let Some(event) = self.paused_events.pop() else {
+                break;
+            };
+            self.dispatch(event);
+        }
+    }
+
+    /// 状态机是否处于暂停状态
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// 暂停期间缓冲的事件数量
+    pub fn buffered_events(&self) -> usize {
+        self.paused_events.len()
+    }
+
+    /// 注册一个事件中间件
+    /// 中间件按注册顺序在 `handle_event` 中执行，位于转换选择之前
+    pub fn use_middleware<F>(&mut self, f: F)
+    where
+        F: Fn(EventId, Option<EventPayload>, Next) + Send + Sync + 'static,
+    {
+        self.middlewares.push(Arc::new(f));
+    }
+
+    /// 分发一个事件
+    /// 负载先经过事件定义的变换管线，事件再穿过中间件链，链输出的每个事件依次执行 `event_happen` + `transform`；
+    /// 暂停期间事件被缓冲，待 `resume` 时再处理
+    pub fn handle_event(&mut self, event_id: EventId, payload: Option<EventPayload>) {
+        let event = EventInstance::new(event_id, payload);
+        if self.paused {
+            self.buffer_event(event);
+        } else {
+            self.dispatch(event);
+        }
+    }
+
+    /// 以请求/响应方式分发一个事件
+    ///
+    /// 与 `handle_event` 相同地处理事件，返回本次处理中被执行的转换的 `respond` 结果；
+    /// 没有转换被执行、执行的转换没有响应函数，或状态机暂停而事件被缓冲时返回 `Ok(None)`。
+    /// 一个事件执行了多个转换时取最后一个响应。选中的转换因校验失败、越权、写冲突或发件箱写入失败
+    /// 而未能提交时返回第一个这样的错误；只记录不阻止提交的错误（如 `AuthorityPolicy::Warn` 的越权警告、
+    /// 确定性审计）不影响结果。错误同样保留在 `take_errors` 中
+    pub fn request(
+        &mut self,
+        event_id: EventId,
+        payload: Option<EventPayload>,
+    ) -> Result<Option<Box<dyn Any + Send>>, StateZenError> {
+        self.request = Some(RequestOutcome::default());
+        self.handle_event(event_id, payload);
+        let outcome = self.request.take().unwrap_or_default();
+        match outcome.rejection {
+            Some(error) => Err(error),
+            None => Ok(outcome.response),
+        }
+    }
+
+    /// 设置单个事件的转换选择策略，不影响 `handle_events`
+    pub fn set_dispatch_policy(&mut self, policy: DispatchPolicy) {
+        self.dispatch_policy = policy;
+    }
+
+    /// 设置 `handle_events` 的写冲突策略
+    pub fn set_batch_policy(&mut self, policy: BatchConflictPolicy) {
+        self.batch_policy = policy;
+    }
+
+    /// 批量分发一组事件
+    /// 每个事件照常穿过中间件链并选择转换，全部转换按写冲突策略合并后只提交一次：
+    /// 观察者的进出只针对最终状态计算一次，各转换的 OnTran 按执行顺序调用。
+    /// 暂停期间事件被缓冲；断点暂停时已执行的转换先提交，其余事件进入缓冲
+    pub fn handle_events(&mut self, events: &[EventInstance]) {
+        if self.paused {
+            for event in events {
+                self.buffer_event(event.clone());
+            }
+            return;
+        }
+        self.refresh_caches();
+
+        let mut queue: VecDeque<EventInstance> = events
+            .iter()
+            .flat_map(|e| {
+                let payload = self.transform_payload(e.event_id, e.payload.clone());
+                middleware::run_chain(&self.middlewares, e.event_id, payload)
+            })
+            .collect();
+        let sequential = self.batch_policy == BatchConflictPolicy::Sequential;
+        let start = self.current_state.clone();
+        let mut state = start.clone();
+        let mut written: BTreeSet<StateAspectId> = BTreeSet::new();
+        let mut steps = Vec::new();
+        let mut held = None;
+
+        while let Some(event) = queue.pop_front() {
+            let before = self.state_pool.copy_of(if sequential { &state } else { &start });
+            // 批次尚未改变状态时选择基于当前状态，可复用守卫缓存
+            let selected = if !sequential || steps.is_empty() {
+                self.select_now(event.event_id)
+            } else {
+                self.select(event.event_id, &before)
+            };
+            self.activity.on_event(event.event_id, selected.as_ref().map(|t| t.id));
+            for tracer in &self.tracers {
+                tracer.on_event(event.event_id, selected.as_ref().map(|t| t.id));
+            }
+            let Some(transition) = selected else {
+                continue;
+            };
+            if let Some(hook) = self.breakpoints.get(&transition.id) {
+                let context = BreakContext {
+                    event_id: Some(event.event_id),
+                    transition: &transition,
+                    state: &before,
+                };
+                match hook(&context) {
+                    BreakAction::Continue => {}
+                    BreakAction::Skip => continue,
+                    BreakAction::Pause => {
+                        held = Some((event.event_id, transition));
+                        break;
+                    }
+                }
+            }
+
+            let Some(after) = self.apply_transfer(&transition, &before) else {
+                continue;
+            };
+            let after = self.blueprint.normalize(&before, after);
+            let after = match self.check(&before, after, Some(transition.id)) {
+                Ok(after) => after,
+                Err(error) => {
+                    self.record_rejection(error);
+                    continue;
+                }
+            };
+            self.record_errors(self.authority_warning(&before, &after, Some(transition.id)));
+            assert_ensures(&transition, &before, &after);
+            if sequential {
+                state.clone_from(&after);
+            } else {
+                let changed = changed_aspects(&start, &after);
+                if self.batch_policy == BatchConflictPolicy::Reject && changed.iter().any(|id| written.contains(id)) {
+                    continue;
+                }
+                for id in &changed {
+                    if self.batch_policy == BatchConflictPolicy::FirstWriteWins && written.contains(id) {
+                        continue;
+                    }
+                    match after.get(id) {
+                        Some(value) => state.insert(*id, value.clone()),
+                        None => state.remove(id),
+                    };
+                }
+                written.extend(changed);
+            }
+            steps.push(BatchStep { transition, before, after });
+        }
+
+        if !steps.is_empty() {
+            let emitted = if self.emits_enabled() {
+                steps
+                    .iter()
+                    .flat_map(|step| step.transition.emits.iter().map(|t| t.render(&step.after)))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            if self.stage_emitted(&emitted) {
+                self.commit(state, Fired::Batch(&steps));
+                self.deliver_emitted(emitted);
+            }
+        }
+        self.recycle_steps(steps);
+        self.state_pool.recycle(start);
+        if let Some((event_id, transition)) = held {
+            self.pending_event = Some(event_id);
+            self.pending_transition = Some(transition);
+            self.paused = true;
+            for event in queue {
+                self.buffer_event(event);
+            }
+        }
+    }
+
+    /// 拉取事件源中当前可用的全部事件并依次分发
+    /// 返回拉取的事件数量
+    pub fn drain_source<S: EventSource + ?Sized>(&mut self, source: &mut S) -> usize {
+        let mut count = 0;
+        while let Some(event) = source.poll() {
+            self.handle_event(event.event_id, event.payload);
+            count += 1;
+        }
+        count
+    }
+
+    /// 按事件定义的负载变换管线整理负载；未声明的事件原样保留
+    fn transform_payload(&self, event_id: EventId, payload: Option<EventPayload>) -> Option<EventPayload> {
+        match self.blueprint.event(event_id) {
+            Some(event) => event.transform(payload),
+            None => payload,
+        }
+    }
+
+    fn dispatch(&mut self, event: EventInstance) {
+        let payload = self.transform_payload(event.event_id, event.payload);
+        let events = middleware::run_chain(&self.middlewares, event.event_id, payload);
+        for event in events {
+            // 断点可能在链输出的中途暂停状态机
+            if self.paused {
+                self.buffer_event(event);
+            } else if self.dispatch_policy == DispatchPolicy::AllNonConflicting {
+                self.broadcast(event.event_id);
+            } else {
+                self.event_happen(event.event_id, event.payload);
+                self.transform();
+            }
+        }
+    }
+
+    /// 按 `DispatchPolicy::AllNonConflicting` 执行事件的全部可用转换
+    fn broadcast(&mut self, event_id: EventId) {
+        self.refresh_caches();
+
+        let before = self.current_state.clone();
+        let mut selected = self.enabled_now(event_id);
+        selected.sort_by_key(|t| std::cmp::Reverse(t.priority));
+        self.activity.on_event(event_id, selected.first().map(|t| t.id));
+        for tracer in &self.tracers {
+            tracer.on_event(event_id, selected.first().map(|t| t.id));
+        }
+
+        let mut state = before.clone();
+        let mut writers: HashMap<StateAspectId, usize> = HashMap::new();
+        let mut steps: Vec<BatchStep> = Vec::new();
+        let mut held = None;
+        for transition in selected {
+            if let Some(hook) = self.breakpoints.get(&transition.id) {
+                let context = BreakContext {
+                    event_id: Some(event_id),
+                    transition: &transition,
+                    state: &before,
+                };
+                match hook(&context) {
+                    BreakAction::Continue => {}
+                    BreakAction::Skip => continue,
+                    BreakAction::Pause => {
+                        held = Some(transition);
+                        break;
+                    }
+                }
+            }
+            let Some(after) = self.apply_transfer(&transition, &before) else {
+                continue;
+            };
+            let after = self.blueprint.normalize(&before, after);
+            let after = match self.check(&before, after, Some(transition.id)) {
+                Ok(after) => after,
+                Err(error) => {
+                    self.record_rejection(error);
+                    continue;
+                }
+            };
+            self.record_errors(self.authority_warning(&before, &after, Some(transition.id)));
+            assert_ensures(&transition, &before, &after);
+            for id in changed_aspects(&before, &after) {
+                if let Some(&i) = writers.get(&id) {
+                    if !self.same_write(&steps[i], &transition, &after, id) {
+                        self.record_rejection(Some(StateZenError::WriteConflict {
+                            aspect: id,
+                            transitions: [steps[i].transition.id, transition.id],
+                        }));
+                        // 整个事件被拒绝，已取得的中间状态全部归还状态池
+                        self.recycle_steps(steps);
+                        for unused in [after, state, before] {
+                            self.state_pool.recycle(unused);
+                        }
+                        return;
+                    }
+                    continue;
+                }
+                writers.insert(id, steps.len());
+                match after.get(&id) {
+                    Some(value) => state.insert(id, value.clone()),
+                    None => state.remove(&id),
+                };
+            }
+            let step_before = self.state_pool.copy_of(&before);
+            steps.push(BatchStep { transition, before: step_before, after });
+        }
+        if let Some(transition) = held {
+            self.pending_event = Some(event_id);
+            self.pending_transition = Some(transition);
+            self.paused = true;
+        }
+        if steps.is_empty() {
+            self.state_pool.recycle(state);
+            self.state_pool.recycle(before);
+            return;
+        }
+
+        let state = self.blueprint.normalize(&before, state);
+        let emitted = if self.emits_enabled() {
+            steps
+                .iter()
+                .flat_map(|step| step.transition.emits.iter().map(|t| t.render(&step.after)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        if self.stage_emitted(&emitted) {
+            self.commit(state, Fired::Batch(&steps));
+            self.deliver_emitted(emitted);
+        }
+        self.recycle_steps(steps);
+        self.state_pool.recycle(before);
+    }
+
+    /// 先执行的 `step` 与转换 `transition`（结果为 `after`）对方面 `id` 的写入是否一致
+    fn same_write(&self, step: &BatchStep, transition: &Transition, after: &State, id: StateAspectId) -> bool {
+        match (step.after.get(&id), after.get(&id)) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                Arc::ptr_eq(a, b)
+                    || self.blueprint.aspect(id).and_then(|aspect| aspect.eq.as_ref()).is_some_and(|eq| eq(&**a, &**b))
+                    || step
+                        .transition
+                        .transfer
+                        .expr()
+                        .final_set(id)
+                        .or_else(|| transition.transfer.expr().final_set(id))
+                        .is_some_and(|v| v.eq_value(a) && v.eq_value(b))
+            }
+            _ => false,
+        }
+    }
+
+    /// 批量分发结束后回收各步的中间状态
+    fn recycle_steps(&mut self, steps: Vec<BatchStep>) {
+        for step in steps {
+            self.state_pool.recycle(step.before);
+            self.state_pool.recycle(step.after);
         }
     }
 
     /// 领域事件 1: EventHappen
     /// 处理事件发生，选择符合条件的转换
-    pub fn event_happen(&mut self, event_id: EventId, _payload: Option<Arc<dyn std::any::Any + Send + Sync>>) {
+    pub fn event_happen(&mut self, event_id: EventId, _payload: Option<EventPayload>) {
+        self.refresh_caches();
+
+        self.pending_transition = self.select_now(event_id);
+        self.pending_event = Some(event_id);
+
+        let selected = self.pending_transition.as_ref().map(|t| t.id);
+        if self.audit.is_some() && self.select(event_id, &self.current_state).map(|t| t.id) != selected {
+            self.record_error(StateZenError::NonDeterministic {
+                event: event_id,
+                transition: selected,
+                aspect: None,
+            });
+        }
+        self.activity.on_event(event_id, selected);
+        for tracer in &self.tracers {
+            tracer.on_event(event_id, selected);
+        }
+    }
+
+    /// 当前状态下守卫成立且停留时间已满足的、监听该事件的转换（按蓝图中的顺序）
+    /// 开启守卫缓存时，读集合未被写入的守卫复用上次的结果
+    fn enabled_now(&mut self, event_id: EventId) -> Vec<Transition> {
+        let mut memo = self.guard_memo.take();
+        let enabled = self
+            .listening_transitions(event_id)
+            .filter(|t| {
+                let holds = profiled(self.profiler.as_ref(), ProfileKey::Guard(t.id), || match &mut memo {
+                    Some(memo) => memo.contains(t.id, &t.guard, &self.current_state, &self.versions),
+                    None => t.guard.contains(&self.current_state),
+                });
+                holds && self.dwelled_long_enough(t)
+            })
+            .cloned()
+            .collect();
+        self.guard_memo = memo;
+        enabled
+    }
+
+    /// 在当前状态下为事件选择转换，规则同 `select`，开启守卫缓存时复用缓存的结果
+    fn select_now(&mut self, event_id: EventId) -> Option<Transition> {
+        let mut enabled = self.enabled_now(event_id);
+        enabled.sort_by_key(|t| std::cmp::Reverse(t.priority));
+        enabled.into_iter().next()
+    }
+
+    /// 在给定状态下为事件选择转换：守卫满足者中优先级最高、同优先级取蓝图中靠前的
+    fn select(&self, event_id: EventId, state: &State) -> Option<Transition> {
         let mut candidates: Vec<&Transition> = self
-            .blueprint
-            .transitions
-            .iter()
-            .filter(|t| t.event_id == event_id && t.guard.contains(&self.current_state))
+            .listening_transitions(event_id)
+            .filter(|t| self.guard_holds(t, state) && self.dwelled_long_enough(t))
             .collect();
 
         // 按优先级降序，同优先级按顺序（取第一个）
-        candidates.sort_by(|a, b| b.priority.cmp(&a.priority));
+        candidates.sort_by_key(|t| std::cmp::Reverse(t.priority));
+        candidates.first().map(|t| (*t).clone())
+    }
+
+    /// 转换的守卫在给定状态下是否成立
+    fn guard_holds(&self, transition: &Transition, state: &State) -> bool {
+        profiled(self.profiler.as_ref(), ProfileKey::Guard(transition.id), || transition.guard.contains(state))
+    }
+
+    /// 执行转换的转换函数；改写受保护方面而被拒绝时返回 `None`，按未选中处理
+    fn apply_transfer(&self, transition: &Transition, state: &State) -> Option<State> {
+        profiled(self.profiler.as_ref(), ProfileKey::Transfer(transition.id), || transition.transfer.try_apply(state))
+    }
 
-        self.pending_transition = candidates.first().cloned().cloned();
+    /// 转换的最短停留时间是否已满足
+    fn dwelled_long_enough(&self, transition: &Transition) -> bool {
+        transition
+            .min_dwell
+            .is_none_or(|min| self.dwell.get(&transition.id).is_some_and(|d| *d >= min))
+    }
+
+    /// 按当前状态更新停留计时：离开守卫区域的转换清零，刚进入的从零开始
+    fn refresh_dwell(&mut self) {
+        for transition in self.blueprint.transitions() {
+            if transition.min_dwell.is_none() {
+                continue;
+            }
+            if self.guard_holds(transition, &self.current_state) {
+                self.dwell.entry(transition.id).or_insert(Duration::ZERO);
+            } else {
+                self.dwell.remove(&transition.id);
+            }
+        }
+    }
+
+    /// `event_happen` 选中、尚未执行的转换
+    pub fn pending_transition(&self) -> Option<&Transition> {
+        self.pending_transition.as_ref()
+    }
+
+    /// 丢弃待执行的转换，随后的 `transform` 不做任何事
+    pub fn discard_pending(&mut self) -> Option<Transition> {
+        self.pending_transition.take()
+    }
+
+    /// 启用或停用转换，停用的转换不参与选择，蓝图不受影响
+    pub fn set_transition_enabled(&mut self, transition_id: TransitionId, enabled: bool) {
+        if enabled {
+            self.disabled_transitions.remove(&transition_id);
+        } else {
+            self.disabled_transitions.insert(transition_id);
+        }
+    }
+
+    /// 转换是否未被停用
+    pub fn is_transition_enabled(&self, transition_id: TransitionId) -> bool {
+        !self.disabled_transitions.contains(&transition_id)
+    }
+
+    /// 启用或停用事件，停用的事件到达时按没有转换可执行处理
+    pub fn set_event_enabled(&mut self, event_id: EventId, enabled: bool) {
+        if enabled {
+            self.disabled_events.remove(&event_id);
+        } else {
+            self.disabled_events.insert(event_id);
+        }
+    }
+
+    /// 事件是否未被停用
+    pub fn is_event_enabled(&self, event_id: EventId) -> bool {
+        !self.disabled_events.contains(&event_id)
+    }
+
+    /// 在转换上设置断点，转换执行前调用钩子决定继续、跳过还是暂停
+    /// 同一转换上已有的断点被替换
+    pub fn set_breakpoint(&mut self, transition_id: TransitionId, hook: BreakHook) {
+        self.breakpoints.insert(transition_id, hook);
+    }
+
+    /// 移除转换上的断点，返回是否存在
+    pub fn clear_breakpoint(&mut self, transition_id: TransitionId) -> bool {
+        self.breakpoints.remove(&transition_id).is_some()
+    }
+
+    /// 监听指定事件且未被停用的转换（按蓝图中的顺序）
+    #[cfg(not(feature = "index-dispatch"))]
+    fn listening_transitions(&self, event_id: EventId) -> impl Iterator<Item = &Transition> {
+        let event_enabled = self.is_event_enabled(event_id);
+        self.blueprint
+            .transition_slice()
+            .iter()
+            .filter(move |t| event_enabled && t.event_id == event_id && self.is_transition_enabled(t.id))
+    }
+
+    /// 监听指定事件且未被停用的转换（按蓝图中的顺序），通过事件索引查找
+    #[cfg(feature = "index-dispatch")]
+    fn listening_transitions(&self, event_id: EventId) -> impl Iterator<Item = &Transition> {
+        let indices = if self.is_event_enabled(event_id) { self.dispatch_index.get(event_id) } else { &[] };
+        indices
+            .iter()
+            .map(|&i| &self.blueprint.transition_slice()[i])
+            .filter(|t| self.is_transition_enabled(t.id))
     }
 
     /// 领域事件 2: Transform
     /// 执行待处理的转换
     pub fn transform(&mut self) {
-        if let Some(transition) = self.pending_transition.take() {
-            let next_state = transition.transfer.apply(&self.current_state);
+        let Some(transition) = self.pending_transition.take() else {
+            return;
+        };
+        if let Some(hook) = self.breakpoints.get(&transition.id) {
+            let context = BreakContext {
+                event_id: self.pending_event,
+                transition: &transition,
+                state: &self.current_state,
+            };
+            match hook(&context) {
+                BreakAction::Continue => {}
+                BreakAction::Skip => return,
+                BreakAction::Pause => {
+                    self.pending_transition = Some(transition);
+                    self.paused = true;
+                    return;
+                }
+            }
+        }
+        self.apply(transition);
+    }
+
+    fn apply(&mut self, transition: Transition) {
+        let Some(next_state) = self.apply_transfer(&transition, &self.current_state) else {
+            return;
+        };
+        if let (Some(audit), Some(event)) = (&self.audit, self.pending_event) {
+            let again = self.apply_transfer(&transition, &self.current_state);
+            let again = again.as_ref().unwrap_or(&self.current_state);
+            if let Some(aspect) = audit.first_difference(&self.current_state, &next_state, again) {
+                self.record_error(StateZenError::NonDeterministic {
+                    event,
+                    transition: Some(transition.id),
+                    aspect: Some(aspect),
+                });
+            }
+        }
+        let next_state = self.blueprint.normalize(&self.current_state, next_state);
+        let next_state = match self.check(&self.current_state, next_state, Some(transition.id)) {
+            Ok(state) => state,
+            Err(error) => {
+                self.record_rejection(error);
+                return;
+            }
+        };
+        self.record_errors(self.authority_warning(&self.current_state, &next_state, Some(transition.id)));
+        assert_ensures(&transition, &self.current_state, &next_state);
+        let emitted = if self.emits_enabled() {
+            transition.emits.iter().map(|t| t.render(&next_state)).collect()
+        } else {
+            Vec::new()
+        };
+        if !self.stage_emitted(&emitted) {
+            return;
+        }
+        self.commit(next_state, Fired::Transition(&transition));
+        self.deliver_emitted(emitted);
+    }
 
-            // 计算 observers 的进出
-            let mut on_exits = Vec::new();
-            let mut on_enters = Vec::new();
+    fn emits_enabled(&self) -> bool {
+        self.event_sink.is_some() || self.outbox.is_some()
+    }
 
-            for observer in &self.blueprint.observers {
-                let was_in = observer.region.contains(&self.current_state);
-                let now_in = observer.region.contains(&next_state);
+    /// 提交前把输出事件写入发件箱，写入失败时记录错误并返回 `false`
+    fn stage_emitted(&mut self, emitted: &[EventInstance]) -> bool {
+        let Some(outbox) = &self.outbox else {
+            return true;
+        };
+        if emitted.is_empty() {
+            return true;
+        }
+        match outbox.commit(emitted) {
+            Ok(()) => true,
+            Err(e) => {
+                self.record_rejection(Some(e));
+                false
+            }
+        }
+    }
 
-                if was_in && !now_in {
-                    if let Some(on_exit) = &observer.on_exit {
-                        on_exits.push(on_exit.clone());
-                    }
+    fn deliver_emitted(&mut self, emitted: Vec<EventInstance>) {
+        if self.outbox.is_some() {
+            self.relay_outbox();
+        } else if let Some(sink) = &mut self.event_sink {
+            for event in emitted {
+                sink.emit(event);
+            }
+        }
+    }
+
+    /// 设置领域事件汇，转换声明的 `emits` 在转换成功后推送到这里
+    pub fn set_event_sink<S>(&mut self, sink: S)
+    where
+        S: EventSink + Send + 'static,
+    {
+        self.event_sink = Some(Box::new(sink));
+    }
+
+    /// 设置输出事件发件箱
+    /// 转换的 `emits` 在状态提交前写入发件箱，写入失败时转换不生效并记录错误；
+    /// 提交后投递到事件汇并逐条确认。未设置事件汇时事件留在发件箱中
+    pub fn set_outbox(&mut self, outbox: Arc<dyn EffectOutbox>) {
+        self.outbox = Some(outbox);
+    }
+
+    /// 把发件箱中全部未确认的事件投递到事件汇并确认，返回投递的数量
+    /// 进程重启后调用一次，重新投递上次未确认的事件
+    pub fn relay_outbox(&mut self) -> usize {
+        let (Some(outbox), Some(sink)) = (&self.outbox, &mut self.event_sink) else {
+            return 0;
+        };
+        let pending = outbox.pending();
+        for entry in &pending {
+            sink.emit(entry.event.clone());
+            outbox.ack(entry.seq);
+        }
+        pending.len()
+    }
+
+    /// 设置 `tick_clock` 使用的时钟，从当前读数开始计时
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        let now = clock.now();
+        self.clock = Some((clock, now));
+    }
+
+    /// 以时钟自上次调用（或 `set_clock`）以来经过的时间调用 `tick`
+    /// 未设置时钟时不做任何事；时钟回拨时按零处理
+    pub fn tick_clock(&mut self) {
+        let Some((clock, last)) = &mut self.clock else {
+            return;
+        };
+        let now = clock.now();
+        let dt = now.saturating_sub(*last);
+        *last = now;
+        self.tick(dt);
+    }
+
+    /// 按策略把状态自动保存到 `store`，替换之前的自动保存设置
+    /// `Interval` 按 `set_clock` 设置的时钟计时，未设置时使用系统时钟；保存失败记录为错误
+    pub fn autosave_every(&mut self, store: Arc<dyn StateStore>, machine_id: MachineId, policy: AutosavePolicy) {
+        let clock = match &self.clock {
+            Some((clock, _)) => clock.clone(),
+            None => Arc::new(SystemClock::new()),
+        };
+        self.autosave = Some(Autosave::new(store, machine_id, policy, clock));
+    }
+
+    /// 停止自动保存
+    pub fn stop_autosave(&mut self) {
+        self.autosave = None;
+    }
+
+    /// 推进时间 `dt`
+    /// 先为处于守卫区域内、带最短停留时间的转换累计停留时间，并为看门狗计时、升级超时的看门狗，
+    /// 再依次应用所有在当前状态下生效的连续转换，并作为一次状态变更提交，
+    /// 最后分发期间到期的周期事件
+    pub fn tick(&mut self, dt: Duration) {
+        self.refresh_dwell();
+        for elapsed in self.dwell.values_mut() {
+            *elapsed += dt;
+        }
+        for (escalation, elapsed) in self.watchdogs.advance(&self.current_state, dt) {
+            match escalation {
+                Escalation::Event(event_id) => self.handle_event(event_id, None),
+                Escalation::Callback(f) => f(&self.current_state, elapsed),
+            }
+        }
+
+        let mut next_state: Option<State> = None;
+        for continuous in self.blueprint.continuous_transfers() {
+            if continuous.region.contains(&self.current_state) {
+                let base = next_state.as_ref().unwrap_or(&self.current_state);
+                next_state = Some(continuous.apply(base, dt));
+            }
+        }
+
+        if let Some(next_state) = next_state {
+            let next_state = self.blueprint.normalize(&self.current_state, next_state);
+            match self.check(&self.current_state, next_state, None) {
+                Ok(next_state) => {
+                    self.record_errors(self.authority_warning(&self.current_state, &next_state, None));
+                    self.commit(next_state, Fired::Tick);
                 }
-                if !was_in && now_in {
-                    if let Some(on_enter) = &observer.on_enter {
-                        on_enters.push(on_enter.clone());
+                Err(error) => self.record_errors(error),
+            }
+        }
+
+        for event_id in self.scheduler.advance(dt) {
+            self.handle_event(event_id, None);
+        }
+    }
+
+    /// 添加一个看门狗，时间随 `tick` 推进
+    pub fn add_watchdog(&mut self, watchdog: Watchdog) -> WatchdogHandle {
+        let handle = self.watchdogs.add(watchdog);
+        self.watchdogs.refresh(&self.current_state);
+        handle
+    }
+
+    /// 移除看门狗，返回是否存在
+    pub fn remove_watchdog(&mut self, handle: WatchdogHandle) -> bool {
+        self.watchdogs.remove(handle)
+    }
+
+    /// 距离下一个周期事件触发还有多久，没有周期事件时为 `None`
+    pub fn next_recurring_in(&self) -> Option<Duration> {
+        self.scheduler.time_to_next()
+    }
+
+    /// 每隔 `every`（再加上 `[0, jitter]` 内的随机抖动）分发一次事件
+    /// 时间随 `tick` 推进，到期的事件在连续转换提交之后按触发时刻顺序经 `handle_event` 分发；
+    /// 抖动使用固定种子的伪随机数，回放同样的 `tick` 序列得到同样的触发序列
+    ///
+    /// # Panics
+    ///
+    /// `every` 为零时 panic
+    pub fn schedule_recurring(&mut self, event_id: EventId, every: Duration, jitter: Option<Duration>) -> RecurringHandle {
+        self.scheduler.schedule(event_id, every, jitter)
+    }
+
+    /// 取消周期事件，返回是否存在
+    pub fn cancel_recurring(&mut self, handle: RecurringHandle) -> bool {
+        self.scheduler.cancel(handle)
+    }
+
+    /// 正在调度的周期事件数量
+    pub fn recurring_events(&self) -> usize {
+        self.scheduler.len()
+    }
+
+    /// 状态机是否已进入终止区域
+    pub fn is_finished(&self) -> bool {
+        self.blueprint
+            .final_region()
+            .is_some_and(|r| r.contains(&self.current_state))
+    }
+
+    /// 设置进入终止区域时的回调函数
+    pub fn set_on_finished<F>(&mut self, f: F)
+    where
+        F: Fn(&State) + Send + Sync + 'static,
+    {
+        self.on_finished = Some(Arc::new(f));
+    }
+
+    /// 计算每个观察者是否包含给定状态（按观察者顺序）
+    fn observer_membership_of(&self, state: &State) -> Vec<bool> {
+        let observers = self.blueprint.observer_slice();
+        let canonical = self.canonical().and_then(|c| Some((c, c.index_of(state)?)));
+        if let Some((c, index)) = canonical
+            && let Some(membership) = (0..observers.len()).map(|i| c.contains(index, i)).collect()
+        {
+            return membership;
+        }
+
+        #[cfg(feature = "parallel")]
+        if self
+            .parallel_observer_threshold
+            .is_some_and(|threshold| observers.len() >= threshold)
+        {
+            use rayon::prelude::*;
+            let profiler = self.profiler.as_ref();
+            return observers.par_iter().map(|o| observer_holds(profiler, o, state)).collect();
+        }
+
+        // 共享同一闭包的区域（见 `intern_predicates`）只求值一次
+        let mut memo: HashMap<usize, bool> = HashMap::new();
+        observers
+            .iter()
+            .enumerate()
+            .map(|(i, o)| match canonical.and_then(|(c, index)| c.contains(index, i)) {
+                Some(holds) => holds,
+                None => {
+                    o.active_when.as_ref().is_none_or(|active| active.contains(state))
+                        && *memo
+                            .entry(o.region.ptr_key())
+                            .or_insert_with(|| region_holds(self.profiler.as_ref(), o, state))
+                }
+            })
+            .collect()
+    }
+
+    /// 注册规范状态集合，`None` 表示取消
+    ///
+    /// 注册时对每个观察者预先计算它在各规范状态上的归属（位集）。之后提交前后的状态若都是规范状态，
+    /// 读集合落在规范方面内的观察者直接查表，全部观察者都可查表时进出检测只需位运算；
+    /// 其余观察者照常求值。蓝图的观察者经方法修改后，位集在下一次分发事件时重新计算
+    pub fn set_canonical_states(&mut self, states: Option<CanonicalStates>) {
+        self.canonical = states.map(|s| CanonicalMembership::build(s, &self.blueprint));
+    }
+
+    /// 仍然有效的规范状态位集
+    fn canonical(&self) -> Option<&CanonicalMembership> {
+        self.canonical.as_ref().filter(|c| !c.is_stale(&self.blueprint))
+    }
+
+    /// 蓝图修改后重建分发索引与规范状态位集
+    fn refresh_caches(&mut self) {
+        #[cfg(feature = "index-dispatch")]
+        self.dispatch_index.refresh(&self.blueprint);
+        if self.canonical.as_ref().is_some_and(|c| c.is_stale(&self.blueprint)) {
+            let states = self.canonical.take().map(CanonicalMembership::into_states);
+            self.set_canonical_states(states);
+        }
+    }
+
+    /// 观察转换 `id` 的转换观察者：先蓝图的，后运行时的
+    fn transition_observers_of(&self, id: TransitionId) -> impl Iterator<Item = &TransitionObserver> {
+        self.blueprint
+            .transition_observers()
+            .chain(&self.transition_observers)
+            .filter(move |o| o.matches(id))
+    }
+
+    /// 提交新状态：计算 observers 的进出并按顺序执行回调
+    fn commit(&mut self, next_state: State, fired: Fired) {
+        self.transform_hooks.run_before(&self.current_state, &next_state);
+        let mut on_exits = Vec::new();
+        let mut on_enters = Vec::new();
+        let mut region_edges = Vec::new();
+
+        // 上一次提交已算出当前状态的区域归属，直接复用
+        let previous = match self.observer_membership.take() {
+            Some(m) if m.len() == self.blueprint.observer_slice().len() => m,
+            _ => self.observer_membership_of(&self.current_state),
+        };
+        let membership = self.observer_membership_of(&next_state);
+
+        // 前后都是规范状态且全部观察者可查表时，归属变化由位集异或得到
+        let changed = self
+            .canonical()
+            .and_then(|c| c.changed(c.index_of(&self.current_state)?, c.index_of(&next_state)?))
+            .unwrap_or_else(|| (0..membership.len()).filter(|&i| previous[i] != membership[i]).collect());
+        for i in changed {
+            let observer = &self.blueprint.observer_slice()[i];
+            let was_in = previous[i];
+            let now_in = membership[i];
+
+            if !self.tracers.is_empty() {
+                let edge = if now_in { RegionEdge::Enter } else { RegionEdge::Exit };
+                region_edges.push((observer.id, edge));
+            }
+
+            if was_in && !now_in {
+                on_exits.push(observer);
+            }
+            if !was_in && now_in {
+                on_enters.push(observer);
+            }
+        }
+        // 嵌套区域先退出内层、先进入外层；同层按优先级从高到低。稳定排序，其余保持蓝图顺序
+        on_exits.sort_by_key(|o| std::cmp::Reverse(self.blueprint.nesting_depth(o.id)));
+        on_enters.sort_by_key(|o| (self.blueprint.nesting_depth(o.id), std::cmp::Reverse(o.priority)));
+
+        let mut on_edges = Vec::new();
+        for edge in self.blueprint.edge_observers() {
+            let key = ProfileKey::Edge(edge.id);
+            let was_true = profiled(self.profiler.as_ref(), key, || edge.condition.contains(&self.current_state));
+            let now_true = profiled(self.profiler.as_ref(), key, || edge.condition.contains(&next_state));
+
+            if !was_true && now_true && let Some(on_rising) = &edge.on_rising {
+                on_edges.push(on_rising.clone());
+            }
+            if was_true && !now_true && let Some(on_falling) = &edge.on_falling {
+                on_edges.push(on_falling.clone());
+            }
+        }
+
+        let finishing = match self.blueprint.final_region() {
+            Some(region) => !region.contains(&self.current_state) && region.contains(&next_state),
+            None => false,
+        };
+
+        // 执行顺序: OnExit -> OnTran -> 转换观察者 -> OnEnter -> 边沿回调 -> OnFinished
+        let mut effects: Vec<Effect> = on_exits
+            .into_iter()
+            .filter_map(|o| o.on_exit.clone())
+            .map(Effect::Exit)
+            .collect();
+        match fired {
+            Fired::Tick => {}
+            Fired::Transition(t) => {
+                if let Some(on_tran) = &t.on_tran {
+                    effects.push(Effect::Tran(on_tran.clone()));
+                }
+                for observer in self.transition_observers_of(t.id) {
+                    effects.push(Effect::Observed(observer.callback.clone(), t.id, None));
+                }
+            }
+            Fired::Batch(steps) => {
+                for step in steps {
+                    let states = || Box::new((step.before.clone(), step.after.clone()));
+                    if let Some(on_tran) = &step.transition.on_tran {
+                        effects.push(Effect::TranBetween(on_tran.clone(), states()));
+                    }
+                    for observer in self.transition_observers_of(step.transition.id) {
+                        effects.push(Effect::Observed(observer.callback.clone(), step.transition.id, Some(states())));
                     }
                 }
             }
+        }
+        for observer in on_enters {
+            let region = observer.region.ptr_key();
+            if let Some(on_enter) = &observer.on_enter {
+                effects.push(Effect::Enter(region, on_enter.clone()));
+            }
+            if let Some(consume) = &observer.on_enter_consume {
+                effects.push(Effect::Consume(region, consume.clone()));
+            }
+        }
+        effects.extend(on_edges.into_iter().map(Effect::Edge));
+        if finishing && let Some(on_finished) = &self.on_finished {
+            effects.push(Effect::Finished(on_finished.clone()));
+        }
 
-            // 执行顺序: OnExit -> OnTran -> OnEnter
-            for on_exit in on_exits {
-                on_exit(&self.current_state);
+        if self.defer_effects {
+            if !effects.is_empty() {
+                self.deferred_effects.push(DeferredEffects {
+                    effects,
+                    prev: self.current_state.clone(),
+                    next: next_state.clone(),
+                });
             }
+        } else {
+            run_effects(&effects, &self.current_state, &next_state);
+        }
 
-            if let Some(on_tran) = &transition.on_tran {
-                on_tran(&self.current_state, &next_state);
+        if let Some(request) = &mut self.request {
+            match fired {
+                Fired::Tick => {}
+                Fired::Transition(t) => {
+                    if let Some(respond) = &t.respond {
+                        request.response = Some(respond(&self.current_state, &next_state));
+                    }
+                }
+                Fired::Batch(steps) => {
+                    for step in steps {
+                        if let Some(respond) = &step.transition.respond {
+                            request.response = Some(respond(&step.before, &step.after));
+                        }
+                    }
+                }
             }
+        }
 
-            for on_enter in on_enters {
-                on_enter(&next_state);
+        match fired {
+            Fired::Tick => {}
+            Fired::Transition(t) => self.activity.on_transition(t.id, &self.current_state, &next_state),
+            Fired::Batch(steps) => {
+                for step in steps {
+                    self.activity.on_transition(step.transition.id, &step.before, &step.after);
+                }
             }
+        }
+        for tracer in &self.tracers {
+            match fired {
+                Fired::Tick => {}
+                Fired::Transition(t) => tracer.on_transition(t.id, &self.current_state, &next_state),
+                Fired::Batch(steps) => {
+                    for step in steps {
+                        tracer.on_transition(step.transition.id, &step.before, &step.after);
+                    }
+                }
+            }
+            for (observer_id, edge) in &region_edges {
+                tracer.on_observer(*observer_id, *edge);
+            }
+            tracer.on_commit(&self.current_state, &next_state);
+        }
 
-            self.current_state = next_state;
+        for id in changed_aspects(&self.current_state, &next_state) {
+            *self.versions.entry(id).or_insert(0) += 1;
+        }
+        let prev = std::mem::replace(&mut self.current_state, next_state);
+        self.transform_hooks.run_after(&prev, &self.current_state);
+        #[cfg(feature = "async")]
+        if !self.waiters.is_empty() {
+            let event_id = match fired {
+                Fired::Tick => None,
+                Fired::Transition(t) => Some(t.event_id),
+                Fired::Batch(steps) => steps.last().map(|step| step.transition.event_id),
+            };
+            self.waiters.retain(|w| w.notify(event_id, &prev, &self.current_state, &self.versions));
+        }
+        self.state_pool.recycle(prev);
+        self.observer_membership = Some(membership);
+        self.refresh_dwell();
+        if !self.watchdogs.is_empty() {
+            self.watchdogs.refresh(&self.current_state);
         }
+        if let Some(autosave) = &mut self.autosave
+            && let Err(e) = autosave.after_commit(&self.current_state)
+        {
+            self.record_error(e);
+        }
+    }
+}
+
+/// 观察者区域是否包含给定状态，开启计时时记录耗时
+fn region_holds(profiler: Option<&Profiler>, observer: &StateObserver, state: &State) -> bool {
+    profiled(profiler, ProfileKey::Observer(observer.id), || observer.region.contains(state))
+}
+
+/// 观察者是否包含给定状态：激活区域不成立时不再求值观察区域
+#[cfg(feature = "parallel")]
+fn observer_holds(profiler: Option<&Profiler>, observer: &StateObserver, state: &State) -> bool {
+    observer.active_when.as_ref().is_none_or(|active| active.contains(state)) && region_holds(profiler, observer, state)
+}
+
+/// 调试构建下断言转换后的状态满足其后置条件，违反时报告转换ID与写入的方面
+fn assert_ensures(transition: &Transition, before: &State, after: &State) {
+    if cfg!(debug_assertions)
+        && let Some(ensures) = &transition.ensures
+        && !ensures.contains(after)
+    {
+        let mut written = changed_aspects(before, after);
+        written.sort_unstable();
+        panic!(
+            "转换 {} 违反后置条件 `{}`，写入的方面：{written:?}",
+            transition.id,
+            ensures.describe()
+        );
     }
-}
\ No newline at end of file
+}
+
+
+/// `after` 相对 `before` 被写入或移除的方面；未被转换触及的方面共享同一个取值
+pub(crate) fn changed_aspects(before: &State, after: &State) -> Vec<StateAspectId> {
+    let mut changed: Vec<StateAspectId> = after
+        .iter()
+        .filter(|(id, value)| before.get(id).is_none_or(|old| !Arc::ptr_eq(old, value)))
+        .map(|(id, _)| *id)
+        .collect();
+    changed.extend(before.keys().filter(|id| !after.contains_key(id)));
+    changed
+}
@@ -0,0 +1,110 @@
+//! 周期事件调度
+
+use std::time::Duration;
+use super::types::EventId;
+
+/// 周期事件句柄，用于取消
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RecurringHandle(u64);
+
+/// 一个周期事件
+struct Recurring {
+    handle: RecurringHandle,
+    event_id: EventId,
+    every: Duration,
+    jitter: Option<Duration>,
+    /// 下一次触发的时刻
+    due: Duration,
+}
+
+/// 运行时内部的调度器
+/// 时间只随 `tick` 推进；抖动由固定种子的伪随机数生成，相同的 `tick` 序列总是产生相同的触发序列
+pub(crate) struct Scheduler {
+    now: Duration,
+    next_handle: u64,
+    entries: Vec<Recurring>,
+    rng: u64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self {
+            now: Duration::ZERO,
+            next_handle: 0,
+            entries: Vec::new(),
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+}
+
+impl Scheduler {
+    /// 添加周期事件，首次触发在一个周期（加抖动）之后
+    pub(crate) fn schedule(&mut self, event_id: EventId, every: Duration, jitter: Option<Duration>) -> RecurringHandle {
+        assert!(!every.is_zero(), "周期事件的间隔必须大于零");
+        let handle = RecurringHandle(self.next_handle);
+        self.next_handle += 1;
+        let due = self.now + every + self.sample(jitter);
+        self.entries.push(Recurring {
+            handle,
+            event_id,
+            every,
+            jitter,
+            due,
+        });
+        handle
+    }
+
+    /// 取消周期事件，返回是否存在
+    pub(crate) fn cancel(&mut self, handle: RecurringHandle) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|e| e.handle != handle);
+        self.entries.len() != before
+    }
+
+    /// 正在调度的周期事件数量
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 推进时间 `dt`，按触发时刻顺序返回期间到期的事件（同一事件可能到期多次）
+    pub(crate) fn advance(&mut self, dt: Duration) -> Vec<EventId> {
+        self.now += dt;
+        let mut fired = Vec::new();
+        while let Some(i) = self.next_due() {
+            let (every, jitter) = (self.entries[i].every, self.entries[i].jitter);
+            let delay = every + self.sample(jitter);
+            let entry = &mut self.entries[i];
+            fired.push(entry.event_id);
+            entry.due += delay;
+        }
+        fired
+    }
+
+    /// 距离最早的下一次触发还有多久，没有周期事件时为 `None`
+    pub(crate) fn time_to_next(&self) -> Option<Duration> {
+        self.entries.iter().map(|e| e.due.saturating_sub(self.now)).min()
+    }
+
+    /// 最早到期的周期事件下标，同时到期时先添加的优先
+    fn next_due(&self) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.due <= self.now)
+            .min_by_key(|(_, e)| (e.due, e.handle.0))
+            .map(|(i, _)| i)
+    }
+
+    /// 在 `[0, jitter]` 内取一个抖动（xorshift64*）
+    fn sample(&mut self, jitter: Option<Duration>) -> Duration {
+        let Some(jitter) = jitter.filter(|j| !j.is_zero()) else {
+            return Duration::ZERO;
+        };
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let random = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        let nanos = jitter.as_nanos().min(u128::from(u64::MAX)) as u64;
+        Duration::from_nanos(random % nanos.saturating_add(1))
+    }
+}
@@ -0,0 +1,86 @@
+//! 受限合并：限制导入蓝图的能力
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use super::types::{StateAspectId, EventId};
+use super::authority::Authority;
+use super::blueprint::StateMachineBlueprint;
+use super::continuous::ContinuousTransfer;
+use super::transfer::restrict_writes;
+
+/// 导入蓝图的能力范围
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Scope {
+    /// 允许监听的事件
+    pub allowed_events: BTreeSet<EventId>,
+    /// 允许写入的方面
+    pub allowed_write_aspects: BTreeSet<StateAspectId>,
+}
+
+impl Scope {
+    /// 创建一个什么都不允许的范围
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 允许监听事件
+    pub fn allow_events<I>(mut self, events: I) -> Self
+    where
+        I: IntoIterator<Item = EventId>,
+    {
+        self.allowed_events.extend(events);
+        self
+    }
+
+    /// 允许写入方面
+    pub fn allow_writes<I>(mut self, aspects: I) -> Self
+    where
+        I: IntoIterator<Item = StateAspectId>,
+    {
+        self.allowed_write_aspects.extend(aspects);
+        self
+    }
+}
+
+impl StateMachineBlueprint {
+    /// 在能力范围内合并一个不受信任的蓝图（如第三方插件）
+    ///
+    /// - 监听范围外事件的转换被过滤，范围外的事件定义不导入
+    /// - 其余转换与连续转换被包装，对范围外方面的写入被丢弃；范围外方面的规范化转换与派生方面不导入
+    /// - 宿主已声明的方面保留宿主的定义（类型、校验、默认值、相等比较）与权威归属，导入蓝图中的
+    ///   同ID声明、派生方面与权威设置被忽略；导入蓝图只能为新方面设置权威归属
+    /// - 导入蓝图的终止区域被忽略，状态机何时完成只由宿主决定
+    /// - 观察者按 `merge` 的规则导入
+    pub fn merge_scoped(&self, other: &Self, scope: &Scope) -> Self {
+        let allowed: Arc<[StateAspectId]> = scope.allowed_write_aspects.iter().copied().collect();
+
+        let mut imported = other.clone();
+        imported.aspects_mut().retain(|id, _| self.aspect(*id).is_none());
+        imported.retain_authority(|id, _| self.aspect(id).is_none() && self.authority(id) == Authority::Shared);
+        imported.clear_final_region();
+        imported.events_mut().retain(|id, _| scope.allowed_events.contains(id));
+        *imported.transitions_mut() = other
+            .transitions()
+            .filter(|t| scope.allowed_events.contains(&t.event_id))
+            .map(|t| {
+                let mut t = t.clone();
+                t.transfer = t.transfer.restricted(allowed.clone());
+                t
+            })
+            .collect();
+        *imported.continuous_transfers_mut() = other
+            .continuous_transfers()
+            .map(|c| {
+                let inner = c.clone();
+                let allowed = allowed.clone();
+                ContinuousTransfer::new(c.region.clone(), move |s, dt| {
+                    restrict_writes(s, &inner.apply(s, dt), &allowed)
+                })
+            })
+            .collect();
+        imported.retain_normalizers(|(id, _)| scope.allowed_write_aspects.contains(id));
+        imported.retain_derived(|d| scope.allowed_write_aspects.contains(&d.id) && self.aspect(d.id).is_none());
+
+        self.merge(&imported)
+    }
+}
@@ -0,0 +1,25 @@
+//! 领域事件汇
+
+use std::collections::VecDeque;
+use std::sync::mpsc::Sender;
+use super::event::EventInstance;
+
+/// 事件汇
+/// 接收转换声明的领域事件，用于状态机之间或状态机到消息总线的通知
+pub trait EventSink {
+    /// 推送一个事件
+    fn emit(&mut self, event: EventInstance);
+}
+
+impl EventSink for VecDeque<EventInstance> {
+    fn emit(&mut self, event: EventInstance) {
+        self.push_back(event);
+    }
+}
+
+impl EventSink for Sender<EventInstance> {
+    fn emit(&mut self, event: EventInstance) {
+        // 接收端已关闭时丢弃事件
+        let _ = self.send(event);
+    }
+}
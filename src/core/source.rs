@@ -0,0 +1,24 @@
+//! 外部事件源
+
+use std::collections::VecDeque;
+use std::sync::mpsc::Receiver;
+use super::event::EventInstance;
+
+/// 事件源
+/// 宿主提供的事件流（通道、输入队列、消息消费者等），由 `drain_source` 拉取
+pub trait EventSource {
+    /// 拉取下一个事件；当前没有可用事件时返回 `None`
+    fn poll(&mut self) -> Option<EventInstance>;
+}
+
+impl EventSource for VecDeque<EventInstance> {
+    fn poll(&mut self) -> Option<EventInstance> {
+        self.pop_front()
+    }
+}
+
+impl EventSource for Receiver<EventInstance> {
+    fn poll(&mut self) -> Option<EventInstance> {
+        self.try_recv().ok()
+    }
+}
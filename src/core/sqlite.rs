@@ -0,0 +1,311 @@
+//! SQLite 历史存储
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use rusqlite::{Connection, OptionalExtension, params};
+use super::types::{StateAspectId, EventId, TransitionId, MachineId};
+use super::runtime::State;
+use super::state_in_range::StateInRange;
+use super::codec::StateCodec;
+use super::store::{StateStore, escape, unescape};
+use super::trace::Tracer;
+use super::error::StateZenError;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transforms (
+    machine_id INTEGER NOT NULL,
+    seq INTEGER NOT NULL,
+    event_id INTEGER,
+    transition_id INTEGER,
+    diff TEXT NOT NULL,
+    PRIMARY KEY (machine_id, seq)
+);
+CREATE TABLE IF NOT EXISTS current (
+    machine_id INTEGER NOT NULL,
+    aspect_id INTEGER NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (machine_id, aspect_id)
+);
+";
+
+/// 一次被持久化的提交
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredTransform {
+    /// 在该状态机内的序号，从 0 开始；序号 0 保存首次记录时的完整状态
+    pub seq: u64,
+    /// 触发提交的事件
+    pub event_id: Option<EventId>,
+    /// 执行的转换
+    pub transition: Option<TransitionId>,
+    /// 被写入的方面及其编码后的新值，`None` 表示方面被移除
+    pub diff: Vec<(StateAspectId, Option<String>)>,
+}
+
+/// SQLite 历史存储
+///
+/// 每次提交写入 `transforms` 表一行（状态机ID、序号、事件ID、转换ID、编码后的差异），
+/// 同时维护 `current` 表保存各状态机的最新状态，用于跨状态机的查询。
+/// 取值通过 `StateCodec` 编码，未注册编解码器的方面无法记录
+pub struct SqliteHistoryStore {
+    conn: Mutex<Connection>,
+    codec: StateCodec,
+}
+
+fn sql_error(machine: MachineId, error: rusqlite::Error) -> StateZenError {
+    StateZenError::StoreFailed {
+        machine,
+        message: error.to_string(),
+    }
+}
+
+fn encode_diff(diff: &[(StateAspectId, Option<String>)]) -> String {
+    diff.iter()
+        .map(|(id, value)| match value {
+            Some(value) => format!("{id}\t{}\n", escape(value)),
+            None => format!("{id}\n"),
+        })
+        .collect()
+}
+
+fn decode_diff(text: &str) -> Option<Vec<(StateAspectId, Option<String>)>> {
+    text.lines()
+        .map(|line| match line.split_once('\t') {
+            Some((id, value)) => Some((id.parse().ok()?, Some(unescape(value)))),
+            None => Some((line.parse().ok()?, None)),
+        })
+        .collect()
+}
+
+impl SqliteHistoryStore {
+    /// 打开（或创建）数据库文件
+    pub fn open(path: impl AsRef<Path>, codec: StateCodec) -> Result<Self, StateZenError> {
+        Self::with_connection(Connection::open(path), codec)
+    }
+
+    /// 使用内存数据库
+    pub fn in_memory(codec: StateCodec) -> Result<Self, StateZenError> {
+        Self::with_connection(Connection::open_in_memory(), codec)
+    }
+
+    fn with_connection(conn: rusqlite::Result<Connection>, codec: StateCodec) -> Result<Self, StateZenError> {
+        let conn = conn.map_err(|e| sql_error(0, e))?;
+        conn.execute_batch(SCHEMA).map_err(|e| sql_error(0, e))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            codec,
+        })
+    }
+
+    /// 记录一次提交
+    /// 状态机首次被记录时先以序号 0 保存 `before` 的完整状态
+    pub fn record(
+        &self,
+        machine_id: MachineId,
+        event_id: Option<EventId>,
+        transition: Option<TransitionId>,
+        before: &State,
+        after: &State,
+    ) -> Result<(), StateZenError> {
+        let err = |e| sql_error(machine_id, e);
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(err)?;
+        let last: Option<u64> = tx
+            .query_row("SELECT MAX(seq) FROM transforms WHERE machine_id = ?1", [machine_id as i64], |r| {
+                r.get::<_, Option<i64>>(0)
+            })
+            .map_err(err)?
+            .map(|seq| seq as u64);
+        let mut rows = Vec::new();
+        if last.is_none() {
+            let baseline = self.codec.encode_state(before)?;
+            rows.push((None, None, baseline.into_iter().map(|(id, v)| (id, Some(v))).collect::<Vec<_>>()));
+        }
+        let first = last.map_or(0, |last| last + 1);
+        let mut diff: Vec<(StateAspectId, Option<String>)> = Vec::new();
+        for (id, value) in after.iter() {
+            if before.get(id).is_none_or(|old| !Arc::ptr_eq(old, value)) {
+                diff.push((*id, Some(self.codec.encode(*id, value)?)));
+            }
+        }
+        diff.extend(before.keys().filter(|id| !after.contains_key(id)).map(|id| (*id, None)));
+        diff.sort_unstable_by_key(|(id, _)| *id);
+        rows.push((event_id, transition, diff));
+
+        for (i, (event_id, transition, diff)) in rows.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO transforms (machine_id, seq, event_id, transition_id, diff) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    machine_id as i64,
+                    (first + i as u64) as i64,
+                    event_id.map(|e| e as i64),
+                    transition.map(|t| t as i64),
+                    encode_diff(diff)
+                ],
+            )
+            .map_err(err)?;
+            for (aspect, value) in diff {
+                match value {
+                    Some(value) => tx.execute(
+                        "INSERT OR REPLACE INTO current (machine_id, aspect_id, value) VALUES (?1, ?2, ?3)",
+                        params![machine_id as i64, *aspect as i64, value],
+                    ),
+                    None => tx.execute(
+                        "DELETE FROM current WHERE machine_id = ?1 AND aspect_id = ?2",
+                        params![machine_id as i64, *aspect as i64],
+                    ),
+                }
+                .map_err(err)?;
+            }
+        }
+        tx.commit().map_err(err)
+    }
+
+    /// 状态机的全部提交记录（按序号升序）
+    pub fn entries(&self, machine_id: MachineId) -> Result<Vec<StoredTransform>, StateZenError> {
+        let err = |e| sql_error(machine_id, e);
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT seq, event_id, transition_id, diff FROM transforms WHERE machine_id = ?1 ORDER BY seq")
+            .map_err(err)?;
+        let rows = stmt
+            .query_map([machine_id as i64], |r| {
+                Ok((r.get::<_, i64>(0)?, r.get::<_, Option<i64>>(1)?, r.get::<_, Option<i64>>(2)?, r.get::<_, String>(3)?))
+            })
+            .map_err(err)?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (seq, event_id, transition, diff) = row.map_err(err)?;
+            let diff = decode_diff(&diff).ok_or_else(|| StateZenError::StoreFailed {
+                machine: machine_id,
+                message: format!("第 {seq} 条记录的差异无法解析"),
+            })?;
+            entries.push(StoredTransform {
+                seq: seq as u64,
+                event_id: event_id.map(|e| e as EventId),
+                transition: transition.map(|t| t as TransitionId),
+                diff,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// 回放到序号 `seq`（含）时的状态；没有该序号的记录时返回 `None`
+    pub fn state_at(&self, machine_id: MachineId, seq: u64) -> Result<Option<State>, StateZenError> {
+        let entries = self.entries(machine_id)?;
+        if !entries.iter().any(|e| e.seq == seq) {
+            return Ok(None);
+        }
+        let mut state = State::new();
+        for entry in entries.iter().take_while(|e| e.seq <= seq) {
+            for (id, value) in &entry.diff {
+                match value {
+                    Some(text) => {
+                        state.insert(*id, self.codec.decode(*id, text)?);
+                    }
+                    None => {
+                        state.remove(id);
+                    }
+                }
+            }
+        }
+        Ok(Some(state))
+    }
+
+    /// 全部被记录过的状态机ID（升序）
+    pub fn machines(&self) -> Result<Vec<MachineId>, StateZenError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT machine_id FROM transforms ORDER BY machine_id")
+            .map_err(|e| sql_error(0, e))?;
+        let ids = stmt
+            .query_map([], |r| r.get::<_, i64>(0))
+            .map_err(|e| sql_error(0, e))?
+            .map(|id| id.map(|id| id as MachineId))
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| sql_error(0, e))?;
+        Ok(ids)
+    }
+
+    /// 最新状态位于 `region` 内的状态机（按ID升序）
+    pub fn machines_in_region(&self, region: &StateInRange) -> Result<Vec<MachineId>, StateZenError> {
+        let mut found = Vec::new();
+        for id in self.machines()? {
+            if self.load(id)?.is_some_and(|s| region.contains(&s)) {
+                found.push(id);
+            }
+        }
+        Ok(found)
+    }
+
+    /// 创建记录指定状态机的追踪器，挂载到运行时后每次提交写入一行
+    pub fn recorder(self: &Arc<Self>, machine_id: MachineId) -> Arc<SqliteRecorder> {
+        Arc::new(SqliteRecorder {
+            store: self.clone(),
+            machine_id,
+            pending: Mutex::new(None),
+            errors: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+/// 以单独一行写入完整状态；读取最新状态
+impl StateStore for SqliteHistoryStore {
+    fn save(&self, machine_id: MachineId, state: &State) -> Result<(), StateZenError> {
+        let current = self.load(machine_id)?.unwrap_or_default();
+        // 解码得到的取值与 `state` 不共享，差异即完整状态
+        self.record(machine_id, None, None, &current, state)
+    }
+
+    fn load(&self, machine_id: MachineId) -> Result<Option<State>, StateZenError> {
+        let err = |e| sql_error(machine_id, e);
+        let conn = self.conn.lock().unwrap();
+        let exists = conn
+            .query_row("SELECT 1 FROM transforms WHERE machine_id = ?1 LIMIT 1", [machine_id as i64], |_| Ok(()))
+            .optional()
+            .map_err(err)?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+        let mut stmt = conn
+            .prepare("SELECT aspect_id, value FROM current WHERE machine_id = ?1")
+            .map_err(err)?;
+        let rows = stmt
+            .query_map([machine_id as i64], |r| Ok((r.get::<_, i64>(0)? as StateAspectId, r.get::<_, String>(1)?)))
+            .map_err(err)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(err)?;
+        self.codec
+            .decode_state(rows.iter().map(|(id, value)| (*id, value.as_str())))
+            .map(Some)
+    }
+}
+
+/// 把一个运行时的提交写入 `SqliteHistoryStore` 的追踪器
+/// 追踪器无法返回错误，写入失败记录在 `take_errors` 中
+pub struct SqliteRecorder {
+    store: Arc<SqliteHistoryStore>,
+    machine_id: MachineId,
+    pending: Mutex<Option<(EventId, TransitionId)>>,
+    errors: Mutex<Vec<StateZenError>>,
+}
+
+impl SqliteRecorder {
+    /// 取出写入失败的错误
+    pub fn take_errors(&self) -> Vec<StateZenError> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+}
+
+impl Tracer for SqliteRecorder {
+    fn on_event(&self, event_id: EventId, selected: Option<TransitionId>) {
+        *self.pending.lock().unwrap() = selected.map(|t| (event_id, t));
+    }
+
+    fn on_commit(&self, prev: &State, next: &State) {
+        let cause = self.pending.lock().unwrap().take();
+        let result = self.store.record(self.machine_id, cause.map(|(e, _)| e), cause.map(|(_, t)| t), prev, next);
+        if let Err(e) = result {
+            self.errors.lock().unwrap().push(e);
+        }
+    }
+}
@@ -0,0 +1,423 @@
+//! 运行时状态的存储
+//!
+//! 大多数状态机只有 1~4 个方面，此时 `HashMap` 的开销远大于数据本身。
+//! `State` 在方面数量较少时使用按ID排序的内联数组，超过阈值后自动切换为 `HashMap`，
+//! 对外保持与 `HashMap` 一致的常用 API。
+
+use std::collections::hash_map;
+use std::fmt;
+use std::collections::HashMap;
+use std::sync::Arc;
+use smallvec::SmallVec;
+use super::types::StateAspectId;
+
+/// 方面取值：类型擦除后的共享值
+pub type AspectValue = Arc<dyn std::any::Any + Send + Sync>;
+
+/// 方面数量不超过该阈值时使用内联数组存储
+pub const SMALL_STATE_THRESHOLD: usize = 8;
+
+/// 内联存储的方面数量，超出后数组在堆上分配
+const INLINE_ASPECTS: usize = 4;
+
+type SmallEntries = SmallVec<[(StateAspectId, AspectValue); INLINE_ASPECTS]>;
+
+type LargeEntries = HashMap<StateAspectId, AspectValue>;
+
+/// 启用 `cow-state` 时大状态写时复制：克隆只增加引用计数，首次写入时才复制
+#[cfg(feature = "cow-state")]
+type LargeRepr = Arc<LargeEntries>;
+#[cfg(not(feature = "cow-state"))]
+type LargeRepr = LargeEntries;
+
+enum Repr {
+    /// 按方面ID升序排列
+    Small(SmallEntries),
+    Large(LargeRepr),
+}
+
+impl Clone for Repr {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Small(entries) => Self::Small(entries.clone()),
+            Self::Large(map) => Self::Large(map.clone()),
+        }
+    }
+
+    /// 存储方式相同时复用已有的分配
+    fn clone_from(&mut self, source: &Self) {
+        match (&mut *self, source) {
+            (Self::Small(a), Self::Small(b)) => a.clone_from(b),
+            (Self::Large(a), Self::Large(b)) => a.clone_from(b),
+            (this, _) => *this = source.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "cow-state")]
+fn to_large(map: LargeEntries) -> LargeRepr {
+    Arc::new(map)
+}
+
+#[cfg(not(feature = "cow-state"))]
+fn to_large(map: LargeEntries) -> LargeRepr {
+    map
+}
+
+/// 取得大状态的可写引用
+#[cfg(feature = "cow-state")]
+fn large_mut(map: &mut LargeRepr) -> &mut LargeEntries {
+    Arc::make_mut(map)
+}
+
+#[cfg(not(feature = "cow-state"))]
+fn large_mut(map: &mut LargeRepr) -> &mut LargeEntries {
+    map
+}
+
+/// 运行时状态：aspect_id -> Arc<dyn Any>
+pub struct State {
+    repr: Repr,
+}
+
+impl Clone for State {
+    fn clone(&self) -> Self {
+        Self { repr: self.repr.clone() }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.repr.clone_from(&source.repr);
+    }
+}
+
+impl State {
+    /// 用 `overrides` 中的取值覆盖同ID的方面，返回新状态
+    pub fn with_overrides<I>(mut self, overrides: I) -> Self
+    where
+        I: IntoIterator<Item = (StateAspectId, AspectValue)>,
+    {
+        self.extend(overrides);
+        self
+    }
+
+    /// 创建一个空状态
+    pub fn new() -> Self {
+        Self {
+            repr: Repr::Small(SmallVec::new()),
+        }
+    }
+
+    /// 创建一个预留 `capacity` 个方面存储的空状态，超过内联阈值时直接使用 `HashMap`
+    pub fn with_capacity(capacity: usize) -> Self {
+        let repr = if capacity > SMALL_STATE_THRESHOLD {
+            Repr::Large(to_large(HashMap::with_capacity(capacity)))
+        } else {
+            Repr::Small(SmallVec::with_capacity(capacity))
+        };
+        Self { repr }
+    }
+
+    /// 读取方面取值
+    pub fn get(&self, id: &StateAspectId) -> Option<&AspectValue> {
+        match &self.repr {
+            Repr::Small(entries) => entries
+                .binary_search_by_key(id, |(k, _)| *k)
+                .ok()
+                .map(|i| &entries[i].1),
+            Repr::Large(map) => map.get(id),
+        }
+    }
+
+    /// 读取方面取值的可变引用
+    pub fn get_mut(&mut self, id: &StateAspectId) -> Option<&mut AspectValue> {
+        match &mut self.repr {
+            Repr::Small(entries) => match entries.binary_search_by_key(id, |(k, _)| *k) {
+                Ok(i) => Some(&mut entries[i].1),
+                Err(_) => None,
+            },
+            Repr::Large(map) => large_mut(map).get_mut(id),
+        }
+    }
+
+    /// 是否包含某个方面
+    pub fn contains_key(&self, id: &StateAspectId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// 写入方面取值，返回旧值
+    pub fn insert(&mut self, id: StateAspectId, value: AspectValue) -> Option<AspectValue> {
+        match &mut self.repr {
+            Repr::Small(entries) => match entries.binary_search_by_key(&id, |(k, _)| *k) {
+                Ok(i) => Some(std::mem::replace(&mut entries[i].1, value)),
+                Err(i) => {
+                    entries.insert(i, (id, value));
+                    if entries.len() > SMALL_STATE_THRESHOLD {
+                        self.repr = Repr::Large(to_large(entries.drain(..).collect()));
+                    }
+                    None
+                }
+            },
+            Repr::Large(map) => large_mut(map).insert(id, value),
+        }
+    }
+
+    /// 移除方面取值
+    pub fn remove(&mut self, id: &StateAspectId) -> Option<AspectValue> {
+        match &mut self.repr {
+            Repr::Small(entries) => entries
+                .binary_search_by_key(id, |(k, _)| *k)
+                .ok()
+                .map(|i| entries.remove(i).1),
+            Repr::Large(map) => large_mut(map).remove(id),
+        }
+    }
+
+    /// 取得方面的条目，用于就地读取或插入
+    pub fn entry(&mut self, id: StateAspectId) -> Entry<'_> {
+        Entry { state: self, id }
+    }
+
+    /// 只保留 `f` 返回 `true` 的方面
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&StateAspectId, &mut AspectValue) -> bool,
+    {
+        match &mut self.repr {
+            Repr::Small(entries) => entries.retain(|(k, v)| f(k, v)),
+            Repr::Large(map) => large_mut(map).retain(|k, v| f(k, v)),
+        }
+    }
+
+    /// 移除所有方面，保留已分配的存储
+    pub fn clear(&mut self) {
+        match &mut self.repr {
+            Repr::Small(entries) => entries.clear(),
+            #[cfg(feature = "cow-state")]
+            Repr::Large(map) => match Arc::get_mut(map) {
+                Some(map) => map.clear(),
+                None => self.repr = Repr::Small(SmallVec::new()),
+            },
+            #[cfg(not(feature = "cow-state"))]
+            Repr::Large(map) => map.clear(),
+        }
+    }
+
+    /// 方面数量
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Small(entries) => entries.len(),
+            Repr::Large(map) => map.len(),
+        }
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 遍历所有 (方面ID, 取值)
+    pub fn iter(&self) -> Iter<'_> {
+        match &self.repr {
+            Repr::Small(entries) => Iter(IterRepr::Small(entries.iter())),
+            Repr::Large(map) => Iter(IterRepr::Large(map.iter())),
+        }
+    }
+
+    /// 遍历所有 (方面ID, 取值的可变引用)
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        match &mut self.repr {
+            Repr::Small(entries) => IterMut(IterMutRepr::Small(entries.iter_mut())),
+            Repr::Large(map) => IterMut(IterMutRepr::Large(large_mut(map).iter_mut())),
+        }
+    }
+
+    /// 遍历所有方面ID
+    pub fn keys(&self) -> impl Iterator<Item = &StateAspectId> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// 遍历所有取值
+    pub fn values(&self) -> impl Iterator<Item = &AspectValue> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// 遍历所有取值的可变引用
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut AspectValue> {
+        self.iter_mut().map(|(_, v)| v)
+    }
+
+    /// 是否使用内联数组存储
+    pub fn is_inline(&self) -> bool {
+        matches!(self.repr, Repr::Small(_))
+    }
+}
+
+/// 取值为类型擦除的 `Any`，只输出方面ID（按升序）
+impl fmt::Debug for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut keys: Vec<StateAspectId> = self.keys().copied().collect();
+        keys.sort_unstable();
+        f.debug_map().entries(keys.iter().map(|k| (k, format_args!("..")))).finish()
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::ops::Index<&StateAspectId> for State {
+    type Output = AspectValue;
+
+    fn index(&self, id: &StateAspectId) -> &AspectValue {
+        self.get(id).expect("状态中不存在该方面")
+    }
+}
+
+impl Extend<(StateAspectId, AspectValue)> for State {
+    fn extend<I: IntoIterator<Item = (StateAspectId, AspectValue)>>(&mut self, iter: I) {
+        for (id, value) in iter {
+            self.insert(id, value);
+        }
+    }
+}
+
+impl FromIterator<(StateAspectId, AspectValue)> for State {
+    fn from_iter<I: IntoIterator<Item = (StateAspectId, AspectValue)>>(iter: I) -> Self {
+        let mut state = Self::new();
+        state.extend(iter);
+        state
+    }
+}
+
+#[cfg(feature = "cow-state")]
+fn into_entries(map: LargeRepr) -> LargeEntries {
+    Arc::unwrap_or_clone(map)
+}
+
+#[cfg(not(feature = "cow-state"))]
+fn into_entries(map: LargeRepr) -> LargeEntries {
+    map
+}
+
+/// `State` 的借用迭代器
+pub struct Iter<'a>(IterRepr<'a>);
+
+enum IterRepr<'a> {
+    Small(std::slice::Iter<'a, (StateAspectId, AspectValue)>),
+    Large(hash_map::Iter<'a, StateAspectId, AspectValue>),
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a StateAspectId, &'a AspectValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IterRepr::Small(it) => it.next().map(|(k, v)| (k, v)),
+            IterRepr::Large(it) => it.next(),
+        }
+    }
+}
+
+/// `State` 的可变借用迭代器
+pub struct IterMut<'a>(IterMutRepr<'a>);
+
+enum IterMutRepr<'a> {
+    Small(std::slice::IterMut<'a, (StateAspectId, AspectValue)>),
+    Large(hash_map::IterMut<'a, StateAspectId, AspectValue>),
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (&'a StateAspectId, &'a mut AspectValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IterMutRepr::Small(it) => it.next().map(|(k, v)| (&*k, v)),
+            IterMutRepr::Large(it) => it.next(),
+        }
+    }
+}
+
+/// `State` 中一个方面的条目，由 `State::entry` 取得
+pub struct Entry<'a> {
+    state: &'a mut State,
+    id: StateAspectId,
+}
+
+impl<'a> Entry<'a> {
+    /// 条目的方面ID
+    pub fn key(&self) -> StateAspectId {
+        self.id
+    }
+
+    /// 方面存在时修改其取值
+    pub fn and_modify<F: FnOnce(&mut AspectValue)>(self, f: F) -> Self {
+        if let Some(value) = self.state.get_mut(&self.id) {
+            f(value);
+        }
+        self
+    }
+
+    /// 方面不存在时写入 `value`，返回取值的可变引用
+    pub fn or_insert(self, value: AspectValue) -> &'a mut AspectValue {
+        self.or_insert_with(|| value)
+    }
+
+    /// 方面不存在时写入 `f` 的结果，返回取值的可变引用
+    pub fn or_insert_with<F: FnOnce() -> AspectValue>(self, f: F) -> &'a mut AspectValue {
+        if !self.state.contains_key(&self.id) {
+            self.state.insert(self.id, f());
+        }
+        self.state.get_mut(&self.id).expect("条目已写入")
+    }
+}
+
+/// `State` 的所有权迭代器
+pub struct IntoIter(IntoIterRepr);
+
+enum IntoIterRepr {
+    Small(smallvec::IntoIter<[(StateAspectId, AspectValue); INLINE_ASPECTS]>),
+    Large(hash_map::IntoIter<StateAspectId, AspectValue>),
+}
+
+impl Iterator for IntoIter {
+    type Item = (StateAspectId, AspectValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IntoIterRepr::Small(it) => it.next(),
+            IntoIterRepr::Large(it) => it.next(),
+        }
+    }
+}
+
+impl IntoIterator for State {
+    type Item = (StateAspectId, AspectValue);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        match self.repr {
+            Repr::Small(entries) => IntoIter(IntoIterRepr::Small(entries.into_iter())),
+            Repr::Large(map) => IntoIter(IntoIterRepr::Large(into_entries(map).into_iter())),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a mut State {
+    type Item = (&'a StateAspectId, &'a mut AspectValue);
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> IterMut<'a> {
+        self.iter_mut()
+    }
+}
+
+impl<'a> IntoIterator for &'a State {
+    type Item = (&'a StateAspectId, &'a AspectValue);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
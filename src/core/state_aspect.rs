@@ -1,7 +1,18 @@
 //! 状态方面定义
 
-use std::any::TypeId;
+use std::any::{Any, TypeId};
+use std::sync::Arc;
 use super::types::StateAspectId;
+use super::runtime::AspectValue;
+
+/// 方面取值校验函数，返回 `false` 表示取值非法
+pub type AspectValidator = Arc<dyn Fn(&dyn Any) -> bool + Send + Sync>;
+
+/// 方面默认值的构造函数
+pub type AspectDefault = Arc<dyn Fn() -> AspectValue + Send + Sync>;
+
+/// 方面取值的相等比较
+pub type AspectEq = Arc<dyn Fn(&dyn Any, &dyn Any) -> bool + Send + Sync>;
 
 /// 状态方面
 /// 表示状态的一个维度，有唯一的ID和值类型
@@ -11,4 +22,68 @@ pub struct StateAspect {
     pub id: StateAspectId,
     /// 值类型的TypeId
     pub value_type_id: TypeId,
-}
\ No newline at end of file
+    /// 取值校验，每次转换后对被写入的取值执行，违反时按运行时的 `ValidationPolicy` 处理
+    pub validator: Option<AspectValidator>,
+    /// 默认值，用于 `StateMachineBlueprint::default_initial_state`
+    pub default: Option<AspectDefault>,
+    /// 取值的相等比较，广播分发据此判断两个转换的写入是否一致；
+    /// 为 `None` 时只能按共享同一取值或 `Transfer::set` 的常量判断
+    pub eq: Option<AspectEq>,
+}
+
+impl StateAspect {
+    /// 创建一个取值类型为 `value_type_id`、不带校验的方面，用于类型只在运行时可知的场合
+    pub fn new(id: StateAspectId, value_type_id: TypeId) -> Self {
+        Self {
+            id,
+            value_type_id,
+            validator: None,
+            default: None,
+            eq: None,
+        }
+    }
+
+    /// 创建一个取值类型为 `T`、不带校验的方面
+    pub fn of<T: 'static>(id: StateAspectId) -> Self {
+        Self::new(id, TypeId::of::<T>())
+    }
+
+    /// 附加取值校验，取值类型不是 `T` 时视为非法
+    pub fn with_validator<T, F>(mut self, f: F) -> Self
+    where
+        T: 'static,
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.validator = Some(Arc::new(move |v| v.downcast_ref::<T>().is_some_and(&f)));
+        self
+    }
+
+    /// 附加默认值，每次构造初始状态时克隆一份
+    pub fn with_default<T>(mut self, value: T) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        self.default = Some(Arc::new(move || Arc::new(value.clone())));
+        self
+    }
+
+    /// 按 `T` 的 `PartialEq` 比较取值，类型不是 `T` 时视为不等
+    pub fn with_eq<T>(mut self) -> Self
+    where
+        T: PartialEq + 'static,
+    {
+        self.eq = Some(Arc::new(|a, b| match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }));
+        self
+    }
+
+    /// 附加 `T::default()` 作为默认值
+    pub fn with_type_default<T>(self) -> Self
+    where
+        T: Default + Clone + Send + Sync + 'static,
+    {
+        self.with_default(T::default())
+    }
+}
@@ -1,24 +1,187 @@
 //! 状态谓词（StateInRange）
 //! 用于判断状态是否在特定范围内
 
+use std::any::TypeId;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::ops::{Bound, RangeBounds};
 use std::sync::Arc;
-use super::runtime::State;
+use super::types::StateAspectId;
+use super::runtime::{State, AspectValue};
+use super::registry::AspectRegistry;
+use super::arg::ArgValue;
+use super::error::StateZenError;
+
+/// 谓词闭包
+pub type GuardFn = Arc<dyn Fn(&State) -> bool + 'static + Send + Sync>;
 
 /// 状态谓词，判断状态是否在特定范围内
 #[derive(Clone)]
 pub struct StateInRange {
-    predicate: Arc<dyn Fn(&State) -> bool + 'static + Send + Sync>,
+    predicate: GuardFn,
+    /// 谓词的结构描述，闭包构造的部分为 `Opaque`
+    expr: Arc<GuardExpr>,
+    /// 读取的方面集合，`None` 表示未声明
+    reads: Option<Arc<[StateAspectId]>>,
 }
 
 impl StateInRange {
     /// 创建一个新的状态谓词
     pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&State) -> bool + 'static + Send + Sync,
+    {
+        let predicate: GuardFn = Arc::new(f);
+        Self {
+            expr: Arc::new(GuardExpr::Opaque(predicate.clone())),
+            predicate,
+            reads: None,
+        }
+    }
+
+    fn declarative<F>(expr: GuardExpr, reads: &[StateAspectId], f: F) -> Self
     where
         F: Fn(&State) -> bool + 'static + Send + Sync,
     {
         Self {
             predicate: Arc::new(f),
+            expr: Arc::new(expr),
+            reads: Some(reads.into()),
+        }
+    }
+
+    /// 声明谓词读取的方面集合
+    /// 闭包构造的谓词默认未声明读集合，声明后可被 `observers_watching` 等查询使用
+    pub fn with_reads<I>(mut self, aspects: I) -> Self
+    where
+        I: IntoIterator<Item = StateAspectId>,
+    {
+        let mut reads: Vec<StateAspectId> = aspects.into_iter().collect();
+        reads.sort_unstable();
+        reads.dedup();
+        self.reads = Some(reads.into());
+        self
+    }
+
+    /// 标记谓词由具名工厂构造，求值与读集合不变
+    pub(crate) fn named(self, name: &str, args: &[ArgValue]) -> Self {
+        Self {
+            expr: Arc::new(GuardExpr::Named {
+                name: name.to_string(),
+                args: args.to_vec(),
+                inner: self.expr,
+            }),
+            ..self
+        }
+    }
+
+    /// 谓词读取的方面集合；未声明时返回 `None`
+    pub fn reads(&self) -> Option<&[StateAspectId]> {
+        self.reads.as_deref()
+    }
+
+    /// 合并两个谓词的读集合，任一未声明则结果未声明
+    fn union_reads(&self, other: &Self) -> Option<Arc<[StateAspectId]>> {
+        let (a, b) = (self.reads()?, other.reads()?);
+        let mut reads: Vec<StateAspectId> = a.iter().chain(b).copied().collect();
+        reads.sort_unstable();
+        reads.dedup();
+        Some(reads.into())
+    }
+
+    /// 包含所有状态的谓词
+    pub fn always() -> Self {
+        Self::declarative(GuardExpr::Always, &[], |_| true)
+    }
+
+    /// 不包含任何状态的谓词
+    pub fn never() -> Self {
+        Self::declarative(GuardExpr::Never, &[], |_| false)
+    }
+
+    /// 由结构描述构造谓词，闭包对表达式求值
+    pub fn from_expr(expr: GuardExpr) -> Self {
+        let reads = expr.reads();
+        let expr = Arc::new(expr);
+        let eval = expr.clone();
+        Self {
+            predicate: Arc::new(move |s| eval.eval(s)),
+            expr,
+            reads: reads.map(Into::into),
+        }
+    }
+
+    /// 已知某个方面取值恒定时化简谓词
+    ///
+    /// 对该方面的声明式条件被替换为常量并向上折叠，例如难度在启动时固定后，
+    /// `difficulty == Hard AND hp < 10` 化简为 `hp < 10` 或 `false`；闭包部分保持不变
+    ///
+    /// `value` 的类型与条件中该方面的取值类型不符时返回 `AspectTypeMismatch`
+    pub fn specialize<T>(&self, aspect_id: StateAspectId, value: T) -> Result<Self, StateZenError>
+    where
+        T: Send + Sync + 'static,
+    {
+        let value: AspectValue = Arc::new(value);
+        self.specialize_value(aspect_id, &value)
+    }
+
+    pub(crate) fn specialize_value(&self, aspect_id: StateAspectId, value: &AspectValue) -> Result<Self, StateZenError> {
+        if !self.reads.as_ref().is_none_or(|r| r.contains(&aspect_id)) {
+            return Ok(self.clone());
         }
+        let expr = self.expr.specialize(aspect_id, value)?;
+        let reads = self.reads.clone();
+        let mut specialized = Self::from_expr(expr);
+        if specialized.reads.is_none() {
+            specialized.reads = reads;
+        }
+        Ok(specialized)
+    }
+
+    /// 谓词是否为常量：恒真返回 `Some(true)`，恒假返回 `Some(false)`
+    pub fn constant(&self) -> Option<bool> {
+        match *self.expr {
+            GuardExpr::Always => Some(true),
+            GuardExpr::Never => Some(false),
+            _ => None,
+        }
+    }
+
+    /// 方面取值等于 `value` 的谓词
+    pub fn aspect_eq<T>(aspect_id: StateAspectId, value: T) -> Self
+    where
+        T: PartialEq + Debug + Send + Sync + 'static,
+    {
+        let value = Arc::new(value);
+        let expr = GuardExpr::AspectEq {
+            aspect: aspect_id,
+            value: GuardValue::new::<T>(value.clone()),
+        };
+        Self::declarative(expr, &[aspect_id], move |s| {
+            s.get(&aspect_id)
+                .and_then(|v| v.downcast_ref::<T>())
+                .is_some_and(|v| *v == *value)
+        })
+    }
+
+    /// 方面取值落在 `range` 内的谓词
+    pub fn aspect_in<T, R>(aspect_id: StateAspectId, range: R) -> Self
+    where
+        T: PartialOrd + Clone + Debug + Send + Sync + 'static,
+        R: RangeBounds<T>,
+    {
+        let lo = range.start_bound().cloned();
+        let hi = range.end_bound().cloned();
+        let expr = GuardExpr::Range {
+            aspect: aspect_id,
+            lo: lo.clone().map(|v| GuardValue::ordered::<T>(Arc::new(v))),
+            hi: hi.clone().map(|v| GuardValue::ordered::<T>(Arc::new(v))),
+        };
+        Self::declarative(expr, &[aspect_id], move |s| {
+            s.get(&aspect_id)
+                .and_then(|v| v.downcast_ref::<T>())
+                .is_some_and(|v| (lo.as_ref(), hi.as_ref()).contains(v))
+        })
     }
 
     /// 判断给定的状态是否满足谓词条件
@@ -28,11 +191,405 @@ impl StateInRange {
 
     /// 创建一个新的谓词，表示当前谓词的逻辑非
     pub fn not(self) -> Self {
-        Self::new(move |s| !self.contains(s))
+        let expr = Arc::new(GuardExpr::Not(self.expr.clone()));
+        let reads = self.reads.clone();
+        Self {
+            expr,
+            reads,
+            ..Self::new(move |s| !self.contains(s))
+        }
     }
 
     /// 创建一个新的谓词，表示当前谓词和另一个谓词的逻辑与
     pub fn and(self, other: Self) -> Self {
-        Self::new(move |s| self.contains(s) && other.contains(s))
+        let expr = Arc::new(GuardExpr::And(self.expr.clone(), other.expr.clone()));
+        let reads = self.union_reads(&other);
+        Self {
+            expr,
+            reads,
+            ..Self::new(move |s| self.contains(s) && other.contains(s))
+        }
+    }
+
+    /// 创建一个新的谓词，表示当前谓词和另一个谓词的逻辑或
+    pub fn or(self, other: Self) -> Self {
+        let expr = Arc::new(GuardExpr::Or(self.expr.clone(), other.expr.clone()));
+        let reads = self.union_reads(&other);
+        Self {
+            expr,
+            reads,
+            ..Self::new(move |s| self.contains(s) || other.contains(s))
+        }
+    }
+
+    /// 是否完全由声明式构造函数构造（结构可比较，不含闭包）
+    pub fn is_declarative(&self) -> bool {
+        !self.expr.is_opaque()
+    }
+
+    /// 谓词的结构描述
+    pub fn expr(&self) -> &GuardExpr {
+        &self.expr
+    }
+
+    /// 可读描述，方面以 `#<id>` 表示，如 `#2 <= 5 AND #1 == Walk`
+    pub fn describe(&self) -> String {
+        self.describe_with(&AspectRegistry::new())
+    }
+
+    /// 可读描述，方面名称取自注册表，如 `hunger <= 5 AND action == Walk`
+    pub fn describe_with(&self, registry: &AspectRegistry) -> String {
+        self.expr.describe(registry)
+    }
+
+    /// 两个谓词是否共享同一个闭包
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.predicate, &b.predicate)
+    }
+
+    /// 闭包地址，用于按共享谓词去重求值
+    pub(crate) fn ptr_key(&self) -> usize {
+        Arc::as_ptr(&self.predicate) as *const () as usize
+    }
+
+    /// 结构上是否等价（仅对声明式谓词有意义）
+    pub(crate) fn structurally_eq(&self, other: &Self) -> bool {
+        if self.is_declarative() && other.is_declarative() {
+            self.expr == other.expr
+        } else {
+            Self::ptr_eq(self, other)
+        }
     }
-}
\ No newline at end of file
+
+    /// 结构形状的哈希（不含具体取值），用于分桶
+    pub(crate) fn shape_hash(&self) -> Option<u64> {
+        if !self.is_declarative() {
+            return None;
+        }
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.expr.hash_shape(&mut hasher);
+        Some(hasher.finish())
+    }
+}
+
+/// 谓词的结构描述
+///
+/// 声明式构造函数和组合子构造的谓词保留完整的表达式树，
+/// 闭包构造的部分记为 `Opaque` 并保留闭包；`Opaque` 只与共享同一闭包的 `Opaque` 相等
+#[derive(Clone)]
+pub enum GuardExpr {
+    /// 恒真
+    Always,
+    /// 恒假
+    Never,
+    /// 方面取值等于给定值
+    AspectEq { aspect: StateAspectId, value: GuardValue },
+    /// 方面取值落在区间内
+    Range {
+        aspect: StateAspectId,
+        lo: Bound<GuardValue>,
+        hi: Bound<GuardValue>,
+    },
+    /// 逻辑与
+    And(Arc<GuardExpr>, Arc<GuardExpr>),
+    /// 逻辑或
+    Or(Arc<GuardExpr>, Arc<GuardExpr>),
+    /// 逻辑非
+    Not(Arc<GuardExpr>),
+    /// 闭包构造，结构未知
+    Opaque(GuardFn),
+    /// 由工厂注册表中的具名工厂构造，`inner` 为工厂返回的谓词结构
+    Named { name: String, args: Vec<ArgValue>, inner: Arc<GuardExpr> },
+}
+
+impl GuardExpr {
+    /// 表达式中是否含有闭包构造的部分
+    pub fn is_opaque(&self) -> bool {
+        match self {
+            Self::Opaque(_) => true,
+            Self::Always | Self::Never | Self::AspectEq { .. } | Self::Range { .. } => false,
+            Self::And(a, b) | Self::Or(a, b) => a.is_opaque() || b.is_opaque(),
+            Self::Not(a) | Self::Named { inner: a, .. } => a.is_opaque(),
+        }
+    }
+
+    /// 对状态求值
+    pub fn eval(&self, state: &State) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::AspectEq { aspect, value } => state.get(aspect).is_some_and(|v| value.eq_value(v)),
+            Self::Range { aspect, lo, hi } => state.get(aspect).is_some_and(|v| in_bounds(lo, hi, v)),
+            Self::And(a, b) => a.eval(state) && b.eval(state),
+            Self::Or(a, b) => a.eval(state) || b.eval(state),
+            Self::Not(a) => !a.eval(state),
+            Self::Opaque(f) => f(state),
+            Self::Named { inner, .. } => inner.eval(state),
+        }
+    }
+
+    /// 读取的方面集合；含闭包部分时为 `None`
+    pub fn reads(&self) -> Option<Vec<StateAspectId>> {
+        let mut reads = Vec::new();
+        self.collect_reads(&mut reads)?;
+        reads.sort_unstable();
+        reads.dedup();
+        Some(reads)
+    }
+
+    fn collect_reads(&self, out: &mut Vec<StateAspectId>) -> Option<()> {
+        match self {
+            Self::Always | Self::Never => {}
+            Self::AspectEq { aspect, .. } | Self::Range { aspect, .. } => out.push(*aspect),
+            Self::And(a, b) | Self::Or(a, b) => {
+                a.collect_reads(out)?;
+                b.collect_reads(out)?;
+            }
+            Self::Not(a) | Self::Named { inner: a, .. } => a.collect_reads(out)?,
+            Self::Opaque(_) => return None,
+        }
+        Some(())
+    }
+
+    /// 把对 `aspect_id` 的条件替换为在 `value` 上的求值结果并折叠常量
+    ///
+    /// 条件中的取值类型与 `value` 不符时返回 `AspectTypeMismatch`，而不是把条件当作恒假
+    pub fn specialize(&self, aspect_id: StateAspectId, value: &AspectValue) -> Result<GuardExpr, StateZenError> {
+        let constant = |b: bool| if b { Self::Always } else { Self::Never };
+        let matches = |expected: &GuardValue| expected.type_id() == (**value).type_id();
+        let bound_matches = |bound: &Bound<GuardValue>| match bound {
+            Bound::Included(b) | Bound::Excluded(b) => matches(b),
+            Bound::Unbounded => true,
+        };
+        Ok(match self {
+            Self::AspectEq { aspect, value: expected } if *aspect == aspect_id => {
+                if !matches(expected) {
+                    return Err(StateZenError::AspectTypeMismatch(aspect_id));
+                }
+                constant(expected.eq_value(value))
+            }
+            Self::Range { aspect, lo, hi } if *aspect == aspect_id => {
+                if !bound_matches(lo) || !bound_matches(hi) {
+                    return Err(StateZenError::AspectTypeMismatch(aspect_id));
+                }
+                constant(in_bounds(lo, hi, value))
+            }
+            Self::And(a, b) => match (a.specialize(aspect_id, value)?, b.specialize(aspect_id, value)?) {
+                (Self::Never, _) | (_, Self::Never) => Self::Never,
+                (Self::Always, x) | (x, Self::Always) => x,
+                (a, b) => Self::And(Arc::new(a), Arc::new(b)),
+            },
+            Self::Or(a, b) => match (a.specialize(aspect_id, value)?, b.specialize(aspect_id, value)?) {
+                (Self::Always, _) | (_, Self::Always) => Self::Always,
+                (Self::Never, x) | (x, Self::Never) => x,
+                (a, b) => Self::Or(Arc::new(a), Arc::new(b)),
+            },
+            Self::Not(a) => match a.specialize(aspect_id, value)? {
+                Self::Always => Self::Never,
+                Self::Never => Self::Always,
+                a => Self::Not(Arc::new(a)),
+            },
+            Self::Named { name, args, inner } => match inner.specialize(aspect_id, value)? {
+                specialized if specialized == **inner => Self::Named { name: name.clone(), args: args.clone(), inner: inner.clone() },
+                specialized => specialized,
+            },
+            other => other.clone(),
+        })
+    }
+
+    /// 可读描述，方面名称取自注册表
+    pub fn describe(&self, registry: &AspectRegistry) -> String {
+        match self {
+            Self::Always => "true".to_string(),
+            Self::Never => "false".to_string(),
+            Self::Opaque(_) => "<closure>".to_string(),
+            Self::AspectEq { aspect, value } => format!("{} == {value}", registry.name(*aspect)),
+            Self::Range { aspect, lo, hi } => {
+                let name = registry.name(*aspect);
+                match (lo, hi) {
+                    (Bound::Unbounded, Bound::Unbounded) => "true".to_string(),
+                    (Bound::Included(v), Bound::Unbounded) => format!("{name} >= {v}"),
+                    (Bound::Excluded(v), Bound::Unbounded) => format!("{name} > {v}"),
+                    (Bound::Unbounded, Bound::Included(v)) => format!("{name} <= {v}"),
+                    (Bound::Unbounded, Bound::Excluded(v)) => format!("{name} < {v}"),
+                    (lo, hi) => {
+                        let lo = match lo {
+                            Bound::Included(v) => format!("{v} <="),
+                            Bound::Excluded(v) => format!("{v} <"),
+                            Bound::Unbounded => unreachable!(),
+                        };
+                        let hi = match hi {
+                            Bound::Included(v) => format!("<= {v}"),
+                            Bound::Excluded(v) => format!("< {v}"),
+                            Bound::Unbounded => unreachable!(),
+                        };
+                        format!("{lo} {name} {hi}")
+                    }
+                }
+            }
+            Self::And(a, b) => format!("{} AND {}", a.describe_operand(registry, 1), b.describe_operand(registry, 1)),
+            Self::Or(a, b) => format!("{} OR {}", a.describe_operand(registry, 0), b.describe_operand(registry, 0)),
+            Self::Not(a) => format!("NOT {}", a.describe_operand(registry, 2)),
+            Self::Named { inner, .. } => inner.describe(registry),
+        }
+    }
+
+    /// 作为操作数描述，优先级低于 `outer` 时加括号（OR=0, AND=1, NOT=2）
+    fn describe_operand(&self, registry: &AspectRegistry, outer: u8) -> String {
+        if let Self::Named { inner, .. } = self {
+            return inner.describe_operand(registry, outer);
+        }
+        let precedence = match self {
+            Self::Or(..) => 0,
+            Self::And(..) => 1,
+            _ => 3,
+        };
+        let text = self.describe(registry);
+        if precedence < outer { format!("({text})") } else { text }
+    }
+
+    fn hash_shape<H: Hasher>(&self, h: &mut H) {
+        std::mem::discriminant(self).hash(h);
+        match self {
+            Self::Always | Self::Never | Self::Opaque(_) => {}
+            Self::AspectEq { aspect, value } => {
+                aspect.hash(h);
+                value.type_id.hash(h);
+            }
+            Self::Range { aspect, lo, hi } => {
+                aspect.hash(h);
+                for bound in [lo, hi] {
+                    std::mem::discriminant(bound).hash(h);
+                }
+            }
+            Self::And(a, b) | Self::Or(a, b) => {
+                a.hash_shape(h);
+                b.hash_shape(h);
+            }
+            Self::Not(a) => a.hash_shape(h),
+            Self::Named { name, inner, .. } => {
+                name.hash(h);
+                inner.hash_shape(h);
+            }
+        }
+    }
+}
+
+impl PartialEq for GuardExpr {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Always, Self::Always) => true,
+            (Self::AspectEq { aspect: a, value: x }, Self::AspectEq { aspect: b, value: y }) => a == b && x == y,
+            (Self::Range { aspect: a, lo: l1, hi: h1 }, Self::Range { aspect: b, lo: l2, hi: h2 }) => {
+                a == b && l1 == l2 && h1 == h2
+            }
+            (Self::And(a1, b1), Self::And(a2, b2)) | (Self::Or(a1, b1), Self::Or(a2, b2)) => a1 == a2 && b1 == b2,
+            (Self::Not(a), Self::Not(b)) => a == b,
+            (Self::Never, Self::Never) => true,
+            (Self::Opaque(a), Self::Opaque(b)) => Arc::ptr_eq(a, b),
+            (Self::Named { name: n1, args: a1, inner: i1 }, Self::Named { name: n2, args: a2, inner: i2 }) => {
+                n1 == n2 && a1 == a2 && i1 == i2
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for GuardExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(&AspectRegistry::new()))
+    }
+}
+
+/// 区间判断，类型不符时为假
+fn in_bounds(lo: &Bound<GuardValue>, hi: &Bound<GuardValue>, value: &AspectValue) -> bool {
+    let above = match lo {
+        Bound::Included(b) => b.cmp_value(value).is_some_and(|o| o.is_le()),
+        Bound::Excluded(b) => b.cmp_value(value).is_some_and(|o| o.is_lt()),
+        Bound::Unbounded => true,
+    };
+    let below = match hi {
+        Bound::Included(b) => b.cmp_value(value).is_some_and(|o| o.is_ge()),
+        Bound::Excluded(b) => b.cmp_value(value).is_some_and(|o| o.is_gt()),
+        Bound::Unbounded => true,
+    };
+    above && below
+}
+
+/// 结构描述中的取值，按原类型的 `PartialEq` / `PartialOrd` 比较，按原类型的 `Debug` 显示
+#[derive(Clone)]
+pub struct GuardValue {
+    value: AspectValue,
+    type_id: TypeId,
+    eq: fn(&AspectValue, &AspectValue) -> bool,
+    cmp: Option<fn(&AspectValue, &AspectValue) -> Option<std::cmp::Ordering>>,
+    fmt: fn(&AspectValue) -> String,
+}
+
+impl GuardValue {
+    pub(crate) fn new<T: PartialEq + Debug + Send + Sync + 'static>(value: Arc<T>) -> Self {
+        Self {
+            value,
+            type_id: TypeId::of::<T>(),
+            eq: |a, b| match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+            cmp: None,
+            fmt: |v| v.downcast_ref::<T>().map_or_else(String::new, |v| format!("{v:?}")),
+        }
+    }
+
+    fn ordered<T: PartialOrd + Debug + Send + Sync + 'static>(value: Arc<T>) -> Self {
+        Self {
+            cmp: Some(|a, b| match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+                (Some(a), Some(b)) => a.partial_cmp(b),
+                _ => None,
+            }),
+            ..Self::new(value)
+        }
+    }
+
+    /// 与方面取值比较是否相等，类型不符时为假
+    pub(crate) fn eq_value(&self, value: &AspectValue) -> bool {
+        (self.eq)(&self.value, value)
+    }
+
+    /// 自身与方面取值的大小关系，类型不符或不可比较时为 `None`
+    fn cmp_value(&self, value: &AspectValue) -> Option<std::cmp::Ordering> {
+        self.cmp.and_then(|cmp| cmp(&self.value, value))
+    }
+
+    /// 取值的类型
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// 类型擦除的取值
+    pub fn value(&self) -> &AspectValue {
+        &self.value
+    }
+
+    /// 按原类型取出取值
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.value.downcast_ref::<T>()
+    }
+}
+
+impl PartialEq for GuardValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id == other.type_id && (self.eq)(&self.value, &other.value)
+    }
+}
+
+impl fmt::Display for GuardValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&(self.fmt)(&self.value))
+    }
+}
+
+impl fmt::Debug for GuardValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
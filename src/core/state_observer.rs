@@ -5,6 +5,21 @@ use super::types::ObserverId;
 use super::state_in_range::StateInRange;
 use super::runtime::State;
 
+/// 观察者回调函数
+pub type ObserverCallback = Arc<dyn Fn(&State) + Send + Sync>;
+
+/// 可消费的进入回调，返回是否继续传递给同一区域的其他观察者
+pub type ConsumeCallback = Arc<dyn Fn(&State) -> Handled + Send + Sync>;
+
+/// 进入回调的处理结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handled {
+    /// 继续传递
+    Continue,
+    /// 消费这次进入：同一区域中优先级更低的观察者不再收到进入回调
+    Stop,
+}
+
 /// 状态观察者
 /// 监控特定状态区域，在状态进入或退出该区域时触发回调
 #[derive(Clone)]
@@ -14,7 +29,40 @@ pub struct StateObserver {
     /// 观察的状态区域
     pub region: StateInRange,
     /// 状态进入该区域时的回调函数
-    pub on_enter: Option<Arc<dyn Fn(&State) + Send + Sync>>,
+    pub on_enter: Option<ObserverCallback>,
     /// 状态退出该区域时的回调函数
-    pub on_exit: Option<Arc<dyn Fn(&State) + Send + Sync>>,
-}
\ No newline at end of file
+    pub on_exit: Option<ObserverCallback>,
+    /// 优先级，同一次提交中进入回调按优先级从高到低执行，相同时按蓝图顺序
+    pub priority: i32,
+    /// 可消费的进入回调，在 `on_enter` 之后调用
+    /// 返回 `Handled::Stop` 时，同一区域中优先级更低的观察者的 `on_enter` 与 `on_enter_consume` 都不再执行。
+    /// 结构相同的声明式区域经 `add_observer` 添加时自动视为同一区域；
+    /// 闭包构造的区域无法比较，需共享同一个 `StateInRange`（克隆）或经 `intern_predicates` 合并
+    pub on_enter_consume: Option<ConsumeCallback>,
+    /// 激活区域，非空时只有状态位于该区域内观察者才参与进出计算，区域谓词也只在此时求值；
+    /// 在观察区域内离开激活区域视为退出，回到激活区域时若仍在观察区域内视为进入
+    pub active_when: Option<StateInRange>,
+    /// 标签，非空时只有在蓝图启用该标签后观察者才生效
+    pub tag: Option<String>,
+}
+
+impl StateObserver {
+    /// 创建观察区域为 `region` 的观察者，优先级为 0，不带回调
+    pub fn new(id: ObserverId, region: StateInRange) -> Self {
+        Self {
+            id,
+            region,
+            on_enter: None,
+            on_exit: None,
+            priority: 0,
+            on_enter_consume: None,
+            active_when: None,
+            tag: None,
+        }
+    }
+
+    /// 观察者是否包含给定状态：位于激活区域内且位于观察区域内
+    pub fn contains(&self, state: &State) -> bool {
+        self.active_when.as_ref().is_none_or(|active| active.contains(state)) && self.region.contains(state)
+    }
+}
@@ -0,0 +1,241 @@
+//! 静态分发的状态机
+//! 状态为用户定义的具体类型，守卫与转换函数为函数指针，完全不经过 `Arc<dyn Any>`
+
+use std::collections::HashMap;
+use super::types::{EventId, TransitionId, ObserverId};
+use super::error::StateZenError;
+
+/// 静态转换
+pub struct StaticTransition<S> {
+    /// 转换的唯一标识符
+    pub id: TransitionId,
+    /// 触发转换的事件
+    pub event_id: EventId,
+    /// 守卫，成立时转换才可被选中
+    pub guard: fn(&S) -> bool,
+    /// 转换函数，由旧状态计算新状态
+    pub transfer: fn(&S) -> S,
+    /// 优先级，同一事件下守卫成立的转换取优先级最高者，同优先级取先添加的
+    pub priority: i32,
+    /// 转换执行时的回调，参数为转换前后的状态，在 OnExit 之后、OnEnter 之前调用
+    pub on_tran: Option<fn(&S, &S)>,
+}
+
+impl<S> StaticTransition<S> {
+    /// 创建优先级为 0、不带回调的转换
+    pub fn new(id: TransitionId, event_id: EventId, guard: fn(&S) -> bool, transfer: fn(&S) -> S) -> Self {
+        Self {
+            id,
+            event_id,
+            guard,
+            transfer,
+            priority: 0,
+            on_tran: None,
+        }
+    }
+}
+
+impl<S> Clone for StaticTransition<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for StaticTransition<S> {}
+
+/// 静态观察者
+pub struct StaticObserver<S> {
+    /// 观察者的唯一标识符
+    pub id: ObserverId,
+    /// 观察的状态区域
+    pub region: fn(&S) -> bool,
+    /// 状态进入该区域时的回调函数，参数为新状态
+    pub on_enter: Option<fn(&S)>,
+    /// 状态退出该区域时的回调函数，参数为旧状态
+    pub on_exit: Option<fn(&S)>,
+    /// 优先级，同一次提交中进入回调按优先级从高到低执行，相同时按添加顺序
+    pub priority: i32,
+}
+
+impl<S> StaticObserver<S> {
+    /// 创建观察区域为 `region` 的观察者，优先级为 0，不带回调
+    pub fn new(id: ObserverId, region: fn(&S) -> bool) -> Self {
+        Self {
+            id,
+            region,
+            on_enter: None,
+            on_exit: None,
+            priority: 0,
+        }
+    }
+}
+
+impl<S> Clone for StaticObserver<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for StaticObserver<S> {}
+
+/// 静态蓝图，结构与 `StateMachineBlueprint` 一致，状态类型在编译期确定
+pub struct StaticBlueprint<S> {
+    transitions: Vec<StaticTransition<S>>,
+    observers: Vec<StaticObserver<S>>,
+    final_region: Option<fn(&S) -> bool>,
+}
+
+impl<S> Clone for StaticBlueprint<S> {
+    fn clone(&self) -> Self {
+        Self {
+            transitions: self.transitions.clone(),
+            observers: self.observers.clone(),
+            final_region: self.final_region,
+        }
+    }
+}
+
+impl<S> Default for StaticBlueprint<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> StaticBlueprint<S> {
+    /// 创建一个空蓝图
+    pub fn new() -> Self {
+        Self {
+            transitions: Vec::new(),
+            observers: Vec::new(),
+            final_region: None,
+        }
+    }
+
+    /// 添加一个转换，ID重复时返回错误
+    pub fn add_transition(&mut self, transition: StaticTransition<S>) -> Result<(), StateZenError> {
+        if self.transitions.iter().any(|t| t.id == transition.id) {
+            return Err(StateZenError::DuplicateTransition(transition.id));
+        }
+        self.transitions.push(transition);
+        Ok(())
+    }
+
+    /// 添加一个观察者，ID重复时返回错误
+    pub fn add_observer(&mut self, observer: StaticObserver<S>) -> Result<(), StateZenError> {
+        if self.observers.iter().any(|o| o.id == observer.id) {
+            return Err(StateZenError::DuplicateObserver(observer.id));
+        }
+        self.observers.push(observer);
+        Ok(())
+    }
+
+    /// 设置终止区域
+    pub fn set_final_region(&mut self, region: fn(&S) -> bool) {
+        self.final_region = Some(region);
+    }
+
+    /// 全部转换
+    pub fn transitions(&self) -> impl Iterator<Item = &StaticTransition<S>> {
+        self.transitions.iter()
+    }
+
+    /// 全部观察者
+    pub fn observers(&self) -> impl Iterator<Item = &StaticObserver<S>> {
+        self.observers.iter()
+    }
+}
+
+/// 静态分发的运行时
+///
+/// 与 `RuntimeStateMachine` 的分发语义一致：事件选出守卫成立的最高优先级转换（同优先级取先添加的），
+/// 提交时按 OnExit（旧状态，按添加顺序）→ OnTran（前后状态）→ OnEnter（新状态，按优先级从高到低）的顺序执行回调。
+/// 不支持负载、中间件、延迟回调、区域嵌套、消费语义、边沿观察者等依赖类型擦除或蓝图扩展的功能
+pub struct StaticMachine<S> {
+    blueprint: StaticBlueprint<S>,
+    /// 事件 -> 转换下标，按优先级降序、同优先级按添加顺序
+    dispatch: HashMap<EventId, Vec<usize>>,
+    state: S,
+    /// 各观察者对当前状态的区域归属
+    membership: Vec<bool>,
+}
+
+impl<S> StaticMachine<S> {
+    /// 创建运行时，初始状态不触发回调
+    pub fn new(blueprint: StaticBlueprint<S>, initial_state: S) -> Self {
+        let mut dispatch: HashMap<EventId, Vec<usize>> = HashMap::new();
+        for (i, t) in blueprint.transitions.iter().enumerate() {
+            dispatch.entry(t.event_id).or_default().push(i);
+        }
+        for indices in dispatch.values_mut() {
+            indices.sort_by_key(|i| std::cmp::Reverse(blueprint.transitions[*i].priority));
+        }
+        let membership = blueprint.observers.iter().map(|o| (o.region)(&initial_state)).collect();
+        Self { blueprint, dispatch, state: initial_state, membership }
+    }
+
+    /// 当前状态
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// 取出当前状态
+    pub fn into_state(self) -> S {
+        self.state
+    }
+
+    /// 蓝图
+    pub fn blueprint(&self) -> &StaticBlueprint<S> {
+        &self.blueprint
+    }
+
+    /// 直接替换当前状态，不触发回调
+    pub fn set_state(&mut self, state: S) {
+        self.membership = self.blueprint.observers.iter().map(|o| (o.region)(&state)).collect();
+        self.state = state;
+    }
+
+    /// 当前状态是否在终止区域内
+    pub fn is_finished(&self) -> bool {
+        self.blueprint.final_region.is_some_and(|region| region(&self.state))
+    }
+
+    /// 在当前状态下为事件选择转换
+    pub fn select(&self, event_id: EventId) -> Option<&StaticTransition<S>> {
+        self.dispatch
+            .get(&event_id)?
+            .iter()
+            .map(|i| &self.blueprint.transitions[*i])
+            .find(|t| (t.guard)(&self.state))
+    }
+
+    /// 处理事件，返回执行的转换
+    pub fn handle_event(&mut self, event_id: EventId) -> Option<TransitionId> {
+        let transition = *self.select(event_id)?;
+        let next = (transition.transfer)(&self.state);
+        self.commit(next, transition.on_tran);
+        Some(transition.id)
+    }
+
+    fn commit(&mut self, next: S, on_tran: Option<fn(&S, &S)>) {
+        let membership: Vec<bool> = self.blueprint.observers.iter().map(|o| (o.region)(&next)).collect();
+        let changes = || self.blueprint.observers.iter().zip(self.membership.iter().zip(&membership));
+        for (observer, (was_in, now_in)) in changes() {
+            if *was_in && !now_in && let Some(on_exit) = observer.on_exit {
+                on_exit(&self.state);
+            }
+        }
+        if let Some(on_tran) = on_tran {
+            on_tran(&self.state, &next);
+        }
+        let mut entered: Vec<&StaticObserver<S>> = changes()
+            .filter(|(_, (was_in, now_in))| !**was_in && **now_in)
+            .map(|(observer, _)| observer)
+            .collect();
+        entered.sort_by_key(|o| std::cmp::Reverse(o.priority));
+        for on_enter in entered.into_iter().filter_map(|o| o.on_enter) {
+            on_enter(&next);
+        }
+        self.state = next;
+        self.membership = membership;
+    }
+}
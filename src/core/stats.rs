@@ -0,0 +1,161 @@
+//! 蓝图统计与复杂度报告
+
+use std::collections::BTreeMap;
+use std::fmt;
+use super::types::EventId;
+use super::blueprint::StateMachineBlueprint;
+
+/// 复杂度警告的阈值
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComplexityThresholds {
+    /// 单个事件上的转换数量达到该值时警告
+    pub transitions_per_event: usize,
+    /// 观察者数量达到该值时警告
+    pub observers: usize,
+}
+
+impl Default for ComplexityThresholds {
+    fn default() -> Self {
+        Self {
+            transitions_per_event: 100,
+            observers: 1000,
+        }
+    }
+}
+
+/// 复杂度警告
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComplexityWarning {
+    /// 单个事件上的转换过多，分发该事件需要逐个检查守卫
+    HotEvent { event_id: EventId, transitions: usize },
+    /// 观察者过多，每次提交都要重新计算区域归属
+    TooManyObservers(usize),
+    /// 声明的事件没有转换监听，也不由任何转换发出
+    UnusedEvent(EventId),
+}
+
+impl fmt::Display for ComplexityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HotEvent { event_id, transitions } => write!(f, "事件 {event_id} 上有 {transitions} 个转换"),
+            Self::TooManyObservers(n) => write!(f, "共有 {n} 个观察者"),
+            Self::UnusedEvent(id) => write!(f, "事件 {id} 未被任何转换使用"),
+        }
+    }
+}
+
+/// 蓝图统计
+///
+/// 记录各部分的数量与守卫、转换函数中可分析（声明式）与不透明（闭包构造）的比例，
+/// 便于跨版本跟踪状态机复杂度的增长
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlueprintStats {
+    /// 方面数量
+    pub aspects: usize,
+    /// 事件数量
+    pub events: usize,
+    /// 转换数量
+    pub transitions: usize,
+    /// 各事件上的转换数量，只含至少有一个转换的事件
+    pub transitions_per_event: BTreeMap<EventId, usize>,
+    /// 观察者数量
+    pub observers: usize,
+    /// 边沿观察者数量
+    pub edge_observers: usize,
+    /// 声明式守卫数量
+    pub declarative_guards: usize,
+    /// 不透明守卫数量
+    pub opaque_guards: usize,
+    /// 声明式转换函数数量
+    pub declarative_transfers: usize,
+    /// 不透明转换函数数量
+    pub opaque_transfers: usize,
+    /// 复杂度警告：热点事件、观察者数量、未使用的事件依次排列，同类按事件ID升序
+    pub warnings: Vec<ComplexityWarning>,
+}
+
+impl BlueprintStats {
+    /// 单个事件上最多的转换数量
+    pub fn max_transitions_per_event(&self) -> usize {
+        self.transitions_per_event.values().copied().max().unwrap_or(0)
+    }
+}
+
+impl fmt::Display for BlueprintStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "方面 {}，事件 {}，转换 {}（单事件最多 {}），观察者 {}，边沿观察者 {}\n\
+             守卫：声明式 {} / 不透明 {}；转换函数：声明式 {} / 不透明 {}",
+            self.aspects,
+            self.events,
+            self.transitions,
+            self.max_transitions_per_event(),
+            self.observers,
+            self.edge_observers,
+            self.declarative_guards,
+            self.opaque_guards,
+            self.declarative_transfers,
+            self.opaque_transfers,
+        )?;
+        for warning in &self.warnings {
+            write!(f, "\n  警告：{warning}")?;
+        }
+        Ok(())
+    }
+}
+
+impl StateMachineBlueprint {
+    /// 按默认阈值统计蓝图
+    pub fn stats(&self) -> BlueprintStats {
+        self.stats_with(ComplexityThresholds::default())
+    }
+
+    /// 按给定阈值统计蓝图
+    pub fn stats_with(&self, thresholds: ComplexityThresholds) -> BlueprintStats {
+        let mut transitions_per_event = BTreeMap::new();
+        let (mut declarative_guards, mut declarative_transfers) = (0, 0);
+        let mut transitions = 0;
+        for t in self.transitions() {
+            transitions += 1;
+            *transitions_per_event.entry(t.event_id).or_insert(0) += 1;
+            declarative_guards += usize::from(t.guard.is_declarative());
+            declarative_transfers += usize::from(!t.transfer.expr().is_opaque());
+        }
+
+        let mut warnings: Vec<ComplexityWarning> = transitions_per_event
+            .iter()
+            .filter(|(_, n)| **n >= thresholds.transitions_per_event)
+            .map(|(event_id, n)| ComplexityWarning::HotEvent { event_id: *event_id, transitions: *n })
+            .collect();
+        let observers = self.observers().count();
+        if observers >= thresholds.observers {
+            warnings.push(ComplexityWarning::TooManyObservers(observers));
+        }
+        let mut events: Vec<EventId> = self.events().map(|e| e.id).collect();
+        events.sort_unstable();
+        warnings.extend(
+            events
+                .iter()
+                .filter(|id| {
+                    !transitions_per_event.contains_key(id)
+                        && !self.transitions().any(|t| t.emits.iter().any(|e| e.event_id == **id))
+                })
+                .map(|id| ComplexityWarning::UnusedEvent(*id)),
+        );
+
+        BlueprintStats {
+            aspects: self.aspects().count(),
+            events: events.len(),
+            transitions,
+            transitions_per_event,
+            observers,
+            edge_observers: self.edge_observers().count(),
+            declarative_guards,
+            opaque_guards: transitions - declarative_guards,
+            declarative_transfers,
+            opaque_transfers: transitions - declarative_transfers,
+            warnings,
+        }
+    }
+}
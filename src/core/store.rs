@@ -0,0 +1,208 @@
+//! 状态持久化
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use super::types::MachineId;
+use super::runtime::State;
+use super::codec::StateCodec;
+use super::clock::Clock;
+use super::error::StateZenError;
+
+/// 状态存储
+/// 按状态机实例ID保存与读取状态快照
+pub trait StateStore: Send + Sync {
+    /// 保存实例的状态，覆盖之前的快照
+    fn save(&self, machine_id: MachineId, state: &State) -> Result<(), StateZenError>;
+
+    /// 读取实例最近保存的状态，从未保存时返回 `None`
+    fn load(&self, machine_id: MachineId) -> Result<Option<State>, StateZenError>;
+}
+
+/// 内存存储，适合测试与单进程内的快照
+#[derive(Default)]
+pub struct MemoryStore {
+    states: Mutex<HashMap<MachineId, State>>,
+}
+
+impl MemoryStore {
+    /// 创建一个空的内存存储
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 已保存的实例数量
+    pub fn len(&self) -> usize {
+        self.states.lock().unwrap().len()
+    }
+
+    /// 是否没有保存任何实例
+    pub fn is_empty(&self) -> bool {
+        self.states.lock().unwrap().is_empty()
+    }
+}
+
+impl StateStore for MemoryStore {
+    fn save(&self, machine_id: MachineId, state: &State) -> Result<(), StateZenError> {
+        self.states.lock().unwrap().insert(machine_id, state.clone());
+        Ok(())
+    }
+
+    fn load(&self, machine_id: MachineId) -> Result<Option<State>, StateZenError> {
+        Ok(self.states.lock().unwrap().get(&machine_id).cloned())
+    }
+}
+
+/// 文件存储
+/// 每个实例一个 `<id>.state` 文件，每行一个方面：`<方面ID>\t<编码后的取值>`。
+/// 先写临时文件再重命名，进程中途退出时不会留下写了一半的快照
+pub struct FileStore {
+    dir: PathBuf,
+    codec: StateCodec,
+}
+
+impl FileStore {
+    /// 在目录 `dir` 下存储，不存在时自动创建
+    pub fn new(dir: impl Into<PathBuf>, codec: StateCodec) -> Self {
+        Self {
+            dir: dir.into(),
+            codec,
+        }
+    }
+
+    fn path(&self, machine_id: MachineId) -> PathBuf {
+        self.dir.join(format!("{machine_id}.state"))
+    }
+}
+
+fn io_error(machine_id: MachineId, error: std::io::Error) -> StateZenError {
+    StateZenError::StoreFailed {
+        machine: machine_id,
+        message: error.to_string(),
+    }
+}
+
+/// 转义换行、制表符与反斜杠，保证每个取值占一行
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub(crate) fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+impl StateStore for FileStore {
+    fn save(&self, machine_id: MachineId, state: &State) -> Result<(), StateZenError> {
+        let mut text = String::new();
+        for (id, value) in self.codec.encode_state(state)? {
+            text.push_str(&format!("{id}\t{}\n", escape(&value)));
+        }
+        fs::create_dir_all(&self.dir).map_err(|e| io_error(machine_id, e))?;
+        let path = self.path(machine_id);
+        let tmp = path.with_extension("state.tmp");
+        fs::write(&tmp, text).map_err(|e| io_error(machine_id, e))?;
+        fs::rename(&tmp, &path).map_err(|e| io_error(machine_id, e))
+    }
+
+    fn load(&self, machine_id: MachineId) -> Result<Option<State>, StateZenError> {
+        let text = match fs::read_to_string(self.path(machine_id)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(io_error(machine_id, e)),
+        };
+        let mut entries = Vec::new();
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            let parsed = line.split_once('\t').and_then(|(id, value)| Some((id.parse().ok()?, unescape(value))));
+            let Some(entry) = parsed else {
+                return Err(StateZenError::StoreFailed {
+                    machine: machine_id,
+                    message: format!("无法解析的行 `{line}`"),
+                });
+            };
+            entries.push(entry);
+        }
+        self.codec
+            .decode_state(entries.iter().map(|(id, value)| (*id, value.as_str())))
+            .map(Some)
+    }
+}
+
+/// 自动保存的时机
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutosavePolicy {
+    /// 每提交 n 次状态变更保存一次
+    Transforms(u32),
+    /// 距上次保存至少经过该时长后，在下一次提交时保存
+    Interval(Duration),
+}
+
+/// 运行时内部的自动保存状态
+pub(crate) struct Autosave {
+    store: Arc<dyn StateStore>,
+    machine_id: MachineId,
+    policy: AutosavePolicy,
+    clock: Arc<dyn Clock>,
+    commits: u32,
+    last_save: Duration,
+}
+
+impl Autosave {
+    pub(crate) fn new(
+        store: Arc<dyn StateStore>,
+        machine_id: MachineId,
+        policy: AutosavePolicy,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let last_save = clock.now();
+        Self {
+            store,
+            machine_id,
+            policy,
+            clock,
+            commits: 0,
+            last_save,
+        }
+    }
+
+    /// 提交后调用，按策略决定是否保存
+    pub(crate) fn after_commit(&mut self, state: &State) -> Result<(), StateZenError> {
+        self.commits += 1;
+        let due = match self.policy {
+            AutosavePolicy::Transforms(n) => self.commits >= n.max(1),
+            AutosavePolicy::Interval(interval) => self.clock.now().saturating_sub(self.last_save) >= interval,
+        };
+        if !due {
+            return Ok(());
+        }
+        self.commits = 0;
+        self.last_save = self.clock.now();
+        self.store.save(self.machine_id, state)
+    }
+}
@@ -0,0 +1,410 @@
+//! 多实例编排：从转换中派生子状态机
+
+use std::collections::{BTreeMap, HashMap};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use super::types::EventId;
+use super::event::EventPayload;
+use super::transition::OnTranCallback;
+use super::runtime::{RuntimeStateMachine, State};
+use super::template::MachineTemplate;
+use super::state_in_range::StateInRange;
+use super::relation::MachineDirectory;
+use super::error::StateZenError;
+use super::label::MachineLabel;
+use super::activity::Activity;
+
+/// 子状态机ID，由监督器分配，不会复用；同时用作状态机目录中的实例ID
+pub type ChildId = super::types::MachineId;
+
+/// 派生请求
+struct SpawnRequest {
+    template: String,
+    overrides: State,
+}
+
+/// 派生句柄
+/// 可克隆、可跨线程，通常被转换的 `on_tran` 捕获；请求在监督器下一次处理时生效
+#[derive(Clone)]
+pub struct Spawner {
+    queue: Arc<Mutex<Vec<SpawnRequest>>>,
+}
+
+impl Spawner {
+    /// 请求按模板派生一个子状态机，`overrides` 覆盖模板的默认初始值
+    pub fn spawn(&self, template: impl Into<String>, overrides: State) {
+        self.queue.lock().unwrap().push(SpawnRequest {
+            template: template.into(),
+            overrides,
+        });
+    }
+
+    /// 构造一个派生子状态机的 `on_tran` 回调
+    /// `overrides` 根据转换前后的状态计算子状态机的初始值
+    pub fn on_tran<F>(&self, template: impl Into<String>, overrides: F) -> OnTranCallback
+    where
+        F: Fn(&State, &State) -> State + Send + Sync + 'static,
+    {
+        let spawner = self.clone();
+        let template = template.into();
+        Arc::new(move |prev, next| spawner.spawn(template.clone(), overrides(prev, next)))
+    }
+}
+
+/// 子状态机出错时的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// 回调 panic 时用模板重新创建子状态机；违反不变式只通知，不处理
+    RestartOnPanic,
+    /// 回调 panic 或违反不变式时把子状态机重置为初始状态（保留已挂载的追踪器等）
+    ResetToInitial,
+    /// 移除子状态机，并由 `dispatch` / `tick` / `send_to` 返回 `ChildFailed`
+    Escalate,
+}
+
+/// 子状态机的出错原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureCause {
+    /// 回调 panic，附带 panic 信息
+    Panic(String),
+    /// 状态离开了模板的不变式区域
+    InvariantViolated,
+}
+
+/// 出错通知
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureNotice {
+    /// 出错的子状态机
+    pub child: ChildId,
+    /// 子状态机的模板名称
+    pub template: String,
+    /// 出错原因
+    pub cause: FailureCause,
+    /// 采取的处理，`None` 表示未配置策略
+    pub action: Option<RestartPolicy>,
+    /// 子状态机出错时的实例标签
+    pub label: Option<MachineLabel>,
+    /// 子状态机出错前的近期活动（从旧到新）
+    pub recent_activity: Vec<Activity>,
+}
+
+/// 出错通知回调
+pub type FailureObserver = Arc<dyn Fn(&FailureNotice) + Send + Sync>;
+
+/// 受监督的子状态机
+/// 子状态机的运行时带有实例标签：ID为子状态机ID，元数据 `template` 为模板名称
+pub struct Child {
+    /// 派生所用的模板名称
+    pub template: String,
+    /// 子状态机
+    pub runtime: RuntimeStateMachine,
+    /// 派生时的初始状态，重置与重启时使用
+    pub initial_state: State,
+}
+
+/// 状态机监督器
+/// 按注册的模板派生子状态机，向子状态机转发事件，并回收进入终止区域的子状态机
+pub struct MachineSupervisor {
+    templates: HashMap<String, MachineTemplate>,
+    children: BTreeMap<ChildId, Child>,
+    next_id: ChildId,
+    queue: Arc<Mutex<Vec<SpawnRequest>>>,
+    policies: HashMap<String, RestartPolicy>,
+    invariants: HashMap<String, StateInRange>,
+    on_failure: Option<FailureObserver>,
+    directory: MachineDirectory,
+}
+
+impl Default for MachineSupervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MachineSupervisor {
+    /// 创建一个空监督器
+    pub fn new() -> Self {
+        Self {
+            templates: HashMap::new(),
+            children: BTreeMap::new(),
+            next_id: 0,
+            queue: Arc::new(Mutex::new(Vec::new())),
+            policies: HashMap::new(),
+            invariants: HashMap::new(),
+            on_failure: None,
+            directory: MachineDirectory::new(),
+        }
+    }
+
+    /// 设置某个模板的子状态机出错时的处理策略
+    /// 未设置策略时 panic 照常向上传播
+    pub fn set_restart_policy(&mut self, template: impl Into<String>, policy: RestartPolicy) {
+        self.policies.insert(template.into(), policy);
+    }
+
+    /// 设置某个模板的不变式：子状态机每次处理事件后状态都必须位于该区域内
+    pub fn set_invariant(&mut self, template: impl Into<String>, invariant: StateInRange) {
+        self.invariants.insert(template.into(), invariant);
+    }
+
+    /// 设置出错通知回调
+    pub fn set_on_failure<F>(&mut self, f: F)
+    where
+        F: Fn(&FailureNotice) + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Arc::new(f));
+    }
+
+    /// 注册模板，同名模板被替换
+    pub fn register_template(&mut self, name: impl Into<String>, template: MachineTemplate) {
+        self.templates.insert(name.into(), template);
+    }
+
+    /// 状态机目录：以子状态机ID发布各子状态机的最新状态
+    /// 子状态机的守卫可通过 `MachineDirectory::other_in_region` 查询其他子状态机；
+    /// 同一次 `dispatch` 中，先处理的子状态机的新状态对后处理的子状态机可见
+    pub fn directory(&self) -> MachineDirectory {
+        self.directory.clone()
+    }
+
+    /// 派生句柄
+    pub fn spawner(&self) -> Spawner {
+        Spawner {
+            queue: self.queue.clone(),
+        }
+    }
+
+    /// 立即按模板派生一个子状态机
+    pub fn spawn(&mut self, template: &str, overrides: State) -> Result<ChildId, StateZenError> {
+        let template_def = self
+            .templates
+            .get(template)
+            .ok_or_else(|| StateZenError::UnknownTemplate(template.to_string()))?;
+        let initial_state = template_def.initial_state(overrides)?;
+        let mut runtime = RuntimeStateMachine::new(template_def.blueprint.clone(), initial_state.clone());
+        let id = self.next_id;
+        self.next_id += 1;
+        runtime.set_label(MachineLabel::new(id).with("template", template));
+        self.directory.publish(id, initial_state.clone());
+        self.children.insert(
+            id,
+            Child {
+                template: template.to_string(),
+                runtime,
+                initial_state,
+            },
+        );
+        Ok(id)
+    }
+
+    /// 处理派生句柄积累的请求，返回新子状态机的ID
+    /// 子状态机在派生时触发的派生请求也会在本次处理
+    ///
+    /// 某个请求失败时丢弃该请求并返回错误，其余请求留在队列中，下次处理时继续
+    pub fn process_spawns(&mut self) -> Result<Vec<ChildId>, StateZenError> {
+        let mut spawned = Vec::new();
+        loop {
+            let mut requests = std::mem::take(&mut *self.queue.lock().unwrap()).into_iter();
+            if requests.len() == 0 {
+                return Ok(spawned);
+            }
+            while let Some(request) = requests.next() {
+                match self.spawn(&request.template, request.overrides) {
+                    Ok(id) => spawned.push(id),
+                    Err(error) => {
+                        self.queue.lock().unwrap().splice(0..0, requests);
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 子状态机
+    pub fn child(&self, id: ChildId) -> Option<&Child> {
+        self.children.get(&id)
+    }
+
+    /// 子状态机（可变）
+    pub fn child_mut(&mut self, id: ChildId) -> Option<&mut Child> {
+        self.children.get_mut(&id)
+    }
+
+    /// 设置子状态机的一项元数据，返回子状态机是否存在
+    pub fn set_child_metadata(&mut self, id: ChildId, key: impl Into<String>, value: impl Into<String>) -> bool {
+        let Some(child) = self.children.get_mut(&id) else {
+            return false;
+        };
+        let label = child.runtime.label().cloned().unwrap_or_else(|| MachineLabel::new(id));
+        child.runtime.set_label(label.with(key, value));
+        true
+    }
+
+    /// 元数据 `key` 取值为 `value` 的子状态机ID（按ID升序）
+    pub fn find_children(&self, key: &str, value: &str) -> Vec<ChildId> {
+        self.children
+            .iter()
+            .filter(|(_, c)| c.runtime.label().and_then(|l| l.get(key)) == Some(value))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// 全部子状态机（按ID升序）
+    pub fn children(&self) -> impl Iterator<Item = (ChildId, &Child)> {
+        self.children.iter().map(|(id, c)| (*id, c))
+    }
+
+    /// 子状态机数量
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    /// 是否没有子状态机
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// 向全部子状态机转发事件，随后处理派生请求并回收已结束的子状态机
+    ///
+    /// 策略为 `Escalate` 的子状态机出错时，其余子状态机照常处理，最后返回第一个 `ChildFailed`；
+    /// 同一轮中派生请求也失败时优先返回 `ChildFailed`，其后的派生请求留在队列中
+    pub fn dispatch(&mut self, event_id: EventId, payload: Option<EventPayload>) -> Result<(), StateZenError> {
+        self.run_all(|runtime| runtime.handle_event(event_id, payload.clone()))
+    }
+
+    /// 向单个子状态机发送事件，返回子状态机是否存在
+    pub fn send_to(
+        &mut self,
+        id: ChildId,
+        event_id: EventId,
+        payload: Option<EventPayload>,
+    ) -> Result<bool, StateZenError> {
+        if !self.children.contains_key(&id) {
+            return Ok(false);
+        }
+        self.run_child(id, |runtime| runtime.handle_event(event_id, payload))?;
+        self.process_spawns()?;
+        self.reap();
+        Ok(true)
+    }
+
+    /// 推进全部子状态机的连续转移，随后处理派生请求并回收已结束的子状态机
+    pub fn tick(&mut self, dt: Duration) -> Result<(), StateZenError> {
+        self.run_all(|runtime| runtime.tick(dt))
+    }
+
+    fn run_all<F>(&mut self, mut f: F) -> Result<(), StateZenError>
+    where
+        F: FnMut(&mut RuntimeStateMachine),
+    {
+        let ids: Vec<ChildId> = self.children.keys().copied().collect();
+        let mut escalated = None;
+        for id in ids {
+            if let Err(e) = self.run_child(id, &mut f) {
+                escalated.get_or_insert(e);
+            }
+        }
+        let spawned = self.process_spawns();
+        self.reap();
+        match escalated {
+            Some(e) => Err(e),
+            None => spawned.map(|_| ()),
+        }
+    }
+
+    /// 在单个子状态机上执行操作，按策略处理 panic 与不变式违例
+    fn run_child<F>(&mut self, id: ChildId, f: F) -> Result<(), StateZenError>
+    where
+        F: FnOnce(&mut RuntimeStateMachine),
+    {
+        let Some(child) = self.children.get_mut(&id) else {
+            return Ok(());
+        };
+        let policy = self.policies.get(&child.template).copied();
+
+        let cause = if policy.is_some() {
+            panic::catch_unwind(AssertUnwindSafe(|| f(&mut child.runtime)))
+                .err()
+                .map(|e| FailureCause::Panic(panic_message(e.as_ref())))
+        } else {
+            f(&mut child.runtime);
+            None
+        };
+        let cause = cause.or_else(|| {
+            let invariant = self.invariants.get(&child.template)?;
+            (!invariant.contains(&child.runtime.current_state)).then_some(FailureCause::InvariantViolated)
+        });
+        let Some(cause) = cause else {
+            self.directory.publish(id, child.runtime.current_state.clone());
+            return Ok(());
+        };
+
+        let action = match (policy, &cause) {
+            (Some(RestartPolicy::RestartOnPanic), FailureCause::InvariantViolated) => None,
+            (policy, _) => policy,
+        };
+        let notice = FailureNotice {
+            child: id,
+            template: child.template.clone(),
+            cause,
+            action,
+            label: child.runtime.label().cloned(),
+            recent_activity: child.runtime.recent_activity(),
+        };
+
+        let result = match action {
+            Some(RestartPolicy::RestartOnPanic) => {
+                let blueprint = self.templates[&child.template].blueprint.clone();
+                let label = child.runtime.label().cloned();
+                child.runtime = RuntimeStateMachine::new(blueprint, child.initial_state.clone());
+                if let Some(label) = label {
+                    child.runtime.set_label(label);
+                }
+                Ok(())
+            }
+            Some(RestartPolicy::ResetToInitial) => {
+                child.runtime.set_state(child.initial_state.clone());
+                Ok(())
+            }
+            Some(RestartPolicy::Escalate) => {
+                self.children.remove(&id);
+                self.directory.remove(id);
+                Err(StateZenError::ChildFailed(id))
+            }
+            None => Ok(()),
+        };
+        if let Some(child) = self.children.get(&id) {
+            self.directory.publish(id, child.runtime.current_state.clone());
+        }
+        if let Some(on_failure) = &self.on_failure {
+            on_failure(&notice);
+        }
+        result
+    }
+
+    /// 回收进入终止区域的子状态机，返回被回收的ID
+    pub fn reap(&mut self) -> Vec<ChildId> {
+        let finished: Vec<ChildId> = self
+            .children
+            .iter()
+            .filter(|(_, c)| c.runtime.is_finished())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &finished {
+            self.children.remove(id);
+            self.directory.remove(*id);
+        }
+        finished
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<非字符串 panic>".to_string()
+    }
+}
@@ -0,0 +1,197 @@
+//! 在独立的 tokio 任务中运行状态机
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use super::types::EventId;
+use super::event::{EventInstance, EventPayload};
+use super::queue::{EventBuffer, OverflowPolicy};
+use super::runtime::{RuntimeStateMachine, State};
+
+/// 状态机任务已结束，事件未送达
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MachineTaskClosed;
+
+impl fmt::Display for MachineTaskClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "状态机任务已结束")
+    }
+}
+
+impl std::error::Error for MachineTaskClosed {}
+
+/// 事件未能送入邮箱
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendError {
+    /// 状态机任务已结束
+    Closed,
+    /// 邮箱已满，事件的溢出策略为 `Error`，或为 `Block` 但发送端无法等待
+    Overflow(EventId),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "{MachineTaskClosed}"),
+            Self::Overflow(event) => write!(f, "邮箱已满，事件 {event} 被拒绝"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+impl From<MachineTaskClosed> for SendError {
+    fn from(_: MachineTaskClosed) -> Self {
+        Self::Closed
+    }
+}
+
+/// 发送端与任务共享的邮箱
+struct Mailbox {
+    buffer: Mutex<EventBuffer>,
+    /// 存活的发送端数量，归零后任务处理完剩余事件并结束
+    senders: AtomicUsize,
+    /// 任务是否已结束（包括被中止或恐慌）
+    closed: AtomicBool,
+    /// 有新事件或发送端全部丢弃时唤醒任务
+    received: Notify,
+    /// 任务取走事件腾出空位或结束时唤醒等待的发送端
+    space: Notify,
+}
+
+/// 任务结束时（无论正常返回、被中止还是恐慌）关闭邮箱
+struct CloseOnDrop(Arc<Mailbox>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+        self.0.space.notify_waiters();
+    }
+}
+
+/// 状态机任务的邮箱，可克隆后在多处发送事件
+/// 全部发送端被丢弃后任务处理完剩余事件并结束
+pub struct EventSender {
+    mailbox: Arc<Mailbox>,
+}
+
+impl EventSender {
+    /// 发送一个事件
+    pub fn send(&self, event_id: EventId, payload: Option<EventPayload>) -> Result<(), SendError> {
+        self.send_instance(EventInstance::new(event_id, payload))
+    }
+
+    /// 发送一个事件实例，不等待
+    ///
+    /// 邮箱已满时按事件的溢出策略处理；`DropNewest` / `DropOldest` / `Coalesce` 丢弃或合并的事件不报告，
+    /// `Error` 与 `Block` 策略的事件被拒绝并返回 `SendError::Overflow`
+    pub fn send_instance(&self, event: EventInstance) -> Result<(), SendError> {
+        self.try_send(event).map_err(|(error, _)| error)
+    }
+
+    /// 发送一个事件实例，`Block` 策略的事件在邮箱已满时等待空位
+    pub async fn send_async(&self, mut event: EventInstance) -> Result<(), SendError> {
+        loop {
+            let space = self.mailbox.space.notified();
+            let mut space = std::pin::pin!(space);
+            space.as_mut().enable();
+            match self.try_send(event) {
+                Err((SendError::Overflow(id), rejected))
+                    if self.mailbox.buffer.lock().unwrap().policy_for(id) == OverflowPolicy::Block =>
+                {
+                    event = rejected;
+                    space.await;
+                }
+                result => return result.map_err(|(error, _)| error),
+            }
+        }
+    }
+
+    fn try_send(&self, event: EventInstance) -> Result<(), (SendError, EventInstance)> {
+        if self.mailbox.closed.load(Ordering::Acquire) {
+            return Err((SendError::Closed, event));
+        }
+        let pushed = self.mailbox.buffer.lock().unwrap().try_push(event);
+        match pushed {
+            Ok(_) => {
+                self.mailbox.received.notify_one();
+                Ok(())
+            }
+            Err(event) => Err((SendError::Overflow(event.event_id), event)),
+        }
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        self.mailbox.senders.fetch_add(1, Ordering::AcqRel);
+        Self { mailbox: self.mailbox.clone() }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        if self.mailbox.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.mailbox.received.notify_one();
+        }
+    }
+}
+
+/// 状态机任务的状态订阅端，只保留最新的状态
+#[derive(Clone)]
+pub struct StateReceiver {
+    rx: watch::Receiver<State>,
+}
+
+impl StateReceiver {
+    /// 最新的状态
+    pub fn current(&self) -> State {
+        self.rx.borrow().clone()
+    }
+
+    /// 等待下一次状态变化并返回变化后的状态
+    pub async fn changed(&mut self) -> Result<State, MachineTaskClosed> {
+        self.rx.changed().await.map_err(|_| MachineTaskClosed)?;
+        Ok(self.rx.borrow_and_update().clone())
+    }
+}
+
+/// 在当前 tokio 运行时中启动一个任务运行状态机
+///
+/// `mailbox` 决定邮箱的容量与（按事件ID的）溢出策略，例如
+/// `EventBuffer::new(Some(64), OverflowPolicy::Block)`；任务逐个处理邮箱中的事件，
+/// 每个事件处理后把状态发布给 `StateReceiver`；
+/// 全部发送端被丢弃后任务结束，`JoinHandle` 返回状态机本身
+pub fn spawn_machine_task(
+    mut runtime: RuntimeStateMachine,
+    mailbox: EventBuffer,
+) -> (EventSender, StateReceiver, JoinHandle<RuntimeStateMachine>) {
+    let mailbox = Arc::new(Mailbox {
+        buffer: Mutex::new(mailbox),
+        senders: AtomicUsize::new(1),
+        closed: AtomicBool::new(false),
+        received: Notify::new(),
+        space: Notify::new(),
+    });
+    let (states, rx) = watch::channel(runtime.current_state.clone());
+    let shared = CloseOnDrop(mailbox.clone());
+    let handle = tokio::spawn(async move {
+        let mailbox = &shared.0;
+        loop {
+            let next = mailbox.buffer.lock().unwrap().pop();
+            match next {
+                Some(event) => {
+                    mailbox.space.notify_one();
+                    runtime.handle_event(event.event_id, event.payload);
+                    states.send_replace(runtime.current_state.clone());
+                }
+                None if mailbox.senders.load(Ordering::Acquire) == 0 => break,
+                None => mailbox.received.notified().await,
+            }
+        }
+        runtime
+    });
+    (EventSender { mailbox }, StateReceiver { rx }, handle)
+}
@@ -0,0 +1,95 @@
+//! 状态机模板：蓝图 + 默认初始状态 + 必需的上下文
+
+use std::any::{Any, TypeId};
+use std::sync::Arc;
+use super::types::StateAspectId;
+use super::blueprint::StateMachineBlueprint;
+use super::runtime::{RuntimeStateMachine, State};
+use super::error::StateZenError;
+
+/// 实例化时必须由调用方提供的上下文方面，如实体ID、所属玩家
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequiredContext {
+    /// 方面ID
+    pub aspect_id: StateAspectId,
+    /// 取值类型
+    pub type_id: TypeId,
+    /// 取值类型名称，用于诊断
+    pub type_name: &'static str,
+}
+
+/// 状态机模板
+/// 把蓝图、各方面的默认初始值与必需的上下文打包在一起，统一初始状态的构造方式
+#[derive(Clone)]
+pub struct MachineTemplate {
+    /// 状态机蓝图
+    pub blueprint: StateMachineBlueprint,
+    /// 默认初始状态
+    pub defaults: State,
+    /// 必需的上下文，每次实例化都必须在覆盖值中提供，不能由默认值补齐
+    pub required: Vec<RequiredContext>,
+}
+
+impl MachineTemplate {
+    /// 基于蓝图创建一个没有默认值的模板
+    pub fn new(blueprint: StateMachineBlueprint) -> Self {
+        Self {
+            blueprint,
+            defaults: State::new(),
+            required: Vec::new(),
+        }
+    }
+
+    /// 要求实例化时在覆盖值中提供类型为 `T` 的上下文方面，重复声明同一方面时以后者为准
+    pub fn require_context<T: Any>(mut self, aspect_id: StateAspectId) -> Self {
+        self.required.retain(|r| r.aspect_id != aspect_id);
+        self.required.push(RequiredContext {
+            aspect_id,
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+        });
+        self
+    }
+
+    /// 设置某个方面的默认初始值
+    pub fn with_default<T>(mut self, aspect_id: StateAspectId, value: T) -> Self
+    where
+        T: std::any::Any + Send + Sync,
+    {
+        self.defaults.insert(aspect_id, Arc::new(value));
+        self
+    }
+
+    /// 构造初始状态：默认值被 `overrides` 覆盖，模板没有默认值的方面使用方面自身的默认值
+    ///
+    /// 必需的上下文必须出现在 `overrides` 中，缺少时返回 `MissingContext`，类型不符时返回
+    /// `AspectTypeMismatch`；蓝图声明的每个方面都必须有取值，且类型与声明一致
+    pub fn initial_state(&self, overrides: State) -> Result<State, StateZenError> {
+        for required in &self.required {
+            match overrides.get(&required.aspect_id) {
+                None => return Err(StateZenError::MissingContext(required.aspect_id)),
+                Some(value) if (**value).type_id() != required.type_id => {
+                    return Err(StateZenError::AspectTypeMismatch(required.aspect_id));
+                }
+                Some(_) => {}
+            }
+        }
+        let mut state = self.defaults.clone();
+        state.extend(overrides);
+        for aspect in self.blueprint.aspects() {
+            if let Some(default) = &aspect.default
+                && !state.contains_key(&aspect.id)
+            {
+                state.insert(aspect.id, default());
+            }
+        }
+        self.blueprint.validate_state(&state)?;
+        Ok(state)
+    }
+
+    /// 用覆盖值实例化一个运行时状态机
+    pub fn instantiate(&self, overrides: State) -> Result<RuntimeStateMachine, StateZenError> {
+        let initial_state = self.initial_state(overrides)?;
+        Ok(RuntimeStateMachine::new(self.blueprint.clone(), initial_state))
+    }
+}
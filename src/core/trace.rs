@@ -0,0 +1,34 @@
+//! 运行时追踪钩子
+
+use super::types::{EventId, TransitionId, ObserverId};
+use super::runtime::State;
+use super::label::MachineLabel;
+
+/// 观察者区域的进出方向
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RegionEdge {
+    /// 进入区域
+    Enter,
+    /// 退出区域
+    Exit,
+}
+
+/// 运行时追踪器
+/// 被动接收运行时内部发生的事情，用于覆盖率、日志、监控等横切关注点。
+/// 所有方法默认为空实现，按需覆盖
+pub trait Tracer: Send + Sync {
+    /// 事件完成转换选择；`selected` 为 `None` 表示事件被忽略
+    fn on_event(&self, _event_id: EventId, _selected: Option<TransitionId>) {}
+
+    /// 转换被执行
+    fn on_transition(&self, _transition_id: TransitionId, _prev: &State, _next: &State) {}
+
+    /// 观察者进入或退出区域（无论是否注册了回调）
+    fn on_observer(&self, _observer_id: ObserverId, _edge: RegionEdge) {}
+
+    /// 新状态被提交
+    fn on_commit(&self, _prev: &State, _next: &State) {}
+
+    /// 得知所在运行时的标签：挂载到已有标签的运行时时，以及运行时的标签被设置时
+    fn on_label(&self, _label: &MachineLabel) {}
+}
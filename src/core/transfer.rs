@@ -1,13 +1,26 @@
 //! 状态转换函数
 
+use std::any::TypeId;
+use std::fmt::{self, Debug};
+use std::ops::{Add, Sub};
 use std::sync::Arc;
-use super::runtime::State;
+use super::types::StateAspectId;
+use super::runtime::{State, AspectValue};
+use super::state_in_range::GuardValue;
+use super::registry::AspectRegistry;
+use super::arg::ArgValue;
 
 /// 状态转换函数
 /// 定义如何从一个状态转换到另一个状态
 #[derive(Clone)]
 pub struct Transfer {
     func: Arc<dyn Fn(&State) -> State + 'static + Send + Sync>,
+    /// 转换的结构描述，闭包构造的为 `Opaque`
+    expr: Arc<TransferExpr>,
+    /// 写入的方面集合，`None` 表示未知（可能写入任意方面）
+    writes: Option<Arc<[StateAspectId]>>,
+    /// 受保护的方面，结果改写了其中任一方面时转换被拒绝
+    protected: Option<Arc<[StateAspectId]>>,
 }
 
 impl Transfer {
@@ -18,11 +31,377 @@ impl Transfer {
     {
         Self {
             func: Arc::new(f),
+            expr: Arc::new(TransferExpr::Opaque),
+            writes: None,
+            protected: None,
         }
     }
 
-    /// 应用转换函数到给定的状态
+    fn declarative<F>(expr: TransferExpr, f: F) -> Self
+    where
+        F: Fn(&State) -> State + 'static + Send + Sync,
+    {
+        let writes = expr.writes().map(Into::into);
+        Self {
+            func: Arc::new(f),
+            expr: Arc::new(expr),
+            writes,
+            protected: None,
+        }
+    }
+
+    /// 把方面设为 `value` 的转换
+    pub fn set<T>(aspect_id: StateAspectId, value: T) -> Self
+    where
+        T: PartialEq + Debug + Send + Sync + 'static,
+    {
+        let value = GuardValue::new(Arc::new(value));
+        let stored = value.value().clone();
+        Self::declarative(TransferExpr::Set { aspect: aspect_id, value }, move |s| {
+            let mut next = s.clone();
+            next.insert(aspect_id, stored.clone());
+            next
+        })
+    }
+
+    /// 把方面加上 `delta` 的转换；方面缺失或类型不符时状态不变
+    pub fn add<T>(aspect_id: StateAspectId, delta: T) -> Self
+    where
+        T: Add<Output = T> + Sub<Output = T> + Clone + Debug + Send + Sync + 'static,
+    {
+        Self::update(aspect_id, UpdateOp::Add(ArithValue::new(delta)))
+    }
+
+    /// 把方面减去 `delta` 的转换；方面缺失或类型不符时状态不变
+    pub fn sub<T>(aspect_id: StateAspectId, delta: T) -> Self
+    where
+        T: Add<Output = T> + Sub<Output = T> + Clone + Debug + Send + Sync + 'static,
+    {
+        Self::update(aspect_id, UpdateOp::Sub(ArithValue::new(delta)))
+    }
+
+    fn update(aspect_id: StateAspectId, op: UpdateOp) -> Self {
+        let expr = TransferExpr::Update { aspect: aspect_id, op };
+        let eval = expr.clone();
+        Self::declarative(expr, move |s| eval.apply(s))
+    }
+
+    /// 标记转换由具名工厂构造，执行与写集合不变
+    pub(crate) fn named(self, name: &str, args: &[ArgValue]) -> Self {
+        Self {
+            expr: Arc::new(TransferExpr::Named {
+                name: name.to_string(),
+                args: args.to_vec(),
+                inner: self.expr,
+            }),
+            ..self
+        }
+    }
+
+    /// 先执行自身再执行 `next` 的复合转换
+    pub fn then(self, next: Self) -> Self {
+        let expr = TransferExpr::Compose(self.expr.clone(), next.expr.clone());
+        let writes = match (self.writes(), next.writes()) {
+            (Some(a), Some(b)) => Some(sorted(a.iter().chain(b).copied()).into()),
+            _ => None,
+        };
+        Self {
+            func: Arc::new(move |s| next.apply(&self.apply(s))),
+            expr: Arc::new(expr),
+            writes,
+            protected: None,
+        }
+    }
+
+    /// 声明转换函数写入的方面集合
+    /// 声明只用于分析与合并时的校验，不会限制函数的实际行为
+    pub fn with_writes<I>(mut self, aspects: I) -> Self
+    where
+        I: IntoIterator<Item = StateAspectId>,
+    {
+        self.writes = Some(sorted(aspects).into());
+        self
+    }
+
+    /// 转换函数写入的方面集合：声明式构造时自动推导，闭包构造时需 `with_writes` 声明；
+    /// 未知时返回 `None`
+    pub fn writes(&self) -> Option<&[StateAspectId]> {
+        self.writes.as_deref()
+    }
+
+    /// 转换的结构描述
+    pub fn expr(&self) -> &TransferExpr {
+        &self.expr
+    }
+
+    /// 可读描述，方面以 `#<id>` 表示，如 `#1 := Walk; #2 += 1`
+    pub fn describe(&self) -> String {
+        self.describe_with(&AspectRegistry::new())
+    }
+
+    /// 可读描述，方面名称取自注册表
+    pub fn describe_with(&self, registry: &AspectRegistry) -> String {
+        self.expr.describe(registry)
+    }
+
+    /// 应用转换函数到给定的状态；被拒绝的转换返回原状态
     pub fn apply(&self, state: &State) -> State {
-        (self.func)(state)
+        self.try_apply(state).unwrap_or_else(|| state.clone())
+    }
+
+    /// 应用转换函数，结果改写了受保护的方面时返回 `None`
+    ///
+    /// 未改写的方面应保留原取值的 `Arc`，重新插入相同的值同样视为改写
+    pub(crate) fn try_apply(&self, state: &State) -> Option<State> {
+        let next = (self.func)(state);
+        let Some(protected) = &self.protected else {
+            return Some(next);
+        };
+        let touched = protected.iter().any(|id| match (state.get(id), next.get(id)) {
+            (Some(a), Some(b)) => !Arc::ptr_eq(a, b),
+            (None, None) => false,
+            _ => true,
+        });
+        if touched { None } else { Some(next) }
     }
-}
\ No newline at end of file
+
+    /// 构造撤销本次转换的逆转换：对 `prev_state` 应用本转换后再应用逆转换，
+    /// 结果与 `prev_state` 一致
+    ///
+    /// 只有由 `set` / `add` / `sub` / `then` 构造的转换可逆，闭包构造的返回 `None`
+    pub fn invert(&self, prev_state: &State) -> Option<Self> {
+        self.expr.invert(prev_state)
+    }
+
+    /// 包装转换函数：丢弃对 `allowed` 以外方面的写入
+    pub(crate) fn restricted(&self, allowed: Arc<[StateAspectId]>) -> Self {
+        let inner = self.clone();
+        let writes = self
+            .writes
+            .as_ref()
+            .map(|w| w.iter().filter(|id| allowed.contains(id)).copied().collect());
+        Self {
+            func: Arc::new(move |state| restrict_writes(state, &inner.apply(state), &allowed)),
+            expr: Arc::new(TransferExpr::Opaque),
+            writes,
+            protected: None,
+        }
+    }
+
+    /// 包装转换函数：若结果改写了任一受保护的方面，整个转换被拒绝，
+    /// 运行时按未选中处理（不提交、不触发回调与发出事件）
+    pub(crate) fn guarded(&self, protected: Arc<[StateAspectId]>) -> Self {
+        Self {
+            expr: Arc::new(TransferExpr::Opaque),
+            protected: Some(protected),
+            ..self.clone()
+        }
+    }
+}
+
+fn sorted(ids: impl IntoIterator<Item = StateAspectId>) -> Vec<StateAspectId> {
+    let mut ids: Vec<StateAspectId> = ids.into_iter().collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// 以 `prev` 为基础，只采纳 `next` 中 `allowed` 方面的取值（包括移除）
+pub(crate) fn restrict_writes(prev: &State, next: &State, allowed: &[StateAspectId]) -> State {
+    let mut state = prev.clone();
+    for id in allowed {
+        match next.get(id) {
+            Some(value) => {
+                state.insert(*id, value.clone());
+            }
+            None => {
+                state.remove(id);
+            }
+        }
+    }
+    state
+}
+
+/// 转换的结构描述
+#[derive(Clone, Debug)]
+pub enum TransferExpr {
+    /// 把方面设为常量
+    Set { aspect: StateAspectId, value: GuardValue },
+    /// 对方面做算术更新
+    Update { aspect: StateAspectId, op: UpdateOp },
+    /// 依次执行两个转换
+    Compose(Arc<TransferExpr>, Arc<TransferExpr>),
+    /// 闭包构造，结构未知
+    Opaque,
+    /// 由工厂注册表中的具名工厂构造，`inner` 为工厂返回的转换结构
+    Named { name: String, args: Vec<ArgValue>, inner: Arc<TransferExpr> },
+}
+
+impl TransferExpr {
+    /// 写入的方面集合；含闭包部分时为 `None`
+    pub fn writes(&self) -> Option<Vec<StateAspectId>> {
+        match self {
+            Self::Set { aspect, .. } | Self::Update { aspect, .. } => Some(vec![*aspect]),
+            Self::Compose(a, b) => Some(sorted(a.writes()?.into_iter().chain(b.writes()?))),
+            Self::Named { inner, .. } => inner.writes(),
+            Self::Opaque => None,
+        }
+    }
+
+    /// 与另一个转换都写入的方面；任一方写集合未知时为 `None`
+    pub fn conflicts(&self, other: &Self) -> Option<Vec<StateAspectId>> {
+        let theirs = other.writes()?;
+        Some(self.writes()?.into_iter().filter(|id| theirs.contains(id)).collect())
+    }
+
+    /// 转换结束时方面被设为的常量；最后一次写入该方面的不是 `Set` 时为 `None`
+    pub(crate) fn final_set(&self, aspect: StateAspectId) -> Option<&GuardValue> {
+        match self {
+            Self::Set { aspect: a, value } if *a == aspect => Some(value),
+            Self::Compose(a, b) => match b.writes() {
+                Some(writes) if !writes.contains(&aspect) => a.final_set(aspect),
+                _ => b.final_set(aspect),
+            },
+            Self::Named { inner, .. } => inner.final_set(aspect),
+            _ => None,
+        }
+    }
+
+    /// 是否含有闭包构造的部分
+    pub fn is_opaque(&self) -> bool {
+        self.writes().is_none()
+    }
+
+    /// 可读描述，方面名称取自注册表
+    pub fn describe(&self, registry: &AspectRegistry) -> String {
+        match self {
+            Self::Set { aspect, value } => format!("{} := {value}", registry.name(*aspect)),
+            Self::Update { aspect, op: UpdateOp::Add(v) } => format!("{} += {v}", registry.name(*aspect)),
+            Self::Update { aspect, op: UpdateOp::Sub(v) } => format!("{} -= {v}", registry.name(*aspect)),
+            Self::Compose(a, b) => format!("{}; {}", a.describe(registry), b.describe(registry)),
+            Self::Named { inner, .. } => inner.describe(registry),
+            Self::Opaque => "<closure>".to_string(),
+        }
+    }
+
+    fn invert(&self, prev: &State) -> Option<Transfer> {
+        match self {
+            Self::Set { aspect, .. } => {
+                let aspect = *aspect;
+                let restored = prev.get(&aspect).cloned();
+                Some(Transfer {
+                    writes: Some(vec![aspect].into()),
+                    ..Transfer::new(move |s| {
+                        let mut next = s.clone();
+                        match &restored {
+                            Some(value) => next.insert(aspect, value.clone()),
+                            None => next.remove(&aspect),
+                        };
+                        next
+                    })
+                })
+            }
+            Self::Update { aspect, op } => {
+                let op = match op {
+                    UpdateOp::Add(v) => UpdateOp::Sub(v.clone()),
+                    UpdateOp::Sub(v) => UpdateOp::Add(v.clone()),
+                };
+                Some(Transfer::update(*aspect, op))
+            }
+            Self::Compose(a, b) => {
+                let undo_b = b.invert(&a.apply(prev))?;
+                let undo_a = a.invert(prev)?;
+                Some(undo_b.then(undo_a))
+            }
+            Self::Named { inner, .. } => inner.invert(prev),
+            Self::Opaque => None,
+        }
+    }
+
+    /// 执行声明式部分；`Opaque` 原样返回状态
+    fn apply(&self, state: &State) -> State {
+        match self {
+            Self::Set { aspect, value } => {
+                let mut next = state.clone();
+                next.insert(*aspect, value.value().clone());
+                next
+            }
+            Self::Update { aspect, op } => {
+                let updated = state.get(aspect).and_then(|current| match op {
+                    UpdateOp::Add(v) => (v.add)(current, &v.value),
+                    UpdateOp::Sub(v) => (v.sub)(current, &v.value),
+                });
+                let mut next = state.clone();
+                if let Some(updated) = updated {
+                    next.insert(*aspect, updated);
+                }
+                next
+            }
+            Self::Compose(a, b) => b.apply(&a.apply(state)),
+            Self::Named { inner, .. } => inner.apply(state),
+            Self::Opaque => state.clone(),
+        }
+    }
+}
+
+/// 算术更新
+#[derive(Clone, Debug)]
+pub enum UpdateOp {
+    /// 加上给定值
+    Add(ArithValue),
+    /// 减去给定值
+    Sub(ArithValue),
+}
+
+/// 算术更新的操作数，保留原类型的加减法与 `Debug` 显示
+#[derive(Clone)]
+pub struct ArithValue {
+    value: AspectValue,
+    type_id: TypeId,
+    add: fn(&AspectValue, &AspectValue) -> Option<AspectValue>,
+    sub: fn(&AspectValue, &AspectValue) -> Option<AspectValue>,
+    fmt: fn(&AspectValue) -> String,
+}
+
+impl ArithValue {
+    fn new<T>(value: T) -> Self
+    where
+        T: Add<Output = T> + Sub<Output = T> + Clone + Debug + Send + Sync + 'static,
+    {
+        Self {
+            value: Arc::new(value),
+            type_id: TypeId::of::<T>(),
+            add: |a, b| match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+                (Some(a), Some(b)) => Some(Arc::new(a.clone() + b.clone())),
+                _ => None,
+            },
+            sub: |a, b| match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+                (Some(a), Some(b)) => Some(Arc::new(a.clone() - b.clone())),
+                _ => None,
+            },
+            fmt: |v| v.downcast_ref::<T>().map_or_else(String::new, |v| format!("{v:?}")),
+        }
+    }
+
+    /// 操作数的类型
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// 按原类型取出操作数
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.value.downcast_ref::<T>()
+    }
+}
+
+impl fmt::Display for ArithValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&(self.fmt)(&self.value))
+    }
+}
+
+impl fmt::Debug for ArithValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
@@ -1,10 +1,19 @@
 //! 状态转换定义
 
+use std::any::Any;
 use std::sync::Arc;
+use std::time::Duration;
 use super::types::{TransitionId, EventId};
 use super::state_in_range::StateInRange;
 use super::transfer::Transfer;
 use super::runtime::State;
+use super::event::EventTemplate;
+
+/// 转换执行时的回调函数
+pub type OnTranCallback = Arc<dyn Fn(&State, &State) + Send + Sync>;
+
+/// 转换的响应函数：由转换前后的状态生成返回给调用方的值
+pub type ResponseFn = Arc<dyn Fn(&State, &State) -> Box<dyn Any + Send> + Send + Sync>;
 
 /// 状态转换
 /// 定义在特定事件和守卫条件下如何转换状态
@@ -21,5 +30,44 @@ pub struct Transition {
     /// 转换优先级（数值越大优先级越高）
     pub priority: i32,
     /// 转换执行时的回调函数
-    pub on_tran: Option<Arc<dyn Fn(&State, &State) + Send + Sync>>,
-}
\ No newline at end of file
+    pub on_tran: Option<OnTranCallback>,
+    /// 转换成功后发往事件汇的事件
+    pub emits: Vec<EventTemplate>,
+    /// 标签，非空时只有在蓝图启用该标签后转换才生效
+    pub tag: Option<String>,
+    /// 最短停留时间：状态连续处于守卫区域内至少这么久（按 `tick` 推进的时间计）转换才可被选中
+    pub min_dwell: Option<Duration>,
+    /// 后置条件：调试构建下运行时断言转换后的状态位于该区域内，违反时 panic 并报告转换ID与写入的方面
+    pub ensures: Option<StateInRange>,
+    /// 响应函数：转换经 `RuntimeStateMachine::request` 触发时，其结果作为响应返回给调用方
+    pub respond: Option<ResponseFn>,
+}
+
+impl Transition {
+    /// 创建转换，优先级为 0，其余可选项为空
+    pub fn new(id: TransitionId, event_id: EventId, guard: StateInRange, transfer: Transfer) -> Self {
+        Self {
+            id,
+            event_id,
+            guard,
+            transfer,
+            priority: 0,
+            on_tran: None,
+            emits: Vec::new(),
+            tag: None,
+            min_dwell: None,
+            ensures: None,
+            respond: None,
+        }
+    }
+
+    /// 设置响应函数，`f` 的结果经 `RuntimeStateMachine::request` 返回给调用方
+    pub fn with_response<R, F>(mut self, f: F) -> Self
+    where
+        R: Any + Send,
+        F: Fn(&State, &State) -> R + Send + Sync + 'static,
+    {
+        self.respond = Some(Arc::new(move |prev, next| Box::new(f(prev, next)) as Box<dyn Any + Send>));
+        self
+    }
+}
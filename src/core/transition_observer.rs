@@ -0,0 +1,59 @@
+//! 转换观察者
+
+use std::sync::Arc;
+use super::types::TransitionId;
+use super::runtime::State;
+
+/// 转换过滤函数，返回 `true` 表示观察该转换
+pub type TransitionFilter = Arc<dyn Fn(TransitionId) -> bool + Send + Sync>;
+
+/// 转换回调函数，参数为（转换ID，转换前状态，转换后状态）
+pub type TransitionCallback = Arc<dyn Fn(TransitionId, &State, &State) + Send + Sync>;
+
+/// 转换观察者
+/// 任何满足过滤条件的转换发生后调用回调，
+/// 使成就、统计这类横切逻辑不必挂在每个转换的 `on_tran` 上。
+/// 回调在该转换的 `on_tran` 之后、进入回调之前执行
+#[derive(Clone)]
+pub struct TransitionObserver {
+    /// 过滤条件
+    pub filter: TransitionFilter,
+    /// 回调函数
+    pub callback: TransitionCallback,
+}
+
+impl TransitionObserver {
+    /// 创建转换观察者
+    pub fn new<P, F>(filter: P, callback: F) -> Self
+    where
+        P: Fn(TransitionId) -> bool + Send + Sync + 'static,
+        F: Fn(TransitionId, &State, &State) + Send + Sync + 'static,
+    {
+        Self {
+            filter: Arc::new(filter),
+            callback: Arc::new(callback),
+        }
+    }
+
+    /// 观察全部转换
+    pub fn all<F>(callback: F) -> Self
+    where
+        F: Fn(TransitionId, &State, &State) + Send + Sync + 'static,
+    {
+        Self::new(|_| true, callback)
+    }
+
+    /// 只观察给定的转换
+    pub fn only<F>(ids: impl IntoIterator<Item = TransitionId>, callback: F) -> Self
+    where
+        F: Fn(TransitionId, &State, &State) + Send + Sync + 'static,
+    {
+        let ids: Vec<TransitionId> = ids.into_iter().collect();
+        Self::new(move |id| ids.contains(&id), callback)
+    }
+
+    /// 是否观察转换 `id`
+    pub fn matches(&self, id: TransitionId) -> bool {
+        (self.filter)(id)
+    }
+}
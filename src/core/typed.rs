@@ -0,0 +1,194 @@
+//! 类型化蓝图
+//! 在动态 `Any` 层之上，为静态已知方面集合的用户提供编译期类型检查
+
+use std::any::TypeId;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use super::types::{StateAspectId, EventId, TransitionId, ObserverId};
+use super::state_aspect::StateAspect;
+use super::state_in_range::StateInRange;
+use super::transfer::Transfer;
+use super::event::EventDef;
+use super::transition::Transition;
+use super::state_observer::StateObserver;
+use super::blueprint::StateMachineBlueprint;
+use super::runtime::State;
+
+/// 方面元组
+/// 由若干方面取值类型组成的元组 `(A1, A2, ...)`，按位置与方面ID一一对应
+pub trait AspectTuple: Clone + Send + Sync + Sized + 'static {
+    /// 方面ID数组，长度与元组一致
+    type Ids: AsRef<[StateAspectId]> + Copy + Send + Sync + 'static;
+
+    /// 各位置的取值类型
+    fn type_ids() -> Vec<TypeId>;
+
+    /// 从动态状态读取元组；任一方面缺失或类型不符时返回 `None`
+    fn read(state: &State, ids: &Self::Ids) -> Option<Self>;
+
+    /// 把元组写回动态状态
+    fn write(self, state: &mut State, ids: &Self::Ids);
+}
+
+macro_rules! impl_aspect_tuple {
+    ($n:literal; $($t:ident : $i:tt),+) => {
+        impl<$($t),+> AspectTuple for ($($t,)+)
+        where
+            $($t: Clone + Send + Sync + 'static),+
+        {
+            type Ids = [StateAspectId; $n];
+
+            fn type_ids() -> Vec<TypeId> {
+                vec![$(TypeId::of::<$t>()),+]
+            }
+
+            fn read(state: &State, ids: &Self::Ids) -> Option<Self> {
+                Some(($(state.get(&ids[$i])?.downcast_ref::<$t>()?.clone(),)+))
+            }
+
+            fn write(self, state: &mut State, ids: &Self::Ids) {
+                $(state.insert(ids[$i], Arc::new(self.$i));)+
+            }
+        }
+    };
+}
+
+impl_aspect_tuple!(1; A0: 0);
+impl_aspect_tuple!(2; A0: 0, A1: 1);
+impl_aspect_tuple!(3; A0: 0, A1: 1, A2: 2);
+impl_aspect_tuple!(4; A0: 0, A1: 1, A2: 2, A3: 3);
+impl_aspect_tuple!(5; A0: 0, A1: 1, A2: 2, A3: 3, A4: 4);
+impl_aspect_tuple!(6; A0: 0, A1: 1, A2: 2, A3: 3, A4: 4, A5: 5);
+
+/// 类型化蓝图
+/// 方面按元组位置做类型级索引：守卫直接拿到 `&(A1, A2, ...)`，
+/// 转换返回同类型元组，类型不匹配在编译期即报错。
+/// 最终通过 `build` 降级为普通的 `StateMachineBlueprint`
+pub struct TypedBlueprint<T: AspectTuple> {
+    ids: T::Ids,
+    blueprint: StateMachineBlueprint,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: AspectTuple> TypedBlueprint<T> {
+    /// 创建一个类型化蓝图，并按元组位置注册方面
+    pub fn new(ids: T::Ids) -> Self {
+        let mut blueprint = StateMachineBlueprint::new();
+        for (id, value_type_id) in ids.as_ref().iter().zip(T::type_ids()) {
+            blueprint.aspects_mut().insert(*id, StateAspect::new(*id, value_type_id));
+        }
+        Self {
+            ids,
+            blueprint,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 方面ID
+    pub fn ids(&self) -> &T::Ids {
+        &self.ids
+    }
+
+    /// 把类型化谓词转换为状态谓词
+    pub fn guard<F>(&self, f: F) -> StateInRange
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        let ids = self.ids;
+        StateInRange::new(move |s| T::read(s, &ids).is_some_and(|v| f(&v)))
+    }
+
+    /// 把类型化转换函数转换为状态转换函数
+    pub fn transfer<F>(&self, f: F) -> Transfer
+    where
+        F: Fn(&T) -> T + Send + Sync + 'static,
+    {
+        let ids = self.ids;
+        Transfer::new(move |s| {
+            let mut next = s.clone();
+            if let Some(v) = T::read(s, &ids) {
+                f(&v).write(&mut next, &ids);
+            }
+            next
+        })
+    }
+
+    /// 声明一个payload类型为 `P` 的事件
+    pub fn add_event<P: 'static>(&mut self, id: EventId) -> &mut Self {
+        self.blueprint.events_mut().insert(id, EventDef::of::<P>(id));
+        self
+    }
+
+    /// 添加一个转换
+    pub fn add_transition<G, F>(
+        &mut self,
+        id: TransitionId,
+        event_id: EventId,
+        guard: G,
+        transfer: F,
+        priority: i32,
+    ) -> &mut Self
+    where
+        G: Fn(&T) -> bool + Send + Sync + 'static,
+        F: Fn(&T) -> T + Send + Sync + 'static,
+    {
+        let transition = Transition {
+            priority,
+            ..Transition::new(id, event_id, self.guard(guard), self.transfer(transfer))
+        };
+        self.blueprint.transitions_mut().push(transition);
+        self
+    }
+
+    /// 添加一个观察者，回调直接拿到类型化的状态
+    pub fn add_observer<R, E, X>(
+        &mut self,
+        id: ObserverId,
+        region: R,
+        on_enter: Option<E>,
+        on_exit: Option<X>,
+    ) -> &mut Self
+    where
+        R: Fn(&T) -> bool + Send + Sync + 'static,
+        E: Fn(&T) + Send + Sync + 'static,
+        X: Fn(&T) + Send + Sync + 'static,
+    {
+        let ids = self.ids;
+        let observer = StateObserver {
+            on_enter: on_enter.map(|f| {
+                Arc::new(move |s: &State| {
+                    if let Some(v) = T::read(s, &ids) {
+                        f(&v);
+                    }
+                }) as _
+            }),
+            on_exit: on_exit.map(|f| {
+                Arc::new(move |s: &State| {
+                    if let Some(v) = T::read(s, &ids) {
+                        f(&v);
+                    }
+                }) as _
+            }),
+            ..StateObserver::new(id, self.guard(region))
+        };
+        self.blueprint.observers_mut().push(observer);
+        self
+    }
+
+    /// 由类型化取值构造初始状态
+    pub fn initial_state(&self, value: T) -> State {
+        let mut state = State::new();
+        value.write(&mut state, &self.ids);
+        state
+    }
+
+    /// 从动态状态读取类型化取值
+    pub fn read(&self, state: &State) -> Option<T> {
+        T::read(state, &self.ids)
+    }
+
+    /// 降级为动态蓝图
+    pub fn build(self) -> StateMachineBlueprint {
+        self.blueprint
+    }
+}
@@ -10,4 +10,9 @@ pub type EventId = u64;
 pub type TransitionId = u64;
 
 /// 观察者ID
-pub type ObserverId = u64;
\ No newline at end of file
+pub type ObserverId = u64;
+/// 状态机实例ID
+pub type MachineId = u64;
+
+/// 关联ID：把属于同一业务流程（订单、工单等）的事件路由到同一个状态机实例
+pub type CorrelationId = String;
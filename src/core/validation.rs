@@ -0,0 +1,19 @@
+//! 方面取值校验策略
+
+use std::sync::Arc;
+use super::runtime::AspectValue;
+
+/// 把非法取值修正为合法取值的函数，返回 `None` 表示无法修正
+pub type AspectClamper = Arc<dyn Fn(&AspectValue) -> Option<AspectValue> + Send + Sync>;
+
+/// 转换写入的取值未通过方面校验时的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValidationPolicy {
+    /// 用注册的修正函数修正；没有修正函数或修正后仍不合法时按 `Error` 处理
+    Clamp,
+    /// 放弃本次转换，状态不变、不触发回调
+    Reject,
+    /// 放弃本次转换，并记录一个 `StateZenError::InvalidAspectValue`
+    #[default]
+    Error,
+}
@@ -0,0 +1,108 @@
+//! 虚拟时间驱动：与墙钟解耦地推进模拟
+
+use std::time::Duration;
+use super::state_in_range::StateInRange;
+use super::runtime::RuntimeStateMachine;
+
+/// 虚拟时间驱动
+///
+/// 以不超过 `step` 的步长反复调用 `tick`，并在周期事件的触发时刻切分步长，
+/// 使连续转换恰好积分到事件触发的那一刻。
+/// 用于快进游戏模拟和长时间的稳定性测试，推进多久完全由调用方决定
+pub struct VirtualTimeDriver {
+    runtime: RuntimeStateMachine,
+    step: Duration,
+    speed: f64,
+    now: Duration,
+}
+
+impl VirtualTimeDriver {
+    /// 以最大步长 `step` 驱动状态机
+    ///
+    /// # Panics
+    ///
+    /// `step` 为零时 panic
+    pub fn new(runtime: RuntimeStateMachine, step: Duration) -> Self {
+        assert!(!step.is_zero(), "虚拟时间的步长必须大于零");
+        Self {
+            runtime,
+            step,
+            speed: 1.0,
+            now: Duration::ZERO,
+        }
+    }
+
+    /// 设置 `advance_real` 的倍速，如 `2.0` 表示真实时间的两倍
+    ///
+    /// # Panics
+    ///
+    /// `speed` 为负数或非有限值时 panic
+    pub fn set_speed(&mut self, speed: f64) {
+        assert!(speed.is_finite() && speed >= 0.0, "倍速必须是非负的有限值");
+        self.speed = speed;
+    }
+
+    /// 当前倍速
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// 自创建以来经过的虚拟时间
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// 推进虚拟时间 `dt`，期间到期的周期事件与连续转换全部生效；返回调用 `tick` 的次数
+    pub fn advance(&mut self, dt: Duration) -> usize {
+        let mut remaining = dt;
+        let mut ticks = 0;
+        while !remaining.is_zero() {
+            let mut step = self.step.min(remaining);
+            if let Some(next) = self.runtime.next_recurring_in().filter(|d| !d.is_zero()) {
+                step = step.min(next);
+            }
+            self.runtime.tick(step);
+            self.now += step;
+            remaining -= step;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// 按倍速推进：真实时间 `real_dt` 对应 `real_dt * speed` 的虚拟时间
+    pub fn advance_real(&mut self, real_dt: Duration) -> usize {
+        self.advance(real_dt.mul_f64(self.speed))
+    }
+
+    /// 推进直到状态进入 `region` 或已推进 `limit`，返回进入时已推进的虚拟时间
+    /// 以步长为粒度检查，开始时已在区域内返回零
+    pub fn advance_until(&mut self, region: &StateInRange, limit: Duration) -> Option<Duration> {
+        let mut elapsed = Duration::ZERO;
+        loop {
+            if region.contains(&self.runtime.current_state) {
+                return Some(elapsed);
+            }
+            if elapsed >= limit {
+                return None;
+            }
+            let before = self.now;
+            self.advance(self.step.min(limit - elapsed));
+            elapsed += self.now - before;
+        }
+    }
+
+    /// 被驱动的状态机
+    pub fn runtime(&self) -> &RuntimeStateMachine {
+        &self.runtime
+    }
+
+    /// 被驱动的状态机（可变），用于分发事件等
+    pub fn runtime_mut(&mut self) -> &mut RuntimeStateMachine {
+        &mut self.runtime
+    }
+
+    /// 取回状态机
+    pub fn into_inner(self) -> RuntimeStateMachine {
+        self.runtime
+    }
+}
@@ -0,0 +1,81 @@
+//! 等待状态机进入区域的 Future
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use super::types::{StateAspectId, EventId};
+use super::state_in_range::StateInRange;
+use super::runtime::State;
+use super::drive::StateSnapshot;
+
+#[derive(Default)]
+struct Slot {
+    snapshot: Option<StateSnapshot>,
+    waker: Option<Waker>,
+}
+
+/// 运行时登记的一个等待者
+pub(crate) struct Waiter {
+    region: StateInRange,
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl Waiter {
+    /// 提交后检查是否进入了区域，进入时填入快照并唤醒 Future。
+    /// 返回 `false` 表示等待者已完成或 Future 已被丢弃，应从运行时移除
+    pub(crate) fn notify(
+        &self,
+        event_id: Option<EventId>,
+        prev: &State,
+        next: &State,
+        versions: &HashMap<StateAspectId, u64>,
+    ) -> bool {
+        if Arc::strong_count(&self.slot) == 1 {
+            return false;
+        }
+        if self.region.contains(prev) || !self.region.contains(next) {
+            return true;
+        }
+        let waker = {
+            let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+            slot.snapshot = Some(StateSnapshot {
+                event_id,
+                state: next.clone(),
+                versions: next.keys().map(|id| (*id, versions.get(id).copied().unwrap_or(0))).collect(),
+            });
+            slot.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        false
+    }
+}
+
+/// `RuntimeStateMachine::wait_for` 返回的 Future
+struct RegionEntry {
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl Future for RegionEntry {
+    type Output = StateSnapshot;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<StateSnapshot> {
+        let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        match slot.snapshot.take() {
+            Some(snapshot) => Poll::Ready(snapshot),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// 创建一对等待者与 Future
+pub(crate) fn wait_for(region: StateInRange) -> (Waiter, impl Future<Output = StateSnapshot> + Send + 'static) {
+    let slot = Arc::new(Mutex::new(Slot::default()));
+    (Waiter { region, slot: slot.clone() }, RegionEntry { slot })
+}
@@ -0,0 +1,139 @@
+//! 看门狗：停留超时升级
+
+use std::sync::Arc;
+use std::time::Duration;
+use super::types::EventId;
+use super::state_in_range::StateInRange;
+use super::runtime::State;
+
+/// 看门狗回调：（当前状态，已停留的时间）
+pub type WatchdogCallback = Arc<dyn Fn(&State, Duration) + Send + Sync>;
+
+/// 看门狗监视的条件
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchdogMode {
+    /// 停留在区域内过久
+    Inside,
+    /// 停留在区域外过久
+    Outside,
+}
+
+/// 超时后的升级方式
+#[derive(Clone)]
+pub enum Escalation {
+    /// 经 `handle_event` 分发一个无负载的事件
+    Event(EventId),
+    /// 调用回调
+    Callback(WatchdogCallback),
+}
+
+/// 看门狗
+///
+/// 状态连续满足条件（按 `tick` 推进的时间计）达到 `timeout` 时升级一次，
+/// 条件不再满足后重新计时；用于发现卡住的工作流与挂起的 AI 状态
+#[derive(Clone)]
+pub struct Watchdog {
+    /// 监视的区域
+    pub region: StateInRange,
+    /// 监视区域内还是区域外
+    pub mode: WatchdogMode,
+    /// 超时时间
+    pub timeout: Duration,
+    /// 升级方式
+    pub escalation: Escalation,
+}
+
+impl Watchdog {
+    /// 在区域内停留超过 `timeout` 时升级
+    pub fn inside(region: StateInRange, timeout: Duration, escalation: Escalation) -> Self {
+        Self {
+            region,
+            mode: WatchdogMode::Inside,
+            timeout,
+            escalation,
+        }
+    }
+
+    /// 在区域外停留超过 `timeout` 时升级
+    pub fn outside(region: StateInRange, timeout: Duration, escalation: Escalation) -> Self {
+        Self {
+            region,
+            mode: WatchdogMode::Outside,
+            timeout,
+            escalation,
+        }
+    }
+
+    fn holds(&self, state: &State) -> bool {
+        self.region.contains(state) == (self.mode == WatchdogMode::Inside)
+    }
+}
+
+/// 看门狗句柄，用于移除
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WatchdogHandle(u64);
+
+struct Entry {
+    handle: WatchdogHandle,
+    watchdog: Watchdog,
+    elapsed: Duration,
+    escalated: bool,
+}
+
+/// 运行时内部的看门狗集合
+#[derive(Default)]
+pub(crate) struct Watchdogs {
+    next_handle: u64,
+    entries: Vec<Entry>,
+}
+
+impl Watchdogs {
+    pub(crate) fn add(&mut self, watchdog: Watchdog) -> WatchdogHandle {
+        let handle = WatchdogHandle(self.next_handle);
+        self.next_handle += 1;
+        self.entries.push(Entry {
+            handle,
+            watchdog,
+            elapsed: Duration::ZERO,
+            escalated: false,
+        });
+        handle
+    }
+
+    pub(crate) fn remove(&mut self, handle: WatchdogHandle) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|e| e.handle != handle);
+        self.entries.len() != before
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 状态变更后，条件不再满足的看门狗重新计时
+    pub(crate) fn refresh(&mut self, state: &State) {
+        for entry in &mut self.entries {
+            if !entry.watchdog.holds(state) {
+                entry.elapsed = Duration::ZERO;
+                entry.escalated = false;
+            }
+        }
+    }
+
+    /// 推进时间 `dt`，返回本次超时的升级及各自已停留的时间
+    pub(crate) fn advance(&mut self, state: &State, dt: Duration) -> Vec<(Escalation, Duration)> {
+        self.refresh(state);
+        let mut due = Vec::new();
+        for entry in &mut self.entries {
+            if !entry.watchdog.holds(state) {
+                continue;
+            }
+            entry.elapsed += dt;
+            if !entry.escalated && entry.elapsed >= entry.watchdog.timeout {
+                entry.escalated = true;
+                due.push((entry.watchdog.escalation.clone(), entry.elapsed));
+            }
+        }
+        due
+    }
+}
@@ -5,7 +5,7 @@ use std::any::TypeId;
 use std::sync::Arc;
 use crate::core::{
     StateAspect, StateInRange, Transfer, EventDef, Transition, StateObserver,
-    StateMachineBlueprint, RuntimeStateMachine, State,
+    StateMachineBlueprint, RuntimeStateMachine, MachineTemplate, State,
 };
 
 /// 玩家动作枚举
@@ -17,29 +17,27 @@ pub enum Action {
 
 /// 创建玩家移动状态机示例
 pub fn create_player_movement_example() -> RuntimeStateMachine {
-    // 1. 定义 aspects
-    let action_aspect = StateAspect {
-        id: 1,
-        value_type_id: TypeId::of::<Action>(),
-    };
+    // 1. 定义 aspects，默认处于 Idle
+    let action_aspect = StateAspect::of::<Action>(1).with_default(Action::Idle);
 
     // 2. 定义事件
     let press_w_event = EventDef {
         id: 100,
         payload_type_id: TypeId::of::<()>(), // 无 payload
+        transformers: Vec::new(),
     };
 
     // 3. 定义谓词
     let is_idle = StateInRange::new(|s| {
         s.get(&1)
             .and_then(|v| v.downcast_ref::<Action>())
-            .map_or(false, |a| *a == Action::Idle)
+            .is_some_and(|a| *a == Action::Idle)
     });
 
     let is_walking = StateInRange::new(|s| {
         s.get(&1)
             .and_then(|v| v.downcast_ref::<Action>())
-            .map_or(false, |a| *a == Action::Walk)
+            .is_some_and(|a| *a == Action::Walk)
     });
 
     // 4. 定义 transfer
@@ -59,6 +57,11 @@ pub fn create_player_movement_example() -> RuntimeStateMachine {
         on_tran: Some(Arc::new(|_prev, _next| {
             println!("OnTran: Playing footstep sound");
         })),
+        emits: Vec::new(),
+        tag: None,
+        min_dwell: None,
+        ensures: None,
+        respond: None,
     };
 
     // 6. 定义 observer
@@ -71,24 +74,21 @@ pub fn create_player_movement_example() -> RuntimeStateMachine {
         on_exit: Some(Arc::new(|_state| {
             println!("OnExit: Stop walking animation");
         })),
+        priority: 0,
+        on_enter_consume: None,
+        active_when: None,
+        tag: None,
     };
 
     // 7. 构建蓝图
     let mut blueprint = StateMachineBlueprint::new();
-    blueprint.aspects.insert(action_aspect.id, action_aspect);
-    blueprint.events.insert(press_w_event.id, press_w_event);
-    blueprint.transitions.push(transition);
-    blueprint.observers.push(walking_observer);
-
-    // 8. 初始状态
-    let initial_state: State = {
-        let mut s = State::new();
-        s.insert(1, Arc::new(Action::Idle));
-        s
-    };
+    blueprint.add_aspect(action_aspect).expect("方面ID唯一");
+    blueprint.add_event(press_w_event).expect("事件ID唯一");
+    blueprint.add_transition(transition).expect("转换ID唯一且事件已声明");
+    blueprint.add_observer(walking_observer).expect("观察者ID唯一");
 
-    // 9. 创建运行时状态机
-    RuntimeStateMachine::new(blueprint, initial_state)
+    // 8. 用方面的默认值创建运行时状态机
+    MachineTemplate::new(blueprint).instantiate(State::new()).expect("初始状态与蓝图一致")
 }
 
 /// 运行玩家移动示例
@@ -0,0 +1,426 @@
+//! C 接口
+//!
+//! 供 C/C++ 编写的引擎嵌入状态机：由清单 JSON 创建状态机，注册 C 回调守卫与回调，
+//! 分发事件，按方面ID以带标签的联合体读取取值。
+//! 构建动态库：`cargo rustc --release --features ffi --crate-type cdylib`。
+//!
+//! 约定：所有指针参数由调用方保证有效；`sz_*_new` 返回的对象必须用对应的 `sz_*_free` 释放；
+//! 失败时返回空指针或 `false`，错误信息由 `sz_last_error` 取得。
+//! 恐慌不会越过 C 边界：分发与创建函数、以及包装 C 回调的闭包捕获恐慌并记为错误。
+//! 回调的 `user_data` 会在任意调用分发函数的线程上被使用，线程安全由调用方负责
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::sync::Arc;
+use crate::core::types::{StateAspectId, EventId, TransitionId, ObserverId};
+use crate::core::state_in_range::StateInRange;
+use crate::core::transition_observer::TransitionObserver;
+use crate::core::runtime::{RuntimeStateMachine, State, AspectValue};
+use crate::core::StateMachineBlueprint;
+use crate::loader::manifest::BlueprintManifest;
+use crate::loader::Registry;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+/// 执行 `f`，恐慌时记录错误并返回 `fallback`
+fn catch_panic<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "未知恐慌".to_string());
+        set_error(format!("恐慌：{message}"));
+        fallback
+    })
+}
+
+/// 回调的用户数据，由调用方保证可跨线程使用
+#[derive(Clone, Copy)]
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    // 通过方法取指针，闭包捕获整个 `UserData` 而不是裸指针字段
+    fn get(self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// 回调中看到的状态（不透明）
+#[repr(transparent)]
+pub struct SzState(State);
+
+impl SzState {
+    fn wrap(state: &State) -> *const SzState {
+        (state as *const State).cast()
+    }
+}
+
+/// 工厂注册表（不透明）
+pub struct SzRegistry(Registry);
+
+/// 状态机（不透明）
+pub struct SzMachine(RuntimeStateMachine);
+
+/// 守卫回调：返回状态是否满足条件
+pub type SzGuardFn = extern "C" fn(state: *const SzState, user_data: *mut c_void) -> bool;
+
+/// 观察者回调
+pub type SzStateFn = extern "C" fn(state: *const SzState, user_data: *mut c_void);
+
+/// 转换回调
+pub type SzTransitionFn =
+    extern "C" fn(transition: TransitionId, prev: *const SzState, next: *const SzState, user_data: *mut c_void);
+
+/// 取值的类型标签，`SzValue::tag` 中以 `u32` 存放
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SzValueTag {
+    I32 = 0,
+    I64 = 1,
+    U32 = 2,
+    U64 = 3,
+    F32 = 4,
+    F64 = 5,
+    Bool = 6,
+    /// UTF-8 字符串，借用自状态机，下次分发事件前有效
+    String = 7,
+    /// 不支持的取值类型
+    Unsupported = 8,
+}
+
+impl SzValueTag {
+    /// 由 C 端传入的数值解析标签，未知的数值返回 `None`
+    pub fn from_raw(tag: u32) -> Option<Self> {
+        Some(match tag {
+            0 => Self::I32,
+            1 => Self::I64,
+            2 => Self::U32,
+            3 => Self::U64,
+            4 => Self::F32,
+            5 => Self::F64,
+            6 => Self::Bool,
+            7 => Self::String,
+            8 => Self::Unsupported,
+            _ => return None,
+        })
+    }
+}
+
+/// 借用的 UTF-8 字符串，不以 `\0` 结尾
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SzStr {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+/// 取值
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union SzValueData {
+    pub i32_: i32,
+    pub i64_: i64,
+    pub u32_: u32,
+    pub u64_: u64,
+    pub f32_: f32,
+    pub f64_: f64,
+    pub bool_: bool,
+    pub string: SzStr,
+}
+
+/// 带标签的取值
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SzValue {
+    /// `SzValueTag` 的数值，读取联合体前先校验
+    pub tag: u32,
+    pub data: SzValueData,
+}
+
+/// 初始状态中一个方面的取值
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SzAspectValue {
+    pub aspect: StateAspectId,
+    pub value: SzValue,
+}
+
+impl SzValue {
+    fn unsupported() -> Self {
+        Self { tag: SzValueTag::Unsupported as u32, data: SzValueData { u64_: 0 } }
+    }
+
+    fn from_aspect(value: &AspectValue) -> Self {
+        macro_rules! scalar {
+            ($($t:ty => $tag:ident . $field:ident),*) => {
+                $(if let Some(v) = value.downcast_ref::<$t>() {
+                    return Self { tag: SzValueTag::$tag as u32, data: SzValueData { $field: *v } };
+                })*
+            };
+        }
+        scalar!(i32 => I32.i32_, i64 => I64.i64_, u32 => U32.u32_, u64 => U64.u64_,
+                f32 => F32.f32_, f64 => F64.f64_, bool => Bool.bool_);
+        match value.downcast_ref::<String>() {
+            Some(s) => Self {
+                tag: SzValueTag::String as u32,
+                data: SzValueData { string: SzStr { ptr: s.as_ptr(), len: s.len() } },
+            },
+            None => Self::unsupported(),
+        }
+    }
+
+    /// 标签未知、不受支持或字符串无效时返回 `None`
+    ///
+    /// # Safety
+    /// 联合体中与标签对应的字段须已初始化，字符串取值的指针须指向 `len` 字节的有效内存
+    unsafe fn to_aspect(self) -> Option<AspectValue> {
+        let tag = SzValueTag::from_raw(self.tag)?;
+        // SAFETY: 标签已校验，决定了联合体中有效的字段
+        unsafe {
+            Some(match tag {
+                SzValueTag::I32 => Arc::new(self.data.i32_),
+                SzValueTag::I64 => Arc::new(self.data.i64_),
+                SzValueTag::U32 => Arc::new(self.data.u32_),
+                SzValueTag::U64 => Arc::new(self.data.u64_),
+                SzValueTag::F32 => Arc::new(self.data.f32_),
+                SzValueTag::F64 => Arc::new(self.data.f64_),
+                SzValueTag::Bool => Arc::new(self.data.bool_),
+                SzValueTag::String => {
+                    let SzStr { ptr, len } = self.data.string;
+                    if ptr.is_null() && len > 0 {
+                        return None;
+                    }
+                    let bytes = if len == 0 { &[][..] } else { std::slice::from_raw_parts(ptr, len) };
+                    Arc::new(std::str::from_utf8(bytes).ok()?.to_string())
+                }
+                SzValueTag::Unsupported => return None,
+            })
+        }
+    }
+}
+
+/// 最近一次失败的错误信息，当前线程内有效至下一次失败
+#[unsafe(no_mangle)]
+pub extern "C" fn sz_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// 创建带内置工厂的注册表
+#[unsafe(no_mangle)]
+pub extern "C" fn sz_registry_new() -> *mut SzRegistry {
+    Box::into_raw(Box::new(SzRegistry(Registry::with_builtins())))
+}
+
+/// 释放注册表
+///
+/// # Safety
+/// `registry` 须为 `sz_registry_new` 返回且未释放的指针，或为空
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_registry_free(registry: *mut SzRegistry) {
+    if !registry.is_null() {
+        // SAFETY: 由调用方保证
+        drop(unsafe { Box::from_raw(registry) });
+    }
+}
+
+/// 注册无参数的 C 守卫，清单中以 `{"call": {"name": ..., "args": []}}` 引用
+///
+/// # Safety
+/// `registry` 须有效，`name` 须为以 `\0` 结尾的 UTF-8 字符串
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_registry_register_guard(
+    registry: *mut SzRegistry,
+    name: *const c_char,
+    guard: Option<SzGuardFn>,
+    user_data: *mut c_void,
+) -> bool {
+    let Some(guard) = guard else {
+        set_error("守卫回调为空");
+        return false;
+    };
+    // SAFETY: 由调用方保证
+    let (registry, name) = unsafe { (&mut (*registry).0, CStr::from_ptr(name)) };
+    let Ok(name) = name.to_str() else {
+        set_error("守卫名称不是有效的 UTF-8");
+        return false;
+    };
+    let user_data = UserData(user_data);
+    registry.register_guard(name, move |()| {
+        StateInRange::new(move |state| catch_panic(false, || guard(SzState::wrap(state), user_data.get())))
+    });
+    true
+}
+
+/// 由清单 JSON 与初始取值创建状态机，失败（包括工厂恐慌）时返回空指针
+///
+/// # Safety
+/// `manifest_json` 须为以 `\0` 结尾的字符串，`registry` 须有效，
+/// `initial` 须指向 `count` 个有效元素（`count` 为 0 时可为空）
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_machine_new(
+    manifest_json: *const c_char,
+    registry: *const SzRegistry,
+    initial: *const SzAspectValue,
+    count: usize,
+) -> *mut SzMachine {
+    // SAFETY: 由调用方保证
+    catch_panic(std::ptr::null_mut(), || unsafe { machine_new(manifest_json, registry, initial, count) })
+}
+
+/// # Safety
+/// 同 `sz_machine_new`
+unsafe fn machine_new(
+    manifest_json: *const c_char,
+    registry: *const SzRegistry,
+    initial: *const SzAspectValue,
+    count: usize,
+) -> *mut SzMachine {
+    // SAFETY: 由调用方保证
+    let (json, registry) = unsafe { (CStr::from_ptr(manifest_json), &(*registry).0) };
+    let Ok(json) = json.to_str() else {
+        set_error("清单不是有效的 UTF-8");
+        return std::ptr::null_mut();
+    };
+    let blueprint = match BlueprintManifest::from_json(json)
+        .and_then(|manifest| StateMachineBlueprint::from_manifest(&manifest, registry))
+    {
+        Ok(blueprint) => blueprint,
+        Err(e) => {
+            set_error(e);
+            return std::ptr::null_mut();
+        }
+    };
+    let initial = if count == 0 {
+        &[][..]
+    } else {
+        // SAFETY: 由调用方保证
+        unsafe { std::slice::from_raw_parts(initial, count) }
+    };
+    let mut state = State::new();
+    for entry in initial {
+        // SAFETY: 由调用方保证
+        let Some(value) = (unsafe { entry.value.to_aspect() }) else {
+            set_error(format!("方面 {} 的初始取值无效", entry.aspect));
+            return std::ptr::null_mut();
+        };
+        state.insert(entry.aspect, value);
+    }
+    if let Err(e) = blueprint.validate_state(&state) {
+        set_error(e);
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(SzMachine(RuntimeStateMachine::new(blueprint, state))))
+}
+
+/// 释放状态机
+///
+/// # Safety
+/// `machine` 须为 `sz_machine_new` 返回且未释放的指针，或为空
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_machine_free(machine: *mut SzMachine) {
+    if !machine.is_null() {
+        // SAFETY: 由调用方保证
+        drop(unsafe { Box::from_raw(machine) });
+    }
+}
+
+/// 为观察者设置进入与退出回调（可为空），观察者不存在时返回 `false`
+///
+/// # Safety
+/// `machine` 须有效
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_machine_on_observer(
+    machine: *mut SzMachine,
+    observer: ObserverId,
+    on_enter: Option<SzStateFn>,
+    on_exit: Option<SzStateFn>,
+    user_data: *mut c_void,
+) -> bool {
+    // SAFETY: 由调用方保证
+    let runtime = unsafe { &mut (*machine).0 };
+    let Some(target) = runtime.blueprint.observers_mut().iter_mut().find(|o| o.id == observer) else {
+        set_error(format!("观察者 {observer} 不存在"));
+        return false;
+    };
+    let user_data = UserData(user_data);
+    let wrap = |f: SzStateFn| Arc::new(move |s: &State| catch_panic((), || f(SzState::wrap(s), user_data.get()))) as _;
+    target.on_enter = on_enter.map(wrap);
+    target.on_exit = on_exit.map(wrap);
+    true
+}
+
+/// 添加转换回调，每次执行转换后调用；回调为空时返回 `false`
+///
+/// # Safety
+/// `machine` 须有效
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_machine_on_transition(
+    machine: *mut SzMachine,
+    callback: Option<SzTransitionFn>,
+    user_data: *mut c_void,
+) -> bool {
+    let Some(callback) = callback else {
+        set_error("转换回调为空");
+        return false;
+    };
+    // SAFETY: 由调用方保证
+    let runtime = unsafe { &mut (*machine).0 };
+    let user_data = UserData(user_data);
+    runtime.add_transition_observer(TransitionObserver::new(|_| true, move |id, prev, next| {
+        catch_panic((), || callback(id, SzState::wrap(prev), SzState::wrap(next), user_data.get()))
+    }));
+    true
+}
+
+/// 分发一个无负载的事件，分发中发生恐慌时返回 `false`
+///
+/// # Safety
+/// `machine` 须有效，且不能在该状态机的回调中调用
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_machine_handle_event(machine: *mut SzMachine, event: EventId) -> bool {
+    // SAFETY: 由调用方保证
+    let runtime = unsafe { &mut (*machine).0 };
+    catch_panic(false, || {
+        runtime.handle_event(event, None);
+        true
+    })
+}
+
+/// 读取当前状态中方面的取值，方面不存在时返回 `false`
+///
+/// # Safety
+/// `machine` 与 `out` 须有效
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_machine_get(machine: *const SzMachine, aspect: StateAspectId, out: *mut SzValue) -> bool {
+    // SAFETY: 由调用方保证
+    unsafe { sz_state_get(SzState::wrap(&(*machine).0.current_state), aspect, out) }
+}
+
+/// 在回调中读取状态中方面的取值，方面不存在时返回 `false`
+///
+/// # Safety
+/// `state` 须为回调收到的指针（仅在回调期间有效），`out` 须有效
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sz_state_get(state: *const SzState, aspect: StateAspectId, out: *mut SzValue) -> bool {
+    // SAFETY: 由调用方保证
+    let state = unsafe { &(*state).0 };
+    match state.get(&aspect) {
+        Some(value) => {
+            // SAFETY: 由调用方保证
+            unsafe { *out = SzValue::from_aspect(value) };
+            true
+        }
+        None => false,
+    }
+}
@@ -5,14 +5,24 @@
 // 导出核心模块
 pub mod core;
 pub mod utils;
+pub mod testing;
+pub mod loader;
+pub mod monitor;
 pub mod examples;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 // 重新导出常用类型，方便用户使用
 pub use core::{
     StateAspectId, EventId, TransitionId, ObserverId,
-    StateAspect, StateInRange, Transfer, EventDef, Transition, StateObserver,
-    StateMachineBlueprint, RuntimeStateMachine,
+    StateAspect, StateInRange, Transfer, EventDef, EventInstance, EventPayload, Transition,
+    StateObserver, EdgeObserver, TransitionObserver,
+    StateMachineBlueprint, RuntimeStateMachine, MachineTemplate, StateZenError, Aspects,
 };
 
+// 派生宏
+#[cfg(feature = "derive")]
+pub use state_zen_derive::Aspects;
+
 // 重新导出 State 类型
-pub use core::runtime::State;
\ No newline at end of file
+pub use core::runtime::{State, AspectValue};
\ No newline at end of file
@@ -0,0 +1,377 @@
+//! 守卫表达式
+//!
+//! 把 `"hunger <= 5 && action == 'Walk'"` 这样的字符串编译为 `StateInRange`，方面按名称从
+//! `AspectRegistry` 查找。语法：
+//!
+//! ```text
+//! expr    := and ("||" and)*
+//! and     := unary ("&&" unary)*
+//! unary   := "!" unary | "(" expr ")" | "true" | "false" | name op literal
+//! op      := "==" | "!=" | "<" | "<=" | ">" | ">="
+//! literal := 整数 | 小数 | 'text' | "text" | true | false | 标识符
+//! ```
+//!
+//! 取值类型为整数、浮点、`bool` 或 `String` 的方面按类型比较，编译结果是可描述、可特化的
+//! 声明式谓词；其他类型（如枚举）只支持 `==` / `!=`，按注册表格式化出的文本与字面量比较，
+//! 例如 `action == Walk`。
+//!
+//! 表达式可能来自不受信任的机器定义，编译时按 `ExprLimits` 限制源文本长度、嵌套深度与
+//! 比较项数量，超出限制的表达式被拒绝，避免解析或守卫求值时递归过深导致栈溢出。
+
+use std::any::TypeId;
+use std::fmt;
+use std::ops::Bound;
+use crate::core::types::StateAspectId;
+use crate::core::state_in_range::StateInRange;
+use crate::core::registry::{AspectRegistry, AspectInfo};
+
+/// 表达式错误
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExprError {
+    /// 出错位置（字节偏移）
+    pub position: usize,
+    /// 错误说明
+    pub message: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "守卫表达式第 {} 字节处：{}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+/// 表达式执行限制
+///
+/// 守卫求值按表达式树递归，树的深度由括号与 `!` 的嵌套层数以及 `&&` / `||` 连接的项数决定，
+/// 两者都被限制后，编译出的守卫求值深度有界
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExprLimits {
+    /// 源文本的最大字节数
+    pub max_length: usize,
+    /// 括号与 `!` 的最大嵌套层数
+    pub max_depth: usize,
+    /// 比较项（含 `true` / `false`）的最大数量
+    pub max_terms: usize,
+}
+
+impl Default for ExprLimits {
+    fn default() -> Self {
+        Self {
+            max_length: 4096,
+            max_depth: 32,
+            max_terms: 64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+}
+
+fn error(position: usize, message: impl Into<String>) -> ExprError {
+    ExprError {
+        position,
+        message: message.into(),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let two = source.get(i..i + 2).unwrap_or("");
+        let (token, len) = match c {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'(' => (Token::LParen, 1),
+            b')' => (Token::RParen, 1),
+            _ if two == "&&" => (Token::And, 2),
+            _ if two == "||" => (Token::Or, 2),
+            _ if two == "==" => (Token::Op("=="), 2),
+            _ if two == "!=" => (Token::Op("!="), 2),
+            _ if two == "<=" => (Token::Op("<="), 2),
+            _ if two == ">=" => (Token::Op(">="), 2),
+            b'<' => (Token::Op("<"), 1),
+            b'>' => (Token::Op(">"), 1),
+            b'!' => (Token::Not, 1),
+            b'\'' | b'"' => {
+                let end = source[i + 1..]
+                    .find(c as char)
+                    .ok_or_else(|| error(start, "字符串缺少结束引号"))?;
+                tokens.push((start, Token::Str(source[i + 1..i + 1 + end].to_string())));
+                i += end + 2;
+                continue;
+            }
+            b'0'..=b'9' | b'-' => {
+                let mut end = i + 1;
+                while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.') {
+                    end += 1;
+                }
+                let text = &source[i..end];
+                let token = if text.contains('.') {
+                    Token::Float(text.parse().map_err(|_| error(start, format!("无效的数字 `{text}`")))?)
+                } else {
+                    Token::Int(text.parse().map_err(|_| error(start, format!("无效的数字 `{text}`")))?)
+                };
+                tokens.push((start, token));
+                i = end;
+                continue;
+            }
+            _ if c.is_ascii_alphabetic() || c == b'_' => {
+                let mut end = i + 1;
+                while end < bytes.len() && (bytes[end].is_ascii_alphanumeric() || bytes[end] == b'_') {
+                    end += 1;
+                }
+                tokens.push((start, Token::Ident(source[i..end].to_string())));
+                i = end;
+                continue;
+            }
+            _ => return Err(error(start, format!("无法识别的字符 `{}`", &source[i..].chars().next().unwrap_or(' ')))),
+        };
+        i += len;
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize,
+    registry: &'a AspectRegistry,
+    limits: ExprLimits,
+    depth: usize,
+    terms: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(_, t)| t.clone());
+        self.next += 1;
+        token
+    }
+
+    /// 进入一层嵌套，超出限制时报错
+    fn enter(&mut self, position: usize) -> Result<(), ExprError> {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            return Err(error(position, format!("嵌套超过 {} 层", self.limits.max_depth)));
+        }
+        Ok(())
+    }
+
+    /// 记录一个比较项，超出限制时报错
+    fn term(&mut self, position: usize) -> Result<(), ExprError> {
+        self.terms += 1;
+        if self.terms > self.limits.max_terms {
+            return Err(error(position, format!("比较项超过 {} 个", self.limits.max_terms)));
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<StateInRange, ExprError> {
+        let mut guard = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.bump();
+            guard = guard.or(self.and()?);
+        }
+        Ok(guard)
+    }
+
+    fn and(&mut self) -> Result<StateInRange, ExprError> {
+        let mut guard = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.bump();
+            guard = guard.and(self.unary()?);
+        }
+        Ok(guard)
+    }
+
+    fn unary(&mut self) -> Result<StateInRange, ExprError> {
+        let position = self.position();
+        match self.bump() {
+            Some(Token::Not) => {
+                self.enter(position)?;
+                let guard = self.unary()?.not();
+                self.depth -= 1;
+                Ok(guard)
+            }
+            Some(Token::LParen) => {
+                self.enter(position)?;
+                let guard = self.or()?;
+                self.depth -= 1;
+                match self.bump() {
+                    Some(Token::RParen) => Ok(guard),
+                    _ => Err(error(self.position(), "缺少 `)`")),
+                }
+            }
+            Some(Token::Ident(name)) if name == "true" => {
+                self.term(position)?;
+                Ok(StateInRange::always())
+            }
+            Some(Token::Ident(name)) if name == "false" => {
+                self.term(position)?;
+                Ok(StateInRange::never())
+            }
+            Some(Token::Ident(name)) => {
+                self.term(position)?;
+                let info = self
+                    .registry
+                    .by_name(&name)
+                    .ok_or_else(|| error(position, format!("未注册的方面 `{name}`")))?;
+                let op_position = self.position();
+                let Some(Token::Op(op)) = self.bump() else {
+                    return Err(error(op_position, "缺少比较运算符"));
+                };
+                let literal_position = self.position();
+                let literal = self
+                    .bump()
+                    .ok_or_else(|| error(literal_position, "缺少比较的取值"))?;
+                compare(info, op, &literal).ok_or_else(|| {
+                    error(literal_position, format!("方面 `{name}` 不支持 `{op}` {}", describe(&literal)))
+                })
+            }
+            Some(_) => Err(error(position, "应为方面名称、`!` 或 `(`")),
+            None => Err(error(position, "表达式不完整")),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(s) => s.clone(),
+        Token::Int(v) => v.to_string(),
+        Token::Float(v) => v.to_string(),
+        Token::Str(s) => format!("'{s}'"),
+        Token::Op(op) => op.to_string(),
+        Token::LParen => "(".to_string(),
+        Token::RParen => ")".to_string(),
+        Token::Not => "!".to_string(),
+        Token::And => "&&".to_string(),
+        Token::Or => "||".to_string(),
+    }
+}
+
+/// 按运算符构造一个有序类型的比较
+fn ordered<T>(id: StateAspectId, op: &str, value: T) -> Option<StateInRange>
+where
+    T: PartialOrd + Clone + fmt::Debug + Send + Sync + 'static,
+{
+    Some(match op {
+        "==" => StateInRange::aspect_eq(id, value),
+        "!=" => StateInRange::aspect_eq(id, value).not(),
+        "<" => StateInRange::aspect_in(id, (Bound::Unbounded, Bound::Excluded(value))),
+        "<=" => StateInRange::aspect_in(id, (Bound::Unbounded, Bound::Included(value))),
+        ">" => StateInRange::aspect_in(id, (Bound::Excluded(value), Bound::Unbounded)),
+        ">=" => StateInRange::aspect_in(id, (Bound::Included(value), Bound::Unbounded)),
+        _ => return None,
+    })
+}
+
+fn compare(info: &AspectInfo, op: &str, literal: &Token) -> Option<StateInRange> {
+    let id = info.id;
+    let ty = info.value_type_id;
+
+    macro_rules! integer {
+        ($($t:ty),*) => {$(
+            if ty == TypeId::of::<$t>() {
+                let Token::Int(v) = literal else { return None };
+                return ordered(id, op, <$t>::try_from(*v).ok()?);
+            }
+        )*};
+    }
+    integer!(i8, i16, i32, i64, u8, u16, u32, u64, usize, isize);
+
+    if ty == TypeId::of::<f64>() || ty == TypeId::of::<f32>() {
+        let v = match literal {
+            Token::Int(v) => *v as f64,
+            Token::Float(v) => *v,
+            _ => return None,
+        };
+        return if ty == TypeId::of::<f64>() { ordered(id, op, v) } else { ordered(id, op, v as f32) };
+    }
+    if ty == TypeId::of::<bool>() {
+        let Token::Ident(v) = literal else { return None };
+        let v = match v.as_str() {
+            "true" => true,
+            "false" => false,
+            _ => return None,
+        };
+        return match op {
+            "==" => Some(StateInRange::aspect_eq(id, v)),
+            "!=" => Some(StateInRange::aspect_eq(id, v).not()),
+            _ => None,
+        };
+    }
+    if ty == TypeId::of::<String>() {
+        let (Token::Str(v) | Token::Ident(v)) = literal else { return None };
+        return ordered(id, op, v.clone());
+    }
+
+    // 其他类型按格式化文本比较
+    let expected = match literal {
+        Token::Str(v) | Token::Ident(v) => v.clone(),
+        Token::Int(v) => v.to_string(),
+        Token::Float(v) => v.to_string(),
+        _ => return None,
+    };
+    let info = info.clone();
+    let matches = StateInRange::new(move |s| s.get(&id).is_some_and(|v| info.format(v) == expected))
+        .with_reads([id]);
+    match op {
+        "==" => Some(matches),
+        "!=" => Some(matches.not()),
+        _ => None,
+    }
+}
+
+/// 按默认限制编译守卫表达式
+pub fn compile(source: &str, registry: &AspectRegistry) -> Result<StateInRange, ExprError> {
+    compile_with(source, registry, ExprLimits::default())
+}
+
+/// 按给定限制编译守卫表达式
+pub fn compile_with(source: &str, registry: &AspectRegistry, limits: ExprLimits) -> Result<StateInRange, ExprError> {
+    if source.len() > limits.max_length {
+        return Err(error(limits.max_length, format!("表达式超过 {} 字节", limits.max_length)));
+    }
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        next: 0,
+        end: source.len(),
+        registry,
+        limits,
+        depth: 0,
+        terms: 0,
+    };
+    let guard = parser.or()?;
+    if parser.peek().is_some() {
+        return Err(error(parser.position(), "多余的内容"));
+    }
+    Ok(guard)
+}
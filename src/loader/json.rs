@@ -0,0 +1,722 @@
+//! JSON 蓝图加载
+//!
+//! 文件格式示例：
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "aspects": [
+//!     { "id": 1, "name": "action", "type": "string", "values": ["Idle", "Walk"], "default": "Idle" },
+//!     { "id": 2, "name": "hunger", "type": "int", "default": 10 }
+//!   ],
+//!   "events": [{ "id": 100, "name": "press_w" }],
+//!   "transitions": [
+//!     { "id": 1, "event": "press_w", "guard": { "action": "Idle", "hunger": { "min": 1 } },
+//!       "set": { "action": "Walk" }, "add": { "hunger": -1 }, "priority": 0 }
+//!   ],
+//!   "observers": [{ "id": 1, "region": { "action": "Walk" } }]
+//! }
+//! ```
+//!
+//! 方面取值类型为 `int`（`i64`）、`float`（`f64`）、`bool` 或 `string`（`String`）；
+//! 守卫按方面名称匹配，取值为标量时表示相等，`{ "min": a, "max": b }` 表示闭区间。
+//! 转换与观察者可带 `"tag"`，只有蓝图启用该标签后才生效。
+//! 观察者可带 `"active_when"`（守卫语法），只在状态位于该区域内时参与进出计算。
+//!
+//! 通过 `load_str_with` 传入工厂注册表后，转换可用 `"guard_call"` / `"transfer_call"`、
+//! 观察者可用 `"region_call"` 引用宿主注册的具名工厂，
+//! 如 `"guard_call": { "name": "hunger_at_most", "args": [5] }`；
+//! 它们与同一条目中的声明式守卫取与、排在声明式写操作之后执行。
+//!
+//! 启用 `expr` 特性后，`"guard_expr"` / `"region_expr"` 可用守卫表达式书写条件，
+//! 如 `"guard_expr": "stamina >= 1 && action == 'Idle'"`，同样与其余条件取与。
+//! 表达式按默认的 `ExprLimits` 编译，嵌套过深或项数过多的表达式使加载失败。
+//!
+//! 启用 `lua` 特性后，可用 Lua 片段书写逻辑（见 `loader::lua`）：转换的 `"guard_lua"`、
+//! `"transfer_lua"`、`"on_tran_lua"`，观察者的 `"region_lua"`、`"on_enter_lua"`、`"on_exit_lua"`，
+//! 如 `"transfer_lua": "stamina = stamina - 1"`。守卫与其余条件取与，转移排在其余写操作之后执行。
+
+use std::any::TypeId;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use serde::Deserialize;
+use serde_json::Value;
+use crate::core::types::{StateAspectId, EventId, TransitionId, ObserverId};
+use crate::core::state_aspect::StateAspect;
+use crate::core::state_in_range::StateInRange;
+use crate::core::transfer::Transfer;
+use crate::core::event::EventDef;
+use crate::core::transition::Transition;
+use crate::core::state_observer::StateObserver;
+use crate::core::blueprint::StateMachineBlueprint;
+use crate::core::runtime::{State, AspectValue, RuntimeStateMachine};
+use crate::core::template::MachineTemplate;
+use crate::core::registry::AspectRegistry;
+use crate::core::error::StateZenError;
+use super::registry::{Registry, ArgValue, FactoryError};
+
+/// 加载错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// 读取文件失败
+    Io(String),
+    /// JSON 格式不正确
+    Parse(String),
+    /// 引用了未声明的方面名称
+    UnknownAspect(String),
+    /// 引用了未声明的事件名称
+    UnknownEvent(String),
+    /// 取值与方面类型不符
+    InvalidValue { aspect: String, value: String },
+    /// 工厂调用失败
+    Factory(FactoryError),
+    /// 清单引用了未注册的取值类型
+    UnknownType(String),
+    /// 清单中含有无法还原的闭包部分
+    Opaque(String),
+    /// 守卫表达式有误
+    #[cfg(feature = "expr")]
+    Expr(super::expr::ExprError),
+    /// Lua 片段有误
+    #[cfg(feature = "lua")]
+    Lua(super::lua::LuaError),
+    /// 蓝图校验失败
+    Blueprint(StateZenError),
+    /// 运行时整数方面的 `add` 溢出，转移被放弃
+    Overflow { aspect: String, delta: i64 },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "读取蓝图文件失败：{e}"),
+            Self::Parse(e) => write!(f, "蓝图 JSON 格式错误：{e}"),
+            Self::UnknownAspect(name) => write!(f, "未声明的方面 `{name}`"),
+            Self::UnknownEvent(name) => write!(f, "未声明的事件 `{name}`"),
+            Self::InvalidValue { aspect, value } => write!(f, "方面 `{aspect}` 不接受取值 {value}"),
+            Self::Factory(e) => write!(f, "{e}"),
+            Self::UnknownType(name) => write!(f, "取值类型 `{name}` 未注册"),
+            Self::Opaque(item) => write!(f, "无法还原闭包构造的部分：{item}"),
+            #[cfg(feature = "expr")]
+            Self::Expr(e) => write!(f, "{e}"),
+            #[cfg(feature = "lua")]
+            Self::Lua(e) => write!(f, "{e}"),
+            Self::Blueprint(e) => write!(f, "{e}"),
+            Self::Overflow { aspect, delta } => write!(f, "方面 `{aspect}` 加上 {delta} 后溢出，转移被放弃"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<FactoryError> for LoadError {
+    fn from(e: FactoryError) -> Self {
+        Self::Factory(e)
+    }
+}
+
+#[cfg(feature = "expr")]
+impl From<super::expr::ExprError> for LoadError {
+    fn from(e: super::expr::ExprError) -> Self {
+        Self::Expr(e)
+    }
+}
+
+#[cfg(feature = "lua")]
+impl From<super::lua::LuaError> for LoadError {
+    fn from(e: super::lua::LuaError) -> Self {
+        Self::Lua(e)
+    }
+}
+
+impl From<StateZenError> for LoadError {
+    fn from(e: StateZenError) -> Self {
+        Self::Blueprint(e)
+    }
+}
+
+/// 加载结果
+#[derive(Clone)]
+pub struct LoadedBlueprint {
+    /// 蓝图及各方面默认值
+    pub template: MachineTemplate,
+    /// 方面名称与格式化
+    pub registry: AspectRegistry,
+    /// 事件ID与名称
+    pub events: Vec<(EventId, String)>,
+    /// 编译 Lua 片段所用的虚拟机
+    #[cfg(feature = "lua")]
+    pub scripts: super::lua::LuaScripts,
+    aspects: Vec<AspectSpec>,
+    last_error: ErrorSlot,
+}
+
+impl LoadedBlueprint {
+    /// 最近一次运行时错误，如整数方面的 `add` 溢出
+    pub fn last_error(&self) -> Option<LoadError> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    /// 按名称查找事件ID
+    pub fn event_id(&self, name: &str) -> Option<EventId> {
+        self.events.iter().find(|(_, n)| n == name).map(|(id, _)| *id)
+    }
+
+    /// 事件名称
+    pub fn event_name(&self, id: EventId) -> Option<&str> {
+        self.events.iter().find(|(i, _)| *i == id).map(|(_, n)| n.as_str())
+    }
+
+    /// 按文件中的守卫语法解析一个区域，如 `{"action": "Walk", "hunger": {"max": 3}}`
+    pub fn parse_region(&self, source: &str) -> Result<StateInRange, LoadError> {
+        let conditions: BTreeMap<String, Condition> =
+            serde_json::from_str(source).map_err(|e| LoadError::Parse(e.to_string()))?;
+        region(&AspectTable { specs: &self.aspects }, &conditions)
+    }
+
+    /// 以默认初始状态实例化运行时
+    pub fn instantiate(&self) -> Result<RuntimeStateMachine, StateZenError> {
+        self.template.instantiate(State::new())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlueprintFile {
+    #[serde(default)]
+    version: u32,
+    aspects: Vec<AspectSpec>,
+    #[serde(default)]
+    events: Vec<EventSpec>,
+    #[serde(default)]
+    transitions: Vec<TransitionSpec>,
+    #[serde(default)]
+    observers: Vec<ObserverSpec>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct AspectSpec {
+    id: StateAspectId,
+    name: String,
+    #[serde(rename = "type")]
+    kind: ValueKind,
+    #[serde(default)]
+    values: Option<Vec<String>>,
+    default: Value,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ValueKind {
+    Int,
+    Float,
+    Bool,
+    String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EventSpec {
+    id: EventId,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EventRef {
+    Id(EventId),
+    Name(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransitionSpec {
+    id: TransitionId,
+    event: EventRef,
+    #[serde(default)]
+    guard: BTreeMap<String, Condition>,
+    #[serde(default)]
+    set: BTreeMap<String, Value>,
+    #[serde(default)]
+    add: BTreeMap<String, Value>,
+    #[serde(default)]
+    guard_call: Option<CallSpec>,
+    #[serde(default)]
+    guard_expr: Option<String>,
+    #[serde(default)]
+    guard_lua: Option<String>,
+    #[serde(default)]
+    transfer_call: Option<CallSpec>,
+    #[serde(default)]
+    transfer_lua: Option<String>,
+    #[serde(default)]
+    on_tran_lua: Option<String>,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    tag: Option<String>,
+    #[serde(default)]
+    ensures: BTreeMap<String, Condition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObserverSpec {
+    id: ObserverId,
+    #[serde(default)]
+    region: BTreeMap<String, Condition>,
+    #[serde(default)]
+    region_call: Option<CallSpec>,
+    #[serde(default)]
+    region_expr: Option<String>,
+    #[serde(default)]
+    region_lua: Option<String>,
+    #[serde(default)]
+    active_when: BTreeMap<String, Condition>,
+    #[serde(default)]
+    on_enter_lua: Option<String>,
+    #[serde(default)]
+    on_exit_lua: Option<String>,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+/// 对具名工厂的调用
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CallSpec {
+    name: String,
+    #[serde(default)]
+    args: Vec<Value>,
+}
+
+impl CallSpec {
+    fn args(&self) -> Result<Vec<ArgValue>, LoadError> {
+        self.args
+            .iter()
+            .map(|value| match value {
+                Value::Bool(b) => Ok(ArgValue::Bool(*b)),
+                Value::Number(n) => Ok(n.as_i64().map_or_else(|| ArgValue::Float(n.as_f64().unwrap_or_default()), ArgValue::Int)),
+                Value::String(s) => Ok(ArgValue::Str(s.clone())),
+                _ => Err(LoadError::InvalidValue {
+                    aspect: self.name.clone(),
+                    value: value.to_string(),
+                }),
+            })
+            .collect()
+    }
+
+    fn guard(&self, factories: &Registry) -> Result<StateInRange, LoadError> {
+        Ok(factories.guard(&self.name, &self.args()?)?)
+    }
+
+    fn transfer(&self, factories: &Registry) -> Result<Transfer, LoadError> {
+        Ok(factories.transfer(&self.name, &self.args()?)?)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Condition {
+    Range(RangeSpec),
+    Eq(Value),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RangeSpec {
+    #[serde(default)]
+    min: Option<Value>,
+    #[serde(default)]
+    max: Option<Value>,
+}
+
+/// 文件中的方面及其类型
+struct AspectTable<'a> {
+    specs: &'a [AspectSpec],
+}
+
+impl AspectTable<'_> {
+    fn get(&self, name: &str) -> Result<&AspectSpec, LoadError> {
+        self.specs
+            .iter()
+            .find(|a| a.name == name)
+            .ok_or_else(|| LoadError::UnknownAspect(name.to_string()))
+    }
+}
+
+impl AspectSpec {
+    fn invalid(&self, value: &Value) -> LoadError {
+        LoadError::InvalidValue {
+            aspect: self.name.clone(),
+            value: value.to_string(),
+        }
+    }
+
+    fn int(&self, value: &Value) -> Result<i64, LoadError> {
+        value.as_i64().ok_or_else(|| self.invalid(value))
+    }
+
+    fn float(&self, value: &Value) -> Result<f64, LoadError> {
+        value.as_f64().ok_or_else(|| self.invalid(value))
+    }
+
+    fn value(&self, value: &Value) -> Result<AspectValue, LoadError> {
+        Ok(match self.kind {
+            ValueKind::Int => Arc::new(self.int(value)?),
+            ValueKind::Float => Arc::new(self.float(value)?),
+            ValueKind::Bool => Arc::new(value.as_bool().ok_or_else(|| self.invalid(value))?),
+            ValueKind::String => {
+                let s = value.as_str().ok_or_else(|| self.invalid(value))?;
+                if let Some(values) = &self.values
+                    && !values.iter().any(|v| v == s)
+                {
+                    return Err(self.invalid(value));
+                }
+                Arc::new(s.to_string())
+            }
+        })
+    }
+
+    fn type_id(&self) -> TypeId {
+        match self.kind {
+            ValueKind::Int => TypeId::of::<i64>(),
+            ValueKind::Float => TypeId::of::<f64>(),
+            ValueKind::Bool => TypeId::of::<bool>(),
+            ValueKind::String => TypeId::of::<String>(),
+        }
+    }
+
+    fn condition(&self, condition: &Condition) -> Result<StateInRange, LoadError> {
+        let id = self.id;
+        match condition {
+            Condition::Eq(value) => Ok(match self.kind {
+                ValueKind::Int => StateInRange::aspect_eq(id, self.int(value)?),
+                ValueKind::Float => StateInRange::aspect_eq(id, self.float(value)?),
+                ValueKind::Bool => {
+                    StateInRange::aspect_eq(id, value.as_bool().ok_or_else(|| self.invalid(value))?)
+                }
+                ValueKind::String => {
+                    self.value(value)?;
+                    StateInRange::aspect_eq(id, value.as_str().unwrap_or_default().to_string())
+                }
+            }),
+            Condition::Range(range) => match self.kind {
+                ValueKind::Int => {
+                    let lo = range.min.as_ref().map(|v| self.int(v)).transpose()?;
+                    let hi = range.max.as_ref().map(|v| self.int(v)).transpose()?;
+                    Ok(StateInRange::aspect_in(id, (bound(lo), bound(hi))))
+                }
+                ValueKind::Float => {
+                    let lo = range.min.as_ref().map(|v| self.float(v)).transpose()?;
+                    let hi = range.max.as_ref().map(|v| self.float(v)).transpose()?;
+                    Ok(StateInRange::aspect_in(id, (bound(lo), bound(hi))))
+                }
+                _ => Err(LoadError::InvalidValue {
+                    aspect: self.name.clone(),
+                    value: "区间".to_string(),
+                }),
+            },
+        }
+    }
+}
+
+fn bound<T>(value: Option<T>) -> Bound<T> {
+    value.map_or(Bound::Unbounded, Bound::Included)
+}
+
+fn region(aspects: &AspectTable, conditions: &BTreeMap<String, Condition>) -> Result<StateInRange, LoadError> {
+    let mut parts = Vec::new();
+    for (name, condition) in conditions {
+        parts.push(aspects.get(name)?.condition(condition)?);
+    }
+    Ok(parts.into_iter().reduce(StateInRange::and).unwrap_or_else(StateInRange::always))
+}
+
+/// 守卫的各种写法
+#[derive(Clone, Copy, Default)]
+struct GuardSources<'a> {
+    call: Option<&'a CallSpec>,
+    expr: Option<&'a str>,
+    lua: Option<&'a str>,
+}
+
+fn region_with(
+    aspects: &AspectTable,
+    conditions: &BTreeMap<String, Condition>,
+    sources: GuardSources,
+    factories: &Registry,
+    registry: &AspectRegistry,
+    scripts: &Scripts,
+) -> Result<StateInRange, LoadError> {
+    let GuardSources { call, expr, lua } = sources;
+    let mut parts = Vec::new();
+    if !conditions.is_empty() {
+        parts.push(region(aspects, conditions)?);
+    }
+    if let Some(call) = call {
+        parts.push(call.guard(factories)?);
+    }
+    if let Some(expr) = expr {
+        parts.push(compile_expr(expr, registry)?);
+    }
+    if let Some(lua) = lua {
+        parts.push(scripts.guard(lua)?);
+    }
+    Ok(parts.into_iter().reduce(StateInRange::and).unwrap_or_else(StateInRange::always))
+}
+
+#[cfg(feature = "expr")]
+fn compile_expr(source: &str, registry: &AspectRegistry) -> Result<StateInRange, LoadError> {
+    Ok(super::expr::compile(source, registry)?)
+}
+
+#[cfg(not(feature = "expr"))]
+fn compile_expr(source: &str, _registry: &AspectRegistry) -> Result<StateInRange, LoadError> {
+    Err(LoadError::Parse(format!("守卫表达式 `{source}` 需要启用 `expr` 特性")))
+}
+
+#[cfg(feature = "lua")]
+type Scripts = super::lua::LuaScripts;
+
+/// 未启用 `lua` 特性时，遇到 Lua 片段即加载失败
+#[cfg(not(feature = "lua"))]
+struct Scripts;
+
+#[cfg(not(feature = "lua"))]
+impl Scripts {
+    fn new(_registry: &AspectRegistry) -> Self {
+        Self
+    }
+
+    fn disabled(source: &str) -> LoadError {
+        LoadError::Parse(format!("Lua 片段 `{source}` 需要启用 `lua` 特性"))
+    }
+
+    fn guard(&self, source: &str) -> Result<StateInRange, LoadError> {
+        Err(Self::disabled(source))
+    }
+
+    fn transfer(&self, source: &str) -> Result<Transfer, LoadError> {
+        Err(Self::disabled(source))
+    }
+
+    fn callback(&self, source: &str) -> Result<crate::core::state_observer::ObserverCallback, LoadError> {
+        Err(Self::disabled(source))
+    }
+
+    fn on_tran(&self, source: &str) -> Result<crate::core::transition::OnTranCallback, LoadError> {
+        Err(Self::disabled(source))
+    }
+}
+
+/// 单个方面的写操作
+enum Write {
+    Set(StateAspectId, AspectValue),
+    AddInt(StateAspectId, String, i64),
+    AddFloat(StateAspectId, f64),
+}
+
+/// 运行时错误的记录位置，由同一次加载得到的转移共享
+type ErrorSlot = Arc<Mutex<Option<LoadError>>>;
+
+fn transfer(
+    aspects: &AspectTable,
+    spec: &TransitionSpec,
+    factories: &Registry,
+    scripts: &Scripts,
+    errors: &ErrorSlot,
+) -> Result<Transfer, LoadError> {
+    let declared = declared_transfer(aspects, spec, errors)?;
+    let mut parts = Vec::new();
+    if !spec.set.is_empty() || !spec.add.is_empty() {
+        parts.push(declared.clone());
+    }
+    if let Some(call) = &spec.transfer_call {
+        parts.push(call.transfer(factories)?);
+    }
+    if let Some(lua) = &spec.transfer_lua {
+        parts.push(scripts.transfer(lua)?);
+    }
+    Ok(parts.into_iter().reduce(Transfer::then).unwrap_or(declared))
+}
+
+/// `set` 与 `add` 声明的转移，写集合即其中出现的方面；整数 `add` 溢出时放弃整个转移（状态不变）并记录错误
+fn declared_transfer(aspects: &AspectTable, spec: &TransitionSpec, errors: &ErrorSlot) -> Result<Transfer, LoadError> {
+    let mut writes = Vec::new();
+    for (name, value) in &spec.set {
+        let aspect = aspects.get(name)?;
+        writes.push(Write::Set(aspect.id, aspect.value(value)?));
+    }
+    for (name, value) in &spec.add {
+        let aspect = aspects.get(name)?;
+        writes.push(match aspect.kind {
+            ValueKind::Int => Write::AddInt(aspect.id, name.clone(), aspect.int(value)?),
+            ValueKind::Float => Write::AddFloat(aspect.id, aspect.float(value)?),
+            _ => return Err(aspect.invalid(value)),
+        });
+    }
+
+    let written: Vec<StateAspectId> = writes
+        .iter()
+        .map(|write| match write {
+            Write::Set(id, _) | Write::AddInt(id, _, _) | Write::AddFloat(id, _) => *id,
+        })
+        .collect();
+    let errors = errors.clone();
+    let transfer = Transfer::new(move |state| {
+        let mut next = state.clone();
+        for write in &writes {
+            match write {
+                Write::Set(id, value) => {
+                    next.insert(*id, value.clone());
+                }
+                Write::AddInt(id, name, delta) => {
+                    let current = state.get(id).and_then(|v| v.downcast_ref::<i64>()).copied().unwrap_or(0);
+                    let Some(sum) = current.checked_add(*delta) else {
+                        *errors.lock().unwrap() = Some(LoadError::Overflow { aspect: name.clone(), delta: *delta });
+                        return state.clone();
+                    };
+                    next.insert(*id, Arc::new(sum));
+                }
+                Write::AddFloat(id, delta) => {
+                    let current = state.get(id).and_then(|v| v.downcast_ref::<f64>()).copied().unwrap_or(0.0);
+                    next.insert(*id, Arc::new(current + delta));
+                }
+            }
+        }
+        next
+    });
+    Ok(transfer.with_writes(written))
+}
+
+/// 从 JSON 文本加载蓝图
+pub fn load_str(source: &str) -> Result<LoadedBlueprint, LoadError> {
+    load_str_with(source, &Registry::new())
+}
+
+/// 从 JSON 文本加载蓝图，`*_call` 引用的工厂从 `factories` 中查找
+pub fn load_str_with(source: &str, factories: &Registry) -> Result<LoadedBlueprint, LoadError> {
+    let file: BlueprintFile = serde_json::from_str(source).map_err(|e| LoadError::Parse(e.to_string()))?;
+    let aspects = AspectTable { specs: &file.aspects };
+    let last_error = ErrorSlot::default();
+
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.set_version(file.version);
+    let mut registry = AspectRegistry::new();
+    let mut defaults = State::new();
+    for spec in &file.aspects {
+        blueprint.add_aspect(StateAspect::new(spec.id, spec.type_id()))?;
+        match spec.kind {
+            ValueKind::Int => registry.register::<i64>(spec.id, spec.name.clone()),
+            ValueKind::Float => registry.register::<f64>(spec.id, spec.name.clone()),
+            ValueKind::Bool => registry.register::<bool>(spec.id, spec.name.clone()),
+            ValueKind::String => registry.register::<String>(spec.id, spec.name.clone()),
+        };
+        defaults.insert(spec.id, spec.value(&spec.default)?);
+    }
+
+    for spec in &file.events {
+        blueprint.add_event(EventDef::new(spec.id))?;
+    }
+    let events: Vec<(EventId, String)> = file.events.iter().map(|e| (e.id, e.name.clone())).collect();
+    let scripts = Scripts::new(&registry);
+
+    for spec in &file.transitions {
+        let event_id = match &spec.event {
+            EventRef::Id(id) => *id,
+            EventRef::Name(name) => events
+                .iter()
+                .find(|(_, n)| n == name)
+                .map(|(id, _)| *id)
+                .ok_or_else(|| LoadError::UnknownEvent(name.clone()))?,
+        };
+        let guard = region_with(
+            &aspects,
+            &spec.guard,
+            GuardSources {
+                call: spec.guard_call.as_ref(),
+                expr: spec.guard_expr.as_deref(),
+                lua: spec.guard_lua.as_deref(),
+            },
+            factories,
+            &registry,
+            &scripts,
+        )?;
+        let transfer = transfer(&aspects, spec, factories, &scripts, &last_error)?;
+        blueprint.add_transition(Transition {
+            priority: spec.priority,
+            on_tran: spec.on_tran_lua.as_deref().map(|s| scripts.on_tran(s)).transpose()?,
+            tag: spec.tag.clone(),
+            ensures: if spec.ensures.is_empty() {
+                None
+            } else {
+                Some(region_with(
+                    &aspects,
+                    &spec.ensures,
+                    GuardSources::default(),
+                    factories,
+                    &registry,
+                    &scripts,
+                )?)
+            },
+            ..Transition::new(spec.id, event_id, guard, transfer)
+        })?;
+    }
+
+    for spec in &file.observers {
+        let observed = region_with(
+            &aspects,
+            &spec.region,
+            GuardSources {
+                call: spec.region_call.as_ref(),
+                expr: spec.region_expr.as_deref(),
+                lua: spec.region_lua.as_deref(),
+            },
+            factories,
+            &registry,
+            &scripts,
+        )?;
+        blueprint.add_observer(StateObserver {
+            on_enter: spec.on_enter_lua.as_deref().map(|s| scripts.callback(s)).transpose()?,
+            on_exit: spec.on_exit_lua.as_deref().map(|s| scripts.callback(s)).transpose()?,
+            priority: spec.priority,
+            active_when: if spec.active_when.is_empty() {
+                None
+            } else {
+                Some(region(&aspects, &spec.active_when)?)
+            },
+            tag: spec.tag.clone(),
+            ..StateObserver::new(spec.id, observed)
+        })?;
+    }
+
+    let mut template = MachineTemplate::new(blueprint);
+    template.defaults = defaults;
+    Ok(LoadedBlueprint {
+        template,
+        registry,
+        events,
+        #[cfg(feature = "lua")]
+        scripts,
+        aspects: file.aspects,
+        last_error,
+    })
+}
+
+/// 从 JSON 文件加载蓝图
+pub fn load_file(path: impl AsRef<Path>) -> Result<LoadedBlueprint, LoadError> {
+    load_file_with(path, &Registry::new())
+}
+
+/// 从 JSON 文件加载蓝图，`*_call` 引用的工厂从 `factories` 中查找
+pub fn load_file_with(path: impl AsRef<Path>, factories: &Registry) -> Result<LoadedBlueprint, LoadError> {
+    let source = std::fs::read_to_string(path).map_err(|e| LoadError::Io(e.to_string()))?;
+    load_str_with(&source, factories)
+}
@@ -0,0 +1,279 @@
+//! Lua 脚本
+//!
+//! 把 Lua 片段编译为守卫、转移与回调，片段中按名称引用方面，名称从 `AspectRegistry` 查找：
+//!
+//! - 守卫是一个表达式，如 `stamina >= 2 and action == "Idle"`；
+//! - 转移是若干语句，对方面名称赋值即写入新状态，如 `stamina = stamina - 1`，赋 `nil` 表示移除；
+//! - 回调是若干语句，只读取状态；转换回调中方面名称取新状态，`prev` 表取旧状态。
+//!
+//! 片段中的赋值只作用于本次执行，需要跨片段保留的数据写入 `_G`，如 `_G.hits = (_G.hits or 0) + 1`。
+//!
+//! 取值类型为各宽度的整数与浮点、`bool` 或 `String` 的方面映射为对应的 Lua 值，整数写回时须在类型范围内；
+//! 其他类型以注册表格式化出的文本呈现且不能写入。
+//!
+//! 所有片段共享同一个 Lua 虚拟机，虚拟机只加载 `math`、`string`、`table` 与基础库（去掉 `dofile`、
+//! `loadfile`），脚本无法访问文件、进程与模块加载；每个片段单独编译为一个代码块，执行时以方面环境表
+//! 作为 `_ENV`，每次执行的指令数受预算（`with_instruction_limit`）限制，超出即中止。
+//! 运行时脚本出错时守卫视为不满足、转移保持状态不变、回调被跳过，错误可由 `last_error` 取得。
+
+use std::any::TypeId;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use mlua::{Function, HookTriggers, Lua, LuaOptions, RegistryKey, StdLib, Table, Value};
+use crate::core::state_in_range::StateInRange;
+use crate::core::transfer::Transfer;
+use crate::core::state_observer::ObserverCallback;
+use crate::core::transition::OnTranCallback;
+use crate::core::runtime::{State, AspectValue};
+use crate::core::registry::{AspectRegistry, AspectInfo};
+
+/// 脚本错误
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LuaError {
+    /// 出错的片段
+    pub source: String,
+    /// 错误说明
+    pub message: String,
+}
+
+impl fmt::Display for LuaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Lua 片段 `{}`：{}", self.source, self.message)
+    }
+}
+
+impl std::error::Error for LuaError {}
+
+/// 每执行多少条指令检查一次预算
+const HOOK_INTERVAL: u32 = 1000;
+
+/// 单次执行默认的指令预算
+pub const DEFAULT_INSTRUCTION_LIMIT: i64 = 1_000_000;
+
+/// Lua 片段的编译环境
+///
+/// 克隆得到的实例共享同一个虚拟机
+#[derive(Clone)]
+pub struct LuaScripts {
+    lua: Arc<Mutex<Lua>>,
+    aspects: Arc<Vec<AspectInfo>>,
+    last_error: Arc<Mutex<Option<String>>>,
+    /// 本次执行剩余的指令数，由指令钩子递减
+    remaining: Arc<AtomicI64>,
+    /// 单次执行的指令预算
+    instruction_limit: i64,
+}
+
+impl LuaScripts {
+    /// 创建编译环境，方面名称与类型取自 `registry`
+    pub fn new(registry: &AspectRegistry) -> Self {
+        let lua = Lua::new_with(StdLib::MATH | StdLib::STRING | StdLib::TABLE, LuaOptions::default())
+            .expect("加载 Lua 标准库失败");
+        let globals = lua.globals();
+        for name in ["dofile", "loadfile"] {
+            globals.raw_set(name, Value::Nil).expect("移除 Lua 文件函数失败");
+        }
+        drop(globals);
+        let remaining = Arc::new(AtomicI64::new(DEFAULT_INSTRUCTION_LIMIT));
+        let budget = remaining.clone();
+        lua.set_hook(HookTriggers::new().every_nth_instruction(HOOK_INTERVAL), move |_, _| {
+            if budget.fetch_sub(HOOK_INTERVAL as i64, Ordering::Relaxed) <= HOOK_INTERVAL as i64 {
+                return Err(mlua::Error::RuntimeError("超出指令预算".to_string()));
+            }
+            Ok(())
+        });
+        Self {
+            lua: Arc::new(Mutex::new(lua)),
+            aspects: Arc::new(registry.iter().cloned().collect()),
+            last_error: Arc::new(Mutex::new(None)),
+            remaining,
+            instruction_limit: DEFAULT_INSTRUCTION_LIMIT,
+        }
+    }
+
+    /// 设置单次执行的指令预算，默认为 `DEFAULT_INSTRUCTION_LIMIT`
+    pub fn with_instruction_limit(mut self, limit: i64) -> Self {
+        self.instruction_limit = limit;
+        self
+    }
+
+    /// 最近一次运行时脚本错误
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    /// 编译守卫表达式
+    pub fn guard(&self, source: &str) -> Result<StateInRange, LuaError> {
+        let key = self.compile(source, &format!("return {source}"))?;
+        let scripts = self.clone();
+        Ok(StateInRange::new(move |state| {
+            scripts
+                .run(&key, |lua, f| {
+                    f.set_environment(scripts.env(lua, state)?)?;
+                    f.call::<_, bool>(())
+                })
+                .unwrap_or(false)
+        }))
+    }
+
+    /// 编译转移语句
+    pub fn transfer(&self, source: &str) -> Result<Transfer, LuaError> {
+        let key = self.compile(source, source)?;
+        let scripts = self.clone();
+        Ok(Transfer::new(move |state| {
+            scripts
+                .run(&key, |lua, f| {
+                    let env = scripts.env(lua, state)?;
+                    f.set_environment(env.clone())?;
+                    f.call::<_, ()>(())?;
+                    scripts.written(lua, state, &env)
+                })
+                .unwrap_or_else(|| state.clone())
+        }))
+    }
+
+    /// 编译观察者回调语句
+    pub fn callback(&self, source: &str) -> Result<ObserverCallback, LuaError> {
+        let key = self.compile(source, source)?;
+        let scripts = self.clone();
+        Ok(Arc::new(move |state: &State| {
+            scripts.run(&key, |lua, f| {
+                f.set_environment(scripts.env(lua, state)?)?;
+                f.call::<_, ()>(())
+            });
+        }))
+    }
+
+    /// 编译转换回调语句
+    pub fn on_tran(&self, source: &str) -> Result<OnTranCallback, LuaError> {
+        let key = self.compile(source, source)?;
+        let scripts = self.clone();
+        Ok(Arc::new(move |prev: &State, next: &State| {
+            scripts.run(&key, |lua, f| {
+                let env = scripts.env(lua, next)?;
+                env.raw_set("prev", scripts.env(lua, prev)?)?;
+                f.set_environment(env)?;
+                f.call::<_, ()>(())
+            });
+        }))
+    }
+
+    /// 把 `chunk` 单独编译为一个代码块，`source` 用于错误信息
+    fn compile(&self, source: &str, chunk: &str) -> Result<RegistryKey, LuaError> {
+        let lua = self.lua.lock().unwrap();
+        lua.load(chunk)
+            .set_name(source)
+            .into_function()
+            .and_then(|f| lua.create_registry_value(f))
+            .map_err(|e| LuaError { source: source.to_string(), message: e.to_string() })
+    }
+
+    fn run<R>(&self, key: &RegistryKey, f: impl FnOnce(&Lua, Function) -> mlua::Result<R>) -> Option<R> {
+        let lua = self.lua.lock().unwrap();
+        self.remaining.store(self.instruction_limit, Ordering::Relaxed);
+        let result = lua.registry_value::<Function>(key).and_then(|function| f(&lua, function));
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                *self.last_error.lock().unwrap() = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    /// 以方面名称为键的环境表，未命中的名称回落到全局表
+    fn env<'lua>(&self, lua: &'lua Lua, state: &State) -> mlua::Result<Table<'lua>> {
+        let env = lua.create_table()?;
+        for info in self.aspects.iter() {
+            if let Some(value) = state.get(&info.id) {
+                env.raw_set(info.name.as_str(), to_lua(lua, info, value)?)?;
+            }
+        }
+        let meta = lua.create_table()?;
+        meta.raw_set("__index", lua.globals())?;
+        env.set_metatable(Some(meta));
+        Ok(env)
+    }
+
+    /// 把环境表中被改写的方面写入新状态
+    fn written(&self, lua: &Lua, state: &State, env: &Table) -> mlua::Result<State> {
+        let mut next = state.clone();
+        for info in self.aspects.iter() {
+            let value: Value = env.raw_get(info.name.as_str())?;
+            let unchanged = match state.get(&info.id) {
+                Some(current) => value == to_lua(lua, info, current)?,
+                None => value.is_nil(),
+            };
+            if unchanged {
+                continue;
+            }
+            if value.is_nil() {
+                next.remove(&info.id);
+            } else {
+                next.insert(info.id, from_lua(info, &value)?);
+            }
+        }
+        Ok(next)
+    }
+}
+
+fn to_lua<'lua>(lua: &'lua Lua, info: &AspectInfo, value: &AspectValue) -> mlua::Result<Value<'lua>> {
+    macro_rules! integers {
+        ($($t:ty),*) => {
+            $(if let Some(v) = value.downcast_ref::<$t>() {
+                return i64::try_from(*v).map(Value::Integer).map_err(|_| {
+                    mlua::Error::RuntimeError(format!("方面 `{}` 的取值 {v} 超出 Lua 整数范围", info.name))
+                });
+            })*
+        };
+    }
+    integers!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+    Ok(if let Some(v) = value.downcast_ref::<f64>() {
+        Value::Number(*v)
+    } else if let Some(v) = value.downcast_ref::<f32>() {
+        Value::Number(f64::from(*v))
+    } else if let Some(v) = value.downcast_ref::<bool>() {
+        Value::Boolean(*v)
+    } else if let Some(v) = value.downcast_ref::<String>() {
+        Value::String(lua.create_string(v)?)
+    } else {
+        Value::String(lua.create_string(info.format(value))?)
+    })
+}
+
+fn from_lua(info: &AspectInfo, value: &Value) -> mlua::Result<AspectValue> {
+    let ty = info.value_type_id;
+    // 整数方面接受整数或没有小数部分的浮点数，浮点方面接受任意数值
+    let integer = match value {
+        Value::Integer(v) => Some(*v),
+        Value::Number(v) if (*v as i64) as f64 == *v => Some(*v as i64),
+        _ => None,
+    };
+    let number = match value {
+        Value::Integer(v) => Some(*v as f64),
+        Value::Number(v) => Some(*v),
+        _ => None,
+    };
+    macro_rules! integers {
+        ($($t:ty),*) => {
+            $(if ty == TypeId::of::<$t>() {
+                integer.and_then(|v| <$t>::try_from(v).ok()).map(|v| Arc::new(v) as AspectValue)
+            } else)* { None }
+        };
+    }
+    let converted = if ty == TypeId::of::<f64>() {
+        number.map(|v| Arc::new(v) as AspectValue)
+    } else if ty == TypeId::of::<f32>() {
+        number.map(|v| Arc::new(v as f32) as AspectValue)
+    } else if let Value::Boolean(v) = value && ty == TypeId::of::<bool>() {
+        Some(Arc::new(*v) as AspectValue)
+    } else if let Value::String(s) = value && ty == TypeId::of::<String>() {
+        Some(Arc::new(s.to_str()?.to_string()) as AspectValue)
+    } else {
+        integers!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize)
+    };
+    converted.ok_or_else(|| {
+        mlua::Error::RuntimeError(format!("方面 `{}`（{}）不接受脚本写入的 {}", info.name, info.type_name(), value.type_name()))
+    })
+}
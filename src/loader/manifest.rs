@@ -0,0 +1,522 @@
+//! 蓝图结构清单
+//!
+//! `BlueprintManifest` 记录蓝图的全部结构数据：各条目的ID、名称、优先级、标签，
+//! 守卫与转换函数的表达式树，以及对工厂注册表中具名工厂的引用。
+//! 清单可序列化为 JSON，便于把蓝图纳入版本管理并对比差异。
+//!
+//! 只记录结构：`on_tran`、观察者回调、发出事件的负载渲染、规范化转换、派生方面与连续转换都不在清单中。
+//! 闭包构造、无法还原的部分记为 `Opaque`（附带其描述），`from_manifest` 遇到时报错；
+//! 由 `Registry` 的具名工厂构造的守卫与转换记为 `Call`，还原时按名称与参数重新调用工厂。
+//! 常量取值只支持 `i32`、`i64`、`u32`、`u64`、`f32`、`f64`、`bool` 与 `String`
+
+use std::any::TypeId;
+use std::ops::Bound;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::core::types::{StateAspectId, EventId, TransitionId, ObserverId};
+use crate::core::state_aspect::StateAspect;
+use crate::core::state_in_range::{StateInRange, GuardExpr, GuardValue};
+use crate::core::transfer::{Transfer, TransferExpr, UpdateOp};
+use crate::core::event::{EventDef, EventTemplate};
+use crate::core::transition::Transition;
+use crate::core::state_observer::StateObserver;
+use crate::core::edge_observer::EdgeObserver;
+use crate::core::blueprint::StateMachineBlueprint;
+use crate::core::registry::AspectRegistry;
+use super::registry::{Registry, ArgValue};
+use super::json::LoadError;
+
+/// 蓝图结构清单
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlueprintManifest {
+    /// 蓝图版本
+    pub version: u32,
+    /// 方面（按ID升序）
+    pub aspects: Vec<AspectManifest>,
+    /// 事件（按ID升序）
+    pub events: Vec<EventManifest>,
+    /// 转换（按蓝图顺序）
+    pub transitions: Vec<TransitionManifest>,
+    /// 状态观察者（按蓝图顺序）
+    pub observers: Vec<ObserverManifest>,
+    /// 条件边沿观察者（按蓝图顺序）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edge_observers: Vec<EdgeObserverManifest>,
+    /// 终止区域
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_region: Option<GuardNode>,
+    /// 受保护的方面（按ID升序）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protected: Vec<StateAspectId>,
+}
+
+/// 方面
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AspectManifest {
+    pub id: StateAspectId,
+    /// 注册表中的名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// 取值类型名称，标量为 `i64` 等，其余取自注册表，未知时为 `?`
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
+/// 事件
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventManifest {
+    pub id: EventId,
+    /// 负载类型名称，无负载时为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+}
+
+/// 转换
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransitionManifest {
+    pub id: TransitionId,
+    pub event: EventId,
+    #[serde(default)]
+    pub priority: i32,
+    pub guard: GuardNode,
+    pub transfer: TransferNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// 发出的事件ID
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emits: Vec<EventId>,
+    /// 最短停留时间（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_dwell_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ensures: Option<GuardNode>,
+}
+
+/// 状态观察者
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObserverManifest {
+    pub id: ObserverId,
+    pub region: GuardNode,
+    /// 激活区域
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_when: Option<GuardNode>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// 条件边沿观察者
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EdgeObserverManifest {
+    pub id: ObserverId,
+    pub condition: GuardNode,
+}
+
+/// 常量取值
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestValue {
+    I32(i32),
+    I64(i64),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    String(String),
+}
+
+/// 区间端点
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoundNode {
+    pub value: ManifestValue,
+    pub inclusive: bool,
+}
+
+/// 守卫表达式树
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardNode {
+    Always,
+    Never,
+    Eq { aspect: StateAspectId, value: ManifestValue },
+    Range {
+        aspect: StateAspectId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<BoundNode>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<BoundNode>,
+    },
+    And(Box<GuardNode>, Box<GuardNode>),
+    Or(Box<GuardNode>, Box<GuardNode>),
+    Not(Box<GuardNode>),
+    /// 对具名守卫工厂的调用
+    Call { name: String, args: Vec<ArgValue> },
+    /// 无法还原的部分及其描述
+    Opaque(String),
+}
+
+/// 转换函数表达式树
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferNode {
+    Set { aspect: StateAspectId, value: ManifestValue },
+    Add { aspect: StateAspectId, delta: ManifestValue },
+    Sub { aspect: StateAspectId, delta: ManifestValue },
+    /// 依次执行
+    Then(Box<TransferNode>, Box<TransferNode>),
+    /// 对具名转换工厂的调用
+    Call { name: String, args: Vec<ArgValue> },
+    /// 无法还原的部分及其描述
+    Opaque(String),
+}
+
+impl BlueprintManifest {
+    /// 序列化为带缩进的 JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// 从 JSON 解析
+    pub fn from_json(source: &str) -> Result<Self, LoadError> {
+        serde_json::from_str(source).map_err(|e| LoadError::Parse(e.to_string()))
+    }
+}
+
+/// 标量类型的名称
+fn scalar_name(type_id: TypeId) -> Option<&'static str> {
+    macro_rules! scalar {
+        ($($t:ty => $name:literal),*) => {
+            $(if type_id == TypeId::of::<$t>() {
+                return Some($name);
+            })*
+        };
+    }
+    scalar!(() => "()", i32 => "i32", i64 => "i64", u32 => "u32", u64 => "u64",
+        f32 => "f32", f64 => "f64", bool => "bool", String => "String");
+    None
+}
+
+/// 按名称还原类型：先查标量，再查注册表
+fn type_by_name(name: &str, registry: &Registry) -> Result<TypeId, LoadError> {
+    macro_rules! scalar {
+        ($($t:ty),*) => {
+            $(if scalar_name(TypeId::of::<$t>()) == Some(name) {
+                return Ok(TypeId::of::<$t>());
+            })*
+        };
+    }
+    scalar!((), i32, i64, u32, u64, f32, f64, bool, String);
+    registry.type_id(name).ok_or_else(|| LoadError::UnknownType(name.to_string()))
+}
+
+/// 类型擦除的常量取值，类型不是支持的标量时为 `None`
+fn manifest_value(value: &GuardValue) -> Option<ManifestValue> {
+    macro_rules! scalar {
+        ($($t:ty => $variant:ident),*) => {
+            $(if let Some(v) = value.downcast_ref::<$t>() {
+                return Some(ManifestValue::$variant(v.clone()));
+            })*
+        };
+    }
+    scalar!(i32 => I32, i64 => I64, u32 => U32, u64 => U64, f32 => F32, f64 => F64, bool => Bool, String => String);
+    None
+}
+
+fn guard_node(expr: &GuardExpr, names: &AspectRegistry) -> GuardNode {
+    let bound = |b: &Bound<GuardValue>| match b {
+        Bound::Included(v) => manifest_value(v).map(|value| Some(BoundNode { value, inclusive: true })),
+        Bound::Excluded(v) => manifest_value(v).map(|value| Some(BoundNode { value, inclusive: false })),
+        Bound::Unbounded => Some(None),
+    };
+    let node = match expr {
+        GuardExpr::Always => Some(GuardNode::Always),
+        GuardExpr::Never => Some(GuardNode::Never),
+        GuardExpr::AspectEq { aspect, value } => {
+            manifest_value(value).map(|value| GuardNode::Eq { aspect: *aspect, value })
+        }
+        GuardExpr::Range { aspect, lo, hi } => match (bound(lo), bound(hi)) {
+            (Some(min), Some(max)) => Some(GuardNode::Range { aspect: *aspect, min, max }),
+            _ => None,
+        },
+        GuardExpr::And(a, b) => Some(GuardNode::And(Box::new(guard_node(a, names)), Box::new(guard_node(b, names)))),
+        GuardExpr::Or(a, b) => Some(GuardNode::Or(Box::new(guard_node(a, names)), Box::new(guard_node(b, names)))),
+        GuardExpr::Not(a) => Some(GuardNode::Not(Box::new(guard_node(a, names)))),
+        GuardExpr::Named { name, args, .. } => Some(GuardNode::Call { name: name.clone(), args: args.clone() }),
+        GuardExpr::Opaque(_) => None,
+    };
+    node.unwrap_or_else(|| GuardNode::Opaque(expr.describe(names)))
+}
+
+fn transfer_node(expr: &TransferExpr, names: &AspectRegistry) -> TransferNode {
+    let arith = |aspect: StateAspectId, op: &UpdateOp| {
+        let (delta, add) = match op {
+            UpdateOp::Add(v) => (v, true),
+            UpdateOp::Sub(v) => (v, false),
+        };
+        macro_rules! scalar {
+            ($($t:ty => $variant:ident),*) => {
+                $(if let Some(v) = delta.downcast_ref::<$t>() {
+                    let delta = ManifestValue::$variant(v.clone());
+                    return Some(if add { TransferNode::Add { aspect, delta } } else { TransferNode::Sub { aspect, delta } });
+                })*
+            };
+        }
+        scalar!(i32 => I32, i64 => I64, u32 => U32, u64 => U64, f32 => F32, f64 => F64);
+        None
+    };
+    let node = match expr {
+        TransferExpr::Set { aspect, value } => {
+            manifest_value(value).map(|value| TransferNode::Set { aspect: *aspect, value })
+        }
+        TransferExpr::Update { aspect, op } => arith(*aspect, op),
+        TransferExpr::Compose(a, b) => {
+            Some(TransferNode::Then(Box::new(transfer_node(a, names)), Box::new(transfer_node(b, names))))
+        }
+        TransferExpr::Named { name, args, .. } => Some(TransferNode::Call { name: name.clone(), args: args.clone() }),
+        TransferExpr::Opaque => None,
+    };
+    node.unwrap_or_else(|| TransferNode::Opaque(expr.describe(names)))
+}
+
+/// 还原时出错的条目，用于错误信息
+struct Context<'a> {
+    item: &'a str,
+}
+
+impl Context<'_> {
+    fn opaque(&self, description: &str) -> LoadError {
+        LoadError::Opaque(format!("{}：{description}", self.item))
+    }
+
+    fn invalid(&self, value: &ManifestValue) -> LoadError {
+        LoadError::InvalidValue {
+            aspect: self.item.to_string(),
+            value: format!("{value:?}"),
+        }
+    }
+
+    fn guard(&self, node: &GuardNode, registry: &Registry) -> Result<StateInRange, LoadError> {
+        Ok(match node {
+            GuardNode::Always => StateInRange::always(),
+            GuardNode::Never => StateInRange::never(),
+            GuardNode::Eq { aspect, value } => match value.clone() {
+                ManifestValue::I32(v) => StateInRange::aspect_eq(*aspect, v),
+                ManifestValue::I64(v) => StateInRange::aspect_eq(*aspect, v),
+                ManifestValue::U32(v) => StateInRange::aspect_eq(*aspect, v),
+                ManifestValue::U64(v) => StateInRange::aspect_eq(*aspect, v),
+                ManifestValue::F32(v) => StateInRange::aspect_eq(*aspect, v),
+                ManifestValue::F64(v) => StateInRange::aspect_eq(*aspect, v),
+                ManifestValue::Bool(v) => StateInRange::aspect_eq(*aspect, v),
+                ManifestValue::String(v) => StateInRange::aspect_eq(*aspect, v),
+            },
+            GuardNode::Range { aspect, min, max } => self.range(*aspect, min.as_ref(), max.as_ref())?,
+            GuardNode::And(a, b) => self.guard(a, registry)?.and(self.guard(b, registry)?),
+            GuardNode::Or(a, b) => self.guard(a, registry)?.or(self.guard(b, registry)?),
+            GuardNode::Not(a) => self.guard(a, registry)?.not(),
+            GuardNode::Call { name, args } => registry.guard(name, args)?,
+            GuardNode::Opaque(description) => return Err(self.opaque(description)),
+        })
+    }
+
+    fn range(&self, aspect: StateAspectId, min: Option<&BoundNode>, max: Option<&BoundNode>) -> Result<StateInRange, LoadError> {
+        macro_rules! typed {
+            ($($variant:ident),*) => {{
+                let value = min.or(max).map(|b| &b.value);
+                match value {
+                    None => return Ok(StateInRange::always()),
+                    $(Some(ManifestValue::$variant(_)) => {
+                        let bound = |b: Option<&BoundNode>| match b {
+                            None => Ok(Bound::Unbounded),
+                            Some(BoundNode { value: ManifestValue::$variant(v), inclusive: true }) => Ok(Bound::Included(v.clone())),
+                            Some(BoundNode { value: ManifestValue::$variant(v), inclusive: false }) => Ok(Bound::Excluded(v.clone())),
+                            Some(other) => Err(self.invalid(&other.value)),
+                        };
+                        Ok(StateInRange::aspect_in(aspect, (bound(min)?, bound(max)?)))
+                    })*
+                }
+            }};
+        }
+        typed!(I32, I64, U32, U64, F32, F64, Bool, String)
+    }
+
+    fn transfer(&self, node: &TransferNode, registry: &Registry) -> Result<Transfer, LoadError> {
+        macro_rules! arith {
+            ($f:path, $aspect:expr, $delta:expr) => {
+                match $delta.clone() {
+                    ManifestValue::I32(v) => $f($aspect, v),
+                    ManifestValue::I64(v) => $f($aspect, v),
+                    ManifestValue::U32(v) => $f($aspect, v),
+                    ManifestValue::U64(v) => $f($aspect, v),
+                    ManifestValue::F32(v) => $f($aspect, v),
+                    ManifestValue::F64(v) => $f($aspect, v),
+                    other => return Err(self.invalid(&other)),
+                }
+            };
+        }
+        Ok(match node {
+            TransferNode::Set { aspect, value } => match value.clone() {
+                ManifestValue::I32(v) => Transfer::set(*aspect, v),
+                ManifestValue::I64(v) => Transfer::set(*aspect, v),
+                ManifestValue::U32(v) => Transfer::set(*aspect, v),
+                ManifestValue::U64(v) => Transfer::set(*aspect, v),
+                ManifestValue::F32(v) => Transfer::set(*aspect, v),
+                ManifestValue::F64(v) => Transfer::set(*aspect, v),
+                ManifestValue::Bool(v) => Transfer::set(*aspect, v),
+                ManifestValue::String(v) => Transfer::set(*aspect, v),
+            },
+            TransferNode::Add { aspect, delta } => arith!(Transfer::add, *aspect, delta),
+            TransferNode::Sub { aspect, delta } => arith!(Transfer::sub, *aspect, delta),
+            TransferNode::Then(a, b) => self.transfer(a, registry)?.then(self.transfer(b, registry)?),
+            TransferNode::Call { name, args } => registry.transfer(name, args)?,
+            TransferNode::Opaque(description) => return Err(self.opaque(description)),
+        })
+    }
+}
+
+impl StateMachineBlueprint {
+    /// 导出结构清单，方面以 `#<id>` 描述
+    pub fn to_manifest(&self) -> BlueprintManifest {
+        self.to_manifest_with(&AspectRegistry::new())
+    }
+
+    /// 导出结构清单，方面名称与非标量类型名称取自注册表
+    pub fn to_manifest_with(&self, names: &AspectRegistry) -> BlueprintManifest {
+        let mut aspects: Vec<AspectManifest> = self
+            .aspects()
+            .map(|a| AspectManifest {
+                id: a.id,
+                name: names.get(a.id).map(|info| info.name.clone()),
+                type_name: scalar_name(a.value_type_id)
+                    .map(str::to_string)
+                    .or_else(|| names.get(a.id).map(|info| info.type_name()))
+                    .unwrap_or_else(|| "?".to_string()),
+            })
+            .collect();
+        aspects.sort_by_key(|a| a.id);
+        let mut events: Vec<EventManifest> = self
+            .events()
+            .map(|e| EventManifest {
+                id: e.id,
+                payload: (e.payload_type_id != TypeId::of::<()>())
+                    .then(|| scalar_name(e.payload_type_id).unwrap_or("?").to_string()),
+            })
+            .collect();
+        events.sort_by_key(|e| e.id);
+
+        BlueprintManifest {
+            version: self.version(),
+            aspects,
+            events,
+            transitions: self
+                .transitions()
+                .map(|t| TransitionManifest {
+                    id: t.id,
+                    event: t.event_id,
+                    priority: t.priority,
+                    guard: guard_node(t.guard.expr(), names),
+                    transfer: transfer_node(t.transfer.expr(), names),
+                    tag: t.tag.clone(),
+                    emits: t.emits.iter().map(|e| e.event_id).collect(),
+                    min_dwell_ms: t.min_dwell.map(|d| d.as_millis() as u64),
+                    ensures: t.ensures.as_ref().map(|e| guard_node(e.expr(), names)),
+                })
+                .collect(),
+            observers: self
+                .observers()
+                .map(|o| ObserverManifest {
+                    id: o.id,
+                    region: guard_node(o.region.expr(), names),
+                    active_when: o.active_when.as_ref().map(|r| guard_node(r.expr(), names)),
+                    priority: o.priority,
+                    tag: o.tag.clone(),
+                })
+                .collect(),
+            edge_observers: self
+                .edge_observers()
+                .map(|e| EdgeObserverManifest {
+                    id: e.id,
+                    condition: guard_node(e.condition.expr(), names),
+                })
+                .collect(),
+            final_region: self.final_region().map(|r| guard_node(r.expr(), names)),
+            protected: self.protected_aspects().collect(),
+        }
+    }
+
+    /// 由结构清单还原蓝图
+    ///
+    /// `Call` 节点按名称调用 `registry` 中的工厂，非标量方面与负载的类型从 `registry` 按名称查找；
+    /// 遇到 `Opaque` 节点返回 `LoadError::Opaque`。回调类字段（`on_tran`、观察者回调等）为空
+    pub fn from_manifest(manifest: &BlueprintManifest, registry: &Registry) -> Result<Self, LoadError> {
+        let mut blueprint = Self::new();
+        blueprint.set_version(manifest.version);
+        for aspect in &manifest.aspects {
+            blueprint.add_aspect(StateAspect::new(aspect.id, type_by_name(&aspect.type_name, registry)?))?;
+        }
+        for event in &manifest.events {
+            blueprint.add_event(EventDef {
+                id: event.id,
+                payload_type_id: match &event.payload {
+                    Some(name) => type_by_name(name, registry)?,
+                    None => TypeId::of::<()>(),
+                },
+                transformers: Vec::new(),
+            })?;
+        }
+        for t in &manifest.transitions {
+            let item = format!("转换 {}", t.id);
+            let cx = Context { item: &item };
+            blueprint.add_transition(Transition {
+                id: t.id,
+                event_id: t.event,
+                guard: cx.guard(&t.guard, registry)?,
+                transfer: cx.transfer(&t.transfer, registry)?,
+                priority: t.priority,
+                on_tran: None,
+                emits: t.emits.iter().map(|e| EventTemplate::new(*e)).collect(),
+                tag: t.tag.clone(),
+                min_dwell: t.min_dwell_ms.map(Duration::from_millis),
+                ensures: t.ensures.as_ref().map(|e| cx.guard(e, registry)).transpose()?,
+                respond: None,
+            })?;
+        }
+        for o in &manifest.observers {
+            let item = format!("观察者 {}", o.id);
+            blueprint.add_observer(StateObserver {
+                id: o.id,
+                region: Context { item: &item }.guard(&o.region, registry)?,
+                on_enter: None,
+                on_exit: None,
+                priority: o.priority,
+                on_enter_consume: None,
+                active_when: o.active_when.as_ref().map(|r| Context { item: &item }.guard(r, registry)).transpose()?,
+                tag: o.tag.clone(),
+            })?;
+        }
+        for e in &manifest.edge_observers {
+            let item = format!("边沿观察者 {}", e.id);
+            blueprint.add_edge_observer(EdgeObserver {
+                id: e.id,
+                condition: Context { item: &item }.guard(&e.condition, registry)?,
+                on_rising: None,
+                on_falling: None,
+            })?;
+        }
+        if let Some(region) = &manifest.final_region {
+            blueprint.set_final_region(Context { item: "终止区域" }.guard(region, registry)?);
+        }
+        for id in &manifest.protected {
+            blueprint.protect_aspect(*id);
+        }
+        Ok(blueprint)
+    }
+}
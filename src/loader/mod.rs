@@ -0,0 +1,17 @@
+//! 蓝图加载模块
+//!
+//! 从数据文件构造蓝图，供调试器等工具在不重新编译的情况下使用
+
+pub mod registry;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "json")]
+pub mod manifest;
+#[cfg(feature = "json")]
+pub mod session;
+#[cfg(feature = "expr")]
+pub mod expr;
+#[cfg(feature = "lua")]
+pub mod lua;
+
+pub use registry::{Registry, ArgValue, FactoryError, FromArg, FromArgs};
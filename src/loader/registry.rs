@@ -0,0 +1,258 @@
+//! 具名守卫/转换工厂注册表
+//!
+//! 目前只有 JSON 加载器（`loader::json`，需启用 `json` 特性）与命令行调试器使用，
+//! 按数据文件中的名称和参数构造守卫与转换
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use crate::core::types::StateAspectId;
+use crate::core::state_in_range::StateInRange;
+use crate::core::transfer::Transfer;
+pub use crate::core::arg::ArgValue;
+
+/// 工厂调用错误
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FactoryError {
+    /// 未注册的工厂
+    Unknown(String),
+    /// 参数个数不符
+    Arity { name: String, expected: usize, found: usize },
+    /// 参数类型不符
+    Argument { name: String, index: usize },
+}
+
+impl fmt::Display for FactoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(f, "工厂 `{name}` 未注册"),
+            Self::Arity { name, expected, found } => {
+                write!(f, "工厂 `{name}` 需要 {expected} 个参数，实际为 {found} 个")
+            }
+            Self::Argument { name, index } => write!(f, "工厂 `{name}` 的第 {index} 个参数类型不符"),
+        }
+    }
+}
+
+impl std::error::Error for FactoryError {}
+
+/// 可从工厂参数转换的类型
+pub trait FromArg: Sized {
+    fn from_arg(arg: &ArgValue) -> Option<Self>;
+}
+
+macro_rules! impl_from_arg_int {
+    ($($t:ty),*) => {$(
+        impl FromArg for $t {
+            fn from_arg(arg: &ArgValue) -> Option<Self> {
+                match arg {
+                    ArgValue::Int(v) => (*v).try_into().ok(),
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_arg_int!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl FromArg for f64 {
+    fn from_arg(arg: &ArgValue) -> Option<Self> {
+        match arg {
+            ArgValue::Float(v) => Some(*v),
+            ArgValue::Int(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromArg for f32 {
+    fn from_arg(arg: &ArgValue) -> Option<Self> {
+        f64::from_arg(arg).map(|v| v as f32)
+    }
+}
+
+impl FromArg for bool {
+    fn from_arg(arg: &ArgValue) -> Option<Self> {
+        match arg {
+            ArgValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl FromArg for String {
+    fn from_arg(arg: &ArgValue) -> Option<Self> {
+        match arg {
+            ArgValue::Str(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// 可从参数列表转换的类型：单个 `FromArg` 类型或由它们组成的元组
+pub trait FromArgs: Sized {
+    /// 参数个数
+    const ARITY: usize;
+    /// 逐个转换参数，返回第一个类型不符的下标
+    fn from_args(args: &[ArgValue]) -> Result<Self, usize>;
+}
+
+impl FromArgs for () {
+    const ARITY: usize = 0;
+    fn from_args(_: &[ArgValue]) -> Result<Self, usize> {
+        Ok(())
+    }
+}
+
+impl<T: FromArg> FromArgs for T {
+    const ARITY: usize = 1;
+    fn from_args(args: &[ArgValue]) -> Result<Self, usize> {
+        T::from_arg(&args[0]).ok_or(0)
+    }
+}
+
+macro_rules! impl_from_args_tuple {
+    ($n:expr; $($name:ident $idx:tt),+) => {
+        impl<$($name: FromArg),+> FromArgs for ($($name,)+) {
+            const ARITY: usize = $n;
+            fn from_args(args: &[ArgValue]) -> Result<Self, usize> {
+                Ok(($($name::from_arg(&args[$idx]).ok_or($idx as usize)?,)+))
+            }
+        }
+    };
+}
+
+impl_from_args_tuple!(2; A 0, B 1);
+impl_from_args_tuple!(3; A 0, B 1, C 2);
+impl_from_args_tuple!(4; A 0, B 1, C 2, D 3);
+
+/// 按参数列表构造 `R` 的工厂
+pub type Factory<R> = Arc<dyn Fn(&[ArgValue]) -> Result<R, FactoryError> + Send + Sync>;
+/// 守卫工厂
+pub type GuardFactory = Factory<StateInRange>;
+/// 转换工厂
+pub type TransferFactory = Factory<Transfer>;
+
+fn typed<A, R, F>(name: &str, f: F) -> Factory<R>
+where
+    A: FromArgs,
+    F: Fn(A) -> R + Send + Sync + 'static,
+{
+    let name = name.to_string();
+    Arc::new(move |args| {
+        if args.len() != A::ARITY {
+            return Err(FactoryError::Arity {
+                name: name.clone(),
+                expected: A::ARITY,
+                found: args.len(),
+            });
+        }
+        let args = A::from_args(args).map_err(|index| FactoryError::Argument {
+            name: name.clone(),
+            index,
+        })?;
+        Ok(f(args))
+    })
+}
+
+/// 具名工厂注册表
+/// 宿主程序注册具名的守卫与转换工厂，数据文件按名称和参数引用它们；
+/// 注册表是普通的值，不存在全局状态
+#[derive(Clone, Default)]
+pub struct Registry {
+    guards: HashMap<String, GuardFactory>,
+    transfers: HashMap<String, TransferFactory>,
+    types: HashMap<String, TypeId>,
+}
+
+impl Registry {
+    /// 创建一个空注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建一个带内置通用工厂的注册表，方面以ID作为第一个参数：
+    ///
+    /// - 守卫：`always()`、`never()`、`int_eq(aspect, v)`、`int_at_most(aspect, v)`、
+    ///   `int_at_least(aspect, v)`、`bool_eq(aspect, v)`、`string_eq(aspect, v)`
+    /// - 转换：`set_int(aspect, v)`、`add_int(aspect, delta)`、`set_bool(aspect, v)`、
+    ///   `set_string(aspect, v)`
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register_guard("always", |()| StateInRange::always())
+            .register_guard("never", |()| StateInRange::never())
+            .register_guard("int_eq", |(id, v): (StateAspectId, i64)| StateInRange::aspect_eq(id, v))
+            .register_guard("int_at_most", |(id, v): (StateAspectId, i64)| StateInRange::aspect_in(id, ..=v))
+            .register_guard("int_at_least", |(id, v): (StateAspectId, i64)| StateInRange::aspect_in(id, v..))
+            .register_guard("bool_eq", |(id, v): (StateAspectId, bool)| StateInRange::aspect_eq(id, v))
+            .register_guard("string_eq", |(id, v): (StateAspectId, String)| StateInRange::aspect_eq(id, v))
+            .register_transfer("set_int", |(id, v): (StateAspectId, i64)| Transfer::set(id, v))
+            .register_transfer("add_int", |(id, v): (StateAspectId, i64)| Transfer::add(id, v))
+            .register_transfer("set_bool", |(id, v): (StateAspectId, bool)| Transfer::set(id, v))
+            .register_transfer("set_string", |(id, v): (StateAspectId, String)| Transfer::set(id, v));
+        registry
+    }
+
+    /// 注册守卫工厂，参数按 `A` 的类型从数据中转换，例如
+    /// `registry.register_guard("hunger_at_most", |n: i32| StateInRange::aspect_in(HUNGER, ..=n))`
+    pub fn register_guard<A, F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        A: FromArgs,
+        F: Fn(A) -> StateInRange + Send + Sync + 'static,
+    {
+        self.guards.insert(name.to_string(), typed(name, f));
+        self
+    }
+
+    /// 注册转换工厂
+    pub fn register_transfer<A, F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        A: FromArgs,
+        F: Fn(A) -> Transfer + Send + Sync + 'static,
+    {
+        self.transfers.insert(name.to_string(), typed(name, f));
+        self
+    }
+
+    /// 按名称和参数构造守卫
+    /// 结果在结构描述中记为 `GuardExpr::Named`，可被 `BlueprintManifest` 按名称引用
+    pub fn guard(&self, name: &str, args: &[ArgValue]) -> Result<StateInRange, FactoryError> {
+        let factory = self.guards.get(name).ok_or_else(|| FactoryError::Unknown(name.to_string()))?;
+        Ok(factory(args)?.named(name, args))
+    }
+
+    /// 按名称和参数构造转换
+    /// 结果在结构描述中记为 `TransferExpr::Named`
+    pub fn transfer(&self, name: &str, args: &[ArgValue]) -> Result<Transfer, FactoryError> {
+        let factory = self.transfers.get(name).ok_or_else(|| FactoryError::Unknown(name.to_string()))?;
+        Ok(factory(args)?.named(name, args))
+    }
+
+    /// 注册具名的取值类型，供 `BlueprintManifest` 还原非标量方面与事件负载的类型
+    pub fn register_type<T: 'static>(&mut self, name: &str) -> &mut Self {
+        self.types.insert(name.to_string(), TypeId::of::<T>());
+        self
+    }
+
+    /// 按名称查找已注册的取值类型
+    pub fn type_id(&self, name: &str) -> Option<TypeId> {
+        self.types.get(name).copied()
+    }
+
+    /// 已注册的守卫工厂名称（按字母序）
+    pub fn guard_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.guards.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// 已注册的转换工厂名称（按字母序）
+    pub fn transfer_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.transfers.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
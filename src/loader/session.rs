@@ -0,0 +1,211 @@
+//! 调试会话文件
+//!
+//! `.szsession` 文件把蓝图清单与运行历史打包为一个 JSON 文档，供调试器离线打开分析，
+//! 便于在报告问题时直接附上会话文件。
+//!
+//! 取值类型为清单支持的标量时按类型记录，可以还原为状态；其余类型记为注册表格式化出的文本，
+//! 只用于展示
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::core::types::{StateAspectId, EventId, TransitionId};
+use crate::core::runtime::{State, AspectValue};
+use crate::core::blueprint::StateMachineBlueprint;
+use crate::core::registry::AspectRegistry;
+use crate::core::history::History;
+use super::json::LoadError;
+use super::manifest::{BlueprintManifest, ManifestValue};
+
+/// 会话文件的扩展名
+pub const SESSION_EXTENSION: &str = "szsession";
+
+/// 当前的会话文件格式版本
+pub const SESSION_FORMAT: u32 = 1;
+
+/// 会话中记录的方面取值
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionValue {
+    /// 标量取值
+    Value(ManifestValue),
+    /// 非标量取值的文本形式
+    Text(String),
+}
+
+impl SessionValue {
+    fn capture(id: StateAspectId, value: &AspectValue, names: &AspectRegistry) -> Self {
+        macro_rules! scalar {
+            ($($t:ty => $variant:ident),*) => {
+                $(if let Some(v) = value.downcast_ref::<$t>() {
+                    return Self::Value(ManifestValue::$variant(v.clone()));
+                })*
+            };
+        }
+        scalar!(i32 => I32, i64 => I64, u32 => U32, u64 => U64, f32 => F32, f64 => F64, bool => Bool, String => String);
+        Self::Text(names.format_value(id, value))
+    }
+
+    /// 还原为方面取值，文本形式的取值返回 `None`
+    pub fn to_aspect_value(&self) -> Option<AspectValue> {
+        let Self::Value(value) = self else {
+            return None;
+        };
+        Some(match value.clone() {
+            ManifestValue::I32(v) => Arc::new(v),
+            ManifestValue::I64(v) => Arc::new(v),
+            ManifestValue::U32(v) => Arc::new(v),
+            ManifestValue::U64(v) => Arc::new(v),
+            ManifestValue::F32(v) => Arc::new(v),
+            ManifestValue::F64(v) => Arc::new(v),
+            ManifestValue::Bool(v) => Arc::new(v),
+            ManifestValue::String(v) => Arc::new(v),
+        })
+    }
+}
+
+impl fmt::Display for SessionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(ManifestValue::I32(v)) => write!(f, "{v}"),
+            Self::Value(ManifestValue::I64(v)) => write!(f, "{v}"),
+            Self::Value(ManifestValue::U32(v)) => write!(f, "{v}"),
+            Self::Value(ManifestValue::U64(v)) => write!(f, "{v}"),
+            Self::Value(ManifestValue::F32(v)) => write!(f, "{v:?}"),
+            Self::Value(ManifestValue::F64(v)) => write!(f, "{v:?}"),
+            Self::Value(ManifestValue::Bool(v)) => write!(f, "{v}"),
+            Self::Value(ManifestValue::String(v)) => write!(f, "{v:?}"),
+            Self::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+/// 会话中记录的状态
+pub type SessionState = BTreeMap<StateAspectId, SessionValue>;
+
+/// 一次提交
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionEntry {
+    /// 触发提交的事件，连续转移提交时为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<EventId>,
+    /// 执行的转换，连续转移提交时为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<TransitionId>,
+    /// 提交前的状态
+    pub before: SessionState,
+    /// 提交后的状态
+    pub after: SessionState,
+}
+
+/// 调试会话
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Session {
+    /// 文件格式版本
+    pub format: u32,
+    /// 蓝图清单
+    pub manifest: BlueprintManifest,
+    /// 事件名称
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub event_names: BTreeMap<EventId, String>,
+    /// 运行历史（从旧到新）
+    pub entries: Vec<SessionEntry>,
+}
+
+impl Session {
+    /// 由蓝图与运行历史生成会话，方面名称与非标量取值的文本取自 `names`
+    pub fn capture(blueprint: &StateMachineBlueprint, names: &AspectRegistry, history: &History) -> Self {
+        let state = |state: &State| -> SessionState {
+            state.iter().map(|(id, value)| (*id, SessionValue::capture(*id, value, names))).collect()
+        };
+        Self {
+            format: SESSION_FORMAT,
+            manifest: blueprint.to_manifest_with(names),
+            event_names: BTreeMap::new(),
+            entries: history
+                .entries()
+                .iter()
+                .map(|entry| SessionEntry {
+                    event_id: entry.event_id,
+                    transition: entry.transition,
+                    before: state(&entry.before),
+                    after: state(&entry.after),
+                })
+                .collect(),
+        }
+    }
+
+    /// 附带事件名称
+    pub fn with_event_names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = (EventId, S)>,
+        S: Into<String>,
+    {
+        self.event_names.extend(names.into_iter().map(|(id, name)| (id, name.into())));
+        self
+    }
+
+    /// 序列化为带缩进的 JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// 从 JSON 解析，格式版本比当前更新时报错
+    pub fn from_json(source: &str) -> Result<Self, LoadError> {
+        let session: Self = serde_json::from_str(source).map_err(|e| LoadError::Parse(e.to_string()))?;
+        if session.format > SESSION_FORMAT {
+            return Err(LoadError::Parse(format!("不支持的会话格式版本 {}", session.format)));
+        }
+        Ok(session)
+    }
+
+    /// 写入会话文件
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), LoadError> {
+        std::fs::write(path, self.to_json()).map_err(|e| LoadError::Io(e.to_string()))
+    }
+
+    /// 读取会话文件
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let source = std::fs::read_to_string(path).map_err(|e| LoadError::Io(e.to_string()))?;
+        Self::from_json(&source)
+    }
+
+    /// 方面名称，未命名时为 `#<id>`
+    pub fn aspect_name(&self, id: StateAspectId) -> String {
+        self.manifest
+            .aspects
+            .iter()
+            .find(|a| a.id == id)
+            .and_then(|a| a.name.clone())
+            .unwrap_or_else(|| format!("#{id}"))
+    }
+
+    /// 事件名称，未命名时为ID
+    pub fn event_name(&self, id: EventId) -> String {
+        self.event_names.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    }
+
+    /// 按 `name=value, ...` 格式化状态
+    pub fn format_state(&self, state: &SessionState) -> String {
+        state
+            .iter()
+            .map(|(id, value)| format!("{}={value}", self.aspect_name(*id)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// 还原状态，文本形式的取值被略过
+    pub fn restore_state(state: &SessionState) -> State {
+        let mut restored = State::new();
+        for (id, value) in state {
+            if let Some(value) = value.to_aspect_value() {
+                restored.insert(*id, value);
+            }
+        }
+        restored
+    }
+}
@@ -0,0 +1,174 @@
+//! 运行时监控
+//!
+//! 通过追踪钩子收集一个或多个运行时的实时状态、转换速率和被忽略的事件数，
+//! 开启 `tui` 特性后可以用终端面板展示，开启 `remote-debug` 特性后可以通过 WebSocket 远程检查
+
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "remote-debug")]
+pub mod remote;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::core::types::{EventId, TransitionId};
+use crate::core::runtime::{RuntimeStateMachine, State};
+use crate::core::registry::AspectRegistry;
+use crate::core::trace::Tracer;
+use crate::core::label::MachineLabel;
+use crate::core::clock::{Clock, SystemClock};
+
+/// 单个运行时的统计快照
+#[derive(Clone, Debug, PartialEq)]
+pub struct MachineStats {
+    /// 运行时名称
+    pub name: String,
+    /// 运行时的实例标签
+    pub label: Option<MachineLabel>,
+    /// 当前状态的文本形式
+    pub state: String,
+    /// 收到的事件数
+    pub events: u64,
+    /// 执行的转换数
+    pub transitions: u64,
+    /// 被忽略（没有转换可执行）的事件数
+    pub ignored: u64,
+    /// 各事件被忽略的次数（按事件ID升序）
+    pub ignored_by_event: Vec<(EventId, u64)>,
+    /// 统计窗口内的转换速率（次/秒）
+    pub transition_rate: f64,
+}
+
+#[derive(Default)]
+struct ProbeData {
+    label: Option<MachineLabel>,
+    state: String,
+    events: u64,
+    transitions: u64,
+    ignored_by_event: BTreeMap<EventId, u64>,
+    recent: VecDeque<Duration>,
+}
+
+/// 监控探针
+/// 挂载到单个运行时上的追踪器
+pub struct MachineProbe {
+    name: String,
+    registry: AspectRegistry,
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    data: Mutex<ProbeData>,
+}
+
+impl MachineProbe {
+    fn prune(recent: &mut VecDeque<Duration>, window: Duration, now: Duration) {
+        while recent.front().is_some_and(|t| now.saturating_sub(*t) > window) {
+            recent.pop_front();
+        }
+    }
+
+    /// 当前统计快照
+    pub fn stats(&self) -> MachineStats {
+        let mut data = self.data.lock().unwrap();
+        Self::prune(&mut data.recent, self.window, self.clock.now());
+        MachineStats {
+            name: self.name.clone(),
+            label: data.label.clone(),
+            state: data.state.clone(),
+            events: data.events,
+            transitions: data.transitions,
+            ignored: data.ignored_by_event.values().sum(),
+            ignored_by_event: data.ignored_by_event.iter().map(|(e, n)| (*e, *n)).collect(),
+            transition_rate: data.recent.len() as f64 / self.window.as_secs_f64(),
+        }
+    }
+}
+
+impl Tracer for MachineProbe {
+    fn on_event(&self, event_id: EventId, selected: Option<TransitionId>) {
+        let mut data = self.data.lock().unwrap();
+        data.events += 1;
+        if selected.is_none() {
+            *data.ignored_by_event.entry(event_id).or_default() += 1;
+        }
+    }
+
+    fn on_transition(&self, _id: TransitionId, _prev: &State, _next: &State) {
+        let now = self.clock.now();
+        let mut data = self.data.lock().unwrap();
+        data.transitions += 1;
+        data.recent.push_back(now);
+        Self::prune(&mut data.recent, self.window, now);
+    }
+
+    fn on_commit(&self, _prev: &State, next: &State) {
+        let state = self.registry.format_state(next);
+        self.data.lock().unwrap().state = state;
+    }
+
+    fn on_label(&self, label: &MachineLabel) {
+        self.data.lock().unwrap().label = Some(label.clone());
+    }
+}
+
+/// 运行时监控器
+/// 可克隆，克隆体共享同一组探针，适合在监控线程中读取
+#[derive(Clone)]
+pub struct Monitor {
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    probes: Arc<Mutex<Vec<Arc<MachineProbe>>>>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Monitor {
+    /// 创建监控器，转换速率按最近 1 秒统计
+    pub fn new() -> Self {
+        Self::with_window(Duration::from_secs(1))
+    }
+
+    /// 创建监控器，转换速率按最近 `window` 统计
+    pub fn with_window(window: Duration) -> Self {
+        Self::with_clock(window, Arc::new(SystemClock::new()))
+    }
+
+    /// 创建监控器，转换速率按 `clock` 计时的最近 `window` 统计
+    pub fn with_clock(window: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            window,
+            clock,
+            probes: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// 把运行时挂载到监控器，返回挂载的探针
+    pub fn attach(
+        &self,
+        name: impl Into<String>,
+        registry: AspectRegistry,
+        runtime: &mut RuntimeStateMachine,
+    ) -> Arc<MachineProbe> {
+        let probe = Arc::new(MachineProbe {
+            name: name.into(),
+            window: self.window,
+            clock: self.clock.clone(),
+            data: Mutex::new(ProbeData {
+                state: registry.format_state(&runtime.current_state),
+                ..ProbeData::default()
+            }),
+            registry,
+        });
+        runtime.add_tracer(probe.clone());
+        self.probes.lock().unwrap().push(probe.clone());
+        probe
+    }
+
+    /// 全部运行时的统计快照（按挂载顺序）
+    pub fn snapshot(&self) -> Vec<MachineStats> {
+        self.probes.lock().unwrap().iter().map(|p| p.stats()).collect()
+    }
+}
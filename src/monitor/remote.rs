@@ -0,0 +1,301 @@
+//! WebSocket 远程调试
+//!
+//! `RemoteDebugger` 在后台线程监听 WebSocket 连接，作为追踪器挂载到运行时后，把追踪事件以 JSON
+//! 文本消息推送给所有客户端，便于从浏览器工具检查运行在无界面服务器中的状态机。
+//!
+//! 推送的消息：
+//!
+//! ```json
+//! {"type": "event", "event": 100, "selected": 1}
+//! {"type": "transition", "transition": 1}
+//! {"type": "observer", "observer": 1, "edge": "enter"}
+//! {"type": "commit", "state": {"action": "\"Walk\""}}
+//! {"type": "state", "state": {...}, "paused": false}
+//! {"type": "error", "message": "..."}
+//! ```
+//!
+//! 客户端可以发送命令：`{"command": "dump"}`、`{"command": "dispatch", "event": 100}`、
+//! `{"command": "pause"}`、`{"command": "resume"}`。运行时不归调试服务所有，命令先排队，
+//! 由宿主循环调用 `poll` 时在运行时上执行；暂停与恢复调用运行时的 `pause` / `resume`，
+//! 暂停期间分发的事件由运行时缓冲，恢复后按到达顺序处理。`dump` 的应答与命令错误只发给发出命令的客户端，
+//! 分发、暂停与恢复后的状态推送给所有客户端。
+//!
+//! 每个客户端的待推送消息有上限，跟不上推送速度的客户端被断开，不会拖慢运行时或占用无限内存；
+//! 没有客户端时追踪回调直接返回，不格式化状态。
+//!
+//! 服务只在调用 `bind` 时启动，`shutdown` 或丢弃服务时停止监听并断开所有客户端；
+//! 服务不做身份验证，应只监听本机或受信任网络的地址
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+use crate::core::types::{EventId, TransitionId, ObserverId};
+use crate::core::runtime::{RuntimeStateMachine, State};
+use crate::core::registry::AspectRegistry;
+use crate::core::trace::{Tracer, RegionEdge};
+
+/// 连接线程检查待推送消息的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 每个客户端最多积压的待推送消息数，超出时断开该客户端
+const CLIENT_BACKLOG: usize = 256;
+
+/// 推送给客户端的消息
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RemoteMessage {
+    /// 事件完成转换选择
+    Event { event: EventId, selected: Option<TransitionId> },
+    /// 转换被执行
+    Transition { transition: TransitionId },
+    /// 观察者进入（`enter`）或退出（`exit`）区域
+    Observer { observer: ObserverId, edge: String },
+    /// 新状态被提交
+    Commit { state: BTreeMap<String, String> },
+    /// 对 `dump` 等命令的应答
+    State { state: BTreeMap<String, String>, paused: bool },
+    /// 命令无法执行
+    Error { message: String },
+}
+
+/// 客户端发送的命令
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum RemoteCommand {
+    /// 取得当前状态
+    Dump,
+    /// 分发一个无负载的事件
+    Dispatch { event: EventId },
+    /// 暂停
+    Pause,
+    /// 恢复
+    Resume,
+}
+
+/// 已连接的客户端：连接编号与待推送消息的发送端
+type Clients = Arc<Mutex<Vec<(u64, SyncSender<String>)>>>;
+
+/// WebSocket 远程调试服务
+pub struct RemoteDebugger {
+    addr: SocketAddr,
+    registry: AspectRegistry,
+    clients: Clients,
+    /// 排队的命令及发出命令的客户端编号
+    commands: Mutex<Receiver<(u64, RemoteCommand)>>,
+    stopped: Arc<AtomicBool>,
+    acceptor: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl RemoteDebugger {
+    /// 在 `addr` 上启动服务，方面名称与取值格式取自 `registry`
+    pub fn bind(addr: impl ToSocketAddrs, registry: AspectRegistry) -> io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        // 非阻塞监听，以便定期检查服务是否已停止
+        listener.set_nonblocking(true)?;
+        let (command_tx, command_rx) = mpsc::channel();
+        let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let acceptor = {
+            let clients = clients.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || accept(listener, clients, command_tx, stopped))
+        };
+        Ok(Arc::new(Self {
+            addr,
+            registry,
+            clients,
+            commands: Mutex::new(command_rx),
+            stopped,
+            acceptor: Mutex::new(Some(acceptor)),
+        }))
+    }
+
+    /// 停止监听并断开所有客户端，返回时端口已释放；重复调用无效果
+    pub fn shutdown(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // 丢弃发送端后连接线程发现通道断开，关闭连接并结束
+        self.clients.lock().unwrap().clear();
+        if let Some(acceptor) = self.acceptor.lock().unwrap().take() {
+            let _ = acceptor.join();
+        }
+    }
+
+    /// 实际监听的地址
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 当前连接的客户端数
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    /// 在运行时上执行排队的命令，返回执行的命令数
+    pub fn poll(&self, runtime: &mut RuntimeStateMachine) -> usize {
+        let commands: Vec<(u64, RemoteCommand)> = self.commands.lock().unwrap().try_iter().collect();
+        for (client, command) in &commands {
+            match command {
+                RemoteCommand::Dump => {
+                    self.reply(*client, &RemoteMessage::State {
+                        state: self.describe(&runtime.current_state),
+                        paused: runtime.is_paused(),
+                    });
+                    continue;
+                }
+                RemoteCommand::Dispatch { event } => {
+                    if runtime.blueprint.event(*event).is_none() {
+                        self.reply(*client, &RemoteMessage::Error { message: format!("未知事件 {event}") });
+                        continue;
+                    }
+                    runtime.handle_event(*event, None);
+                }
+                RemoteCommand::Pause => runtime.pause(),
+                RemoteCommand::Resume => runtime.resume(),
+            }
+            self.broadcast(|| RemoteMessage::State {
+                state: self.describe(&runtime.current_state),
+                paused: runtime.is_paused(),
+            });
+        }
+        commands.len()
+    }
+
+    fn describe(&self, state: &State) -> BTreeMap<String, String> {
+        state
+            .iter()
+            .map(|(id, value)| (self.registry.name(*id), self.registry.format_value(*id, value)))
+            .collect()
+    }
+
+    /// 推送消息，没有客户端时不构造消息；断开或积压已满的客户端被移除
+    fn broadcast(&self, message: impl FnOnce() -> RemoteMessage) {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return;
+        }
+        let Ok(text) = serde_json::to_string(&message()) else {
+            return;
+        };
+        clients.retain(|(_, client)| client.try_send(text.clone()).is_ok());
+    }
+
+    /// 只发给一个客户端，积压已满的客户端被移除
+    fn reply(&self, client: u64, message: &RemoteMessage) {
+        let Ok(text) = serde_json::to_string(message) else {
+            return;
+        };
+        self.clients.lock().unwrap().retain(|(id, tx)| *id != client || tx.try_send(text.clone()).is_ok());
+    }
+}
+
+impl Tracer for RemoteDebugger {
+    fn on_event(&self, event_id: EventId, selected: Option<TransitionId>) {
+        self.broadcast(|| RemoteMessage::Event { event: event_id, selected });
+    }
+
+    fn on_transition(&self, transition_id: TransitionId, _prev: &State, _next: &State) {
+        self.broadcast(|| RemoteMessage::Transition { transition: transition_id });
+    }
+
+    fn on_observer(&self, observer_id: ObserverId, edge: RegionEdge) {
+        let edge = match edge {
+            RegionEdge::Enter => "enter",
+            RegionEdge::Exit => "exit",
+        };
+        self.broadcast(|| RemoteMessage::Observer { observer: observer_id, edge: edge.to_string() });
+    }
+
+    fn on_commit(&self, _prev: &State, next: &State) {
+        self.broadcast(|| RemoteMessage::Commit { state: self.describe(next) });
+    }
+}
+
+impl Drop for RemoteDebugger {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// 监听线程：接受连接直到服务停止
+fn accept(listener: TcpListener, clients: Clients, commands: Sender<(u64, RemoteCommand)>, stopped: Arc<AtomicBool>) {
+    let mut next_id = 0;
+    while !stopped.load(Ordering::SeqCst) {
+        let Ok((stream, _)) = listener.accept() else {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+        if stream.set_nonblocking(false).is_err() {
+            continue;
+        }
+        let id = next_id;
+        next_id += 1;
+        let (tx, rx) = mpsc::sync_channel(CLIENT_BACKLOG);
+        clients.lock().unwrap().push((id, tx));
+        let commands = commands.clone();
+        let clients = clients.clone();
+        thread::spawn(move || {
+            serve(stream, id, rx, commands);
+            // 连接结束后立即移除，客户端数不包含已断开的连接
+            clients.lock().unwrap().retain(|(client, _)| *client != id);
+        });
+    }
+}
+
+/// 单个连接：转发待推送的消息，解析客户端命令，连接断开、出错或被服务移除时结束
+fn serve(stream: TcpStream, id: u64, outgoing: Receiver<String>, commands: Sender<(u64, RemoteCommand)>) {
+    let Ok(mut socket) = tungstenite::accept(stream) else {
+        return;
+    };
+    if socket.get_ref().set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        return;
+    }
+    loop {
+        loop {
+            match outgoing.try_recv() {
+                Ok(text) => {
+                    if socket.send(Message::Text(text)).is_err() {
+                        return;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    let _ = socket.close(None);
+                    let _ = socket.flush();
+                    return;
+                }
+            }
+        }
+        match socket.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<RemoteCommand>(&text) {
+                Ok(command) => {
+                    if commands.send((id, command)).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    let error = RemoteMessage::Error { message: format!("无效的命令：{e}") };
+                    if !reply(&mut socket, &error) {
+                        return;
+                    }
+                }
+            },
+            Ok(Message::Close(_)) => return,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+/// 直接应答当前连接，返回是否发送成功
+fn reply(socket: &mut WebSocket<TcpStream>, message: &RemoteMessage) -> bool {
+    socket.send(Message::Text(serde_json::to_string(message).unwrap_or_default())).is_ok()
+}
@@ -0,0 +1,80 @@
+//! 终端监控面板
+
+use std::io;
+use std::time::Duration;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table};
+use super::{MachineStats, Monitor};
+
+/// 绘制一帧监控面板
+pub fn render(frame: &mut Frame, stats: &[MachineStats]) {
+    let [table_area, ignored_area, help_area] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let header = Row::new(["名称", "事件", "转换", "转换/秒", "忽略", "状态"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows = stats.iter().map(|s| {
+        Row::new([
+            s.name.clone(),
+            s.events.to_string(),
+            s.transitions.to_string(),
+            format!("{:.1}", s.transition_rate),
+            s.ignored.to_string(),
+            s.state.clone(),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Min(10),
+        ],
+    )
+    .header(header)
+    .block(Block::default().borders(Borders::ALL).title("state-zen 运行时"));
+    frame.render_widget(table, table_area);
+
+    let ignored: Vec<String> = stats
+        .iter()
+        .filter(|s| s.ignored > 0)
+        .map(|s| {
+            let by_event: Vec<String> = s.ignored_by_event.iter().map(|(e, n)| format!("{e}×{n}")).collect();
+            format!("{}: {}", s.name, by_event.join(" "))
+        })
+        .collect();
+    let ignored = Paragraph::new(Line::from(ignored.join("  |  ")))
+        .block(Block::default().borders(Borders::ALL).title("被忽略的事件"));
+    frame.render_widget(ignored, ignored_area);
+    frame.render_widget(Paragraph::new("q 退出"), help_area);
+}
+
+/// 运行监控面板，每隔 `refresh` 刷新一次，按 `q` 或 `Esc` 退出
+///
+/// 运行时在其他线程中驱动，面板只读取监控器的快照
+pub fn run(monitor: &Monitor, refresh: Duration) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = (|| loop {
+        terminal.draw(|frame| render(frame, &monitor.snapshot()))?;
+        if event::poll(refresh)?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+        {
+            return Ok(());
+        }
+    })();
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,155 @@
+//! 转换与观察者覆盖率统计
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use crate::core::types::{TransitionId, ObserverId};
+use crate::core::blueprint::StateMachineBlueprint;
+use crate::core::runtime::{RuntimeStateMachine, State};
+use crate::core::trace::{Tracer, RegionEdge};
+
+/// 覆盖率记录器
+/// 可以被多个运行时共享，在整个测试套件范围内累计触发过的转换和观察者
+#[derive(Default)]
+pub struct CoverageRecorder {
+    fired: Mutex<Fired>,
+}
+
+#[derive(Default)]
+struct Fired {
+    transitions: BTreeSet<TransitionId>,
+    observers: BTreeSet<ObserverId>,
+}
+
+impl CoverageRecorder {
+    /// 创建一个新的共享记录器
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// 以蓝图为分母生成覆盖率报告
+    pub fn report(&self, blueprint: &StateMachineBlueprint) -> CoverageReport {
+        let fired = self.fired.lock().unwrap();
+        let defined_transitions: BTreeSet<_> = blueprint.transitions().map(|t| t.id).collect();
+        let defined_observers: BTreeSet<_> = blueprint.observers().map(|o| o.id).collect();
+        CoverageReport {
+            fired_transitions: fired.transitions.intersection(&defined_transitions).copied().collect(),
+            fired_observers: fired.observers.intersection(&defined_observers).copied().collect(),
+            defined_transitions,
+            defined_observers,
+        }
+    }
+}
+
+impl Tracer for CoverageRecorder {
+    fn on_transition(&self, transition_id: TransitionId, _prev: &State, _next: &State) {
+        self.fired.lock().unwrap().transitions.insert(transition_id);
+    }
+
+    fn on_observer(&self, observer_id: ObserverId, _edge: RegionEdge) {
+        self.fired.lock().unwrap().observers.insert(observer_id);
+    }
+}
+
+/// 覆盖率报告
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// 蓝图中定义的转换
+    pub defined_transitions: BTreeSet<TransitionId>,
+    /// 触发过的转换
+    pub fired_transitions: BTreeSet<TransitionId>,
+    /// 蓝图中定义的观察者
+    pub defined_observers: BTreeSet<ObserverId>,
+    /// 进入或退出过区域的观察者
+    pub fired_observers: BTreeSet<ObserverId>,
+}
+
+impl CoverageReport {
+    /// 从未触发的转换
+    pub fn dead_transitions(&self) -> Vec<TransitionId> {
+        self.defined_transitions.difference(&self.fired_transitions).copied().collect()
+    }
+
+    /// 从未触发的观察者
+    pub fn dead_observers(&self) -> Vec<ObserverId> {
+        self.defined_observers.difference(&self.fired_observers).copied().collect()
+    }
+
+    /// 转换覆盖率（0.0 ~ 1.0），没有转换时为 1.0
+    pub fn transition_ratio(&self) -> f64 {
+        ratio(self.fired_transitions.len(), self.defined_transitions.len())
+    }
+
+    /// 观察者覆盖率（0.0 ~ 1.0），没有观察者时为 1.0
+    pub fn observer_ratio(&self) -> f64 {
+        ratio(self.fired_observers.len(), self.defined_observers.len())
+    }
+}
+
+fn ratio(fired: usize, defined: usize) -> f64 {
+    if defined == 0 { 1.0 } else { fired as f64 / defined as f64 }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "转换: {}/{} ({:.1}%)，未触发: {:?}",
+            self.fired_transitions.len(),
+            self.defined_transitions.len(),
+            self.transition_ratio() * 100.0,
+            self.dead_transitions(),
+        )?;
+        write!(
+            f,
+            "观察者: {}/{} ({:.1}%)，未触发: {:?}",
+            self.fired_observers.len(),
+            self.defined_observers.len(),
+            self.observer_ratio() * 100.0,
+            self.dead_observers(),
+        )
+    }
+}
+
+/// 记录覆盖率的测试运行时
+/// 包装一个运行时并挂载共享的覆盖率记录器，其余用法与运行时完全相同
+pub struct CoverageRuntime {
+    runtime: RuntimeStateMachine,
+    recorder: Arc<CoverageRecorder>,
+}
+
+impl CoverageRuntime {
+    /// 包装运行时
+    pub fn new(mut runtime: RuntimeStateMachine, recorder: &Arc<CoverageRecorder>) -> Self {
+        runtime.add_tracer(recorder.clone());
+        Self {
+            runtime,
+            recorder: recorder.clone(),
+        }
+    }
+
+    /// 以本运行时的蓝图为分母生成覆盖率报告（计数来自共享记录器）
+    pub fn report(&self) -> CoverageReport {
+        self.recorder.report(&self.runtime.blueprint)
+    }
+
+    /// 取回内部运行时
+    pub fn into_inner(self) -> RuntimeStateMachine {
+        self.runtime
+    }
+}
+
+impl Deref for CoverageRuntime {
+    type Target = RuntimeStateMachine;
+
+    fn deref(&self) -> &RuntimeStateMachine {
+        &self.runtime
+    }
+}
+
+impl DerefMut for CoverageRuntime {
+    fn deref_mut(&mut self) -> &mut RuntimeStateMachine {
+        &mut self.runtime
+    }
+}
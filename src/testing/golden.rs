@@ -0,0 +1,239 @@
+//! 金标准追踪测试
+//!
+//! 记录一次运行的完整追踪（事件、选中的转换、状态差异）并写入文件，
+//! 之后重跑相同输入时逐行比对，用于发现重构蓝图带来的意外行为变化。
+
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use crate::core::types::{EventId, TransitionId};
+use crate::core::runtime::State;
+use crate::core::registry::AspectRegistry;
+use crate::core::trace::Tracer;
+
+/// 该环境变量为 `1` 时，`assert_golden` 用本次追踪写入（或覆盖）金标准文件
+pub const UPDATE_GOLDEN_ENV: &str = "STATE_ZEN_UPDATE_GOLDEN";
+
+/// 单个方面的取值变化
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AspectChange {
+    /// 方面名称
+    pub aspect: String,
+    /// 旧值，`None` 表示之前不存在
+    pub before: Option<String>,
+    /// 新值，`None` 表示被移除
+    pub after: Option<String>,
+}
+
+/// 追踪记录
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEntry {
+    /// 事件及选中的转换（`None` 表示事件被忽略）
+    Event {
+        event_id: EventId,
+        transition: Option<TransitionId>,
+    },
+    /// 提交的状态差异
+    Commit(Vec<AspectChange>),
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Event { event_id, transition: Some(t) } => write!(f, "event {event_id} -> transition {t}"),
+            Self::Event { event_id, transition: None } => write!(f, "event {event_id} -> ignored"),
+            Self::Commit(changes) => {
+                write!(f, "commit")?;
+                for c in changes {
+                    let before = c.before.as_deref().unwrap_or("-");
+                    let after = c.after.as_deref().unwrap_or("-");
+                    write!(f, " | {}: {} -> {}", c.aspect, before, after)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 追踪记录器
+/// 作为 `Tracer` 挂载到运行时，借助方面注册表把状态差异格式化为文本
+pub struct TraceRecorder {
+    registry: AspectRegistry,
+    entries: Mutex<Vec<TraceEntry>>,
+}
+
+impl TraceRecorder {
+    /// 创建一个新的记录器
+    pub fn new(registry: AspectRegistry) -> Arc<Self> {
+        Arc::new(Self {
+            registry,
+            entries: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// 已记录的追踪
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// 追踪的文本形式，每条记录一行
+    pub fn to_text(&self) -> String {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| format!("{e}\n"))
+            .collect()
+    }
+
+    fn diff(&self, prev: &State, next: &State) -> Vec<AspectChange> {
+        let mut ids: Vec<_> = prev.keys().chain(next.keys()).copied().collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter()
+            .filter_map(|id| {
+                let before = prev.get(&id).map(|v| self.registry.format_value(id, v));
+                let after = next.get(&id).map(|v| self.registry.format_value(id, v));
+                (before != after).then(|| AspectChange {
+                    aspect: self.registry.name(id),
+                    before,
+                    after,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Tracer for TraceRecorder {
+    fn on_event(&self, event_id: EventId, selected: Option<TransitionId>) {
+        self.entries.lock().unwrap().push(TraceEntry::Event {
+            event_id,
+            transition: selected,
+        });
+    }
+
+    fn on_commit(&self, prev: &State, next: &State) {
+        let changes = self.diff(prev, next);
+        self.entries.lock().unwrap().push(TraceEntry::Commit(changes));
+    }
+}
+
+/// 与金标准不一致的一行
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceLineDiff {
+    /// 金标准中有、本次追踪中没有的行，行号为金标准中的行号（从 1 开始）
+    Removed { line: usize, text: String },
+    /// 本次追踪中新增的行，行号为本次追踪中的行号（从 1 开始）
+    Added { line: usize, text: String },
+}
+
+/// 追踪与金标准不一致
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceMismatch {
+    /// 追踪内容与金标准不同
+    Lines {
+        /// 金标准文件路径
+        path: String,
+        /// 按出现顺序排列的删除与新增的行
+        lines: Vec<TraceLineDiff>,
+    },
+    /// 读取或写入金标准文件失败（含文件缺失）
+    Io {
+        /// 金标准文件路径
+        path: String,
+        /// 错误类型
+        kind: std::io::ErrorKind,
+        /// 错误描述
+        message: String,
+    },
+}
+
+impl fmt::Display for TraceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lines { path, lines } => {
+                writeln!(f, "追踪与金标准 `{path}` 不一致（{} 行）：", lines.len())?;
+                for d in lines {
+                    match d {
+                        TraceLineDiff::Removed { line, text } => writeln!(f, "  - 第 {line} 行（金标准）：{text}")?,
+                        TraceLineDiff::Added { line, text } => writeln!(f, "  + 第 {line} 行（本次）：{text}")?,
+                    }
+                }
+            }
+            Self::Io { path, message, .. } => writeln!(f, "无法访问金标准 `{path}`：{message}")?,
+        }
+        write!(f, "如果变化符合预期，设置 {UPDATE_GOLDEN_ENV}=1 重新生成")
+    }
+}
+
+impl std::error::Error for TraceMismatch {}
+
+/// 按最长公共子序列比较两段追踪文本，返回删除与新增的行
+///
+/// 插入或删除一行只报告这一行，之后对齐的行不会被误报为不一致
+pub fn diff_traces(expected: &str, actual: &str) -> Vec<TraceLineDiff> {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    // 共同的前后缀不参与动态规划
+    let prefix = expected.iter().zip(&actual).take_while(|(e, a)| e == a).count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let e = &expected[prefix..expected.len() - suffix];
+    let a = &actual[prefix..actual.len() - suffix];
+
+    // lcs[i][j]：e[i..] 与 a[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0usize; a.len() + 1]; e.len() + 1];
+    for i in (0..e.len()).rev() {
+        for j in (0..a.len()).rev() {
+            lcs[i][j] = if e[i] == a[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < e.len() || j < a.len() {
+        if i < e.len() && j < a.len() && e[i] == a[j] {
+            i += 1;
+            j += 1;
+        } else if i < e.len() && (j == a.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diffs.push(TraceLineDiff::Removed { line: prefix + i + 1, text: e[i].to_string() });
+            i += 1;
+        } else {
+            diffs.push(TraceLineDiff::Added { line: prefix + j + 1, text: a[j].to_string() });
+            j += 1;
+        }
+    }
+    diffs
+}
+
+/// 与金标准文件比对追踪
+///
+/// 只有 `STATE_ZEN_UPDATE_GOLDEN=1` 时才写入本次追踪并通过；
+/// 否则金标准文件缺失或无法读取都返回 `TraceMismatch::Io`，避免遗漏的金标准被悄悄生成
+pub fn assert_golden(path: impl AsRef<Path>, trace: &str) -> Result<(), TraceMismatch> {
+    let path = path.as_ref();
+    let update = std::env::var_os(UPDATE_GOLDEN_ENV).is_some_and(|v| v == "1");
+    let io = |e: std::io::Error| TraceMismatch::Io {
+        path: path.display().to_string(),
+        kind: e.kind(),
+        message: e.to_string(),
+    };
+
+    if update {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io)?;
+        }
+        return std::fs::write(path, trace).map_err(io);
+    }
+    let expected = std::fs::read_to_string(path).map_err(io)?;
+    let lines = diff_traces(&expected, trace);
+    if lines.is_empty() {
+        Ok(())
+    } else {
+        Err(TraceMismatch::Lines { path: path.display().to_string(), lines })
+    }
+}
@@ -0,0 +1,17 @@
+//! 测试辅助模块
+//!
+//! 面向使用者编写状态机测试的工具：覆盖率统计、脚本化场景、金标准追踪、变异测试、属性测试策略等
+
+pub mod coverage;
+pub mod scenario;
+pub mod golden;
+pub mod mutation;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+
+pub use coverage::{CoverageRecorder, CoverageReport, CoverageRuntime};
+pub use scenario::{Scenario, ScenarioFailure};
+pub use golden::{TraceRecorder, TraceEntry, TraceMismatch, TraceLineDiff, assert_golden, diff_traces};
+pub use mutation::{mutate, mutants, Mutation, Mutant, MutationReport};
+#[cfg(feature = "proptest")]
+pub use strategy::{StateDomains, GeneratedState, event_sequence, blueprint_events};
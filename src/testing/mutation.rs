@@ -0,0 +1,135 @@
+//! 蓝图变异测试
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use crate::core::types::TransitionId;
+use crate::core::blueprint::StateMachineBlueprint;
+
+/// 对蓝图的一处变异
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mutation {
+    /// 守卫取反
+    NegateGuard(TransitionId),
+    /// 删除转换
+    DropTransition(TransitionId),
+    /// 修改优先级，使转换在同一事件的竞争者中由最高变为最低，或反之
+    ChangePriority { transition: TransitionId, from: i32, to: i32 },
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NegateGuard(id) => write!(f, "转换 {id} 的守卫取反"),
+            Self::DropTransition(id) => write!(f, "删除转换 {id}"),
+            Self::ChangePriority { transition, from, to } => {
+                write!(f, "转换 {transition} 的优先级 {from} -> {to}")
+            }
+        }
+    }
+}
+
+/// 变异体：一处变异及变异后的蓝图
+#[derive(Clone)]
+pub struct Mutant {
+    /// 变异
+    pub mutation: Mutation,
+    /// 变异后的蓝图
+    pub blueprint: StateMachineBlueprint,
+}
+
+/// 生成蓝图的全部变异体
+///
+/// 每个转换产生守卫取反、删除两个变异体；与其他转换监听同一事件的转换
+/// 再产生一个调换优先级次序的变异体。顺序按蓝图中转换的顺序，结果是确定的
+pub fn mutants(blueprint: &StateMachineBlueprint) -> Vec<Mutant> {
+    let mut mutants = Vec::new();
+    for (i, transition) in blueprint.transitions().enumerate() {
+        let mut negated = blueprint.clone();
+        negated.transitions_mut()[i].guard = transition.guard.clone().not();
+        mutants.push(Mutant {
+            mutation: Mutation::NegateGuard(transition.id),
+            blueprint: negated,
+        });
+
+        let mut dropped = blueprint.clone();
+        dropped.transitions_mut().remove(i);
+        mutants.push(Mutant {
+            mutation: Mutation::DropTransition(transition.id),
+            blueprint: dropped,
+        });
+
+        let rivals: Vec<i32> = blueprint
+            .transitions_for_event(transition.event_id)
+            .filter(|t| t.id != transition.id)
+            .map(|t| t.priority)
+            .collect();
+        let (Some(&lowest), Some(&highest)) = (rivals.iter().min(), rivals.iter().max()) else {
+            continue;
+        };
+        let to = if transition.priority >= highest {
+            lowest.saturating_sub(1)
+        } else {
+            highest.saturating_add(1)
+        };
+        let mut reprioritized = blueprint.clone();
+        reprioritized.transitions_mut()[i].priority = to;
+        mutants.push(Mutant {
+            mutation: Mutation::ChangePriority {
+                transition: transition.id,
+                from: transition.priority,
+                to,
+            },
+            blueprint: reprioritized,
+        });
+    }
+    mutants
+}
+
+/// 变异测试报告
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MutationReport {
+    /// 变异体总数
+    pub total: usize,
+    /// 测试套件未能发现的变异（存活的变异体）
+    pub survivors: Vec<Mutation>,
+}
+
+impl MutationReport {
+    /// 被测试套件发现的变异体数量
+    pub fn killed(&self) -> usize {
+        self.total - self.survivors.len()
+    }
+
+    /// 变异得分（0.0 ~ 1.0），没有变异体时为 1.0
+    pub fn score(&self) -> f64 {
+        if self.total == 0 { 1.0 } else { self.killed() as f64 / self.total as f64 }
+    }
+}
+
+impl fmt::Display for MutationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "变异体: {}/{} 被发现 ({:.1}%)", self.killed(), self.total, self.score() * 100.0)?;
+        for survivor in &self.survivors {
+            write!(f, "\n  存活: {survivor}")?;
+        }
+        Ok(())
+    }
+}
+
+/// 对蓝图的每个变异体运行测试套件，统计未被发现的变异
+///
+/// `suite` 用变异后的蓝图构造状态机并执行测试，全部通过时返回 `true`；
+/// 返回 `false` 或 panic 都视为发现了变异。原蓝图应能通过测试套件
+pub fn mutate<F>(blueprint: &StateMachineBlueprint, suite: F) -> MutationReport
+where
+    F: Fn(&StateMachineBlueprint) -> bool,
+{
+    let mutants = mutants(blueprint);
+    let total = mutants.len();
+    let survivors = mutants
+        .into_iter()
+        .filter(|m| panic::catch_unwind(AssertUnwindSafe(|| suite(&m.blueprint))).unwrap_or(false))
+        .map(|m| m.mutation)
+        .collect();
+    MutationReport { total, survivors }
+}
@@ -0,0 +1,191 @@
+//! 脚本化场景
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::core::types::{StateAspectId, EventId};
+use crate::core::event::EventPayload;
+use crate::core::state_in_range::StateInRange;
+use crate::core::runtime::{RuntimeStateMachine, AspectValue};
+
+/// 方面取值检查：通过返回 `Ok`，失败返回实际取值的描述
+type AspectCheck = Arc<dyn Fn(Option<&AspectValue>) -> Result<(), String> + Send + Sync>;
+
+#[derive(Clone)]
+enum Step {
+    Dispatch(EventId, Option<EventPayload>),
+    Advance(Duration),
+    ExpectRegion {
+        label: String,
+        region: StateInRange,
+        inside: bool,
+    },
+    ExpectAspect {
+        aspect_id: StateAspectId,
+        expected: String,
+        check: AspectCheck,
+    },
+    ExpectFinished(bool),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dispatch(event_id, _) => write!(f, "分发事件 {event_id}"),
+            Self::Advance(dt) => write!(f, "推进时间 {dt:?}"),
+            Self::ExpectRegion { label, inside: true, .. } => write!(f, "期望位于区域 `{label}`"),
+            Self::ExpectRegion { label, inside: false, .. } => write!(f, "期望不在区域 `{label}`"),
+            Self::ExpectAspect { aspect_id, expected, .. } => write!(f, "期望方面 {aspect_id} == {expected}"),
+            Self::ExpectFinished(true) => write!(f, "期望已完成"),
+            Self::ExpectFinished(false) => write!(f, "期望未完成"),
+        }
+    }
+}
+
+/// 脚本化测试场景
+/// 由分发事件、推进时间和各种期望组成的声明式步骤列表，
+/// 对运行时依次执行，第一个不满足的期望给出可读的失败信息
+#[derive(Clone)]
+pub struct Scenario {
+    name: String,
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// 创建一个空场景
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// 分发一个无负载事件
+    pub fn dispatch(self, event_id: EventId) -> Self {
+        self.push(Step::Dispatch(event_id, None))
+    }
+
+    /// 分发一个带负载的事件
+    pub fn dispatch_with(self, event_id: EventId, payload: EventPayload) -> Self {
+        self.push(Step::Dispatch(event_id, Some(payload)))
+    }
+
+    /// 推进时间（驱动连续转换）
+    pub fn advance(self, dt: Duration) -> Self {
+        self.push(Step::Advance(dt))
+    }
+
+    /// 期望当前状态位于区域内
+    pub fn expect_in(self, label: impl Into<String>, region: StateInRange) -> Self {
+        self.push(Step::ExpectRegion {
+            label: label.into(),
+            region,
+            inside: true,
+        })
+    }
+
+    /// 期望当前状态不在区域内
+    pub fn expect_not_in(self, label: impl Into<String>, region: StateInRange) -> Self {
+        self.push(Step::ExpectRegion {
+            label: label.into(),
+            region,
+            inside: false,
+        })
+    }
+
+    /// 期望方面取值等于 `expected`
+    pub fn expect_aspect<T>(self, aspect_id: StateAspectId, expected: T) -> Self
+    where
+        T: PartialEq + fmt::Debug + Send + Sync + 'static,
+    {
+        let description = format!("{expected:?}");
+        let check: AspectCheck = Arc::new(move |actual| match actual {
+            None => Err("缺失".to_string()),
+            Some(v) => match v.downcast_ref::<T>() {
+                None => Err("类型不符".to_string()),
+                Some(v) if *v == expected => Ok(()),
+                Some(v) => Err(format!("{v:?}")),
+            },
+        });
+        self.push(Step::ExpectAspect {
+            aspect_id,
+            expected: description,
+            check,
+        })
+    }
+
+    /// 期望状态机是否已完成（位于终止区域）
+    pub fn expect_finished(self, finished: bool) -> Self {
+        self.push(Step::ExpectFinished(finished))
+    }
+
+    fn push(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// 对运行时执行场景
+    pub fn run(&self, runtime: &mut RuntimeStateMachine) -> Result<(), ScenarioFailure> {
+        for (index, step) in self.steps.iter().enumerate() {
+            let fail = |message: String| ScenarioFailure {
+                scenario: self.name.clone(),
+                step: index + 1,
+                description: step.to_string(),
+                message,
+            };
+            match step {
+                Step::Dispatch(event_id, payload) => runtime.handle_event(*event_id, payload.clone()),
+                Step::Advance(dt) => runtime.tick(*dt),
+                Step::ExpectRegion { region, inside, .. } => {
+                    if region.contains(&runtime.current_state) != *inside {
+                        let actual = if *inside { "不在区域内" } else { "位于区域内" };
+                        return Err(fail(format!("实际{actual}")));
+                    }
+                }
+                Step::ExpectAspect { aspect_id, check, .. } => {
+                    check(runtime.current_state.get(aspect_id))
+                        .map_err(|actual| fail(format!("实际为 {actual}")))?;
+                }
+                Step::ExpectFinished(finished) => {
+                    if runtime.is_finished() != *finished {
+                        let actual = if *finished { "未完成" } else { "已完成" };
+                        return Err(fail(format!("实际{actual}")));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 执行场景，失败时以可读信息 panic
+    pub fn assert(&self, runtime: &mut RuntimeStateMachine) {
+        if let Err(failure) = self.run(runtime) {
+            panic!("{failure}");
+        }
+    }
+}
+
+/// 场景执行失败
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScenarioFailure {
+    /// 场景名
+    pub scenario: String,
+    /// 失败步骤序号（从 1 开始）
+    pub step: usize,
+    /// 失败步骤的描述
+    pub description: String,
+    /// 失败原因
+    pub message: String,
+}
+
+impl fmt::Display for ScenarioFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "场景 `{}` 第 {} 步（{}）失败：{}",
+            self.scenario, self.step, self.description, self.message
+        )
+    }
+}
+
+impl std::error::Error for ScenarioFailure {}
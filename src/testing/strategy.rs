@@ -0,0 +1,92 @@
+//! proptest 策略：随机状态与事件序列
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+use proptest::prelude::*;
+use crate::core::types::{StateAspectId, EventId};
+use crate::core::runtime::{State, AspectValue};
+use crate::core::blueprint::StateMachineBlueprint;
+use crate::utils::AspectDomain;
+
+/// 由 `StateDomains` 生成的状态
+/// 同时记录各方面在取值域中的下标，失败用例缩减时下标趋向 0，`Debug` 按下标显示
+#[derive(Clone)]
+pub struct GeneratedState {
+    /// 生成的状态
+    pub state: State,
+    picks: Vec<(StateAspectId, usize)>,
+}
+
+impl fmt::Debug for GeneratedState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.picks.iter().map(|(id, i)| (format!("#{id}"), format!("domain[{i}]"))))
+            .finish()
+    }
+}
+
+/// 各方面的取值域，用于生成随机状态
+#[derive(Clone, Default)]
+pub struct StateDomains {
+    domains: BTreeMap<StateAspectId, AspectDomain>,
+}
+
+impl StateDomains {
+    /// 创建空的取值域集合
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记方面的候选取值，替换之前的登记
+    pub fn with<T, I>(self, aspect_id: StateAspectId, values: I) -> Self
+    where
+        T: Send + Sync + 'static,
+        I: IntoIterator<Item = T>,
+    {
+        let domain = values.into_iter().map(|v| Arc::new(v) as AspectValue).collect();
+        self.with_domain(aspect_id, domain)
+    }
+
+    /// 登记类型擦除后的取值域
+    pub fn with_domain(mut self, aspect_id: StateAspectId, domain: AspectDomain) -> Self {
+        self.domains.insert(aspect_id, domain);
+        self
+    }
+
+    /// 生成状态的策略：每个登记的方面从取值域中任取一个值
+    ///
+    /// # Panics
+    ///
+    /// 某个方面的取值域为空时 panic
+    pub fn strategy(&self) -> impl Strategy<Value = GeneratedState> + use<> {
+        let domains: Vec<(StateAspectId, AspectDomain)> =
+            self.domains.iter().map(|(id, d)| (*id, d.clone())).collect();
+        for (id, domain) in &domains {
+            assert!(!domain.is_empty(), "方面 {id} 的取值域为空");
+        }
+        let indices: Vec<Range<usize>> = domains.iter().map(|(_, d)| 0..d.len()).collect();
+        indices.prop_map(move |picks| GeneratedState {
+            state: domains.iter().zip(&picks).map(|((id, d), i)| (*id, d[*i].clone())).collect(),
+            picks: domains.iter().map(|(id, _)| *id).zip(picks).collect(),
+        })
+    }
+}
+
+/// 事件序列策略：长度落在 `len` 内，每个事件从 `events` 中任取
+///
+/// # Panics
+///
+/// `events` 为空时 panic
+pub fn event_sequence(events: Vec<EventId>, len: Range<usize>) -> impl Strategy<Value = Vec<EventId>> {
+    assert!(!events.is_empty(), "候选事件为空");
+    proptest::collection::vec(proptest::sample::select(events), len)
+}
+
+/// 以蓝图声明的全部事件为候选的事件序列策略
+pub fn blueprint_events(blueprint: &StateMachineBlueprint, len: Range<usize>) -> impl Strategy<Value = Vec<EventId>> {
+    let mut events: Vec<EventId> = blueprint.events().map(|e| e.id).collect();
+    events.sort_unstable();
+    event_sequence(events, len)
+}
@@ -1,6 +1,10 @@
 //! 工具函数模块
 
 pub mod tool;
+pub mod sample;
+pub mod product;
 
 // 重新导出工具函数
-pub use tool::partition_range_by_transfer_target;
\ No newline at end of file
+pub use tool::{partition_range_by_transfer_target, find_write_conflicts, WriteConflict};
+pub use sample::{sample_guard, AspectDomain};
+pub use product::product;
@@ -0,0 +1,91 @@
+//! 状态机的同步积
+
+use std::sync::Arc;
+use crate::core::StateMachineBlueprint;
+use crate::core::transition::{Transition, OnTranCallback};
+use crate::core::types::{EventId, TransitionId};
+use crate::core::error::StateZenError;
+
+/// 合并两个同步转换的回调，`a` 先执行
+fn both(a: &Option<OnTranCallback>, b: &Option<OnTranCallback>) -> Option<OnTranCallback> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let (a, b) = (a.clone(), b.clone());
+            Some(Arc::new(move |prev, next| {
+                a(prev, next);
+                b(prev, next);
+            }))
+        }
+        (a, b) => a.clone().or_else(|| b.clone()),
+    }
+}
+
+/// 一对同步转换合成的转换：守卫取与，`a` 的转换函数先执行，
+/// 优先级为两者之和，最短停留取较长者，后置条件取与，发出的事件依次拼接
+fn synchronized(id: TransitionId, a: &Transition, b: &Transition) -> Transition {
+    Transition {
+        id,
+        event_id: a.event_id,
+        guard: a.guard.clone().and(b.guard.clone()),
+        transfer: a.transfer.clone().then(b.transfer.clone()),
+        priority: a.priority.saturating_add(b.priority),
+        on_tran: both(&a.on_tran, &b.on_tran),
+        emits: a.emits.iter().chain(&b.emits).cloned().collect(),
+        tag: a.tag.clone().or_else(|| b.tag.clone()),
+        min_dwell: a.min_dwell.max(b.min_dwell),
+        ensures: match (&a.ensures, &b.ensures) {
+            (Some(x), Some(y)) => Some(x.clone().and(y.clone())),
+            (x, y) => x.clone().or_else(|| y.clone()),
+        },
+        respond: a.respond.clone().or_else(|| b.respond.clone()),
+    }
+}
+
+/// 构造两个状态机的同步积
+///
+/// 积状态机的状态由两者的方面并列组成，两者的方面ID不能重叠。
+/// `sync` 中的每对事件 `(ea, eb)` 只能一起发生：`a` 监听 `ea` 的转换与 `b` 监听 `eb` 的转换
+/// 两两合成为监听 `ea` 的同步转换，两侧守卫同时成立时一起执行；原有的这些转换被移除。
+/// 其余转换、观察者、事件等照常并列（同 `merge`），两侧的转换ID不能重叠，
+/// 同步转换的ID从两者最大的转换ID之后依次分配，顺序为 `sync` 的顺序、再按各自蓝图中的顺序
+pub fn product(
+    a: &StateMachineBlueprint,
+    b: &StateMachineBlueprint,
+    sync: &[(EventId, EventId)],
+) -> Result<StateMachineBlueprint, StateZenError> {
+    if let Some(aspect) = a.aspects().find(|x| b.aspect(x.id).is_some()) {
+        return Err(StateZenError::DuplicateAspect(aspect.id));
+    }
+    if let Some(t) = a.transitions().find(|x| b.transition(x.id).is_some()) {
+        return Err(StateZenError::DuplicateTransition(t.id));
+    }
+    for &(ea, eb) in sync {
+        if a.event(ea).is_none() {
+            return Err(StateZenError::UnknownEvent(ea));
+        }
+        if b.event(eb).is_none() {
+            return Err(StateZenError::UnknownEvent(eb));
+        }
+    }
+
+    let mut next_id = a.transitions().chain(b.transitions()).map(|t| t.id + 1).max().unwrap_or(0);
+    let mut synced = Vec::new();
+    for &(ea, eb) in sync {
+        for x in a.transitions_for_event(ea) {
+            for y in b.transitions_for_event(eb) {
+                synced.push(synchronized(next_id, x, y));
+                next_id += 1;
+            }
+        }
+    }
+
+    let mut composed = a.merge(b);
+    *composed.transitions_mut() = a
+        .transitions()
+        .filter(|t| !sync.iter().any(|(ea, _)| *ea == t.event_id))
+        .chain(b.transitions().filter(|t| !sync.iter().any(|(_, eb)| *eb == t.event_id)))
+        .cloned()
+        .chain(synced)
+        .collect();
+    Ok(composed)
+}
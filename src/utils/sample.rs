@@ -0,0 +1,67 @@
+//! 守卫条件可满足性采样
+
+use std::collections::HashMap;
+use crate::core::types::StateAspectId;
+use crate::core::state_in_range::StateInRange;
+use crate::core::runtime::{State, AspectValue};
+
+/// 方面取值域：某个方面的全部候选值
+pub type AspectDomain = Vec<AspectValue>;
+
+/// 在声明的方面取值域上采样满足守卫条件的状态
+///
+/// 按方面ID升序对各取值域做笛卡尔积枚举候选状态，
+/// 返回最多 `n` 个满足 `guard` 的状态（见证状态）。
+/// 枚举顺序是确定的，便于在文档示例和测试中复现。
+///
+/// # 参数
+/// - `guard`: 待采样的守卫条件
+/// - `domains`: 每个方面的候选取值
+/// - `n`: 最多返回的状态个数
+///
+/// # 返回值
+/// 满足守卫条件的状态列表；若守卫不可满足（在给定取值域内），返回空列表
+pub fn sample_guard(
+    guard: &StateInRange,
+    domains: &HashMap<StateAspectId, AspectDomain>,
+    n: usize,
+) -> Vec<State> {
+    let mut ids: Vec<StateAspectId> = domains.keys().copied().collect();
+    ids.sort_unstable();
+
+    let mut witnesses = Vec::new();
+    if n == 0 || ids.iter().any(|id| domains[id].is_empty()) {
+        return witnesses;
+    }
+
+    // 以“里程表”方式遍历每个方面的取值下标
+    let mut indices = vec![0usize; ids.len()];
+    loop {
+        let candidate: State = ids
+            .iter()
+            .zip(&indices)
+            .map(|(id, &i)| (*id, domains[id][i].clone()))
+            .collect();
+
+        if guard.contains(&candidate) {
+            witnesses.push(candidate);
+            if witnesses.len() == n {
+                return witnesses;
+            }
+        }
+
+        // 进位到下一个组合
+        let mut pos = 0;
+        loop {
+            if pos == ids.len() {
+                return witnesses;
+            }
+            indices[pos] += 1;
+            if indices[pos] < domains[&ids[pos]].len() {
+                break;
+            }
+            indices[pos] = 0;
+            pos += 1;
+        }
+    }
+}
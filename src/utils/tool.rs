@@ -4,6 +4,7 @@ use crate::core::state_in_range::StateInRange;
 use crate::core::transfer::Transfer;
 use crate::core::StateMachineBlueprint;
 use crate::core::transition::Transition;
+use crate::core::types::{StateAspectId, EventId, TransitionId};
 
 /// 根据转换目标对状态范围进行分区
 /// 
@@ -34,6 +35,7 @@ pub fn partition_range_by_transfer_target(
 }
 
 /// 将 blueprint 中所有 Transition 按 forbidden 区域拆分为两组
+#[allow(dead_code)]
 fn split_blueprint_by_forbidden_region(
     blueprint: StateMachineBlueprint,
     forbidden: StateInRange,
@@ -42,9 +44,8 @@ fn split_blueprint_by_forbidden_region(
     let mut not_into_forbidden = blueprint.clone();
 
     // 处理 into_forbidden：保留会进入 forbidden 的部分
-    into_forbidden.transitions = blueprint
-        .transitions
-        .iter()
+    *into_forbidden.transitions_mut() = blueprint
+        .transitions()
         .cloned()
         .map(|t| {
             let (into, _) = partition_range_by_transfer_target(t.guard.clone(), forbidden.clone(), t.transfer.clone());
@@ -56,9 +57,8 @@ fn split_blueprint_by_forbidden_region(
         .collect();
 
     // 处理 not_into_forbidden：保留不会进入 forbidden 的部分
-    not_into_forbidden.transitions = blueprint
-        .transitions
-        .iter()
+    *not_into_forbidden.transitions_mut() = blueprint
+        .transitions()
         .cloned()
         .map(|t| {
             let (_, not_into) = partition_range_by_transfer_target(t.guard.clone(), forbidden.clone(), t.transfer.clone());
@@ -69,4 +69,45 @@ fn split_blueprint_by_forbidden_region(
         })
         .collect();
     (into_forbidden, not_into_forbidden)
-}
\ No newline at end of file
+}
+
+/// 写冲突：监听同一事件的两个转换写入了相同的方面
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteConflict {
+    /// 共同监听的事件
+    pub event_id: EventId,
+    /// 冲突的两个转换（按蓝图中的顺序）
+    pub transitions: (TransitionId, TransitionId),
+    /// 两者都写入的方面
+    pub aspects: Vec<StateAspectId>,
+}
+
+/// 找出广播式执行（同一事件的多个转换同时生效）时会互相覆盖写入的转换对
+///
+/// 写集合未知的转换无法分析，不会出现在结果中
+pub fn find_write_conflicts(blueprint: &StateMachineBlueprint) -> Vec<WriteConflict> {
+    let transitions: Vec<&Transition> = blueprint.transitions().collect();
+    let mut conflicts = Vec::new();
+    for (i, a) in transitions.iter().enumerate() {
+        let Some(writes_a) = a.transfer.writes() else {
+            continue;
+        };
+        for b in &transitions[i + 1..] {
+            if a.event_id != b.event_id {
+                continue;
+            }
+            let Some(writes_b) = b.transfer.writes() else {
+                continue;
+            };
+            let aspects: Vec<StateAspectId> = writes_a.iter().filter(|id| writes_b.contains(id)).copied().collect();
+            if !aspects.is_empty() {
+                conflicts.push(WriteConflict {
+                    event_id: a.event_id,
+                    transitions: (a.id, b.id),
+                    aspects,
+                });
+            }
+        }
+    }
+    conflicts
+}
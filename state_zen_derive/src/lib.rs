@@ -0,0 +1,144 @@
+//! State-Zen 派生宏
+//!
+//! 提供 `#[derive(Aspects)]`，把普通结构体的每个字段映射为一个状态方面
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitInt};
+
+/// 为结构体派生 `state_zen::Aspects`
+///
+/// 每个具名字段对应一个方面，方面ID默认从 `#[aspects(base = N)]`（缺省为 1）起按字段顺序递增，
+/// 也可以用 `#[aspect(id = N)]` 单独指定。生成内容：
+/// - 每个字段的方面ID常量（字段名大写）
+/// - `Aspects` 实现：注册方面、与动态 `State` 互相转换、列出各方面的取值类型
+/// - `<结构体名>Ext` 扩展 trait：在 `State` 上按字段名读取类型化取值
+#[proc_macro_derive(Aspects, attributes(aspects, aspect))]
+pub fn derive_aspects(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let vis = &input.vis;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => return Err(syn::Error::new_spanned(name, "Aspects 只支持具名字段结构体")),
+        },
+        _ => return Err(syn::Error::new_spanned(name, "Aspects 只支持结构体")),
+    };
+
+    let mut next_id = parse_id_attr(&input.attrs, "aspects", "base")?.unwrap_or(1);
+
+    let mut consts = Vec::new();
+    let mut registers = Vec::new();
+    let mut writes = Vec::new();
+    let mut reads = Vec::new();
+    let mut types = Vec::new();
+    let mut getter_sigs = Vec::new();
+    let mut getter_impls = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().expect("具名字段");
+        let ty = &field.ty;
+        let id = match parse_id_attr(&field.attrs, "aspect", "id")? {
+            Some(id) => id,
+            None => next_id,
+        };
+        next_id = id + 1;
+
+        let const_name = Ident::new(&ident.to_string().to_uppercase(), Span::call_site());
+        let id_lit = LitInt::new(&format!("{id}u64"), Span::call_site());
+
+        consts.push(quote! {
+            #[doc = concat!("`", stringify!(#ident), "` 字段对应的方面ID")]
+            pub const #const_name: ::state_zen::StateAspectId = #id_lit;
+        });
+        registers.push(quote! {
+            blueprint.add_aspect(::state_zen::StateAspect::of::<#ty>(Self::#const_name))?;
+        });
+        writes.push(quote! {
+            state.insert(Self::#const_name, ::std::sync::Arc::new(::std::clone::Clone::clone(&self.#ident)));
+        });
+        types.push(quote! {
+            (Self::#const_name, ::std::any::TypeId::of::<#ty>()),
+        });
+        reads.push(quote! {
+            #ident: state.get(&Self::#const_name)?.downcast_ref::<#ty>()?.clone(),
+        });
+        getter_sigs.push(quote! {
+            #[doc = concat!("读取 `", stringify!(#ident), "` 方面")]
+            fn #ident(&self) -> ::std::option::Option<&#ty>;
+        });
+        getter_impls.push(quote! {
+            fn #ident(&self) -> ::std::option::Option<&#ty> {
+                self.get(&#name::#const_name)?.downcast_ref::<#ty>()
+            }
+        });
+    }
+
+    let ext = format_ident!("{}Ext", name);
+
+    Ok(quote! {
+        impl #name {
+            #(#consts)*
+        }
+
+        impl ::state_zen::Aspects for #name {
+            fn register_aspects(
+                blueprint: &mut ::state_zen::StateMachineBlueprint,
+            ) -> ::std::result::Result<(), ::state_zen::StateZenError> {
+                #(#registers)*
+                ::std::result::Result::Ok(())
+            }
+
+            fn to_state(&self) -> ::state_zen::State {
+                let mut state = ::state_zen::State::new();
+                #(#writes)*
+                state
+            }
+
+            fn from_state(state: &::state_zen::State) -> ::std::option::Option<Self> {
+                ::std::option::Option::Some(Self {
+                    #(#reads)*
+                })
+            }
+
+            fn aspect_types() -> ::std::vec::Vec<(::state_zen::StateAspectId, ::std::any::TypeId)> {
+                ::std::vec![#(#types)*]
+            }
+        }
+
+        #[doc = concat!("在 `State` 上读取 `", stringify!(#name), "` 各方面的扩展 trait")]
+        #vis trait #ext {
+            #(#getter_sigs)*
+        }
+
+        impl #ext for ::state_zen::State {
+            #(#getter_impls)*
+        }
+    })
+}
+
+/// 解析形如 `#[outer(key = N)]` 的属性
+fn parse_id_attr(attrs: &[syn::Attribute], outer: &str, key: &str) -> syn::Result<Option<u64>> {
+    let mut value = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident(outer)) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                let lit: LitInt = meta.value()?.parse()?;
+                value = Some(lit.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error(format!("未知的参数，期望 `{key}`")))
+            }
+        })?;
+    }
+    Ok(value)
+}
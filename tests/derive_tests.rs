@@ -0,0 +1,77 @@
+//! `#[derive(Aspects)]` 测试
+
+#![cfg(feature = "derive")]
+
+use std::sync::Arc;
+use state_zen::{Aspects, EventDef, RuntimeStateMachine, State, StateInRange, StateMachineBlueprint, Transfer, Transition};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Idle,
+    Walk,
+}
+
+#[derive(Aspects, Debug, Clone, PartialEq)]
+#[aspects(base = 10)]
+struct PlayerState {
+    action: Action,
+    hunger: i32,
+    #[aspect(id = 42)]
+    stamina: f32,
+}
+
+#[test]
+fn test_derived_ids_and_registration() {
+    assert_eq!(PlayerState::ACTION, 10);
+    assert_eq!(PlayerState::HUNGER, 11);
+    assert_eq!(PlayerState::STAMINA, 42);
+
+    let mut blueprint = StateMachineBlueprint::new();
+    PlayerState::register_aspects(&mut blueprint).unwrap();
+    assert_eq!(blueprint.aspects().count(), 3);
+    assert_eq!(blueprint.aspect(11).map(|a| a.value_type_id), Some(std::any::TypeId::of::<i32>()));
+}
+
+#[test]
+fn test_derived_state_round_trip_and_getters() {
+    let player = PlayerState { action: Action::Idle, hunger: 7, stamina: 1.5 };
+    let state = player.to_state();
+    assert_eq!(state.action(), Some(&Action::Idle));
+    assert_eq!(state.hunger(), Some(&7));
+    assert_eq!(PlayerState::from_state(&state), Some(player));
+
+    let mut blueprint = StateMachineBlueprint::new();
+    PlayerState::register_aspects(&mut blueprint).unwrap();
+    blueprint.add_event(EventDef::new(100)).unwrap();
+    blueprint.add_transition(Transition::new(
+        1,
+        100,
+        StateInRange::new(|s: &State| s.action() == Some(&Action::Idle)),
+        Transfer::new(|s| {
+            let mut next = s.clone();
+            next.insert(PlayerState::ACTION, Arc::new(Action::Walk));
+            next
+        }),
+    )).unwrap();
+    let mut runtime = RuntimeStateMachine::new(blueprint, state);
+    runtime.handle_event(100, None);
+    assert_eq!(runtime.current_state.action(), Some(&Action::Walk));
+}
+
+#[test]
+fn test_state_bridge_from_derive() {
+    use state_zen::StateZenError;
+    use state_zen::core::StateBridge;
+
+    let bridge = StateBridge::<PlayerState>::derived();
+    let player = PlayerState { action: Action::Walk, hunger: 2, stamina: 0.5 };
+    let state = bridge.to_state(&player);
+    assert_eq!(bridge.from_state(&state), Ok(player));
+    assert_eq!(bridge.aspect_types().len(), 3);
+
+    let mut missing = state.clone();
+    missing.remove(&PlayerState::STAMINA);
+    assert_eq!(bridge.from_state(&missing), Err(StateZenError::MissingAspect(42)));
+    missing.insert(PlayerState::STAMINA, Arc::new(1i32));
+    assert_eq!(bridge.from_state(&missing), Err(StateZenError::AspectTypeMismatch(42)));
+}
@@ -0,0 +1,98 @@
+//! 守卫表达式测试
+
+#![cfg(feature = "expr")]
+
+use std::sync::Arc;
+use state_zen::core::AspectRegistry;
+use state_zen::loader::expr::{self, ExprError, ExprLimits};
+use state_zen::State;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Idle,
+    Walk,
+}
+
+fn registry() -> AspectRegistry {
+    let mut registry = AspectRegistry::new();
+    registry
+        .register::<Action>(1, "action")
+        .register::<i32>(2, "hunger")
+        .register::<f64>(3, "speed")
+        .register::<bool>(4, "alive")
+        .register::<String>(5, "name");
+    registry
+}
+
+fn state(action: Action, hunger: i32) -> State {
+    let mut s = State::new();
+    s.insert(1, Arc::new(action));
+    s.insert(2, Arc::new(hunger));
+    s.insert(3, Arc::new(1.5f64));
+    s.insert(4, Arc::new(true));
+    s.insert(5, Arc::new("zen".to_string()));
+    s
+}
+
+#[test]
+fn test_compile_typed_comparisons() {
+    let registry = registry();
+    let guard = expr::compile("hunger <= 5 && action == 'Walk'", &registry).unwrap();
+    assert!(guard.contains(&state(Action::Walk, 5)));
+    assert!(!guard.contains(&state(Action::Walk, 6)));
+    assert!(!guard.contains(&state(Action::Idle, 0)));
+
+    let guard = expr::compile("!(speed > 2 || name != \"zen\") && alive == true", &registry).unwrap();
+    assert!(guard.contains(&state(Action::Idle, 0)));
+
+    // 基本类型的比较是声明式的
+    let guard = expr::compile("hunger > 3 && hunger < 10", &registry).unwrap();
+    assert!(guard.is_declarative());
+    assert!(guard.contains(&state(Action::Idle, 4)));
+    assert!(!guard.contains(&state(Action::Idle, 10)));
+}
+
+#[test]
+fn test_compile_reports_errors_with_position() {
+    let registry = registry();
+    assert_eq!(
+        expr::compile("hunger <= 5 && thirst > 1", &registry).err(),
+        Some(ExprError { position: 15, message: "未注册的方面 `thirst`".to_string() })
+    );
+    assert_eq!(expr::compile("action < Walk", &registry).err().map(|e| e.position), Some(9));
+    assert_eq!(expr::compile("hunger == 'x'", &registry).err().map(|e| e.position), Some(10));
+    assert_eq!(expr::compile("(hunger == 1", &registry).err().map(|e| e.position), Some(12));
+    assert_eq!(expr::compile("hunger == 1 1", &registry).err().map(|e| e.position), Some(12));
+}
+
+#[test]
+fn test_compile_rejects_expressions_beyond_limits() {
+    let registry = registry();
+
+    // 深层嵌套在解析阶段即被拒绝，不会耗尽栈
+    let nested = format!("{}hunger == 1{}", "(".repeat(100_000), ")".repeat(100_000));
+    let limits = ExprLimits { max_length: usize::MAX, ..ExprLimits::default() };
+    assert_eq!(
+        expr::compile_with(&nested, &registry, limits).err(),
+        Some(ExprError { position: 32, message: "嵌套超过 32 层".to_string() })
+    );
+    let negated = format!("{}alive == true", "!".repeat(33));
+    assert_eq!(expr::compile(&negated, &registry).err().map(|e| e.position), Some(32));
+    assert!(expr::compile(&format!("{}alive == true", "!".repeat(32)), &registry).is_ok());
+
+    // 过长的 `&&` 链
+    let chain = vec!["hunger > 0"; 65].join(" && ");
+    assert_eq!(
+        expr::compile(&chain, &registry).err().map(|e| e.message),
+        Some("比较项超过 64 个".to_string())
+    );
+    let guard = expr::compile(&vec!["hunger > 0"; 64].join(" && "), &registry).unwrap();
+    assert!(guard.contains(&state(Action::Idle, 1)));
+
+    // 源文本长度
+    let strict = ExprLimits { max_length: 8, ..ExprLimits::default() };
+    assert_eq!(
+        expr::compile_with("hunger == 1", &registry, strict).err().map(|e| e.message),
+        Some("表达式超过 8 字节".to_string())
+    );
+}
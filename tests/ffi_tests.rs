@@ -0,0 +1,137 @@
+//! C 接口测试
+
+#![cfg(feature = "ffi")]
+
+use std::ffi::{CStr, CString, c_void};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use state_zen::core::AspectRegistry;
+use state_zen::ffi::*;
+use state_zen::loader::Registry;
+use state_zen::{EventDef, StateAspect, StateInRange, StateMachineBlueprint, StateObserver, Transfer, Transition};
+
+const HP: u64 = 1;
+const MODE: u64 = 2;
+
+fn manifest_json() -> CString {
+    let mut registry = Registry::with_builtins();
+    registry.register_guard("c_ready", |()| StateInRange::new(|_| true));
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.add_aspect(StateAspect::of::<i64>(HP)).unwrap();
+    blueprint.add_aspect(StateAspect::of::<String>(MODE)).unwrap();
+    blueprint.add_event(EventDef::new(10)).unwrap();
+    blueprint.add_transition(Transition::new(
+        1,
+        10,
+        registry.guard("c_ready", &[]).unwrap().and(StateInRange::aspect_in(HP, 1i64..)),
+        Transfer::set(MODE, "run".to_string()).then(Transfer::sub(HP, 1i64)),
+    )).unwrap();
+    blueprint.add_observer(StateObserver::new(1, StateInRange::aspect_eq(MODE, "run".to_string()))).unwrap();
+    let mut names = AspectRegistry::new();
+    names.register::<i64>(HP, "hp").register::<String>(MODE, "mode");
+    CString::new(blueprint.to_manifest_with(&names).to_json()).unwrap()
+}
+
+fn initial(mode: &str) -> [SzAspectValue; 2] {
+    [
+        SzAspectValue { aspect: HP, value: SzValue { tag: SzValueTag::I64 as u32, data: SzValueData { i64_: 2 } } },
+        SzAspectValue {
+            aspect: MODE,
+            value: SzValue {
+                tag: SzValueTag::String as u32,
+                data: SzValueData { string: SzStr { ptr: mode.as_ptr(), len: mode.len() } },
+            },
+        },
+    ]
+}
+
+extern "C" fn ready(state: *const SzState, user_data: *mut c_void) -> bool {
+    let calls = unsafe { &*(user_data as *const AtomicUsize) };
+    calls.fetch_add(1, Ordering::SeqCst);
+    let mut hp = SzValue { tag: SzValueTag::Unsupported as u32, data: SzValueData { u64_: 0 } };
+    assert!(unsafe { sz_state_get(state, HP, &mut hp) });
+    assert_eq!(SzValueTag::from_raw(hp.tag), Some(SzValueTag::I64));
+    unsafe { hp.data.i64_ > 1 }
+}
+
+extern "C" fn count_state(_: *const SzState, user_data: *mut c_void) {
+    unsafe { &*(user_data as *const AtomicUsize) }.fetch_add(1, Ordering::SeqCst);
+}
+
+extern "C" fn count_transition(id: u64, _: *const SzState, _: *const SzState, user_data: *mut c_void) {
+    assert_eq!(id, 1);
+    unsafe { &*(user_data as *const AtomicUsize) }.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn test_ffi_machine_dispatches_through_c_callbacks() {
+    let guard_calls = AtomicUsize::new(0);
+    let entered = AtomicUsize::new(0);
+    let transitions = AtomicUsize::new(0);
+    let json = manifest_json();
+    let name = CString::new("c_ready").unwrap();
+    let values = initial("idle");
+    unsafe {
+        let registry = sz_registry_new();
+        assert!(sz_registry_register_guard(registry, name.as_ptr(), Some(ready), &guard_calls as *const _ as *mut c_void));
+        let machine = sz_machine_new(json.as_ptr(), registry, values.as_ptr(), values.len());
+        sz_registry_free(registry);
+        assert!(!machine.is_null());
+        assert!(sz_machine_on_observer(machine, 1, Some(count_state), None, &entered as *const _ as *mut c_void));
+        assert!(!sz_machine_on_observer(machine, 9, None, None, std::ptr::null_mut()));
+        assert!(sz_machine_on_transition(machine, Some(count_transition), &transitions as *const _ as *mut c_void));
+
+        assert!(sz_machine_handle_event(machine, 10));
+        let mut out = SzValue { tag: SzValueTag::Unsupported as u32, data: SzValueData { u64_: 0 } };
+        assert!(sz_machine_get(machine, MODE, &mut out));
+        assert_eq!(out.tag, SzValueTag::String as u32);
+        let SzStr { ptr, len } = out.data.string;
+        assert_eq!(std::slice::from_raw_parts(ptr, len), b"run");
+        assert!(sz_machine_get(machine, HP, &mut out));
+        assert_eq!((out.tag, out.data.i64_), (SzValueTag::I64 as u32, 1));
+
+        // C 守卫拒绝 hp 为 1 的状态
+        assert!(sz_machine_handle_event(machine, 10));
+        assert!(sz_machine_get(machine, HP, &mut out));
+        assert_eq!(out.data.i64_, 1);
+        assert!(!sz_machine_get(machine, 99, &mut out));
+        sz_machine_free(machine);
+    }
+    assert_eq!(guard_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(entered.load(Ordering::SeqCst), 1);
+    assert_eq!(transitions.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_ffi_reports_errors() {
+    let registry = sz_registry_new();
+    let broken = CString::new("{ not json").unwrap();
+    unsafe {
+        assert!(sz_machine_new(broken.as_ptr(), registry, std::ptr::null(), 0).is_null());
+        assert!(!CStr::from_ptr(sz_last_error()).to_bytes().is_empty());
+
+        // 清单引用了未注册的 C 守卫
+        let json = manifest_json();
+        let values = initial("idle");
+        assert!(sz_machine_new(json.as_ptr(), registry, values.as_ptr(), values.len()).is_null());
+        assert!(CStr::from_ptr(sz_last_error()).to_str().unwrap().contains("c_ready"));
+
+        // 空回调被拒绝
+        let name = CString::new("c_ready").unwrap();
+        assert!(!sz_registry_register_guard(registry, name.as_ptr(), None, std::ptr::null_mut()));
+        assert!(!CStr::from_ptr(sz_last_error()).to_bytes().is_empty());
+        assert!(sz_registry_register_guard(registry, name.as_ptr(), Some(ready), std::ptr::null_mut()));
+
+        // 未知的取值标签在读取联合体前被拒绝
+        let mut values = initial("idle");
+        values[0].value.tag = 42;
+        assert!(sz_machine_new(json.as_ptr(), registry, values.as_ptr(), values.len()).is_null());
+        assert!(CStr::from_ptr(sz_last_error()).to_str().unwrap().contains(&HP.to_string()));
+
+        let values = initial("idle");
+        let machine = sz_machine_new(json.as_ptr(), registry, values.as_ptr(), values.len());
+        assert!(!machine.is_null());
+        assert!(!sz_machine_on_transition(machine, None, std::ptr::null_mut()));
+        sz_machine_free(machine);
+        sz_registry_free(registry);
+    }
+}
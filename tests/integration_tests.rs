@@ -2,6 +2,9 @@
 //! 
 //! 这些测试验证状态机框架的实际使用场景
 
+// 早期的夹具保持原有写法
+#![allow(clippy::unnecessary_map_or, clippy::collapsible_if)]
+
 use std::any::TypeId;
 use std::sync::Arc;
 
@@ -9,8 +12,9 @@ use std::sync::Arc;
 use state_zen::{
     StateAspectId,
     StateAspect, StateInRange, Transfer, EventDef, Transition, StateObserver,
-    StateMachineBlueprint, RuntimeStateMachine, State,
+    StateMachineBlueprint, RuntimeStateMachine, State, MachineTemplate,
 };
+use state_zen::testing::Scenario;
 
 // 测试中使用的类型定义
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
@@ -21,14 +25,13 @@ enum Action {
 
 // 辅助函数：创建玩家移动蓝图
 fn create_player_blueprint() -> (StateMachineBlueprint, State) {
-    let action_aspect = StateAspect {
-        id: 1,
-        value_type_id: TypeId::of::<Action>(),
-    };
+    // 默认处于 Idle
+    let action_aspect = StateAspect::of::<Action>(1).with_default(Action::Idle);
 
     let press_w_event = EventDef {
         id: 100,
         payload_type_id: TypeId::of::<()>(),
+        transformers: Vec::new(),
     };
 
     let is_idle = StateInRange::new(|s| {
@@ -56,36 +59,47 @@ fn create_player_blueprint() -> (StateMachineBlueprint, State) {
     });
 
     let mut blueprint = StateMachineBlueprint::new();
-    blueprint.aspects.insert(action_aspect.id, action_aspect);
-    blueprint.events.insert(press_w_event.id, press_w_event);
+    blueprint.add_aspect(action_aspect).unwrap();
+    blueprint.add_event(press_w_event).unwrap();
 
     // Walk transition
-    blueprint.transitions.push(Transition {
+    blueprint.add_transition(Transition {
         id: 1,
         event_id: 100,
         guard: is_idle.clone(),
         transfer: press_w_to_walk,
         priority: 0,
         on_tran: None,
-    });
+        emits: Vec::new(),
+        tag: None,
+        min_dwell: None,
+        ensures: None,
+        respond: None,
+    }).unwrap();
 
     // Idle transition
     let press_s_event = EventDef {
         id: 101,
         payload_type_id: TypeId::of::<()>(),
+        transformers: Vec::new(),
     };
-    blueprint.events.insert(press_s_event.id, press_s_event);
-    blueprint.transitions.push(Transition {
+    blueprint.add_event(press_s_event).unwrap();
+    blueprint.add_transition(Transition {
         id: 2,
         event_id: 101,
         guard: is_walking,
         transfer: press_s_to_idle,
         priority: 0,
         on_tran: None,
-    });
+        emits: Vec::new(),
+        tag: None,
+        min_dwell: None,
+        ensures: None,
+        respond: None,
+    }).unwrap();
 
     // Observer
-    blueprint.observers.push(StateObserver {
+    blueprint.add_observer(StateObserver {
         id: 1,
         region: StateInRange::new(|s| {
             s.get(&1)
@@ -94,17 +108,21 @@ fn create_player_blueprint() -> (StateMachineBlueprint, State) {
         }),
         on_enter: None,
         on_exit: None,
-    });
-
-    let initial_state: State = {
-        let mut s = State::new();
-        s.insert(1, Arc::new(Action::Idle));
-        s
-    };
+        priority: 0,
+        on_enter_consume: None,
+        active_when: None,
+        tag: None,
+    }).unwrap();
 
+    let initial_state = blueprint.default_initial_state().unwrap();
     (blueprint, initial_state)
 }
 
+// 辅助函数：玩家状态机模板，默认处于 Idle
+fn player_template() -> MachineTemplate {
+    MachineTemplate::new(create_player_blueprint().0)
+}
+
 // 辅助函数：获取 Action 状态
 fn get_action(state: &State) -> Option<Action> {
     state
@@ -157,36 +175,24 @@ mod tests {
         let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
 
         // 触发 PressW
-        runtime.event_happen(100, None);
-        runtime.transform();
-
-        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+        Scenario::new("Idle -> Walk").dispatch(100).expect_aspect(1, Action::Walk).assert(&mut runtime);
     }
 
     #[test]
     fn test_transition_walk_to_idle() {
-        let (blueprint, _) = create_player_blueprint();
-        let mut runtime = RuntimeStateMachine::new(blueprint, {
-            let mut s = State::new();
-            s.insert(1, Arc::new(Action::Walk));
-            s
-        });
+        let mut runtime = player_template()
+            .instantiate(State::new().with_overrides([(1, Arc::new(Action::Walk) as _)]))
+            .unwrap();
 
         // 触发 PressS
-        runtime.event_happen(101, None);
-        runtime.transform();
-
-        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        Scenario::new("Walk -> Idle").dispatch(101).expect_aspect(1, Action::Idle).assert(&mut runtime);
     }
 
     #[test]
     fn test_no_transition_when_guard_fails() {
-        let (blueprint, _) = create_player_blueprint();
-        let mut runtime = RuntimeStateMachine::new(blueprint, {
-            let mut s = State::new();
-            s.insert(1, Arc::new(Action::Walk));
-            s
-        });
+        let mut runtime = player_template()
+            .instantiate(State::new().with_overrides([(1, Arc::new(Action::Walk) as _)]))
+            .unwrap();
 
         // 在 Walk 状态下触发 PressW（应无效）
         let prev_state = runtime.current_state.clone();
@@ -208,7 +214,7 @@ mod tests {
         let enter_flag = enter_triggered.clone();
         let exit_flag = exit_triggered.clone();
 
-        blueprint.observers.push(StateObserver {
+        blueprint.add_observer(StateObserver {
             id: 2,
             region: StateInRange::new(|s| {
                 s.get(&1)
@@ -221,7 +227,11 @@ mod tests {
             on_exit: Some(Arc::new(move |_| {
                 exit_flag.store(true, std::sync::atomic::Ordering::Relaxed);
             })),
-        });
+            priority: 0,
+            on_enter_consume: None,
+            active_when: None,
+            tag: None,
+        }).unwrap();
 
         let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
 
@@ -246,21 +256,21 @@ mod multi_aspect_tests {
 
     // 辅助函数：创建饥饿系统蓝图
     fn create_hunger_blueprint() -> (StateMachineBlueprint, State) {
-        let hunger_aspect = StateAspect {
-            id: HUNGER_ASPECT_ID,
-            value_type_id: TypeId::of::<i32>(),
-        };
+        // 初始饱食度 = 10
+        let hunger_aspect = StateAspect::of::<i32>(HUNGER_ASPECT_ID).with_default(10i32);
 
         // 事件：吃东西（+5 饱食度）
         let eat_event = EventDef {
             id: 200,
             payload_type_id: TypeId::of::<()>(),
+            transformers: Vec::new(),
         };
 
         // 事件：饥饿（-1 饱食度）
         let starve_event = EventDef {
             id: 201,
             payload_type_id: TypeId::of::<()>(),
+            transformers: Vec::new(),
         };
 
         // 谓词：饥饿（<= 5）
@@ -297,109 +307,100 @@ mod multi_aspect_tests {
         });
 
         let mut blueprint = StateMachineBlueprint::new();
-        blueprint.aspects.insert(hunger_aspect.id, hunger_aspect);
-        blueprint.events.insert(eat_event.id, eat_event);
-        blueprint.events.insert(starve_event.id, starve_event);
+        blueprint.add_aspect(hunger_aspect).unwrap();
+        blueprint.add_event(eat_event).unwrap();
+        blueprint.add_event(starve_event).unwrap();
 
         // Eat transition（任何状态都能吃）
-        blueprint.transitions.push(Transition {
+        blueprint.add_transition(Transition {
             id: 3,
             event_id: 200,
             guard: StateInRange::new(|_| true), // 通配
             transfer: eat_transfer,
             priority: 0,
             on_tran: None,
-        });
+            emits: Vec::new(),
+            tag: None,
+            min_dwell: None,
+            ensures: None,
+            respond: None,
+        }).unwrap();
 
         // Starve transition（任何状态都能饿）
-        blueprint.transitions.push(Transition {
+        blueprint.add_transition(Transition {
             id: 4,
             event_id: 201,
             guard: StateInRange::new(|_| true),
             transfer: starve_transfer,
             priority: 0,
             on_tran: None,
-        });
+            emits: Vec::new(),
+            tag: None,
+            min_dwell: None,
+            ensures: None,
+            respond: None,
+        }).unwrap();
 
         // Observer: 进入饥饿状态
-        blueprint.observers.push(StateObserver {
+        blueprint.add_observer(StateObserver {
             id: 3,
             region: is_hungry,
             on_enter: None,
             on_exit: None,
-        });
-
-        // 初始状态：饱食度 = 10
-        let initial_state: State = {
-            let mut s = State::new();
-            s.insert(HUNGER_ASPECT_ID, Arc::new(10i32));
-            s
-        };
+            priority: 0,
+            on_enter_consume: None,
+            active_when: None,
+            tag: None,
+        }).unwrap();
 
+        let initial_state = blueprint.default_initial_state().unwrap();
         (blueprint, initial_state)
     }
 
-    // 辅助函数：获取 Hunger 状态
-    fn get_hunger(state: &State) -> Option<i32> {
-        state
-            .get(&HUNGER_ASPECT_ID)
-            .and_then(|v| v.downcast_ref::<i32>().copied())
-    }
-
     #[test]
     fn test_blueprint_merge() {
         // 1. 创建两个独立蓝图
-        let (action_bp, action_state) = create_player_blueprint();
-        let (hunger_bp, hunger_state) = create_hunger_blueprint();
+        let (action_bp, _) = create_player_blueprint();
+        let (hunger_bp, _) = create_hunger_blueprint();
 
         // 2. 合并蓝图
         let merged_bp = action_bp.merge(&hunger_bp);
 
-        // 3. 合并初始状态
-        let mut initial_state = action_state;
-        initial_state.extend(hunger_state);
-
-        // 4. 创建运行时
-        let mut runtime = RuntimeStateMachine::new(merged_bp, initial_state);
-
-        // 验证初始状态
-        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
-        assert_eq!(get_hunger(&runtime.current_state), Some(10));
-
-        // 5. 触发行为事件：PressW → Walk
-        runtime.event_happen(100, None);
-        runtime.transform();
-        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
-        assert_eq!(get_hunger(&runtime.current_state), Some(10)); // 饱食度不变
-
-        // 6. 触发饱食度事件：Starve → 饱食度-1
-        runtime.event_happen(201, None);
-        runtime.transform();
-        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk)); // 行为不变
-        assert_eq!(get_hunger(&runtime.current_state), Some(9));
-
-        // 7. 再次触发行为事件：PressS → Idle
-        runtime.event_happen(101, None);
-        runtime.transform();
-        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
-        assert_eq!(get_hunger(&runtime.current_state), Some(9));
+        // 3. 初始状态取自两个蓝图中方面的默认值，4. 创建运行时
+        let mut runtime = MachineTemplate::new(merged_bp).instantiate(State::new()).unwrap();
 
-        // 8. 触发 Eat → 饱食度+5
-        runtime.event_happen(200, None);
-        runtime.transform();
-        assert_eq!(get_hunger(&runtime.current_state), Some(14));
+        Scenario::new("合并后的行为与饱食度")
+            // 验证初始状态
+            .expect_aspect(1, Action::Idle)
+            .expect_aspect(HUNGER_ASPECT_ID, 10i32)
+            // 5. 触发行为事件：PressW → Walk，饱食度不变
+            .dispatch(100)
+            .expect_aspect(1, Action::Walk)
+            .expect_aspect(HUNGER_ASPECT_ID, 10i32)
+            // 6. 触发饱食度事件：Starve → 饱食度-1，行为不变
+            .dispatch(201)
+            .expect_aspect(1, Action::Walk)
+            .expect_aspect(HUNGER_ASPECT_ID, 9i32)
+            // 7. 再次触发行为事件：PressS → Idle
+            .dispatch(101)
+            .expect_aspect(1, Action::Idle)
+            .expect_aspect(HUNGER_ASPECT_ID, 9i32)
+            // 8. 触发 Eat → 饱食度+5
+            .dispatch(200)
+            .expect_aspect(HUNGER_ASPECT_ID, 14i32)
+            .assert(&mut runtime);
     }
 
     #[test]
     fn test_observer_in_merged_blueprint() {
-        let (action_bp, action_state) = create_player_blueprint();
-        let (hunger_bp, hunger_state) = create_hunger_blueprint();
+        let (action_bp, _) = create_player_blueprint();
+        let (hunger_bp, _) = create_hunger_blueprint();
 
         // 添加饥饿 Observer（带副作用）
         let hunger_enter_triggered = Arc::new(std::sync::atomic::AtomicBool::new(false));
         let flag = hunger_enter_triggered.clone();
         let mut hunger_bp_with_observer = hunger_bp.clone();
-        hunger_bp_with_observer.observers.push(StateObserver {
+        hunger_bp_with_observer.add_observer(StateObserver {
             id: 4,
             region: StateInRange::new(|s| {
                 s.get(&HUNGER_ASPECT_ID)
@@ -410,21 +411,4602 @@ mod multi_aspect_tests {
                 flag.store(true, std::sync::atomic::Ordering::Relaxed);
             })),
             on_exit: None,
-        });
+            priority: 0,
+            on_enter_consume: None,
+            active_when: None,
+            tag: None,
+        }).unwrap();
 
         let merged_bp = action_bp.merge(&hunger_bp_with_observer);
-        let mut initial_state = action_state;
-        initial_state.extend(hunger_state);
+        let mut runtime = MachineTemplate::new(merged_bp).instantiate(State::new()).unwrap();
+
+        // 将饱食度降到 5 以下：Starve 6 次，10 → 4
+        (0..6)
+            .fold(Scenario::new("挨饿"), |scenario, _| scenario.dispatch(201))
+            .expect_aspect(HUNGER_ASPECT_ID, 4i32)
+            .assert(&mut runtime);
+        assert!(hunger_enter_triggered.load(std::sync::atomic::Ordering::Relaxed));
+    }
+}
+// --- 守卫采样测试 ---
+#[cfg(test)]
+mod sample_tests {
+    use super::*;
+    use std::collections::HashMap;
+    use state_zen::utils::{sample_guard, AspectDomain};
 
-        let mut runtime = RuntimeStateMachine::new(merged_bp, initial_state);
+    #[test]
+    fn test_sample_guard_returns_witnesses() {
+        let mut domains: HashMap<StateAspectId, AspectDomain> = HashMap::new();
+        domains.insert(1, vec![Arc::new(Action::Idle), Arc::new(Action::Walk)]);
+        domains.insert(2, (0..=20i32).map(|h| Arc::new(h) as _).collect());
 
-        // 将饱食度降到 5 以下
-        for _ in 0..6 {
-            runtime.event_happen(201, None); // Starve 6 次: 10 → 4
-            runtime.transform();
+        let walking_and_hungry = StateInRange::new(|s| {
+            s.get(&1)
+                .and_then(|v| v.downcast_ref::<Action>())
+                .is_some_and(|a| *a == Action::Walk)
+        })
+        .and(StateInRange::new(|s| {
+            s.get(&2)
+                .and_then(|v| v.downcast_ref::<i32>())
+                .is_some_and(|h| *h <= 5)
+        }));
+
+        let witnesses = sample_guard(&walking_and_hungry, &domains, 3);
+        assert_eq!(witnesses.len(), 3);
+        assert!(witnesses.iter().all(|s| walking_and_hungry.contains(s)));
+
+        // 全部可满足状态只有 6 个
+        assert_eq!(sample_guard(&walking_and_hungry, &domains, 100).len(), 6);
+        // 不可满足的守卫没有见证状态
+        assert!(sample_guard(&StateInRange::new(|_| false), &domains, 10).is_empty());
+    }
+}
+
+// --- 事件中间件测试 ---
+#[cfg(test)]
+mod middleware_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[test]
+    fn test_middleware_remaps_and_drops_events() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        // 过场动画期间静音所有移动事件
+        let cutscene = Arc::new(AtomicBool::new(true));
+        let muted = cutscene.clone();
+        runtime.use_middleware(move |event, payload, next| {
+            if !muted.load(Ordering::Relaxed) {
+                next(event, payload);
+            }
+        });
+        // 输入重映射：方向键上（102）映射为 PressW（100）
+        runtime.use_middleware(|event, payload, next| {
+            next(if event == 102 { 100 } else { event }, payload)
+        });
+
+        runtime.handle_event(102, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+
+        cutscene.store(false, Ordering::Relaxed);
+        runtime.handle_event(102, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+    }
+
+    #[test]
+    fn test_middleware_duplicates_events() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        let seen = Arc::new(AtomicUsize::new(0));
+        let counter = seen.clone();
+        runtime.use_middleware(|event, payload, next| {
+            next(event, payload.clone());
+            next(event, payload);
+        });
+        runtime.use_middleware(move |event, payload, next| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            next(event, payload);
+        });
+
+        runtime.handle_event(100, None);
+        assert_eq!(seen.load(Ordering::Relaxed), 2);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+    }
+}
+
+// --- 暂停与事件缓冲测试 ---
+#[cfg(test)]
+mod pause_tests {
+    use super::*;
+    use state_zen::core::OverflowPolicy;
+
+    #[test]
+    fn test_paused_events_processed_in_order_on_resume() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        runtime.pause();
+        runtime.handle_event(100, None); // Idle -> Walk
+        runtime.handle_event(101, None); // Walk -> Idle
+        runtime.handle_event(100, None); // Idle -> Walk
+        assert_eq!(runtime.buffered_events(), 3);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+
+        runtime.resume();
+        assert!(!runtime.is_paused());
+        assert_eq!(runtime.buffered_events(), 0);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+    }
+
+    #[test]
+    fn test_pause_buffer_overflow_policy() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        // 只保留最新的一个事件
+        runtime.set_pause_buffer(Some(1), OverflowPolicy::DropOldest);
+        runtime.pause();
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        assert_eq!(runtime.buffered_events(), 1);
+        runtime.resume();
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+
+        // 只保留最早的一个事件
+        runtime.set_pause_buffer(Some(1), OverflowPolicy::DropNewest);
+        runtime.pause();
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        runtime.resume();
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+    }
+}
+
+// --- 终止区域测试 ---
+#[cfg(test)]
+mod final_region_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_finished_detection_and_callback() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.set_final_region(StateInRange::new(|s| get_action(s) == Some(Action::Walk)));
+
+        let finished = Arc::new(AtomicUsize::new(0));
+        let counter = finished.clone();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_on_finished(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert!(!runtime.is_finished());
+        runtime.handle_event(100, None);
+        assert!(runtime.is_finished());
+        assert_eq!(finished.load(Ordering::Relaxed), 1);
+
+        // 离开后再次进入会再次触发
+        runtime.handle_event(101, None);
+        assert!(!runtime.is_finished());
+        runtime.handle_event(100, None);
+        assert_eq!(finished.load(Ordering::Relaxed), 2);
+    }
+}
+
+// --- 条件边沿观察者测试 ---
+#[cfg(test)]
+mod edge_observer_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use state_zen::EdgeObserver;
+
+    #[test]
+    fn test_edge_observer_rising_and_falling() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+
+        let rising = Arc::new(AtomicUsize::new(0));
+        let falling = Arc::new(AtomicUsize::new(0));
+        let (r, f) = (rising.clone(), falling.clone());
+        blueprint.add_edge_observer(EdgeObserver {
+            id: 10,
+            condition: StateInRange::new(|s| get_action(s) == Some(Action::Walk)),
+            on_rising: Some(Arc::new(move |prev, next| {
+                assert_eq!(get_action(prev), Some(Action::Idle));
+                assert_eq!(get_action(next), Some(Action::Walk));
+                r.fetch_add(1, Ordering::Relaxed);
+            })),
+            on_falling: Some(Arc::new(move |_, _| {
+                f.fetch_add(1, Ordering::Relaxed);
+            })),
+        }).unwrap();
+
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.handle_event(100, None);
+        runtime.handle_event(100, None); // 守卫不满足，不触发
+        assert_eq!((rising.load(Ordering::Relaxed), falling.load(Ordering::Relaxed)), (1, 0));
+
+        runtime.handle_event(101, None);
+        assert_eq!((rising.load(Ordering::Relaxed), falling.load(Ordering::Relaxed)), (1, 1));
+    }
+}
+
+// --- 连续转换测试 ---
+#[cfg(test)]
+mod continuous_tests {
+    use super::*;
+    use std::time::Duration;
+    use state_zen::core::ContinuousTransfer;
+
+    fn get_stamina(state: &State) -> Option<f64> {
+        state.get(&3).and_then(|v| v.downcast_ref::<f64>().copied())
+    }
+
+    #[test]
+    fn test_tick_applies_transfers_inside_region() {
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        initial_state.insert(3, Arc::new(10.0f64));
+
+        // 行走时体力每秒消耗 2
+        blueprint.add_continuous_transfer(ContinuousTransfer::new(
+            StateInRange::new(|s| get_action(s) == Some(Action::Walk)),
+            |s, dt| {
+                let mut next = s.clone();
+                let stamina = get_stamina(s).unwrap_or(0.0) - 2.0 * dt.as_secs_f64();
+                next.insert(3, Arc::new(stamina.max(0.0)));
+                next
+            },
+        ));
+
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.tick(Duration::from_secs(1));
+        assert_eq!(get_stamina(&runtime.current_state), Some(10.0));
+
+        runtime.handle_event(100, None);
+        runtime.tick(Duration::from_millis(1500));
+        assert_eq!(get_stamina(&runtime.current_state), Some(7.0));
+    }
+}
+
+// --- 状态机模板测试 ---
+#[cfg(test)]
+mod template_tests {
+    use super::*;
+    use state_zen::StateZenError;
+
+    #[test]
+    fn test_instantiate_with_defaults_and_overrides() {
+        let (blueprint, _) = create_player_blueprint();
+        // 模板的默认值优先于方面自身的默认值
+        let template = MachineTemplate::new(blueprint).with_default(1, Action::Walk);
+
+        let runtime = template.instantiate(State::new()).unwrap();
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+
+        let mut overrides = State::new();
+        overrides.insert(1, Arc::new(Action::Idle));
+        let runtime = template.instantiate(overrides).unwrap();
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+    }
+
+    #[test]
+    fn test_instantiate_rejects_missing_or_mistyped_aspects() {
+        let (mut blueprint, _) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<i32>(2)).unwrap();
+        let template = MachineTemplate::new(blueprint);
+        assert_eq!(
+            template.instantiate(State::new()).err(),
+            Some(StateZenError::MissingAspect(2))
+        );
+
+        let mut overrides = State::new();
+        overrides.insert(1, Arc::new(42i32));
+        overrides.insert(2, Arc::new(0i32));
+        assert_eq!(
+            template.instantiate(overrides).err(),
+            Some(StateZenError::AspectTypeMismatch(1))
+        );
+    }
+
+    #[test]
+    fn test_instantiate_requires_context_from_overrides() {
+        const OWNER: StateAspectId = 9;
+        let template = player_template().require_context::<u64>(OWNER).with_default(OWNER, 0u64);
+
+        // 上下文不能由默认值补齐
+        assert_eq!(template.instantiate(State::new()).err(), Some(StateZenError::MissingContext(OWNER)));
+        let mistyped = State::new().with_overrides([(OWNER, Arc::new("p1") as _)]);
+        assert_eq!(template.instantiate(mistyped).err(), Some(StateZenError::AspectTypeMismatch(OWNER)));
+
+        let runtime = template.instantiate(State::new().with_overrides([(OWNER, Arc::new(7u64) as _)])).unwrap();
+        assert_eq!(runtime.current_state.get(&OWNER).unwrap().downcast_ref::<u64>(), Some(&7));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+    }
+}
+
+// --- 类型化蓝图测试 ---
+#[cfg(test)]
+mod typed_tests {
+    use super::*;
+    use state_zen::core::TypedBlueprint;
+
+    #[test]
+    fn test_typed_blueprint_drives_runtime() {
+        let mut typed = TypedBlueprint::<(Action, i32)>::new([1, 2]);
+        typed
+            .add_event::<()>(100)
+            .add_transition(
+                1,
+                100,
+                |(action, hunger): &(Action, i32)| *action == Action::Idle && *hunger > 0,
+                |(_, hunger): &(Action, i32)| (Action::Walk, hunger - 1),
+                0,
+            );
+
+        let initial_state = typed.initial_state((Action::Idle, 10));
+        let blueprint_reader = TypedBlueprint::<(Action, i32)>::new(*typed.ids());
+        let mut runtime = RuntimeStateMachine::new(typed.build(), initial_state);
+
+        runtime.handle_event(100, None);
+        assert_eq!(blueprint_reader.read(&runtime.current_state), Some((Action::Walk, 9)));
+        assert_eq!(runtime.blueprint.aspect(2).unwrap().value_type_id, TypeId::of::<i32>());
+    }
+}
+
+// --- 外部事件源测试 ---
+#[cfg(test)]
+mod event_source_tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::mpsc;
+    use state_zen::EventInstance;
+
+    #[test]
+    fn test_drain_queue_and_channel_sources() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        let mut queue: VecDeque<EventInstance> =
+            [100, 101, 100].into_iter().map(|e| EventInstance::new(e, None)).collect();
+        assert_eq!(runtime.drain_source(&mut queue), 3);
+        assert!(queue.is_empty());
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+
+        let (tx, mut rx) = mpsc::channel();
+        tx.send(EventInstance::new(101, None)).unwrap();
+        assert_eq!(runtime.drain_source(&mut rx), 1);
+        assert_eq!(runtime.drain_source(&mut rx), 0);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+    }
+}
+
+// --- 领域事件汇测试 ---
+#[cfg(test)]
+mod event_sink_tests {
+    use super::*;
+    use std::sync::mpsc;
+    use state_zen::core::EventTemplate;
+
+    #[test]
+    fn test_transition_emits_rendered_events_to_sink() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.transition_mut(1).unwrap().emits = vec![
+            EventTemplate::new(300),
+            EventTemplate::with_payload(301, |s| Arc::new(get_action(s))),
+        ];
+
+        let (tx, rx) = mpsc::channel();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_event_sink(tx);
+
+        runtime.handle_event(101, None); // 守卫不满足，不发出事件
+        assert!(rx.try_recv().is_err());
+
+        runtime.handle_event(100, None);
+        let emitted: Vec<_> = rx.try_iter().collect();
+        assert_eq!(emitted.len(), 2);
+        assert_eq!(emitted[0].event_id, 300);
+        assert!(emitted[0].payload.is_none());
+        let payload = emitted[1].payload.as_ref().unwrap();
+        assert_eq!(payload.downcast_ref::<Option<Action>>(), Some(&Some(Action::Walk)));
+    }
+}
+
+// --- 小状态存储测试 ---
+#[cfg(test)]
+mod small_state_tests {
+    use super::*;
+    use state_zen::core::state::SMALL_STATE_THRESHOLD;
+    use state_zen::core::AspectValue;
+
+    #[test]
+    fn test_state_switches_representation_above_threshold() {
+        let mut state = State::new();
+        for id in (0..SMALL_STATE_THRESHOLD as u64).rev() {
+            state.insert(id, Arc::new(id as i32));
         }
+        assert!(state.is_inline());
+        // 内联存储按方面ID有序
+        assert!(state.keys().copied().eq(0..SMALL_STATE_THRESHOLD as u64));
 
-        assert_eq!(get_hunger(&runtime.current_state), Some(4));
-        assert!(hunger_enter_triggered.load(std::sync::atomic::Ordering::Relaxed));
+        state.insert(100, Arc::new(100i32));
+        assert!(!state.is_inline());
+        assert_eq!(state.len(), SMALL_STATE_THRESHOLD + 1);
+        for (id, value) in &state {
+            assert_eq!(value.downcast_ref::<i32>(), Some(&(*id as i32)));
+        }
+
+        let old = state.insert(3, Arc::new(-3i32)).unwrap();
+        assert_eq!(old.downcast_ref::<i32>(), Some(&3));
+        assert_eq!(state.remove(&3).unwrap().downcast_ref::<i32>(), Some(&-3));
+        assert!(!state.contains_key(&3));
+    }
+
+    #[test]
+    fn test_clone_from_and_clear_reuse_storage() {
+        let large: State = (0..=SMALL_STATE_THRESHOLD as u64).map(|id| (id, Arc::new(id as i32) as AspectValue)).collect();
+        let small: State = [(1, Arc::new(1i32) as AspectValue)].into_iter().collect();
+
+        let mut target = small.clone();
+        target.clone_from(&large);
+        assert!(!target.is_inline());
+        assert_eq!(target.len(), large.len());
+        target.clone_from(&small);
+        assert!(target.is_inline());
+        assert_eq!(target.get(&1).unwrap().downcast_ref::<i32>(), Some(&1));
+
+        let value: AspectValue = Arc::new(7i32);
+        let mut state = large.clone().with_overrides([(0, value.clone())]);
+        state.clear();
+        assert!(state.is_empty());
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_entry_retain_and_mutable_iteration() {
+        for capacity in [2, SMALL_STATE_THRESHOLD + 1] {
+            let mut state = State::with_capacity(capacity);
+            assert_eq!(state.is_inline(), capacity <= SMALL_STATE_THRESHOLD);
+            for id in 0..capacity as u64 {
+                state.entry(id).or_insert(Arc::new(id as i32));
+            }
+            state.entry(0).and_modify(|v| *v = Arc::new(-1i32)).or_insert(Arc::new(0i32));
+            assert_eq!(state.get(&0).unwrap().downcast_ref::<i32>(), Some(&-1));
+
+            for (id, value) in state.iter_mut() {
+                *value = Arc::new(*id as i32 * 10);
+            }
+            state.retain(|id, _| id % 2 == 0);
+            assert!(state.keys().all(|id| id % 2 == 0));
+            assert!(state.iter().all(|(id, v)| v.downcast_ref::<i32>() == Some(&(*id as i32 * 10))));
+            assert_eq!(state.values_mut().count(), capacity.div_ceil(2));
+        }
+
+        let state: State = [(2, Arc::new(1i32) as AspectValue), (1, Arc::new(2i32) as AspectValue)].into_iter().collect();
+        assert_eq!(format!("{state:?}"), "{1: .., 2: ..}");
+    }
+}
+
+// --- 分发索引测试 ---
+#[cfg(test)]
+mod dispatch_index_tests {
+    use super::*;
+
+    #[test]
+    fn test_replacing_transition_with_same_count_is_dispatched() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        Scenario::new("起步").dispatch(100).expect_aspect(1, Action::Walk).assert(&mut runtime);
+
+        // 转换数量不变，但事件 100 多了一个监听者
+        runtime.blueprint.remove_transition(2);
+        runtime.blueprint.add_transition(Transition::new(
+            3,
+            100,
+            StateInRange::aspect_eq(1, Action::Walk),
+            Transfer::set(1, Action::Idle),
+        )).unwrap();
+        Scenario::new("新监听者").dispatch(100).expect_aspect(1, Action::Idle).assert(&mut runtime);
+    }
+}
+
+// --- 观察者区域归属缓存测试 ---
+#[cfg(test)]
+mod observer_cache_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_membership_cache_halves_region_evaluations() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.remove_observer(1);
+
+        let evaluations = Arc::new(AtomicUsize::new(0));
+        let counter = evaluations.clone();
+        let entered = Arc::new(AtomicUsize::new(0));
+        let entered_counter = entered.clone();
+        blueprint.add_observer(StateObserver {
+            on_enter: Some(Arc::new(move |_| {
+                entered_counter.fetch_add(1, Ordering::Relaxed);
+            })),
+            ..StateObserver::new(
+                1,
+                StateInRange::new(move |s| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    get_action(s) == Some(Action::Walk)
+                }),
+            )
+        }).unwrap();
+
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        // 首次提交计算 2 次，之后每次只计算新状态
+        assert_eq!(evaluations.load(Ordering::Relaxed), 4);
+        assert_eq!(entered.load(Ordering::Relaxed), 2);
+
+        // 直接替换状态后缓存失效，不会误判进出
+        let mut idle = State::new();
+        idle.insert(1, Arc::new(Action::Idle));
+        runtime.set_state(idle);
+        runtime.handle_event(100, None);
+        assert_eq!(entered.load(Ordering::Relaxed), 3);
     }
-}
\ No newline at end of file
+}
+
+// --- 观察者并行计算测试 ---
+#[cfg(all(test, feature = "parallel"))]
+mod parallel_observer_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_parallel_membership_keeps_callback_order() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.remove_observer(1);
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for id in 0..1000u64 {
+            let order = order.clone();
+            blueprint.add_observer(StateObserver {
+                on_enter: Some(Arc::new(move |_| order.lock().unwrap().push(id))),
+                ..StateObserver::new(
+                    id,
+                    StateInRange::new(|s| get_action(s) == Some(Action::Walk)),
+                )
+            }).unwrap();
+        }
+
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_parallel_observer_threshold(Some(100));
+        runtime.handle_event(100, None);
+
+        let order = order.lock().unwrap();
+        assert!(order.iter().copied().eq(0..1000));
+    }
+}
+
+// --- 谓词驻留测试 ---
+#[cfg(test)]
+mod intern_tests {
+    use super::*;
+    use state_zen::core::PredicateInterner;
+
+    #[test]
+    fn test_structurally_equal_predicates_share_closure() {
+        let mut interner = PredicateInterner::new();
+        let a = interner.intern(StateInRange::aspect_eq(1, Action::Walk));
+        let b = interner.intern(StateInRange::aspect_eq(1, Action::Walk));
+        let c = interner.intern(StateInRange::aspect_eq(1, Action::Idle));
+        let d = interner.intern(
+            StateInRange::aspect_in(2, 0..=5).and(StateInRange::aspect_eq(1, Action::Walk)),
+        );
+        let e = interner.intern(
+            StateInRange::aspect_in(2, 0..=5).and(StateInRange::aspect_eq(1, Action::Walk)),
+        );
+        let f = interner.intern(StateInRange::aspect_in(2, 0..5));
+
+        assert!(StateInRange::ptr_eq(&a, &b));
+        assert!(!StateInRange::ptr_eq(&a, &c));
+        assert!(StateInRange::ptr_eq(&d, &e));
+        assert!(!StateInRange::ptr_eq(&d, &f));
+        assert_eq!(interner.len(), 4);
+
+        // 闭包谓词不参与驻留
+        let opaque = StateInRange::new(|_| true);
+        assert!(StateInRange::ptr_eq(&interner.intern(opaque.clone()), &opaque));
+    }
+
+    #[test]
+    fn test_intern_merged_blueprint() {
+        let (mut left, initial_state) = create_player_blueprint();
+        let (mut right, _) = create_player_blueprint();
+        left.observer_mut(1).unwrap().region = StateInRange::aspect_eq(1, Action::Walk);
+        right.observer_mut(1).unwrap().region = StateInRange::aspect_eq(1, Action::Walk);
+
+        let mut merged = left.merge(&right);
+        assert_eq!(merged.intern_predicates(), 1);
+        let regions: Vec<_> = merged.observers().map(|o| &o.region).collect();
+        assert!(StateInRange::ptr_eq(regions[0], regions[1]));
+
+        let mut runtime = RuntimeStateMachine::new(merged, initial_state);
+        runtime.handle_event(100, None);
+        assert!(runtime.blueprint.observers().all(|o| o.region.contains(&runtime.current_state)));
+    }
+}
+
+// --- 蓝图查询接口测试 ---
+#[cfg(test)]
+mod blueprint_query_tests {
+    use super::*;
+
+    #[test]
+    fn test_query_transitions_and_observers() {
+        let (mut blueprint, _) = create_player_blueprint();
+        blueprint.add_observer(StateObserver::new(
+            2,
+            StateInRange::aspect_in(2, ..=5).and(StateInRange::aspect_eq(1, Action::Idle)),
+        )).unwrap();
+        blueprint.add_observer(StateObserver::new(3, StateInRange::new(|s| s.contains_key(&3)).with_reads([3]))).unwrap();
+
+        let ids: Vec<_> = blueprint.transitions_for_event(100).map(|t| t.id).collect();
+        assert_eq!(ids, vec![1]);
+        assert_eq!(blueprint.transition(2).map(|t| t.event_id), Some(101));
+        assert_eq!(blueprint.events().count(), 2);
+        assert!(blueprint.event(101).is_some());
+        assert_eq!(blueprint.aspect(1).map(|a| a.value_type_id), Some(TypeId::of::<Action>()));
+
+        // 观察者 1 的区域是未声明读集合的闭包，保守地视为依赖所有方面
+        let watching = |aspect| -> Vec<_> {
+            blueprint.observers_watching(aspect).map(|o| o.id).collect()
+        };
+        assert_eq!(watching(1), vec![1, 2]);
+        assert_eq!(watching(2), vec![1, 2]);
+        assert_eq!(watching(3), vec![1, 3]);
+        assert_eq!(blueprint.observer(2).and_then(|o| o.region.reads()), Some(&[1, 2][..]));
+    }
+
+    #[test]
+    fn test_mutate_and_remove_through_methods() {
+        let (mut blueprint, _) = create_player_blueprint();
+        blueprint.add_observer(StateObserver::new(2, StateInRange::aspect_eq(1, Action::Walk))).unwrap();
+        blueprint.declare_containment(1, 2).unwrap();
+
+        let generation = blueprint.generation();
+        blueprint.transition_mut(1).unwrap().priority = 4;
+        assert_eq!(blueprint.transition(1).unwrap().priority, 4);
+        assert!(blueprint.generation() > generation);
+
+        assert_eq!(blueprint.remove_transition(2).map(|t| t.event_id), Some(101));
+        assert!(blueprint.remove_transition(2).is_none());
+        assert!(blueprint.remove_observer(1).is_some());
+        assert_eq!(blueprint.containments().count(), 0);
+        assert_eq!(blueprint.nesting_depth(2), 0);
+    }
+}
+
+// --- 蓝图修改接口测试 ---
+#[cfg(test)]
+mod blueprint_mutation_tests {
+    use super::*;
+    use state_zen::StateZenError;
+
+    #[test]
+    fn test_add_methods_reject_conflicts() {
+        let (mut blueprint, _) = create_player_blueprint();
+
+        // 同类型重复声明是允许的
+        assert_eq!(blueprint.add_aspect(StateAspect::of::<Action>(1)), Ok(()));
+        assert_eq!(
+            blueprint.add_aspect(StateAspect::of::<i32>(1)),
+            Err(StateZenError::DuplicateAspect(1))
+        );
+        assert_eq!(
+            blueprint.add_event(EventDef::of::<i32>(100)),
+            Err(StateZenError::DuplicateEvent(100))
+        );
+
+        let transition = |id, event_id| Transition::new(id, event_id, StateInRange::always(), Transfer::new(|s| s.clone()));
+        assert_eq!(blueprint.add_transition(transition(1, 100)), Err(StateZenError::DuplicateTransition(1)));
+        assert_eq!(blueprint.add_transition(transition(9, 999)), Err(StateZenError::UnknownEvent(999)));
+        assert_eq!(blueprint.add_transition(transition(9, 100)), Ok(()));
+        assert_eq!(blueprint.transitions_for_event(100).count(), 2);
+
+        let observer = StateObserver::new(1, StateInRange::always());
+        assert_eq!(blueprint.add_observer(observer), Err(StateZenError::DuplicateObserver(1)));
+    }
+}
+
+// --- 版本迁移测试 ---
+#[cfg(test)]
+mod migration_tests {
+    use super::*;
+    use state_zen::core::MigrationPlan;
+    use state_zen::StateZenError;
+
+    #[test]
+    fn test_migrate_old_snapshot_through_plans() {
+        let (mut blueprint, _) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<i32>(5)).unwrap();
+        blueprint.set_version(3);
+
+        // 版本 1：方面 1 用 bool 表示是否在走，饱食度在方面 2
+        let mut old = State::new();
+        old.insert(1, Arc::new(true));
+        old.insert(2, Arc::new(7i32));
+
+        let plans = [
+            MigrationPlan::new(2, 3).rename(2, 5),
+            MigrationPlan::new(1, 2).convert(1, 1, |walking: &bool| {
+                if *walking { Action::Walk } else { Action::Idle }
+            }),
+        ];
+        let migrated = blueprint.migrate_state(&old, 1, &plans).unwrap();
+        assert_eq!(get_action(&migrated), Some(Action::Walk));
+        assert_eq!(migrated.get(&5).and_then(|v| v.downcast_ref::<i32>()), Some(&7));
+        assert!(!migrated.contains_key(&2));
+
+        // 缺少迁移路径
+        assert_eq!(
+            blueprint.migrate_state(&old, 0, &plans).err(),
+            Some(StateZenError::NoMigrationPath { from: 0, to: 3 })
+        );
+        // 旧值类型不符
+        let mut bad = old.clone();
+        bad.insert(1, Arc::new("walk"));
+        assert_eq!(
+            blueprint.migrate_state(&bad, 1, &plans).err(),
+            Some(StateZenError::MigrationFailed(1))
+        );
+    }
+}
+
+// --- 覆盖率统计测试 ---
+#[cfg(test)]
+mod coverage_tests {
+    use super::*;
+    use state_zen::testing::{CoverageRecorder, CoverageRuntime};
+
+    #[test]
+    fn test_coverage_accumulates_across_runtimes() {
+        let recorder = CoverageRecorder::new();
+        let (blueprint, initial_state) = create_player_blueprint();
+
+        let mut first = CoverageRuntime::new(
+            RuntimeStateMachine::new(blueprint.clone(), initial_state.clone()),
+            &recorder,
+        );
+        first.handle_event(101, None); // 被忽略
+        let report = first.report();
+        assert_eq!(report.dead_transitions(), vec![1, 2]);
+        assert_eq!(report.transition_ratio(), 0.0);
+
+        first.handle_event(100, None);
+        let mut second = CoverageRuntime::new(RuntimeStateMachine::new(blueprint, initial_state), &recorder);
+        second.handle_event(100, None);
+        second.handle_event(101, None);
+
+        let report = second.report();
+        assert!(report.dead_transitions().is_empty());
+        assert_eq!(report.fired_observers.len(), 1);
+        assert_eq!(report.observer_ratio(), 1.0);
+        assert!(report.to_string().contains("转换: 2/2"));
+    }
+}
+
+// --- 脚本化场景测试 ---
+#[cfg(test)]
+mod scenario_tests {
+    use super::*;
+
+    #[test]
+    fn test_scenario_runs_and_reports_first_failure() {
+        let walking = StateInRange::aspect_eq(1, Action::Walk);
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        Scenario::new("走走停停")
+            .expect_not_in("walking", walking.clone())
+            .dispatch(100)
+            .expect_in("walking", walking.clone())
+            .dispatch(101)
+            .expect_aspect(1, Action::Idle)
+            .assert(&mut runtime);
+
+        let failure = Scenario::new("重复按键")
+            .dispatch(100)
+            .dispatch(100)
+            .expect_aspect(1, Action::Idle)
+            .run(&mut runtime)
+            .unwrap_err();
+        assert_eq!(failure.step, 3);
+        assert_eq!(
+            failure.to_string(),
+            "场景 `重复按键` 第 3 步（期望方面 1 == Idle）失败：实际为 Walk"
+        );
+    }
+}
+
+// --- 金标准追踪测试 ---
+#[cfg(test)]
+mod golden_tests {
+    use super::*;
+    use state_zen::core::AspectRegistry;
+    use state_zen::testing::{TraceRecorder, TraceMismatch, TraceLineDiff, assert_golden, diff_traces};
+
+    fn record_run(events: &[u64]) -> String {
+        let mut registry = AspectRegistry::new();
+        registry.register::<Action>(1, "action");
+        let recorder = TraceRecorder::new(registry);
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.add_tracer(recorder.clone());
+        for &event in events {
+            runtime.handle_event(event, None);
+        }
+        recorder.to_text()
+    }
+
+    #[test]
+    fn test_golden_trace_records_and_detects_changes() {
+        let path = std::env::temp_dir().join(format!("state_zen_golden_{}.trace", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let trace = record_run(&[100, 100, 101]);
+        assert_eq!(
+            trace,
+            "event 100 -> transition 1\n\
+             commit | action: Idle -> Walk\n\
+             event 100 -> ignored\n\
+             event 101 -> transition 2\n\
+             commit | action: Walk -> Idle\n"
+        );
+
+        // 缺失的金标准不会被悄悄生成
+        let missing = assert_golden(&path, &trace).unwrap_err();
+        assert!(matches!(missing, TraceMismatch::Io { kind: std::io::ErrorKind::NotFound, .. }));
+        assert!(!path.exists());
+
+        // 写入金标准后重跑相同输入应一致
+        std::fs::write(&path, &trace).unwrap();
+        assert_golden(&path, &record_run(&[100, 100, 101])).unwrap();
+
+        let mismatch = assert_golden(&path, &record_run(&[100, 101, 101])).unwrap_err();
+        let _ = std::fs::remove_file(&path);
+        // 少了一次忽略、多了一次忽略，中间对齐的两行不算不一致
+        let TraceMismatch::Lines { lines, .. } = mismatch else { panic!("{mismatch}") };
+        assert_eq!(
+            lines,
+            vec![
+                TraceLineDiff::Removed { line: 3, text: "event 100 -> ignored".to_string() },
+                TraceLineDiff::Added { line: 5, text: "event 101 -> ignored".to_string() },
+            ]
+        );
+
+        // 开头插入一行只报告这一行
+        let inserted = diff_traces("a\nb\nc\n", "x\na\nb\nc\n");
+        assert_eq!(inserted, vec![TraceLineDiff::Added { line: 1, text: "x".to_string() }]);
+    }
+}
+
+// --- 运行历史测试 ---
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+    use state_zen::core::History;
+
+    #[test]
+    fn test_history_records_and_steps_back() {
+        let history = History::new(Some(2));
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.add_tracer(history.clone());
+
+        runtime.handle_event(100, None);
+        runtime.handle_event(100, None); // 被忽略，不产生记录
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+
+        // 容量为 2，只保留最近两次提交
+        let entries = history.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].event_id, Some(101));
+        assert_eq!(entries[1].transition, Some(1));
+
+        let entry = history.step_back(&mut runtime).unwrap();
+        assert_eq!(get_action(&entry.after), Some(Action::Walk));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        history.step_back(&mut runtime).unwrap();
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+        assert!(history.step_back(&mut runtime).is_none());
+    }
+}
+
+// --- 转换断点测试 ---
+#[cfg(test)]
+mod breakpoint_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use state_zen::core::BreakAction;
+
+    #[test]
+    fn test_breakpoint_skip_and_continue() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        runtime.set_breakpoint(1, Box::new(move |ctx| {
+            assert_eq!(ctx.event_id, Some(100));
+            assert_eq!(get_action(ctx.state), Some(Action::Idle));
+            // 第一次跳过，之后放行
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 { BreakAction::Skip } else { BreakAction::Continue }
+        }));
+
+        Scenario::new("跳过后放行")
+            .dispatch(100)
+            .expect_aspect(1, Action::Idle)
+            .dispatch(100)
+            .expect_aspect(1, Action::Walk)
+            .assert(&mut runtime);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        assert!(runtime.clear_breakpoint(1));
+        assert!(!runtime.clear_breakpoint(1));
+    }
+
+    #[test]
+    fn test_breakpoint_pause_holds_transition_until_resume() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_breakpoint(1, Box::new(|_| BreakAction::Pause));
+
+        runtime.handle_event(100, None);
+        assert!(runtime.is_paused());
+        assert_eq!(runtime.pending_transition().map(|t| t.id), Some(1));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+
+        // 暂停期间的事件被缓冲，恢复时先执行挂起的转换
+        runtime.handle_event(101, None);
+        assert_eq!(runtime.buffered_events(), 1);
+        runtime.resume();
+        assert!(!runtime.is_paused());
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+
+        // 丢弃挂起的转换后恢复
+        runtime.handle_event(100, None);
+        assert!(runtime.discard_pending().is_some());
+        runtime.resume();
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+    }
+}
+
+// --- 运行时监控测试 ---
+#[cfg(test)]
+mod monitor_tests {
+    use super::*;
+    use state_zen::core::AspectRegistry;
+    use state_zen::monitor::Monitor;
+
+    fn registry() -> AspectRegistry {
+        let mut registry = AspectRegistry::new();
+        registry.register::<Action>(1, "action");
+        registry
+    }
+
+    #[test]
+    fn test_monitor_collects_stats_per_machine() {
+        let monitor = Monitor::new();
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut a = RuntimeStateMachine::new(blueprint.clone(), initial_state.clone());
+        let mut b = RuntimeStateMachine::new(blueprint, initial_state);
+        monitor.attach("a", registry(), &mut a);
+        monitor.attach("b", registry(), &mut b);
+
+        a.handle_event(100, None);
+        a.handle_event(100, None);
+        a.handle_event(101, None);
+        a.handle_event(101, None);
+        a.handle_event(101, None);
+
+        let stats = monitor.snapshot();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].events, 5);
+        assert_eq!(stats[0].transitions, 2);
+        assert_eq!(stats[0].ignored, 3);
+        assert_eq!(stats[0].ignored_by_event, vec![(100, 1), (101, 2)]);
+        assert_eq!(stats[0].transition_rate, 2.0);
+        assert_eq!(stats[0].state, "action=Idle");
+        assert_eq!(stats[1].events, 0);
+        assert_eq!(stats[1].state, "action=Idle");
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_monitor_renders_dashboard() {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let monitor = Monitor::new();
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        monitor.attach("player", registry(), &mut runtime);
+        runtime.handle_event(100, None);
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 10)).unwrap();
+        terminal
+            .draw(|frame| state_zen::monitor::tui::render(frame, &monitor.snapshot()))
+            .unwrap();
+        let text: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(text.contains("player"));
+        assert!(text.contains("action=Walk"));
+    }
+}
+
+// --- 子状态机编排测试 ---
+#[cfg(test)]
+mod supervisor_tests {
+    use super::*;
+    use state_zen::core::MachineSupervisor;
+    use state_zen::{MachineTemplate, StateZenError};
+
+    const TTL: StateAspectId = 10;
+    const TICK: u64 = 300;
+
+    fn projectile_template() -> MachineTemplate {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(TTL)).unwrap();
+        blueprint.add_event(EventDef::new(TICK)).unwrap();
+        blueprint.add_transition(Transition::new(
+            1,
+            TICK,
+            StateInRange::always(),
+            Transfer::new(|s| {
+                let ttl = *s.get(&TTL).unwrap().downcast_ref::<i32>().unwrap();
+                let mut next = s.clone();
+                next.insert(TTL, Arc::new(ttl - 1));
+                next
+            }),
+        )).unwrap();
+        blueprint.set_final_region(StateInRange::aspect_in(TTL, ..=0));
+        MachineTemplate::new(blueprint).with_default(TTL, 2i32)
+    }
+
+    #[test]
+    fn test_transition_spawns_and_supervisor_reaps_children() {
+        let mut supervisor = MachineSupervisor::new();
+        supervisor.register_template("projectile", projectile_template());
+
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        // 开始行走时发射一枚寿命为 1 的投射物
+        blueprint.transition_mut(1).unwrap().on_tran = Some(supervisor.spawner().on_tran("projectile", |_, _| {
+            let mut overrides = State::new();
+            overrides.insert(TTL, Arc::new(1i32));
+            overrides
+        }));
+        let mut player = RuntimeStateMachine::new(blueprint, initial_state);
+
+        player.handle_event(100, None);
+        assert_eq!(supervisor.process_spawns().unwrap(), vec![0]);
+        let long_lived = supervisor.spawn("projectile", State::new()).unwrap();
+        assert_eq!(supervisor.len(), 2);
+
+        supervisor.dispatch(TICK, None).unwrap();
+        assert_eq!(supervisor.children().map(|(id, _)| id).collect::<Vec<_>>(), vec![long_lived]);
+        supervisor.dispatch(TICK, None).unwrap();
+        assert!(supervisor.is_empty());
+
+        assert_eq!(
+            supervisor.spawn("rocket", State::new()).err(),
+            Some(StateZenError::UnknownTemplate("rocket".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_failed_spawn_keeps_remaining_requests_queued() {
+        let mut supervisor = MachineSupervisor::new();
+        supervisor.register_template("projectile", projectile_template());
+        let spawner = supervisor.spawner();
+        spawner.spawn("rocket", State::new());
+        spawner.spawn("projectile", State::new());
+
+        assert_eq!(
+            supervisor.process_spawns().err(),
+            Some(StateZenError::UnknownTemplate("rocket".to_string()))
+        );
+        assert!(supervisor.is_empty());
+        assert_eq!(supervisor.process_spawns().unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn test_restart_policies_handle_panics_and_invariants() {
+        use std::sync::Mutex;
+        use state_zen::core::{Activity, FailureCause, RestartPolicy};
+
+        const BOOM: u64 = 301;
+        let mut template = projectile_template();
+        template.blueprint.add_event(EventDef::new(BOOM)).unwrap();
+        template.blueprint.add_transition(Transition {
+            on_tran: Some(Arc::new(|_, _| panic!("boom"))),
+            ..Transition::new(2, BOOM, StateInRange::always(), Transfer::new(|s| s.clone()))
+        }).unwrap();
+
+        let notices = Arc::new(Mutex::new(Vec::new()));
+        let sink = notices.clone();
+        let mut supervisor = MachineSupervisor::new();
+        supervisor.register_template("reset", template.clone());
+        supervisor.register_template("escalate", template);
+        supervisor.set_restart_policy("reset", RestartPolicy::ResetToInitial);
+        supervisor.set_restart_policy("escalate", RestartPolicy::Escalate);
+        supervisor.set_invariant("reset", StateInRange::aspect_in(TTL, 2..));
+        supervisor.set_on_failure(move |n| sink.lock().unwrap().push(n.clone()));
+
+        let mut overrides = State::new();
+        overrides.insert(TTL, Arc::new(5i32));
+        let reset = supervisor.spawn("reset", overrides.clone()).unwrap();
+        let escalate = supervisor.spawn("escalate", overrides).unwrap();
+
+        // ttl 5 -> 4，仍满足不变式
+        supervisor.dispatch(TICK, None).unwrap();
+        let err = supervisor.dispatch(BOOM, None).unwrap_err();
+        assert_eq!(err, StateZenError::ChildFailed(escalate));
+        assert!(supervisor.child(escalate).is_none());
+
+        // panic 后重置为初始状态
+        let ttl = |s: &MachineSupervisor| *s.child(reset).unwrap().runtime.current_state.get(&TTL).unwrap().downcast_ref::<i32>().unwrap();
+        assert_eq!(ttl(&supervisor), 5);
+
+        // 违反不变式后同样重置
+        for _ in 0..4 {
+            supervisor.send_to(reset, TICK, None).unwrap();
+        }
+        assert_eq!(ttl(&supervisor), 5);
+
+        let notices = notices.lock().unwrap();
+        let causes: Vec<_> = notices.iter().map(|n| (n.child, n.cause.clone(), n.action)).collect();
+        assert_eq!(causes, vec![
+            (reset, FailureCause::Panic("boom".to_string()), Some(RestartPolicy::ResetToInitial)),
+            (escalate, FailureCause::Panic("boom".to_string()), Some(RestartPolicy::Escalate)),
+            (reset, FailureCause::InvariantViolated, Some(RestartPolicy::ResetToInitial)),
+        ]);
+        // 通知附带出错前的近期活动
+        assert_eq!(notices[0].recent_activity, vec![
+            Activity::Event { seq: 0, event_id: TICK, selected: Some(1) },
+            Activity::Transition { transition_id: 1, written: vec![TTL] },
+            Activity::Event { seq: 1, event_id: BOOM, selected: Some(2) },
+        ]);
+    }
+    #[test]
+    fn test_escalation_reported_before_spawn_failure() {
+        use state_zen::core::RestartPolicy;
+
+        const BOOM: u64 = 301;
+        let mut template = projectile_template();
+        template.blueprint.add_event(EventDef::new(BOOM)).unwrap();
+        template.blueprint.add_transition(Transition {
+            on_tran: Some(Arc::new(|_, _| panic!("boom"))),
+            ..Transition::new(2, BOOM, StateInRange::always(), Transfer::new(|s| s.clone()))
+        }).unwrap();
+
+        let mut supervisor = MachineSupervisor::new();
+        supervisor.register_template("escalate", template);
+        supervisor.set_restart_policy("escalate", RestartPolicy::Escalate);
+        let escalate = supervisor.spawn("escalate", State::new()).unwrap();
+        supervisor.register_template("projectile", projectile_template());
+        supervisor.spawner().spawn("rocket", State::new());
+        supervisor.spawner().spawn("projectile", State::new());
+
+        // 同一轮中子状态机失败、派生也失败：报告子状态机失败，其后的派生请求留待下次处理
+        assert_eq!(supervisor.dispatch(BOOM, None).err(), Some(StateZenError::ChildFailed(escalate)));
+        assert!(supervisor.is_empty());
+        assert_eq!(supervisor.process_spawns().unwrap().len(), 1);
+    }
+}
+
+// --- 受保护方面测试 ---
+#[cfg(test)]
+mod protected_aspect_tests {
+    use super::*;
+    use state_zen::StateZenError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const COINS: StateAspectId = 20;
+
+    fn plugin(transfer: Transfer) -> StateMachineBlueprint {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<u32>(COINS)).unwrap();
+        blueprint.add_event(EventDef::new(100)).unwrap();
+        blueprint.add_transition(Transition {
+            priority: 10,
+            ..Transition::new(50, 100, StateInRange::always(), transfer)
+        }).unwrap();
+        blueprint
+    }
+
+    fn cheat(s: &State) -> State {
+        let mut next = s.clone();
+        next.insert(1, Arc::new(Action::Walk));
+        next.insert(COINS, Arc::new(99u32));
+        next
+    }
+
+    #[test]
+    fn test_declared_write_to_protected_aspect_is_rejected() {
+        let (mut player, _) = create_player_blueprint();
+        player.protect_aspect(1);
+
+        let declared = plugin(Transfer::new(cheat).with_writes([COINS, 1]));
+        assert_eq!(
+            player.try_merge(&declared).err(),
+            Some(StateZenError::ProtectedAspectWrite { transition: 50, aspect: 1 })
+        );
+
+        let honest = plugin(Transfer::new(|s| s.clone()).with_writes([COINS]));
+        let merged = player.try_merge(&honest).unwrap();
+        assert_eq!(merged.protected_aspects().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_misdeclared_write_to_protected_aspect_is_rejected_at_runtime() {
+        let (mut player, mut initial_state) = create_player_blueprint();
+        player.protect_aspect(1);
+        initial_state.insert(COINS, Arc::new(0u32));
+
+        // 写集合只声明了金币，实际还改写了动作
+        let merged = player.try_merge(&plugin(Transfer::new(cheat).with_writes([COINS]))).unwrap();
+        let mut runtime = RuntimeStateMachine::new(merged, initial_state);
+        runtime.handle_event(100, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert_eq!(runtime.current_state.get(&COINS).unwrap().downcast_ref::<u32>(), Some(&0));
+    }
+
+    #[test]
+    fn test_undeclared_write_to_protected_aspect_is_rejected_at_runtime() {
+        let (mut player, mut initial_state) = create_player_blueprint();
+        player.protect_aspect(1);
+        initial_state.insert(COINS, Arc::new(0u32));
+
+        let merged = player.try_merge(&plugin(Transfer::new(cheat))).unwrap();
+        let mut runtime = RuntimeStateMachine::new(merged, initial_state);
+        runtime.handle_event(100, None);
+
+        // 插件转换优先级更高被选中，但它改写了受保护的方面，整个转换被拒绝
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert_eq!(runtime.current_state.get(&COINS).unwrap().downcast_ref::<u32>(), Some(&0));
+    }
+
+    #[test]
+    fn test_rejected_transition_does_not_fire_on_tran() {
+        let (mut player, mut initial_state) = create_player_blueprint();
+        player.protect_aspect(1);
+        initial_state.insert(COINS, Arc::new(0u32));
+
+        let fired = Arc::new(AtomicUsize::new(0));
+        let counter = fired.clone();
+        let mut cheating = plugin(Transfer::new(cheat));
+        cheating.transition_mut(50).unwrap().on_tran = Some(Arc::new(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        let merged = player.try_merge(&cheating).unwrap();
+        let mut runtime = RuntimeStateMachine::new(merged, initial_state);
+        runtime.handle_event(100, None);
+
+        // 被拒绝的转换按未选中处理：不触发回调，也不提交
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+    }
+
+    #[test]
+    fn test_merge_scoped_drops_writes_and_events_outside_scope() {
+        use state_zen::core::Scope;
+
+        let (player, mut initial_state) = create_player_blueprint();
+        initial_state.insert(COINS, Arc::new(0u32));
+        let mut untrusted = plugin(Transfer::new(cheat));
+        untrusted.add_event(EventDef::new(101)).unwrap();
+        untrusted.add_transition(Transition {
+            priority: 10,
+            ..Transition::new(51, 101, StateInRange::always(), Transfer::new(cheat))
+        }).unwrap();
+
+        let scope = Scope::new().allow_events([100]).allow_writes([COINS]);
+        let merged = player.merge_scoped(&untrusted, &scope);
+        assert!(merged.transition(51).is_none());
+        assert!(merged.transition(50).is_some());
+
+        let mut runtime = RuntimeStateMachine::new(merged, initial_state);
+        runtime.handle_event(100, None);
+        // 插件只能改写金币，对动作的写入被丢弃
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert_eq!(runtime.current_state.get(&COINS).unwrap().downcast_ref::<u32>(), Some(&99));
+    }
+
+    #[test]
+    fn test_merge_scoped_keeps_host_aspect_definitions() {
+        use state_zen::core::{Authority, Scope};
+
+        let mut host = StateMachineBlueprint::new();
+        host.add_aspect(StateAspect::of::<u32>(COINS).with_default(5u32)).unwrap();
+        host.set_authority(COINS, Authority::Server);
+        let mut untrusted = StateMachineBlueprint::new();
+        untrusted.add_aspect(
+            StateAspect::of::<u32>(COINS).with_validator(|c: &u32| *c > 1000).with_default(0u32),
+        ).unwrap();
+        untrusted.add_aspect(StateAspect::of::<u32>(21).with_default(1u32)).unwrap();
+        untrusted.set_authority(COINS, Authority::Client);
+        untrusted.set_authority(21, Authority::Client);
+        untrusted.set_final_region(StateInRange::always());
+
+        let merged = host.merge_scoped(&untrusted, &Scope::new().allow_writes([COINS, 21]));
+        // 宿主的默认值与（无）校验保留，插件的同ID声明被忽略
+        let initial = merged.default_initial_state().unwrap();
+        assert_eq!(initial.get(&COINS).unwrap().downcast_ref::<u32>(), Some(&5));
+        assert_eq!(merged.authority(COINS), Authority::Server);
+        // 插件的新方面照常导入
+        assert_eq!(initial.get(&21).unwrap().downcast_ref::<u32>(), Some(&1));
+        assert_eq!(merged.authority(21), Authority::Client);
+        assert!(!RuntimeStateMachine::new(merged, initial).is_finished());
+    }
+}
+
+// --- 事件别名测试 ---
+#[cfg(test)]
+mod event_alias_tests {
+    use super::*;
+    use state_zen::core::EventAliasMap;
+
+    const JUMPS: StateAspectId = 30;
+
+    #[test]
+    fn test_merge_aliased_rewrites_events_to_canonical_id() {
+        // 独立构建的蓝图里，"按下 W" 是事件 42
+        let mut counter = StateMachineBlueprint::new();
+        counter.add_aspect(StateAspect::of::<u32>(JUMPS)).unwrap();
+        counter.add_event(EventDef::new(42)).unwrap();
+        counter.add_transition(Transition::new(
+            9,
+            42,
+            StateInRange::always(),
+            Transfer::new(|s| {
+                let n = *s.get(&JUMPS).unwrap().downcast_ref::<u32>().unwrap();
+                let mut next = s.clone();
+                next.insert(JUMPS, Arc::new(n + 1));
+                next
+            }),
+        )).unwrap();
+
+        let aliases = EventAliasMap::new().alias(42, 100);
+        assert_eq!(aliases.canonical(42), 100);
+        assert_eq!(aliases.canonical(7), 7);
+
+        let (player, mut initial_state) = create_player_blueprint();
+        initial_state.insert(JUMPS, Arc::new(0u32));
+        let merged = player.merge_aliased(&counter, &aliases);
+        assert!(merged.event(42).is_none());
+        assert_eq!(merged.transitions_for_event(100).count(), 2);
+
+        // 两个转换监听同一个规范事件，按优先级只执行一个；拆开运行验证各自响应
+        let aliased_counter = aliases.apply(&counter);
+        let mut runtime = RuntimeStateMachine::new(aliased_counter, initial_state);
+        runtime.handle_event(100, None);
+        assert_eq!(runtime.current_state.get(&JUMPS).unwrap().downcast_ref::<u32>(), Some(&1));
+    }
+}
+
+// --- 标签测试 ---
+#[cfg(test)]
+mod tag_tests {
+    use super::*;
+
+    #[test]
+    fn test_tagged_entries_only_active_when_enabled() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        // 调试模式下 PressS 不会停下
+        blueprint.transition_mut(2).unwrap().tag = Some("normal".to_string());
+        blueprint.add_transition(Transition {
+            priority: 5,
+            tag: Some("debug".to_string()),
+            ..Transition::new(
+                3,
+                100,
+                StateInRange::aspect_eq(1, Action::Idle),
+                Transfer::new(|s| s.clone()),
+            )
+        }).unwrap();
+        assert!(blueprint.is_tag_enabled(None));
+        assert!(!blueprint.is_tag_enabled(Some("debug")));
+
+        let mut plain = RuntimeStateMachine::new(blueprint.clone(), initial_state.clone());
+        assert_eq!(plain.blueprint.transitions().map(|t| t.id).collect::<Vec<_>>(), vec![1]);
+        plain.handle_event(100, None);
+        assert_eq!(get_action(&plain.current_state), Some(Action::Walk));
+
+        blueprint.enable_tags(&["debug", "normal"]);
+        let mut debug = RuntimeStateMachine::new(blueprint, initial_state);
+        assert_eq!(debug.blueprint.transitions().count(), 3);
+        // 调试转换优先级更高，按 W 不再起步
+        debug.handle_event(100, None);
+        assert_eq!(get_action(&debug.current_state), Some(Action::Idle));
+    }
+}
+
+// --- 守卫描述测试 ---
+#[cfg(test)]
+mod guard_describe_tests {
+    use super::*;
+    use state_zen::core::{AspectRegistry, GuardExpr};
+
+    #[test]
+    fn test_describe_declarative_and_opaque_guards() {
+        let mut registry = AspectRegistry::new();
+        registry.register::<Action>(1, "action").register::<i32>(2, "hunger");
+
+        let guard = StateInRange::aspect_in(2, ..=5).and(StateInRange::aspect_eq(1, Action::Walk));
+        assert_eq!(guard.describe_with(&registry), "hunger <= 5 AND action == Walk");
+        assert_eq!(guard.describe(), "#2 <= 5 AND #1 == Walk");
+        assert!(matches!(guard.expr(), GuardExpr::And(..)));
+
+        let mixed = StateInRange::aspect_in(2, 1..10)
+            .or(StateInRange::aspect_eq(1, Action::Idle))
+            .and(StateInRange::new(|_| true).not());
+        assert_eq!(mixed.describe_with(&registry), "(1 <= hunger < 10 OR action == Idle) AND NOT <closure>");
+        assert!(!mixed.is_declarative());
+        assert!(mixed.expr().is_opaque());
+    }
+}
+
+// --- 守卫特化测试 ---
+#[cfg(test)]
+mod specialize_tests {
+    use super::*;
+    use state_zen::StateZenError;
+
+    const DIFFICULTY: StateAspectId = 40;
+
+    #[test]
+    fn test_specialize_guard_folds_constants() {
+        let hard_and_hungry = StateInRange::aspect_eq(DIFFICULTY, 3u8).and(StateInRange::aspect_in(2, ..=5));
+        assert_eq!(hard_and_hungry.specialize(DIFFICULTY, 3u8).unwrap().describe(), "#2 <= 5");
+        assert_eq!(hard_and_hungry.specialize(DIFFICULTY, 1u8).unwrap().constant(), Some(false));
+
+        let easy_or_opaque = StateInRange::aspect_in(DIFFICULTY, ..2u8).or(StateInRange::new(|_| false));
+        assert_eq!(easy_or_opaque.specialize(DIFFICULTY, 1u8).unwrap().constant(), Some(true));
+        let kept = easy_or_opaque.specialize(DIFFICULTY, 2u8).unwrap();
+        assert_eq!(kept.describe(), "<closure>");
+        assert!(!kept.contains(&State::new()));
+    }
+
+    #[test]
+    fn test_specialize_blueprint_prunes_dead_transitions() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let stop = blueprint.transition_mut(2).unwrap();
+        stop.guard = stop.guard.clone().and(StateInRange::aspect_eq(DIFFICULTY, 1u8));
+
+        let hard = blueprint.specialize(DIFFICULTY, 3u8).unwrap();
+        assert_eq!(hard.transitions().map(|t| t.id).collect::<Vec<_>>(), vec![1]);
+        let easy = blueprint.specialize(DIFFICULTY, 1u8).unwrap();
+        assert_eq!(easy.transitions().count(), 2);
+
+        // 特化后的守卫不再读取难度，状态里没有这个方面也能运行
+        let mut runtime = RuntimeStateMachine::new(easy, initial_state);
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+    }
+
+    #[test]
+    fn test_specialize_rejects_mismatched_value_type() {
+        let hard = StateInRange::aspect_eq(DIFFICULTY, 3u8);
+        assert_eq!(hard.specialize(DIFFICULTY, 3i32).err(), Some(StateZenError::AspectTypeMismatch(DIFFICULTY)));
+
+        let (mut blueprint, _) = create_player_blueprint();
+        let stop = blueprint.transition_mut(2).unwrap();
+        stop.guard = stop.guard.clone().and(hard);
+        assert_eq!(blueprint.specialize(DIFFICULTY, 3i32).err(), Some(StateZenError::AspectTypeMismatch(DIFFICULTY)));
+
+        // 方面声明的类型同样参与检查
+        assert_eq!(blueprint.specialize(1, 3u8).err(), Some(StateZenError::AspectTypeMismatch(1)));
+    }
+}
+
+// --- 转换表达式测试 ---
+#[cfg(test)]
+mod transfer_expr_tests {
+    use super::*;
+    use state_zen::core::AspectRegistry;
+    use state_zen::utils::find_write_conflicts;
+
+    #[test]
+    fn test_declarative_transfers_apply_and_describe() {
+        let transfer = Transfer::set(1, Action::Walk).then(Transfer::add(2, 3i32)).then(Transfer::sub(2, 1i32));
+        assert_eq!(transfer.writes(), Some(&[1, 2][..]));
+
+        let mut registry = AspectRegistry::new();
+        registry.register::<Action>(1, "action").register::<i32>(2, "hunger");
+        assert_eq!(transfer.describe_with(&registry), "action := Walk; hunger += 3; hunger -= 1");
+        assert_eq!(Transfer::new(|s| s.clone()).describe(), "<closure>");
+
+        let mut state = State::new();
+        state.insert(1, Arc::new(Action::Idle));
+        state.insert(2, Arc::new(10i32));
+        let next = transfer.apply(&state);
+        assert_eq!(get_action(&next), Some(Action::Walk));
+        assert_eq!(next.get(&2).unwrap().downcast_ref::<i32>(), Some(&12));
+
+        // 类型不符时算术更新不生效
+        let next = Transfer::add(2, 1u8).apply(&state);
+        assert_eq!(next.get(&2).unwrap().downcast_ref::<i32>(), Some(&10));
+    }
+
+    #[test]
+    fn test_find_write_conflicts_between_transitions_on_same_event() {
+        let (mut blueprint, _) = create_player_blueprint();
+        for (id, transfer) in [(10, Transfer::set(1, Action::Walk)), (11, Transfer::add(2, 1i32)), (12, Transfer::set(1, Action::Idle).then(Transfer::add(2, 1i32)))] {
+            blueprint.add_transition(Transition::new(id, 101, StateInRange::always(), transfer)).unwrap();
+        }
+
+        let conflicts = find_write_conflicts(&blueprint);
+        let pairs: Vec<_> = conflicts.iter().map(|c| (c.transitions, c.aspects.clone())).collect();
+        assert_eq!(pairs, vec![((10, 12), vec![1]), ((11, 12), vec![2])]);
+        assert_eq!(
+            Transfer::set(1, Action::Walk).expr().conflicts(Transfer::set(1, Action::Idle).expr()),
+            Some(vec![1])
+        );
+    }
+
+    #[test]
+    fn test_invert_restores_previous_state() {
+        let mut state = State::new();
+        state.insert(1, Arc::new(Action::Idle));
+        state.insert(2, Arc::new(10i32));
+
+        let transfer = Transfer::set(1, Action::Walk).then(Transfer::add(2, 3i32)).then(Transfer::set(3, 1u8));
+        let next = transfer.apply(&state);
+        let undone = transfer.invert(&state).unwrap().apply(&next);
+        assert_eq!(undone.len(), 2);
+        assert_eq!(get_action(&undone), Some(Action::Idle));
+        assert_eq!(undone.get(&2).unwrap().downcast_ref::<i32>(), Some(&10));
+
+        assert!(Transfer::new(|s| s.clone()).invert(&state).is_none());
+    }
+
+    #[test]
+    fn test_undo_log_uses_inverses_for_declarative_transfers() {
+        use state_zen::core::UndoLog;
+
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.transition_mut(1).unwrap().transfer = Transfer::set(1, Action::Walk);
+        let log = UndoLog::new(&blueprint, None);
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.add_tracer(log.clone());
+
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        assert_eq!(log.len(), 3);
+        // 只有闭包构造的 PressS 转换需要快照
+        assert_eq!(log.snapshot_count(), 1);
+
+        assert!(log.undo(&mut runtime));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert!(log.undo(&mut runtime));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+        assert!(log.undo(&mut runtime));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert!(!log.undo(&mut runtime));
+    }
+}
+
+#[cfg(test)]
+mod batch_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use state_zen::core::BatchConflictPolicy;
+    use state_zen::{EventId, EventInstance};
+
+    fn batch(events: &[EventId]) -> Vec<EventInstance> {
+        events.iter().map(|&e| EventInstance::new(e, None)).collect()
+    }
+
+    #[test]
+    fn test_handle_events_runs_one_observer_pass() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let enters = Arc::new(AtomicUsize::new(0));
+        let exits = Arc::new(AtomicUsize::new(0));
+        let counter = enters.clone();
+        blueprint.observer_mut(1).unwrap().on_enter = Some(Arc::new(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        let counter = exits.clone();
+        blueprint.observer_mut(1).unwrap().on_exit = Some(Arc::new(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        runtime.handle_events(&batch(&[100, 101, 100, 101]));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert_eq!(enters.load(Ordering::SeqCst), 0);
+        assert_eq!(exits.load(Ordering::SeqCst), 0);
+
+        runtime.handle_events(&batch(&[100, 101, 100]));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+        assert_eq!(enters.load(Ordering::SeqCst), 1);
+        assert_eq!(exits.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_handle_events_conflict_policies() {
+        let run = |policy| {
+            let (mut blueprint, initial_state) = create_player_blueprint();
+            blueprint.add_transition(Transition {
+                priority: -1,
+                ..Transition::new(3, 101, StateInRange::always(), Transfer::set(1, Action::Idle))
+            }).unwrap();
+            let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+            runtime.set_batch_policy(policy);
+            runtime.handle_events(&batch(&[100, 101]));
+            get_action(&runtime.current_state)
+        };
+
+        // 顺序执行时 PressS 看到 Walk，选中优先级更高的转换 2
+        assert_eq!(run(BatchConflictPolicy::Sequential), Some(Action::Idle));
+        // 基于批次开始状态时 PressS 只能选中转换 3，与转换 1 写同一方面
+        assert_eq!(run(BatchConflictPolicy::LastWriteWins), Some(Action::Idle));
+        assert_eq!(run(BatchConflictPolicy::FirstWriteWins), Some(Action::Walk));
+        assert_eq!(run(BatchConflictPolicy::Reject), Some(Action::Walk));
+    }
+}
+
+#[cfg(test)]
+mod deferred_effect_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_effects_run_at_flush_in_commit_order() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let sink = log.clone();
+        blueprint.observer_mut(1).unwrap().on_enter = Some(Arc::new(move |s| {
+            sink.lock().unwrap().push(format!("enter {:?}", get_action(s).unwrap()));
+        }));
+        let sink = log.clone();
+        blueprint.observer_mut(1).unwrap().on_exit = Some(Arc::new(move |s| {
+            sink.lock().unwrap().push(format!("exit {:?}", get_action(s).unwrap()));
+        }));
+        let sink = log.clone();
+        blueprint.transition_mut(2).unwrap().on_tran = Some(Arc::new(move |prev, next| {
+            sink.lock().unwrap().push(format!(
+                "tran {:?} -> {:?}",
+                get_action(prev).unwrap(),
+                get_action(next).unwrap()
+            ));
+        }));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_defer_effects(true);
+
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(runtime.pending_effects(), 3);
+
+        assert_eq!(runtime.flush_effects(), 3);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["enter Walk", "exit Walk", "tran Walk -> Idle"]
+        );
+        assert_eq!(runtime.flush_effects(), 0);
+
+        runtime.set_defer_effects(false);
+        runtime.handle_event(100, None);
+        assert_eq!(log.lock().unwrap().len(), 4);
+    }
+}
+
+#[cfg(test)]
+mod min_dwell_tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_transition_requires_min_dwell_in_guard_region() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.transition_mut(1).unwrap().min_dwell = Some(Duration::from_millis(500));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        Scenario::new("停留时间")
+            .dispatch(100)
+            .expect_aspect(1, Action::Idle)
+            .advance(Duration::from_millis(300))
+            .dispatch(100)
+            .expect_aspect(1, Action::Idle)
+            .advance(Duration::from_millis(200))
+            .dispatch(100)
+            .expect_aspect(1, Action::Walk)
+            // 离开再回到守卫区域后重新计时
+            .dispatch(101)
+            .dispatch(100)
+            .expect_aspect(1, Action::Idle)
+            .advance(Duration::from_millis(500))
+            .dispatch(100)
+            .expect_aspect(1, Action::Walk)
+            .assert(&mut runtime);
+    }
+}
+
+#[cfg(test)]
+mod recurring_event_tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_recurring_events_fire_on_tick_until_cancelled() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        let handle = runtime.schedule_recurring(100, Duration::from_millis(100), None);
+        let toggle = runtime.schedule_recurring(101, Duration::from_millis(250), None);
+        assert_eq!(runtime.recurring_events(), 2);
+
+        runtime.tick(Duration::from_millis(50));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        runtime.tick(Duration::from_millis(50));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+
+        // 200ms 时 PressW 被忽略，250ms 时 PressS 回到 Idle
+        runtime.tick(Duration::from_millis(160));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+
+        assert!(runtime.cancel_recurring(handle));
+        assert!(!runtime.cancel_recurring(handle));
+        runtime.tick(Duration::from_millis(100));
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert!(runtime.cancel_recurring(toggle));
+    }
+
+    #[test]
+    fn test_recurring_jitter_is_bounded_and_deterministic() {
+        let fire_times = || {
+            let (blueprint, initial_state) = create_player_blueprint();
+            let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+            runtime.schedule_recurring(100, Duration::from_millis(100), Some(Duration::from_millis(20)));
+            let mut times = Vec::new();
+            for ms in 1..=1000u64 {
+                runtime.tick(Duration::from_millis(1));
+                if get_action(&runtime.current_state) == Some(Action::Walk) {
+                    times.push(ms);
+                    runtime.handle_event(101, None);
+                }
+            }
+            times
+        };
+
+        let times = fire_times();
+        assert_eq!(times, fire_times());
+        assert!(times.len() >= 8);
+        let mut previous = 0;
+        for t in times {
+            assert!((100..=120).contains(&(t - previous)));
+            previous = t;
+        }
+    }
+}
+
+#[cfg(test)]
+mod merge_priority_tests {
+    use super::*;
+    use state_zen::core::{MergeOptions, PriorityBias};
+
+    fn run_with(bias: PriorityBias) -> Option<Action> {
+        let (mut base, initial_state) = create_player_blueprint();
+        base.transition_mut(1).unwrap().priority = 5;
+        // 覆盖蓝图：PressW 时保持 Idle
+        let mut patch = StateMachineBlueprint::new();
+        patch.add_event(EventDef::new(100)).unwrap();
+        patch.add_transition(Transition::new(10, 100, StateInRange::always(), Transfer::set(1, Action::Idle))).unwrap();
+
+        let merged = base.merge_with(&patch, &MergeOptions::new().priority(bias));
+        let mut runtime = RuntimeStateMachine::new(merged, initial_state);
+        runtime.handle_event(100, None);
+        get_action(&runtime.current_state)
+    }
+
+    #[test]
+    fn test_merge_priority_bias() {
+        assert_eq!(run_with(PriorityBias::Keep), Some(Action::Walk));
+        assert_eq!(run_with(PriorityBias::Offset(5)), Some(Action::Walk));
+        assert_eq!(run_with(PriorityBias::Offset(6)), Some(Action::Idle));
+        assert_eq!(run_with(PriorityBias::Override), Some(Action::Idle));
+    }
+
+    #[test]
+    fn test_offset_priorities_saturates() {
+        let (mut blueprint, _) = create_player_blueprint();
+        blueprint.transition_mut(1).unwrap().priority = i32::MAX - 1;
+        blueprint.offset_priorities(3);
+        assert_eq!(blueprint.transition(1).unwrap().priority, i32::MAX);
+        assert_eq!(blueprint.transition(2).unwrap().priority, 3);
+    }
+}
+
+#[cfg(test)]
+mod merge_policy_tests {
+    use super::*;
+    use state_zen::core::{MergeOptions, MergePolicy};
+
+    #[test]
+    fn test_replace_by_id_patches_transitions_in_place() {
+        let (base, initial_state) = create_player_blueprint();
+        let mut patch = StateMachineBlueprint::new();
+        patch.add_event(EventDef::new(101)).unwrap();
+        patch.add_transition(Transition::new(1, 101, StateInRange::always(), Transfer::set(1, Action::Walk))).unwrap();
+
+        let appended = base.merge(&patch);
+        assert_eq!(appended.transitions().filter(|t| t.id == 1).count(), 2);
+
+        let merged = base.merge_with(&patch, &MergeOptions::new().policy(MergePolicy::ReplaceById));
+        let ids: Vec<_> = merged.transitions().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        let mut runtime = RuntimeStateMachine::new(merged, initial_state);
+        Scenario::new("按ID替换")
+            .dispatch(100)
+            .expect_aspect(1, Action::Idle)
+            .dispatch(101)
+            .expect_aspect(1, Action::Walk)
+            .assert(&mut runtime);
+    }
+}
+
+#[cfg(test)]
+mod blueprint_diff_tests {
+    use super::*;
+    use state_zen::core::AspectRegistry;
+
+    #[test]
+    fn test_diff_lists_added_removed_and_changed_items() {
+        let (mut old, _) = create_player_blueprint();
+        let walk = old.transition_mut(1).unwrap();
+        walk.guard = StateInRange::aspect_eq(1, Action::Idle);
+        walk.transfer = Transfer::set(1, Action::Walk);
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = old.clone();
+        let walk = new.transition_mut(1).unwrap();
+        walk.priority = 2;
+        walk.transfer = Transfer::set(1, Action::Idle);
+        new.remove_transition(2);
+        new.add_aspect(StateAspect::of::<i32>(2)).unwrap();
+        new.observer_mut(1).unwrap().tag = Some("debug".to_string());
+
+        let mut registry = AspectRegistry::new();
+        registry.register::<Action>(1, "action").register::<i32>(2, "hunger");
+        let diff = old.diff_with(&new, &registry);
+        assert_eq!(diff.aspects.added, vec![2]);
+        assert_eq!(diff.transitions.removed, vec![2]);
+        assert!(diff.events.is_empty());
+        assert_eq!(
+            diff.to_string(),
+            "+ aspect hunger(2)\n\
+             - transition 2\n\
+             ~ transition 1 priority: 0 -> 2\n\
+             ~ transition 1 transfer: action := Walk -> action := Idle\n\
+             ~ observer 1 tag: - -> \"debug\"\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod aspect_validation_tests {
+    use super::*;
+    use state_zen::StateZenError;
+    use state_zen::core::ValidationPolicy;
+
+    const HUNGER: StateAspectId = 2;
+
+    fn runtime_with(policy: ValidationPolicy) -> RuntimeStateMachine {
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        blueprint
+            .add_aspect(StateAspect::of::<i32>(HUNGER).with_validator(|h: &i32| (0..=20).contains(h)))
+            .unwrap();
+        blueprint.add_event(EventDef::new(102)).unwrap();
+        blueprint.add_transition(Transition::new(
+            3,
+            102,
+            StateInRange::always(),
+            Transfer::set(1, Action::Walk).then(Transfer::add(HUNGER, 8)),
+        )).unwrap();
+        initial_state.insert(HUNGER, Arc::new(10i32));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_validation_policy(policy);
+        runtime.set_clamper(HUNGER, |h: &i32| (*h).clamp(0, 20));
+        runtime
+    }
+
+    fn hunger(runtime: &RuntimeStateMachine) -> i32 {
+        *runtime.current_state.get(&HUNGER).unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    #[test]
+    fn test_validation_policies() {
+        let mut runtime = runtime_with(ValidationPolicy::Clamp);
+        runtime.handle_event(102, None);
+        runtime.handle_event(102, None);
+        assert_eq!(hunger(&runtime), 20);
+        assert!(runtime.take_errors().is_empty());
+
+        let mut runtime = runtime_with(ValidationPolicy::Reject);
+        runtime.handle_event(102, None);
+        runtime.handle_event(101, None);
+        runtime.handle_event(102, None);
+        assert_eq!(hunger(&runtime), 18);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert!(runtime.take_errors().is_empty());
+
+        let mut runtime = runtime_with(ValidationPolicy::Error);
+        runtime.handle_event(102, None);
+        runtime.handle_event(102, None);
+        assert_eq!(hunger(&runtime), 18);
+        assert_eq!(
+            runtime.take_errors(),
+            vec![StateZenError::InvalidAspectValue { aspect: HUNGER, transition: Some(3) }]
+        );
+    }
+
+    #[test]
+    fn test_error_capacity_drops_oldest() {
+        let mut runtime = runtime_with(ValidationPolicy::Error);
+        runtime.set_error_capacity(2);
+        for _ in 0..4 {
+            runtime.handle_event(102, None);
+        }
+        assert_eq!(runtime.dropped_errors(), 1);
+        assert_eq!(runtime.take_errors().len(), 2);
+        assert!(runtime.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_validate_state_checks_validators() {
+        let runtime = runtime_with(ValidationPolicy::Error);
+        let mut state = runtime.current_state.clone();
+        assert_eq!(runtime.blueprint.validate_state(&state), Ok(()));
+        state.insert(HUNGER, Arc::new(-1i32));
+        assert_eq!(
+            runtime.blueprint.validate_state(&state),
+            Err(StateZenError::InvalidAspectValue { aspect: HUNGER, transition: None })
+        );
+    }
+}
+
+#[cfg(test)]
+mod normalizer_tests {
+    use super::*;
+
+    const POSITION: StateAspectId = 2;
+
+    #[test]
+    fn test_normalizers_run_after_writes() {
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<f64>(POSITION)).unwrap();
+        blueprint.add_event(EventDef::new(102)).unwrap();
+        blueprint.add_transition(Transition::new(3, 102, StateInRange::always(), Transfer::add(POSITION, 0.3f64))).unwrap();
+        // 对齐到 0.5 的网格；顺带写入 action 的部分会被丢弃
+        blueprint.add_normalizer(POSITION, Transfer::new(|s| {
+            let mut next = s.clone();
+            let p = *s.get(&POSITION).unwrap().downcast_ref::<f64>().unwrap();
+            next.insert(POSITION, Arc::new((p * 2.0).round() / 2.0));
+            next.insert(1, Arc::new(Action::Walk));
+            next
+        }));
+        initial_state.insert(POSITION, Arc::new(0.0f64));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        let position = |r: &RuntimeStateMachine| *r.current_state.get(&POSITION).unwrap().downcast_ref::<f64>().unwrap();
+
+        runtime.handle_event(102, None);
+        assert_eq!(position(&runtime), 0.5);
+        runtime.handle_event(102, None);
+        assert_eq!(position(&runtime), 1.0);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+
+        // 未写入 POSITION 的转换不触发规范化
+        runtime.handle_event(100, None);
+        assert_eq!(position(&runtime), 1.0);
+    }
+}
+
+#[cfg(test)]
+mod derived_aspect_tests {
+    use super::*;
+    use state_zen::core::DerivedAspect;
+
+    const HUNGER: StateAspectId = 2;
+    const EXHAUSTED: StateAspectId = 3;
+
+    fn hunger(s: &State) -> i32 {
+        *s.get(&HUNGER).unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    #[test]
+    fn test_derived_aspects_follow_inputs() {
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<i32>(HUNGER)).unwrap();
+        blueprint
+            .add_derived_aspect(DerivedAspect::new(EXHAUSTED, [HUNGER], |s| hunger(s) <= 2))
+            .unwrap();
+        blueprint.add_event(EventDef::new(102)).unwrap();
+        // 消耗饱食度，同时试图写入派生方面
+        blueprint.add_transition(Transition::new(
+            3,
+            102,
+            StateInRange::aspect_eq(EXHAUSTED, false),
+            Transfer::add(HUNGER, -2i32).then(Transfer::set(EXHAUSTED, false)),
+        )).unwrap();
+        initial_state.insert(HUNGER, Arc::new(5i32));
+        assert_eq!(blueprint.validate_state(&initial_state), Ok(()));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        let exhausted = |r: &RuntimeStateMachine| *r.current_state.get(&EXHAUSTED).unwrap().downcast_ref::<bool>().unwrap();
+
+        assert!(!exhausted(&runtime));
+        runtime.handle_event(102, None);
+        assert!(!exhausted(&runtime));
+        runtime.handle_event(102, None);
+        assert_eq!(hunger(&runtime.current_state), 1);
+        assert!(exhausted(&runtime));
+
+        // 守卫读取派生方面
+        runtime.handle_event(102, None);
+        assert_eq!(hunger(&runtime.current_state), 1);
+
+        let mut state = runtime.current_state.clone();
+        state.insert(HUNGER, Arc::new(9i32));
+        runtime.set_state(state);
+        assert!(!exhausted(&runtime));
+    }
+}
+
+#[cfg(test)]
+mod derived_memo_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use state_zen::core::DerivedAspect;
+
+    const HUNGER: StateAspectId = 2;
+    const STARVING: StateAspectId = 3;
+
+    #[test]
+    fn test_derived_aspects_recompute_only_on_input_writes() {
+        let computed = Arc::new(AtomicUsize::new(0));
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<i32>(HUNGER)).unwrap();
+        let counter = computed.clone();
+        blueprint
+            .add_derived_aspect(DerivedAspect::new(STARVING, [HUNGER], move |s| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                *s.get(&HUNGER).unwrap().downcast_ref::<i32>().unwrap() <= 0
+            }))
+            .unwrap();
+        blueprint.add_event(EventDef::new(102)).unwrap();
+        blueprint.add_transition(Transition::new(3, 102, StateInRange::always(), Transfer::add(HUNGER, -1i32))).unwrap();
+        initial_state.insert(HUNGER, Arc::new(1i32));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+
+        // 与 HUNGER 无关的转换不触发重新计算
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        assert_eq!(runtime.aspect_version(1), 2);
+        assert_eq!(runtime.aspect_version(STARVING), 0);
+
+        runtime.handle_event(102, None);
+        assert_eq!(computed.load(Ordering::SeqCst), 2);
+        assert_eq!(runtime.aspect_version(HUNGER), 1);
+        assert_eq!(runtime.aspect_version(STARVING), 1);
+        assert_eq!(runtime.current_state.get(&STARVING).unwrap().downcast_ref::<bool>(), Some(&true));
+    }
+}
+
+#[cfg(test)]
+mod drive_tests {
+    use super::*;
+    use state_zen::core::EventInstance;
+
+    #[test]
+    fn test_drive_yields_snapshot_per_event() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        let actions: Vec<_> = runtime
+            .drive([100, 100, 101].map(|id| EventInstance::new(id, None)))
+            .map(|snapshot| (snapshot.event_id, get_action(&snapshot.state)))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![(Some(100), Some(Action::Walk)), (Some(100), Some(Action::Walk)), (Some(101), Some(Action::Idle))]
+        );
+
+        // 未消费的事件不会被处理
+        let mut drive = runtime.drive([100, 101].map(|id| EventInstance::new(id, None)));
+        drive.next();
+        drop(drive);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+    }
+}
+
+#[cfg(test)]
+mod overflow_policy_tests {
+    use super::*;
+    use state_zen::core::{EventBuffer, EventInstance, OverflowPolicy, StateZenError};
+
+    const MOVE: u64 = 7;
+
+    /// 合并移动增量
+    fn sum_moves(queued: &mut EventInstance, event: EventInstance) {
+        let total = [&*queued, &event]
+            .iter()
+            .map(|e| *e.payload.as_ref().unwrap().downcast_ref::<i32>().unwrap())
+            .sum::<i32>();
+        queued.payload = Some(Arc::new(total));
+    }
+
+    fn delta(event: &EventInstance) -> i32 {
+        *event.payload.as_ref().unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    #[test]
+    fn test_per_event_overflow_policies() {
+        let mut buffer = EventBuffer::new(Some(2), OverflowPolicy::DropNewest);
+        buffer.set_event_policy(MOVE, OverflowPolicy::Coalesce(sum_moves));
+        buffer.set_event_policy(100, OverflowPolicy::Block);
+        buffer.set_event_policy(101, OverflowPolicy::Error);
+
+        buffer.push(EventInstance::new(MOVE, Some(Arc::new(1i32))));
+        buffer.push(EventInstance::new(MOVE, Some(Arc::new(2i32))));
+        // 已满：移动事件合并进最近的一个
+        assert!(buffer.push(EventInstance::new(MOVE, Some(Arc::new(3i32)))).is_none());
+        assert_eq!(buffer.len(), 2);
+        // 关键指令不会被悄悄丢弃：同步压入无法等待，交还给调用方
+        assert_eq!(buffer.try_push(EventInstance::new(100, None)).err().map(|e| e.event_id), Some(100));
+        assert_eq!(buffer.len(), 2);
+        assert!(buffer.try_push(EventInstance::new(101, None)).is_err());
+        assert_eq!(buffer.policy_for(102), OverflowPolicy::DropNewest);
+        assert!(buffer.push(EventInstance::new(102, None)).is_some());
+
+        let deltas: Vec<_> = std::iter::from_fn(|| buffer.pop())
+            .filter(|e| e.event_id == MOVE)
+            .map(|e| delta(&e))
+            .collect();
+        assert_eq!(deltas, vec![1, 5]);
+    }
+
+    #[test]
+    fn test_pause_buffer_reports_rejected_events() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_pause_buffer(Some(1), OverflowPolicy::DropNewest);
+        runtime.set_pause_event_policy(101, OverflowPolicy::Error);
+        runtime.pause();
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        assert_eq!(runtime.buffered_events(), 1);
+        assert_eq!(runtime.take_errors(), vec![StateZenError::EventOverflow(101)]);
+    }
+}
+
+#[cfg(test)]
+mod consume_tests {
+    use super::*;
+    use std::sync::Mutex;
+    use state_zen::core::Handled;
+
+    #[test]
+    fn test_higher_priority_observer_consumes_region_entry() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let walking = StateInRange::new(|s| get_action(s) == Some(Action::Walk));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let tutorial_active = Arc::new(std::sync::atomic::AtomicBool::new(true));
+
+        let gameplay_log = log.clone();
+        blueprint.add_observer(StateObserver {
+            on_enter: Some(Arc::new(move |_| gameplay_log.lock().unwrap().push("gameplay"))),
+            ..StateObserver::new(10, walking.clone())
+        }).unwrap();
+        let tutorial_log = log.clone();
+        let active = tutorial_active.clone();
+        blueprint.add_observer(StateObserver {
+            priority: 10,
+            on_enter_consume: Some(Arc::new(move |_| {
+                tutorial_log.lock().unwrap().push("tutorial");
+                if active.load(std::sync::atomic::Ordering::SeqCst) { Handled::Stop } else { Handled::Continue }
+            })),
+            ..StateObserver::new(11, walking)
+        }).unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        runtime.handle_event(100, None);
+        assert_eq!(*log.lock().unwrap(), vec!["tutorial"]);
+
+        tutorial_active.store(false, std::sync::atomic::Ordering::SeqCst);
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        assert_eq!(*log.lock().unwrap(), vec!["tutorial", "tutorial", "gameplay"]);
+    }
+
+    #[test]
+    fn test_consume_applies_to_separately_built_declarative_regions() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let gameplay_log = log.clone();
+        blueprint.add_observer(StateObserver {
+            on_enter: Some(Arc::new(move |_| gameplay_log.lock().unwrap().push("gameplay"))),
+            ..StateObserver::new(10, StateInRange::aspect_eq(1, Action::Walk))
+        }).unwrap();
+        let tutorial_log = log.clone();
+        blueprint.add_observer(StateObserver {
+            priority: 10,
+            on_enter_consume: Some(Arc::new(move |_| {
+                tutorial_log.lock().unwrap().push("tutorial");
+                Handled::Stop
+            })),
+            ..StateObserver::new(11, StateInRange::aspect_eq(1, Action::Walk))
+        }).unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        // 两个区域分别构造，结构相同即视为同一区域
+        runtime.handle_event(100, None);
+        assert_eq!(*log.lock().unwrap(), vec!["tutorial"]);
+    }
+}
+
+#[cfg(test)]
+mod region_hierarchy_tests {
+    use super::*;
+    use std::sync::Mutex;
+    use state_zen::core::StateZenError;
+
+    const HUNGER: StateAspectId = 2;
+
+    fn hunger(s: &State) -> i32 {
+        *s.get(&HUNGER).unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    fn logging_observer(id: u64, region: StateInRange, log: &Arc<Mutex<Vec<String>>>) -> StateObserver {
+        let (enter_log, exit_log) = (log.clone(), log.clone());
+        StateObserver {
+            on_enter: Some(Arc::new(move |_| enter_log.lock().unwrap().push(format!("enter {id}")))),
+            on_exit: Some(Arc::new(move |_| exit_log.lock().unwrap().push(format!("exit {id}")))),
+            ..StateObserver::new(id, region)
+        }
+    }
+
+    #[test]
+    fn test_nested_regions_fire_in_nesting_order() {
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<i32>(HUNGER)).unwrap();
+        blueprint.add_event(EventDef::of::<i32>(102)).unwrap();
+        blueprint.add_transition(Transition::new(
+            3,
+            102,
+            StateInRange::always(),
+            Transfer::new(|s| {
+                let mut next = s.clone();
+                next.insert(HUNGER, Arc::new(if hunger(s) == 0 { 10i32 } else { 0 }));
+                next
+            }),
+        )).unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        // 内层先声明，默认顺序会先触发内层
+        blueprint.add_observer(logging_observer(20, StateInRange::new(|s| hunger(s) <= 2), &log)).unwrap();
+        blueprint.add_observer(logging_observer(21, StateInRange::new(|s| hunger(s) <= 5), &log)).unwrap();
+
+        let samples: Vec<State> = (0..10)
+            .map(|h| {
+                let mut s = initial_state.clone();
+                s.insert(HUNGER, Arc::new(h));
+                s
+            })
+            .collect();
+        assert_eq!(
+            blueprint.declare_containment_verified(20, 21, &samples),
+            Err(StateZenError::InvalidContainment { outer: 20, inner: 21 })
+        );
+        blueprint.declare_containment_verified(21, 20, &samples).unwrap();
+        assert_eq!(
+            blueprint.declare_containment(20, 21),
+            Err(StateZenError::InvalidContainment { outer: 20, inner: 21 })
+        );
+        assert_eq!(blueprint.declare_containment(21, 99), Err(StateZenError::UnknownObserver(99)));
+        assert_eq!(blueprint.nesting_depth(20), 1);
+        assert!(blueprint.region_contains(21, 20));
+
+        initial_state.insert(HUNGER, Arc::new(10i32));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.handle_event(102, None);
+        runtime.handle_event(102, None);
+        assert_eq!(*log.lock().unwrap(), vec!["enter 21", "enter 20", "exit 20", "exit 21"]);
+    }
+
+    #[test]
+    fn test_nesting_depth_follows_later_declarations() {
+        let (mut blueprint, _) = create_player_blueprint();
+        for id in 30..34 {
+            blueprint.add_observer(StateObserver::new(id, StateInRange::always())).unwrap();
+        }
+        blueprint.declare_containment(31, 32).unwrap();
+        blueprint.declare_containment(32, 33).unwrap();
+        assert_eq!(blueprint.nesting_depth(33), 2);
+
+        // 在链的外侧再声明一层，已有内层的深度随之更新
+        blueprint.declare_containment(30, 31).unwrap();
+        assert_eq!((30..34).map(|id| blueprint.nesting_depth(id)).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        let projected = blueprint.project(&[1]);
+        assert_eq!(projected.nesting_depth(33), 3);
+    }
+}
+
+#[cfg(test)]
+mod default_initial_state_tests {
+    use super::*;
+    use state_zen::core::StateZenError;
+
+    const HUNGER: StateAspectId = 2;
+
+    #[test]
+    fn test_default_initial_state_from_aspect_defaults() {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<Action>(1).with_default(Action::Idle)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i32>(HUNGER)).unwrap();
+        assert_eq!(blueprint.default_initial_state().err(), Some(StateZenError::MissingAspect(HUNGER)));
+
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<Action>(1).with_default(Action::Idle)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i32>(HUNGER).with_type_default::<i32>()).unwrap();
+        let state = blueprint.default_initial_state().unwrap();
+        assert_eq!(get_action(&state), Some(Action::Idle));
+        assert_eq!(state.get(&HUNGER).unwrap().downcast_ref::<i32>(), Some(&0));
+
+        let state = state.with_overrides([(HUNGER, Arc::new(7i32) as _)]);
+        assert_eq!(state.get(&HUNGER).unwrap().downcast_ref::<i32>(), Some(&7));
+        assert_eq!(blueprint.validate_state(&state), Ok(()));
+
+        // 默认值类型与声明不一致
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(HUNGER).with_default(1u8)).unwrap();
+        assert_eq!(blueprint.default_initial_state().err(), Some(StateZenError::AspectTypeMismatch(HUNGER)));
+    }
+}
+
+#[cfg(test)]
+mod aspect_bundle_tests {
+    use super::*;
+    use state_zen::core::AspectBundle;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Body {
+        position: (f32, f32),
+        velocity: (f32, f32),
+        facing_left: bool,
+    }
+
+    const BODY: AspectBundle<Body> = AspectBundle::new(2);
+
+    #[test]
+    fn test_bundle_field_predicates_and_updates() {
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        BODY.register(&mut blueprint).unwrap();
+        blueprint.add_event(EventDef::new(102)).unwrap();
+        blueprint.add_transition(Transition::new(
+            3,
+            102,
+            BODY.field_in(|b| b.position.0, ..10.0),
+            BODY.update(|b| {
+                b.position.0 += b.velocity.0;
+                b.facing_left = b.velocity.0 < 0.0;
+            }),
+        )).unwrap();
+        BODY.insert(&mut initial_state, Body { position: (0.0, 0.0), velocity: (6.0, 0.0), facing_left: true });
+        assert_eq!(blueprint.validate_state(&initial_state), Ok(()));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        for _ in 0..3 {
+            runtime.handle_event(102, None);
+        }
+        let body = BODY.get(&runtime.current_state).unwrap();
+        assert_eq!(body.position.0, 12.0);
+        assert!(BODY.field_eq(|b| b.facing_left, false).contains(&runtime.current_state));
+        assert_eq!(runtime.blueprint.transition(3).unwrap().transfer.writes(), Some(&[2][..]));
+    }
+}
+
+#[cfg(test)]
+mod collection_aspect_tests {
+    use super::*;
+    use state_zen::core::{MapAspect, SetAspect, VecAspect};
+
+    const INVENTORY: VecAspect<&'static str> = VecAspect::new(2);
+    const BUFFS: SetAspect<u32> = SetAspect::new(3);
+    const COOLDOWNS: MapAspect<u32, i32> = MapAspect::new(4);
+
+    #[test]
+    fn test_collection_predicates_and_transfers() {
+        let mut blueprint = StateMachineBlueprint::new();
+        INVENTORY.register(&mut blueprint).unwrap();
+        BUFFS.register(&mut blueprint).unwrap();
+        COOLDOWNS.register(&mut blueprint).unwrap();
+        let state = blueprint.default_initial_state().unwrap();
+        assert!(INVENTORY.is_empty().contains(&state));
+
+        let state = INVENTORY.push("potion").then(INVENTORY.push("sword")).then(INVENTORY.push("potion")).apply(&state);
+        let state = INVENTORY.remove("potion").apply(&state);
+        assert_eq!(INVENTORY.get(&state), Some(&vec!["sword", "potion"]));
+        assert!(INVENTORY.contains("sword").and(INVENTORY.len_at_least(2)).contains(&state));
+        assert!(!INVENTORY.len_at_most(1).contains(&state));
+
+        let state = BUFFS.insert(7).then(BUFFS.insert(7)).then(BUFFS.insert(9)).apply(&state);
+        let state = BUFFS.remove(9).apply(&state);
+        assert!(BUFFS.contains(7).contains(&state));
+        assert!(BUFFS.len_at_most(1).contains(&state));
+
+        let state = COOLDOWNS.insert(1, 30).apply(&state);
+        assert!(COOLDOWNS.value_is(1, |t| *t > 0).contains(&state));
+        let state = COOLDOWNS.remove(1).then(INVENTORY.clear()).apply(&state);
+        assert!(!COOLDOWNS.contains_key(1).contains(&state));
+        assert!(INVENTORY.is_empty().contains(&state));
+        assert_eq!(INVENTORY.push("x").writes(), Some(&[2][..]));
+        assert_eq!(BUFFS.contains(1).reads(), Some(&[3][..]));
+    }
+}
+
+#[cfg(test)]
+mod machine_ref_tests {
+    use super::*;
+    use state_zen::core::{MachineDirectory, MachineRef, MachineSupervisor};
+    use state_zen::MachineTemplate;
+
+    const VULNERABLE: StateAspectId = 1;
+    const TARGET: StateAspectId = 2;
+    const HITS: StateAspectId = 3;
+    const EXPOSE: u64 = 10;
+    const ATTACK: u64 = 11;
+
+    fn target_template() -> MachineTemplate {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<bool>(VULNERABLE).with_default(false)).unwrap();
+        blueprint.add_event(EventDef::new(EXPOSE)).unwrap();
+        blueprint.add_transition(Transition::new(1, EXPOSE, StateInRange::always(), Transfer::set(VULNERABLE, true))).unwrap();
+        MachineTemplate::new(blueprint)
+    }
+
+    fn attacker_template(directory: &MachineDirectory) -> MachineTemplate {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<MachineRef>(TARGET)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i32>(HITS).with_default(0)).unwrap();
+        blueprint.add_event(EventDef::new(ATTACK)).unwrap();
+        blueprint.add_transition(Transition::new(
+            1,
+            ATTACK,
+            directory.other_in_region(TARGET, StateInRange::aspect_eq(VULNERABLE, true)),
+            Transfer::add(HITS, 1i32),
+        )).unwrap();
+        MachineTemplate::new(blueprint)
+    }
+
+    #[test]
+    fn test_guard_queries_referenced_machine() {
+        let mut supervisor = MachineSupervisor::new();
+        let directory = supervisor.directory();
+        supervisor.register_template("target", target_template());
+        supervisor.register_template("attacker", attacker_template(&directory));
+
+        let target = supervisor.spawn("target", State::new()).unwrap();
+        let attacker = supervisor
+            .spawn("attacker", State::new().with_overrides([(TARGET, Arc::new(MachineRef(target)) as _)]))
+            .unwrap();
+        let hits = |s: &MachineSupervisor| {
+            *s.child(attacker).unwrap().runtime.current_state.get(&HITS).unwrap().downcast_ref::<i32>().unwrap()
+        };
+
+        supervisor.send_to(attacker, ATTACK, None).unwrap();
+        assert_eq!(hits(&supervisor), 0);
+
+        supervisor.send_to(target, EXPOSE, None).unwrap();
+        assert!(directory.in_region(target, &StateInRange::aspect_eq(VULNERABLE, true)));
+        supervisor.send_to(attacker, ATTACK, None).unwrap();
+        assert_eq!(hits(&supervisor), 1);
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::*;
+    use std::time::Duration;
+    use state_zen::core::{Clock, ContinuousTransfer, ManualClock, RecordedClock};
+    use state_zen::monitor::Monitor;
+
+    const ELAPSED: StateAspectId = 2;
+
+    fn timed_runtime() -> RuntimeStateMachine {
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<u128>(ELAPSED)).unwrap();
+        blueprint.add_continuous_transfer(ContinuousTransfer::new(StateInRange::always(), |s, dt| {
+            let elapsed = *s.get(&ELAPSED).unwrap().downcast_ref::<u128>().unwrap();
+            let mut next = s.clone();
+            next.insert(ELAPSED, Arc::new(elapsed + dt.as_millis()));
+            next
+        }));
+        initial_state.insert(ELAPSED, Arc::new(0u128));
+        RuntimeStateMachine::new(blueprint, initial_state)
+    }
+
+    fn elapsed(runtime: &RuntimeStateMachine) -> u128 {
+        *runtime.current_state.get(&ELAPSED).unwrap().downcast_ref::<u128>().unwrap()
+    }
+
+    #[test]
+    fn test_recorded_clock_replays_tick_sequence() {
+        let manual = ManualClock::new();
+        let recorder = Arc::new(RecordedClock::recording(Arc::new(manual.clone())));
+        let mut runtime = timed_runtime();
+        runtime.set_clock(recorder.clone());
+        for ms in [16, 17, 33] {
+            manual.advance(Duration::from_millis(ms));
+            runtime.tick_clock();
+        }
+        assert_eq!(elapsed(&runtime), 66);
+
+        let mut replayed = timed_runtime();
+        replayed.set_clock(Arc::new(RecordedClock::replay(recorder.readings())));
+        for _ in 0..3 {
+            replayed.tick_clock();
+        }
+        assert_eq!(elapsed(&replayed), 66);
+    }
+
+    #[test]
+    fn test_monitor_rate_uses_clock() {
+        let clock = ManualClock::new();
+        let monitor = Monitor::with_clock(Duration::from_secs(1), Arc::new(clock.clone()));
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        let probe = monitor.attach("player", state_zen::core::AspectRegistry::new(), &mut runtime);
+
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        assert_eq!(probe.stats().transition_rate, 2.0);
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now(), Duration::from_millis(1500));
+        assert_eq!(probe.stats().transition_rate, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod state_store_tests {
+    use super::*;
+    use std::time::Duration;
+    use state_zen::core::{AutosavePolicy, FileStore, ManualClock, MemoryStore, StateCodec, StateStore, StateZenError};
+
+    const NOTE: StateAspectId = 2;
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("state-zen-store-{}", std::process::id()));
+        let mut codec = StateCodec::new();
+        codec
+            .register::<String>(NOTE)
+            .register_with::<Action, _, _>(1, |a| format!("{a:?}"), |s| match s {
+                "Idle" => Some(Action::Idle),
+                "Walk" => Some(Action::Walk),
+                _ => None,
+            });
+        let store = FileStore::new(&dir, codec);
+        assert!(store.load(7).unwrap().is_none());
+
+        let (_, mut state) = create_player_blueprint();
+        state.insert(NOTE, Arc::new("line one\n\ttab \\ slash".to_string()));
+        store.save(7, &state).unwrap();
+        let loaded = store.load(7).unwrap().unwrap();
+        assert_eq!(get_action(&loaded), Some(Action::Idle));
+        assert_eq!(loaded.get(&NOTE).unwrap().downcast_ref::<String>().unwrap(), "line one\n\ttab \\ slash");
+
+        // 未注册编解码的方面无法保存
+        state.insert(3, Arc::new(1u8));
+        assert_eq!(store.save(7, &state), Err(StateZenError::CodecFailed(3)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_autosave_policies() {
+        let store = Arc::new(MemoryStore::new());
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint.clone(), initial_state.clone());
+        runtime.autosave_every(store.clone(), 1, AutosavePolicy::Transforms(2));
+        runtime.handle_event(100, None);
+        assert!(store.load(1).unwrap().is_none());
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        assert_eq!(get_action(&store.load(1).unwrap().unwrap()), Some(Action::Idle));
+
+        let clock = ManualClock::new();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_clock(Arc::new(clock.clone()));
+        runtime.autosave_every(store.clone(), 2, AutosavePolicy::Interval(Duration::from_secs(5)));
+        runtime.handle_event(100, None);
+        assert!(store.load(2).unwrap().is_none());
+        clock.advance(Duration::from_secs(5));
+        runtime.handle_event(101, None);
+        assert_eq!(get_action(&store.load(2).unwrap().unwrap()), Some(Action::Idle));
+        assert_eq!(store.len(), 2);
+    }
+}
+
+#[cfg(test)]
+mod machine_router_tests {
+    use super::*;
+    use state_zen::core::{EventInstance, MachineRouter, StateZenError};
+    use state_zen::MachineTemplate;
+
+    const APPROVED: StateAspectId = 1;
+    const AMOUNT: StateAspectId = 2;
+    const SUBMIT: u64 = 10;
+    const APPROVE: u64 = 11;
+
+    fn approval_template() -> MachineTemplate {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<bool>(APPROVED).with_default(false)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i64>(AMOUNT).with_default(0i64)).unwrap();
+        blueprint.add_event(EventDef::of::<i64>(SUBMIT)).unwrap();
+        blueprint.add_event(EventDef::new(APPROVE)).unwrap();
+        blueprint.add_transition(Transition::new(
+            1,
+            APPROVE,
+            StateInRange::aspect_eq(APPROVED, false),
+            Transfer::set(APPROVED, true),
+        )).unwrap();
+        blueprint.set_final_region(StateInRange::aspect_eq(APPROVED, true));
+        MachineTemplate::new(blueprint)
+    }
+
+    #[test]
+    fn test_router_creates_instance_per_correlation_id() {
+        let mut router = MachineRouter::new(approval_template());
+        router.set_initializer(|event| {
+            let mut state = State::new();
+            if let Some(amount) = event.payload.as_ref().and_then(|p| p.downcast_ref::<i64>()) {
+                state.insert(AMOUNT, Arc::new(*amount));
+            }
+            state
+        });
+
+        let submit = |id: &str, amount: i64| EventInstance::new(SUBMIT, Some(Arc::new(amount))).with_correlation(id);
+        assert_eq!(router.route(submit("order-1", 120)), Ok(true));
+        assert_eq!(router.route(submit("order-2", 80)), Ok(true));
+        assert_eq!(router.route(EventInstance::new(APPROVE, None).with_correlation("order-1")), Ok(false));
+        assert_eq!(router.len(), 2);
+
+        let amount = |router: &MachineRouter, id: &str| {
+            *router.get(id).unwrap().current_state.get(&AMOUNT).unwrap().downcast_ref::<i64>().unwrap()
+        };
+        assert_eq!(amount(&router, "order-1"), 120);
+        assert_eq!(amount(&router, "order-2"), 80);
+        assert_eq!(router.in_region(&StateInRange::aspect_eq(APPROVED, false)), vec!["order-2"]);
+
+        // 没有关联ID的事件无法路由
+        assert_eq!(
+            router.route(EventInstance::new(APPROVE, None)),
+            Err(StateZenError::MissingCorrelationId(APPROVE))
+        );
+
+        // 已结束的实例被回收，同一关联ID的后续事件创建新实例
+        assert_eq!(router.reap(), vec!["order-1".to_string()]);
+        assert!(!router.contains("order-1"));
+        assert_eq!(router.route(submit("order-1", 5)), Ok(true));
+        assert_eq!(amount(&router, "order-1"), 5);
+    }
+}
+
+#[cfg(test)]
+mod effect_outbox_tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use state_zen::core::{EffectOutbox, EventInstance, EventTemplate, MemoryOutbox, OutboxEntry, StateZenError};
+
+    struct BrokenOutbox;
+
+    impl EffectOutbox for BrokenOutbox {
+        fn commit(&self, _events: &[EventInstance]) -> Result<(), StateZenError> {
+            Err(StateZenError::StoreFailed { machine: 0, message: "磁盘已满".to_string() })
+        }
+
+        fn pending(&self) -> Vec<OutboxEntry> {
+            Vec::new()
+        }
+
+        fn ack(&self, _seq: u64) -> bool {
+            false
+        }
+    }
+
+    fn emitting_blueprint() -> (StateMachineBlueprint, State) {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.transition_mut(1).unwrap().emits = vec![EventTemplate::new(300), EventTemplate::new(301)];
+        (blueprint, initial_state)
+    }
+
+    #[test]
+    fn test_unacked_effects_are_relayed_after_restart() {
+        let (blueprint, initial_state) = emitting_blueprint();
+        let outbox = Arc::new(MemoryOutbox::new());
+
+        // 没有事件汇（模拟投递前进程退出）：状态已提交，事件留在发件箱
+        let mut runtime = RuntimeStateMachine::new(blueprint.clone(), initial_state);
+        runtime.set_outbox(outbox.clone());
+        runtime.handle_event(100, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+        assert_eq!(outbox.len(), 2);
+        let state = runtime.current_state.clone();
+        drop(runtime);
+
+        let mut restarted = RuntimeStateMachine::new(blueprint, state);
+        restarted.set_outbox(outbox.clone());
+        restarted.set_event_sink(VecDeque::<EventInstance>::new());
+        assert_eq!(restarted.relay_outbox(), 2);
+        assert!(outbox.is_empty());
+
+        // 有事件汇时提交后立即投递并确认
+        restarted.handle_event(101, None);
+        restarted.handle_event(100, None);
+        assert!(outbox.is_empty());
+        assert_eq!(restarted.relay_outbox(), 0);
+    }
+
+    #[test]
+    fn test_outbox_failure_rejects_transition() {
+        let (blueprint, initial_state) = emitting_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_outbox(Arc::new(BrokenOutbox));
+        runtime.handle_event(100, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert!(matches!(runtime.take_errors().as_slice(), [StateZenError::StoreFailed { .. }]));
+
+        // 不产生事件的转换不受影响
+        runtime.handle_event(101, None);
+        assert!(runtime.take_errors().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod broadcast_dispatch_tests {
+    use super::*;
+    use state_zen::core::{DispatchPolicy, StateZenError};
+
+    fn transition(id: u64, event_id: u64, transfer: Transfer) -> Transition {
+        Transition::new(id, event_id, StateInRange::always(), transfer)
+    }
+
+    fn broadcast_runtime() -> RuntimeStateMachine {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(1).with_default(0)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i32>(2).with_default(0)).unwrap();
+        for event in [10, 11, 12] {
+            blueprint.add_event(EventDef::new(event)).unwrap();
+        }
+        blueprint.add_transition(transition(1, 10, Transfer::set(1, 1i32))).unwrap();
+        blueprint.add_transition(transition(2, 10, Transfer::set(2, 2i32))).unwrap();
+        blueprint.add_transition(transition(3, 10, Transfer::set(1, 1i32))).unwrap();
+        blueprint.add_transition(transition(4, 11, Transfer::set(1, 5i32))).unwrap();
+        blueprint.add_transition(transition(5, 11, Transfer::set(1, 6i32))).unwrap();
+        blueprint.add_transition(transition(6, 12, Transfer::add(2, 1i32))).unwrap();
+        blueprint.add_transition(transition(7, 12, Transfer::add(2, 1i32))).unwrap();
+        let state = blueprint.default_initial_state().unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.set_dispatch_policy(DispatchPolicy::AllNonConflicting);
+        runtime
+    }
+
+    fn value(runtime: &RuntimeStateMachine, id: StateAspectId) -> i32 {
+        *runtime.current_state.get(&id).unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    #[test]
+    fn test_non_conflicting_transitions_all_fire() {
+        let mut runtime = broadcast_runtime();
+        runtime.handle_event(10, None);
+        // 转换 1 与 3 写入相同的值，不算冲突
+        assert_eq!((value(&runtime, 1), value(&runtime, 2)), (1, 2));
+        assert!(runtime.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_conflicting_writes_reject_event() {
+        let mut runtime = broadcast_runtime();
+        runtime.handle_event(11, None);
+        assert_eq!(value(&runtime, 1), 0);
+        assert_eq!(
+            runtime.take_errors(),
+            vec![StateZenError::WriteConflict { aspect: 1, transitions: [4, 5] }]
+        );
+
+        // 无法按值比较的写入保守地视为冲突
+        runtime.handle_event(12, None);
+        assert_eq!(value(&runtime, 2), 0);
+        assert_eq!(
+            runtime.take_errors(),
+            vec![StateZenError::WriteConflict { aspect: 2, transitions: [6, 7] }]
+        );
+
+        // 默认策略只执行优先级最高的一个
+        runtime.set_dispatch_policy(DispatchPolicy::HighestPriority);
+        runtime.handle_event(11, None);
+        assert_eq!(value(&runtime, 1), 5);
+    }
+
+    #[test]
+    fn test_declared_equality_compares_written_values() {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(2).with_default(0).with_eq::<i32>()).unwrap();
+        blueprint.add_event(EventDef::new(12)).unwrap();
+        blueprint.add_transition(transition(6, 12, Transfer::add(2, 1i32))).unwrap();
+        blueprint.add_transition(transition(7, 12, Transfer::add(2, 1i32))).unwrap();
+        let state = blueprint.default_initial_state().unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.set_dispatch_policy(DispatchPolicy::AllNonConflicting);
+
+        // 两次写入是不同的 `Arc`，但按声明的相等比较取值一致
+        runtime.handle_event(12, None);
+        assert_eq!(value(&runtime, 2), 1);
+        assert!(runtime.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_breakpoints_apply_to_broadcast() {
+        use state_zen::core::BreakAction;
+
+        let mut runtime = broadcast_runtime();
+        runtime.set_breakpoint(2, Box::new(|_| BreakAction::Skip));
+        runtime.handle_event(10, None);
+        assert_eq!((value(&runtime, 1), value(&runtime, 2)), (1, 0));
+
+        let mut runtime = broadcast_runtime();
+        runtime.set_breakpoint(2, Box::new(|_| BreakAction::Pause));
+        runtime.handle_event(10, None);
+        // 断点之前的转换已提交，命中断点的转换挂起
+        assert!(runtime.is_paused());
+        assert_eq!(runtime.pending_transition().map(|t| t.id), Some(2));
+        assert_eq!((value(&runtime, 1), value(&runtime, 2)), (1, 0));
+
+        runtime.resume();
+        assert_eq!((value(&runtime, 1), value(&runtime, 2)), (1, 2));
+    }
+}
+
+#[cfg(test)]
+mod virtual_time_tests {
+    use super::*;
+    use std::time::Duration;
+    use state_zen::core::{ContinuousTransfer, VirtualTimeDriver};
+
+    const ELAPSED: StateAspectId = 2;
+
+    fn elapsed(runtime: &RuntimeStateMachine) -> u128 {
+        *runtime.current_state.get(&ELAPSED).unwrap().downcast_ref::<u128>().unwrap()
+    }
+
+    fn driver() -> VirtualTimeDriver {
+        let (mut blueprint, mut initial_state) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<u128>(ELAPSED)).unwrap();
+        blueprint.add_continuous_transfer(ContinuousTransfer::new(StateInRange::always(), |s, dt| {
+            let elapsed = *s.get(&ELAPSED).unwrap().downcast_ref::<u128>().unwrap();
+            let mut next = s.clone();
+            next.insert(ELAPSED, Arc::new(elapsed + dt.as_millis()));
+            next
+        }));
+        initial_state.insert(ELAPSED, Arc::new(0u128));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.schedule_recurring(100, Duration::from_millis(250), None);
+        VirtualTimeDriver::new(runtime, Duration::from_millis(100))
+    }
+
+    #[test]
+    fn test_advance_splits_steps_at_timer_boundaries() {
+        let mut driver = driver();
+        // 100ms 步长在 250 / 500 / 750 / 1000 处被切分
+        assert_eq!(driver.advance(Duration::from_secs(1)), 12);
+        assert_eq!(driver.now(), Duration::from_secs(1));
+        assert_eq!(elapsed(driver.runtime()), 1000);
+        assert_eq!(get_action(&driver.runtime().current_state), Some(Action::Walk));
+        assert_eq!(driver.runtime().next_recurring_in(), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn test_speed_and_advance_until() {
+        let mut driver = driver();
+        driver.set_speed(4.0);
+        driver.advance_real(Duration::from_millis(250));
+        assert_eq!(elapsed(driver.runtime()), 1000);
+
+        let late = StateInRange::new(|s| s.get(&ELAPSED).and_then(|v| v.downcast_ref::<u128>()).is_some_and(|e| *e >= 1500));
+        assert_eq!(driver.advance_until(&late, Duration::from_secs(10)), Some(Duration::from_millis(500)));
+        let never = StateInRange::new(|_| false);
+        assert_eq!(driver.advance_until(&never, Duration::from_millis(300)), None);
+        assert_eq!(driver.into_inner().current_state.get(&ELAPSED).unwrap().downcast_ref::<u128>(), Some(&1800));
+    }
+}
+
+#[cfg(test)]
+mod guard_memo_tests {
+    use super::*;
+    use state_zen::core::{EventInstance, GuardMemoStats};
+
+    const COUNT: StateAspectId = 1;
+    const MODE: StateAspectId = 2;
+
+    fn memo_runtime() -> RuntimeStateMachine {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(COUNT).with_default(0)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i32>(MODE).with_default(0)).unwrap();
+        blueprint.add_event(EventDef::new(10)).unwrap();
+        let guards = [
+            StateInRange::aspect_eq(MODE, 0i32),
+            StateInRange::aspect_in(MODE, 5i32..10),
+            StateInRange::new(|_| false),
+        ];
+        for (id, guard) in (1..).zip(guards) {
+            blueprint.add_transition(Transition::new(id, 10, guard, Transfer::add(COUNT, 1i32))).unwrap();
+        }
+        let state = blueprint.default_initial_state().unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.set_guard_memo(true);
+        runtime
+    }
+
+    fn count(runtime: &RuntimeStateMachine) -> i32 {
+        *runtime.current_state.get(&COUNT).unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    #[test]
+    fn test_guards_reuse_results_until_inputs_change() {
+        let mut runtime = memo_runtime();
+        for _ in 0..3 {
+            runtime.handle_event(10, None);
+        }
+        assert_eq!(count(&runtime), 3);
+        // 前两个守卫只读 MODE，首次求值后一直命中；闭包守卫每次都求值
+        assert_eq!(runtime.guard_memo_stats(), Some(GuardMemoStats { hits: 4, misses: 5 }));
+
+        let mut state = runtime.current_state.clone();
+        state.insert(MODE, Arc::new(7i32));
+        runtime.set_state(state);
+        runtime.handle_event(10, None);
+        assert_eq!(count(&runtime), 4);
+        assert_eq!(runtime.guard_memo_stats(), Some(GuardMemoStats { hits: 4, misses: 8 }));
+
+        // 直接修改状态后需要使缓存失效
+        runtime.current_state.insert(MODE, Arc::new(1i32));
+        runtime.invalidate_observer_cache();
+        runtime.handle_event(10, None);
+        assert_eq!(count(&runtime), 4);
+
+        runtime.set_guard_memo(false);
+        assert_eq!(runtime.guard_memo_stats(), None);
+    }
+
+    #[test]
+    fn test_batch_dispatch_uses_guard_memo() {
+        let mut runtime = memo_runtime();
+        runtime.handle_event(10, None);
+        assert_eq!(runtime.guard_memo_stats(), Some(GuardMemoStats { hits: 0, misses: 3 }));
+
+        // 每批第一个事件基于当前状态选择，MODE 未被写入，只读 MODE 的守卫命中缓存
+        runtime.handle_events(&[EventInstance::new(10, None)]);
+        assert_eq!(count(&runtime), 2);
+        assert_eq!(runtime.guard_memo_stats(), Some(GuardMemoStats { hits: 2, misses: 4 }));
+    }
+}
+
+#[cfg(test)]
+mod transition_chain_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use state_zen::core::{Tracer, TransitionChain};
+
+    const POTIONS: StateAspectId = 1;
+    const BUFFED: StateAspectId = 2;
+    const HP: StateAspectId = 3;
+    const USE_POTION: u64 = 10;
+
+    #[derive(Default)]
+    struct CommitCounter(AtomicUsize);
+
+    impl Tracer for CommitCounter {
+        fn on_commit(&self, _prev: &State, _next: &State) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn value<T: Copy + 'static>(runtime: &RuntimeStateMachine, id: StateAspectId) -> T {
+        *runtime.current_state.get(&id).unwrap().downcast_ref::<T>().unwrap()
+    }
+
+    #[test]
+    fn test_chain_commits_once_and_aborts_on_failed_step() {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(POTIONS).with_default(1)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<bool>(BUFFED).with_default(false)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i32>(HP).with_default(50)).unwrap();
+        blueprint.add_event(EventDef::new(USE_POTION)).unwrap();
+
+        let chain = TransitionChain::new(1, USE_POTION)
+            .with_priority(1)
+            .then_if(StateInRange::aspect_in(POTIONS, 1i32..), Transfer::sub(POTIONS, 1i32))
+            .then(Transfer::set(BUFFED, true))
+            .then(Transfer::add(HP, 10i32));
+        assert_eq!(chain.len(), 3);
+        let transition = chain.build();
+        assert_eq!(transition.transfer.writes(), Some(&[POTIONS, BUFFED, HP][..]));
+        blueprint.add_transition(transition).unwrap();
+        // 链不生效时退回到低优先级的转换
+        blueprint.add_transition(TransitionChain::new(2, USE_POTION).then(Transfer::sub(HP, 1i32)).build()).unwrap();
+
+        let state = blueprint.default_initial_state().unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        let commits = Arc::new(CommitCounter::default());
+        runtime.add_tracer(commits.clone());
+
+        runtime.handle_event(USE_POTION, None);
+        assert_eq!(commits.0.load(Ordering::SeqCst), 1);
+        assert_eq!(value::<i32>(&runtime, POTIONS), 0);
+        assert!(value::<bool>(&runtime, BUFFED));
+        assert_eq!(value::<i32>(&runtime, HP), 60);
+
+        runtime.handle_event(USE_POTION, None);
+        assert_eq!(value::<i32>(&runtime, POTIONS), 0);
+        assert_eq!(value::<i32>(&runtime, HP), 59);
+    }
+
+    #[test]
+    fn test_unconditional_chain_stays_declarative() {
+        let transition = TransitionChain::new(1, USE_POTION)
+            .then(Transfer::set(BUFFED, true))
+            .then(Transfer::add(HP, 10i32))
+            .build();
+        assert_eq!(transition.transfer.describe(), "#2 := true; #3 += 10");
+        assert!(transition.guard.is_declarative());
+    }
+}
+
+#[cfg(test)]
+mod postcondition_tests {
+    use super::*;
+
+    fn runtime_with_ensures(ensures: StateInRange) -> RuntimeStateMachine {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.transition_mut(1).unwrap().ensures = Some(ensures);
+        RuntimeStateMachine::new(blueprint, initial_state)
+    }
+
+    #[test]
+    fn test_satisfied_postcondition_is_silent() {
+        let mut runtime = runtime_with_ensures(StateInRange::aspect_eq(1, Action::Walk));
+        runtime.handle_event(100, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "转换 1 违反后置条件 `#1 == Idle`，写入的方面：[1]")]
+    fn test_violated_postcondition_panics_in_debug() {
+        let mut runtime = runtime_with_ensures(StateInRange::aspect_eq(1, Action::Idle));
+        runtime.handle_event(100, None);
+    }
+}
+
+#[cfg(test)]
+mod mutation_tests {
+    use super::*;
+    use state_zen::testing::{mutants, mutate, Mutation, Scenario};
+
+    #[test]
+    fn test_mutation_report_lists_survivors() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let walk_only = |blueprint: &StateMachineBlueprint| {
+            let mut runtime = RuntimeStateMachine::new(blueprint.clone(), initial_state.clone());
+            Scenario::new("起步").dispatch(100).expect_aspect(1, Action::Walk).run(&mut runtime).is_ok()
+        };
+        let report = mutate(&blueprint, walk_only);
+        assert_eq!(report.total, 4);
+        assert_eq!(report.survivors, vec![Mutation::NegateGuard(2), Mutation::DropTransition(2)]);
+        assert_eq!(report.score(), 0.5);
+
+        // 覆盖往返的套件（用断言表达失败）发现全部变异
+        let round_trip = |blueprint: &StateMachineBlueprint| {
+            let mut runtime = RuntimeStateMachine::new(blueprint.clone(), initial_state.clone());
+            Scenario::new("往返")
+                .dispatch(101)
+                .expect_aspect(1, Action::Idle)
+                .dispatch(100)
+                .expect_aspect(1, Action::Walk)
+                .dispatch(101)
+                .expect_aspect(1, Action::Idle)
+                .assert(&mut runtime);
+            true
+        };
+        assert!(mutate(&blueprint, round_trip).survivors.is_empty());
+    }
+
+    #[test]
+    fn test_priority_mutants_for_competing_transitions() {
+        let (mut blueprint, _) = create_player_blueprint();
+        let mut rival = blueprint.transition(1).unwrap().clone();
+        rival.id = 3;
+        rival.priority = 5;
+        blueprint.add_transition(rival).unwrap();
+
+        let priorities: Vec<Mutation> = mutants(&blueprint)
+            .into_iter()
+            .map(|m| m.mutation)
+            .filter(|m| matches!(m, Mutation::ChangePriority { .. }))
+            .collect();
+        assert_eq!(
+            priorities,
+            vec![
+                Mutation::ChangePriority { transition: 1, from: 0, to: 6 },
+                Mutation::ChangePriority { transition: 3, from: 5, to: -1 },
+            ]
+        );
+        assert_eq!(priorities[0].to_string(), "转换 1 的优先级 0 -> 6");
+    }
+}
+
+#[cfg(test)]
+mod determinism_audit_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+    use state_zen::core::{DeterminismAudit, StateZenError};
+
+    const COUNT: StateAspectId = 1;
+
+    fn transition(id: u64, event_id: u64, guard: StateInRange, transfer: Transfer) -> Transition {
+        Transition::new(id, event_id, guard, transfer)
+    }
+
+    fn audited_runtime(audit: DeterminismAudit) -> RuntimeStateMachine {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(COUNT).with_default(0)).unwrap();
+        for event in [10, 11, 12] {
+            blueprint.add_event(EventDef::new(event)).unwrap();
+        }
+        let flip = AtomicBool::new(false);
+        let coin = StateInRange::new(move |_| flip.fetch_xor(true, Ordering::SeqCst));
+        blueprint.add_transition(transition(1, 10, coin, Transfer::add(COUNT, 1i32))).unwrap();
+        let ticket = Arc::new(AtomicI32::new(100));
+        let draw = Transfer::new(move |s| {
+            let mut next = s.clone();
+            next.insert(COUNT, Arc::new(ticket.fetch_add(1, Ordering::SeqCst)));
+            next
+        });
+        blueprint.add_transition(transition(2, 11, StateInRange::always(), draw)).unwrap();
+        blueprint.add_transition(transition(3, 12, StateInRange::always(), Transfer::add(COUNT, 1i32))).unwrap();
+        let state = blueprint.default_initial_state().unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.set_determinism_audit(Some(audit));
+        runtime
+    }
+
+    #[test]
+    fn test_audit_flags_hidden_state_in_guards_and_transfers() {
+        let mut runtime = audited_runtime(DeterminismAudit::new().compare::<i32>(COUNT));
+        runtime.handle_event(12, None);
+        assert!(runtime.take_errors().is_empty());
+
+        runtime.handle_event(10, None);
+        assert_eq!(
+            runtime.take_errors(),
+            vec![StateZenError::NonDeterministic { event: 10, transition: None, aspect: None }]
+        );
+
+        runtime.handle_event(11, None);
+        let errors = runtime.take_errors();
+        assert_eq!(errors, vec![StateZenError::NonDeterministic { event: 11, transition: Some(2), aspect: Some(COUNT) }]);
+        assert_eq!(errors[0].to_string(), "事件 11 触发的转换 2 两次写入方面 1 的结果不同");
+    }
+
+    #[test]
+    fn test_values_compared_only_for_registered_aspects() {
+        let mut runtime = audited_runtime(DeterminismAudit::new());
+        runtime.handle_event(11, None);
+        assert!(runtime.take_errors().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod transition_observer_tests {
+    use super::*;
+    use std::sync::Mutex;
+    use state_zen::TransitionObserver;
+
+    type Log = Arc<Mutex<Vec<(&'static str, u64)>>>;
+
+    fn record(log: &Log, who: &'static str) -> impl Fn(u64, &State, &State) + Send + Sync + 'static {
+        let log = log.clone();
+        move |id, _, _| log.lock().unwrap().push((who, id))
+    }
+
+    #[test]
+    fn test_blueprint_and_runtime_observers_see_matching_transitions() {
+        let log: Log = Arc::default();
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.add_transition_observer(TransitionObserver::only([1], record(&log, "walk")));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.add_transition_observer(TransitionObserver::all(record(&log, "all")));
+
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        runtime.handle_event(101, None);
+
+        assert_eq!(*log.lock().unwrap(), vec![("walk", 1), ("all", 1), ("all", 2)]);
+    }
+
+    #[test]
+    fn test_observer_receives_states_and_runs_after_on_tran() {
+        let log: Log = Arc::default();
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let on_tran = log.clone();
+        blueprint.transition_mut(1).unwrap().on_tran = Some(Arc::new(move |_, _| on_tran.lock().unwrap().push(("on_tran", 1))));
+        let seen = log.clone();
+        blueprint.add_transition_observer(TransitionObserver::new(
+            |id| id % 2 == 1,
+            move |id, before, after| {
+                assert_eq!((get_action(before), get_action(after)), (Some(Action::Idle), Some(Action::Walk)));
+                seen.lock().unwrap().push(("observer", id));
+            },
+        ));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        runtime.handle_event(100, None);
+
+        assert_eq!(*log.lock().unwrap(), vec![("on_tran", 1), ("observer", 1)]);
+    }
+}
+
+#[cfg(test)]
+mod transform_hook_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_hooks_wrap_every_commit_and_can_be_removed() {
+        let log: Arc<Mutex<Vec<String>>> = Arc::default();
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let on_tran = log.clone();
+        blueprint.transition_mut(1).unwrap().on_tran = Some(Arc::new(move |_, _| on_tran.lock().unwrap().push("on_tran".into())));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+
+        let before = log.clone();
+        let before = runtime.on_before_transform(move |prev, next| {
+            before.lock().unwrap().push(format!("before {:?}->{:?}", get_action(prev).unwrap(), get_action(next).unwrap()));
+        });
+        let after = log.clone();
+        let after = runtime.on_after_transform(move |prev, next| {
+            after.lock().unwrap().push(format!("after {:?}->{:?}", get_action(prev).unwrap(), get_action(next).unwrap()));
+        });
+        assert_ne!(before, after);
+
+        runtime.handle_event(100, None);
+        assert_eq!(*log.lock().unwrap(), ["before Idle->Walk", "on_tran", "after Idle->Walk"]);
+
+        assert!(runtime.remove_transform_hook(before));
+        assert!(!runtime.remove_transform_hook(before));
+        log.lock().unwrap().clear();
+        runtime.handle_event(101, None);
+        assert_eq!(*log.lock().unwrap(), ["after Walk->Idle"]);
+
+        // 没有转换被选中时不调用钩子
+        log.lock().unwrap().clear();
+        runtime.handle_event(101, None);
+        assert!(log.lock().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod machine_label_tests {
+    use super::*;
+    use state_zen::core::{MachineLabel, MachineSupervisor, MachineTemplate, RestartPolicy, ValidationPolicy};
+    use state_zen::monitor::Monitor;
+    use state_zen::core::AspectRegistry;
+
+    const HP: StateAspectId = 1;
+    const HEAL: u64 = 10;
+
+    fn blueprint() -> StateMachineBlueprint {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint
+            .add_aspect(StateAspect::of::<i32>(HP).with_default(5).with_validator(|hp: &i32| (0..=10).contains(hp)))
+            .unwrap();
+        blueprint.add_event(EventDef::new(HEAL)).unwrap();
+        blueprint.add_transition(Transition::new(1, HEAL, StateInRange::always(), Transfer::add(HP, 20i32))).unwrap();
+        blueprint
+    }
+
+    #[test]
+    fn test_label_reaches_tracers_and_errors() {
+        let blueprint = blueprint();
+        let state = blueprint.default_initial_state().unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.set_validation_policy(ValidationPolicy::Error);
+        assert_eq!(runtime.machine_id(), None);
+
+        let monitor = Monitor::new();
+        monitor.attach("goblin", AspectRegistry::new(), &mut runtime);
+        let label = MachineLabel::new(7).with("name", "goblin").with("shard", "2");
+        runtime.set_label(label.clone());
+        assert_eq!(runtime.machine_id(), Some(7));
+        assert_eq!(runtime.label().and_then(|l| l.get("shard")), Some("2"));
+        assert_eq!(monitor.snapshot()[0].label.as_ref(), Some(&label));
+
+        runtime.handle_event(HEAL, None);
+        let errors = runtime.take_labeled_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "状态机 7 (name=goblin, shard=2)：转换 1 写入的方面 1 取值未通过校验");
+        assert!(runtime.take_labeled_errors().is_empty());
+    }
+
+    #[test]
+    fn test_supervisor_labels_children() {
+        use std::sync::Mutex;
+
+        let mut supervisor = MachineSupervisor::new();
+        supervisor.register_template("goblin", MachineTemplate::new(blueprint()).with_default(HP, 5i32));
+        supervisor.set_restart_policy("goblin", RestartPolicy::ResetToInitial);
+        let notices = Arc::new(Mutex::new(Vec::new()));
+        let sink = notices.clone();
+        supervisor.set_on_failure(move |n| sink.lock().unwrap().push(n.label.clone()));
+        supervisor.set_invariant("goblin", StateInRange::aspect_in(HP, ..=5));
+
+        let a = supervisor.spawn("goblin", State::new()).unwrap();
+        let b = supervisor.spawn("goblin", State::new()).unwrap();
+        assert_eq!(supervisor.child(b).unwrap().runtime.label(), Some(&MachineLabel::new(b).with("template", "goblin")));
+        assert!(supervisor.set_child_metadata(b, "owner", "alice"));
+        assert!(!supervisor.set_child_metadata(99, "owner", "alice"));
+        assert_eq!(supervisor.find_children("template", "goblin"), vec![a, b]);
+        assert_eq!(supervisor.find_children("owner", "alice"), vec![b]);
+
+        let mut overrides = State::new();
+        overrides.insert(HP, Arc::new(6i32));
+        supervisor.child_mut(b).unwrap().runtime.set_state(overrides);
+        supervisor.send_to(b, HEAL, None).unwrap();
+        let notices = notices.lock().unwrap();
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].as_ref().and_then(|l| l.get("owner")), Some("alice"));
+    }
+}
+
+#[cfg(test)]
+mod blueprint_docs_tests {
+    use super::*;
+    use state_zen::core::AspectRegistry;
+
+    #[test]
+    fn test_generate_docs_describes_every_item() {
+        let (mut blueprint, _) = create_player_blueprint();
+        let walk = blueprint.transition_mut(1).unwrap();
+        walk.guard = StateInRange::aspect_eq(1, Action::Idle);
+        walk.transfer = Transfer::set(1, Action::Walk).then(Transfer::add(2, 1i32));
+        walk.priority = 3;
+        blueprint.add_aspect(StateAspect::of::<i32>(2).with_default(0).with_validator(|h: &i32| *h >= 0)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<Option<Action>>(3)).unwrap();
+
+        let mut registry = AspectRegistry::new();
+        registry
+            .register::<Action>(1, "action")
+            .register::<i32>(2, "hunger")
+            .register::<Option<Action>>(3, "queued");
+        let docs = blueprint.generate_docs(&registry);
+
+        for line in [
+            "# 状态机蓝图",
+            "| 1 | action | `Action` | `Idle` | - |",
+            "| 2 | hunger | `i32` | `0` | 取值校验 |",
+            "| 3 | queued | `Option<Action>` | - | - |",
+            "| 100 | 无 | 1 | - |",
+            "### 转换 1\n\n- 事件：100\n- 优先级：3\n- 守卫：`action == Idle`\n- 效果：`action := Walk; hunger += 1`\n",
+            "### 转换 2\n\n- 事件：101\n- 优先级：0\n- 守卫：`<closure>`\n",
+            "| 1 | `<closure>` | 0 |",
+        ] {
+            assert!(docs.contains(line), "缺少 {line:?}:\n{docs}");
+        }
+        assert!(docs.find("## 方面") < docs.find("## 事件"));
+        assert!(docs.find("## 转换") < docs.find("## 观察者"));
+    }
+}
+
+#[cfg(test)]
+mod mermaid_tests {
+    use super::*;
+    use state_zen::core::AspectRegistry;
+
+    #[test]
+    fn test_to_mermaid_links_canonical_states() {
+        let (mut blueprint, _) = create_player_blueprint();
+        let walk = blueprint.transition_mut(1).unwrap();
+        walk.guard = StateInRange::aspect_eq(1, Action::Idle);
+        walk.transfer = Transfer::set(1, Action::Walk).then(Transfer::add(2, 1i32));
+        let stop = blueprint.transition_mut(2).unwrap();
+        stop.guard = StateInRange::aspect_eq(1, Action::Walk).and(StateInRange::aspect_eq(2, 1i32));
+        stop.transfer = Transfer::set(1, Action::Idle);
+        blueprint.set_final_region(StateInRange::aspect_eq(1, Action::Idle));
+
+        let mut registry = AspectRegistry::new();
+        registry.register::<Action>(1, "action").register::<i32>(2, "hunger");
+        assert_eq!(
+            blueprint.to_mermaid(&registry),
+            "stateDiagram-v2\n\
+             \x20   s0 : action = Idle\n\
+             \x20   s1 : action = Walk, hunger = ?\n\
+             \x20   s2 : action = Walk, hunger = 1\n\
+             \x20   s3 : action = Idle, hunger = 1\n\
+             \x20   s0 --> s1 : 事件 100 (转换 1)\n\
+             \x20   s2 --> s3 : 事件 101 (转换 2)\n\
+             \x20   s0 --> [*]\n"
+        );
+    }
+
+    #[test]
+    fn test_opaque_guards_and_transfers() {
+        let (blueprint, _) = create_player_blueprint();
+        let diagram = blueprint.to_mermaid(&AspectRegistry::new());
+        assert_eq!(
+            diagram,
+            "stateDiagram-v2\n\
+             \x20   s0 : 任意状态\n\
+             \x20   s1 : ?\n\
+             \x20   s0 --> s1 : 事件 100 (转换 1)\n\
+             \x20   s0 --> s1 : 事件 101 (转换 2)\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod payload_transformer_tests {
+    use super::*;
+    use std::sync::Mutex;
+    use state_zen::EventInstance;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Direction {
+        Left,
+        Neutral,
+        Right,
+    }
+
+    fn runtime_recording_payloads() -> (RuntimeStateMachine, Arc<Mutex<Vec<Option<Direction>>>>) {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        // 原始摇杆轴值按死区归一化为离散方向
+        let event = EventDef::of::<Direction>(100)
+        .with_transformer(|axis: &f32| axis.clamp(-1.0, 1.0))
+        .with_transformer(|axis: &f32| match *axis {
+            a if a < -0.25 => Direction::Left,
+            a if a > 0.25 => Direction::Right,
+            _ => Direction::Neutral,
+        });
+        *blueprint.event_mut(100).unwrap() = event;
+
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        runtime.use_middleware(move |event, payload, next| {
+            let direction = payload.as_ref().and_then(|p| p.downcast_ref::<Direction>().copied());
+            log.lock().unwrap().push(direction);
+            next(event, payload);
+        });
+        (runtime, seen)
+    }
+
+    #[test]
+    fn test_transformers_condition_payload_before_dispatch() {
+        let (mut runtime, seen) = runtime_recording_payloads();
+        runtime.handle_event(100, Some(Arc::new(-3.0f32)));
+        runtime.handle_event(101, Some(Arc::new(0.9f32)));
+        runtime.handle_event(100, Some(Arc::new(0.1f32)));
+        // 已是目标类型的负载与无负载的事件原样通过
+        runtime.handle_event(100, Some(Arc::new(Direction::Right)));
+        runtime.handle_event(100, None);
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![Some(Direction::Left), None, Some(Direction::Neutral), Some(Direction::Right), None]
+        );
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+    }
+
+    #[test]
+    fn test_transformers_apply_to_batches_and_buffered_events() {
+        let (mut runtime, seen) = runtime_recording_payloads();
+        runtime.handle_events(&[EventInstance::new(100, Some(Arc::new(0.5f32)))]);
+
+        runtime.pause();
+        runtime.handle_event(100, Some(Arc::new(-0.5f32)));
+        assert_eq!(seen.lock().unwrap().len(), 1);
+        runtime.resume();
+
+        assert_eq!(*seen.lock().unwrap(), vec![Some(Direction::Right), Some(Direction::Left)]);
+    }
+}
+
+#[cfg(test)]
+mod request_response_tests {
+    use super::*;
+    use state_zen::StateZenError;
+
+    /// 从 Idle 进入 Walk 时返回新旧动作
+    fn runtime() -> RuntimeStateMachine {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let walk = blueprint
+            .remove_transition(1)
+            .unwrap()
+            .with_response(|prev, next| (get_action(prev), get_action(next)));
+        blueprint.add_transition(walk).unwrap();
+        RuntimeStateMachine::new(blueprint, initial_state)
+    }
+
+    #[test]
+    fn test_request_returns_transition_response() {
+        let mut runtime = runtime();
+        let response = runtime.request(100, None).unwrap().unwrap();
+        assert_eq!(
+            response.downcast_ref::<(Option<Action>, Option<Action>)>(),
+            Some(&(Some(Action::Idle), Some(Action::Walk)))
+        );
+
+        // 没有可用转换、转换没有响应函数时没有响应
+        assert!(runtime.request(100, None).unwrap().is_none());
+        assert!(runtime.request(101, None).unwrap().is_none());
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+
+        // 普通分发不产生响应，也不影响之后的请求
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        assert!(runtime.request(100, None).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_request_reports_errors_and_buffering() {
+        let mut runtime = runtime();
+        runtime.pause();
+        assert!(runtime.request(100, None).unwrap().is_none());
+        runtime.resume();
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        blueprint.aspect_mut(1).unwrap().validator =
+            Some(Arc::new(|v| v.downcast_ref::<Action>() != Some(&Action::Walk)));
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        assert_eq!(
+            runtime.request(100, None).err(),
+            Some(StateZenError::InvalidAspectValue { aspect: 1, transition: Some(1) })
+        );
+        assert_eq!(runtime.take_errors().len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod watchdog_tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use state_zen::core::{Escalation, Watchdog};
+
+    fn walking() -> StateInRange {
+        StateInRange::aspect_eq(1, Action::Walk)
+    }
+
+    #[test]
+    fn test_watchdog_escalates_stuck_state_with_event() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.add_watchdog(Watchdog::inside(walking(), Duration::from_secs(1), Escalation::Event(101)));
+
+        Scenario::new("卡在行走")
+            .dispatch(100)
+            .advance(Duration::from_millis(600))
+            // 离开再进入后重新计时
+            .dispatch(101)
+            .dispatch(100)
+            .advance(Duration::from_millis(600))
+            .expect_aspect(1, Action::Walk)
+            .advance(Duration::from_millis(400))
+            .expect_aspect(1, Action::Idle)
+            .assert(&mut runtime);
+    }
+
+    #[test]
+    fn test_watchdog_outside_region_invokes_callback_once_per_stay() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let log = calls.clone();
+        let handle = runtime.add_watchdog(Watchdog::outside(
+            walking(),
+            Duration::from_millis(500),
+            Escalation::Callback(Arc::new(move |state, elapsed| {
+                log.lock().unwrap().push((get_action(state), elapsed));
+            })),
+        ));
+
+        for _ in 0..4 {
+            runtime.tick(Duration::from_millis(300));
+        }
+        assert_eq!(*calls.lock().unwrap(), vec![(Some(Action::Idle), Duration::from_millis(600))]);
+
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        runtime.tick(Duration::from_millis(500));
+        assert_eq!(calls.lock().unwrap().len(), 2);
+
+        assert!(runtime.remove_watchdog(handle));
+        assert!(!runtime.remove_watchdog(handle));
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        runtime.tick(Duration::from_secs(5));
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+}
+
+#[cfg(test)]
+mod recent_activity_tests {
+    use super::*;
+    use state_zen::core::Activity;
+
+    #[test]
+    fn test_recent_activity_keeps_last_events_and_transitions() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_activity_log(3, 1);
+
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        runtime.handle_event(100, None);
+        assert_eq!(runtime.recent_activity(), vec![
+            Activity::Event { seq: 1, event_id: 100, selected: Some(1) },
+            Activity::Transition { transition_id: 1, written: vec![1] },
+            Activity::Event { seq: 2, event_id: 100, selected: None },
+        ]);
+        let dump: Vec<String> = runtime.recent_activity().iter().map(ToString::to_string).collect();
+        assert_eq!(dump, ["#1 事件 100 -> 转换 1", "转换 1 写入 [1]", "#2 事件 100 被忽略"]);
+    }
+
+    #[test]
+    fn test_recent_activity_samples_events() {
+        let (blueprint, initial_state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, initial_state);
+        runtime.set_activity_log(16, 2);
+        for event in [100, 101, 100, 101] {
+            runtime.handle_event(event, None);
+        }
+        let events: Vec<u64> = runtime
+            .recent_activity()
+            .iter()
+            .filter_map(|a| match a {
+                Activity::Event { seq, .. } => Some(*seq),
+                Activity::Transition { .. } => None,
+            })
+            .collect();
+        assert_eq!(events, vec![0, 2]);
+        // 转换不受采样影响
+        assert_eq!(runtime.recent_activity().len(), 6);
+
+        runtime.set_activity_log(0, 1);
+        runtime.handle_event(100, None);
+        assert!(runtime.recent_activity().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod blueprint_stats_tests {
+    use super::*;
+    use state_zen::core::{ComplexityThresholds, ComplexityWarning};
+
+    #[test]
+    fn test_stats_counts_and_warnings() {
+        let (mut blueprint, _) = create_player_blueprint();
+        let walk = blueprint.transition_mut(1).unwrap();
+        walk.guard = StateInRange::aspect_eq(1, Action::Idle);
+        walk.transfer = Transfer::set(1, Action::Walk);
+        blueprint.add_event(EventDef::new(102)).unwrap();
+        for id in 3..6 {
+            let mut extra = blueprint.transition(2).unwrap().clone();
+            extra.id = id;
+            blueprint.add_transition(extra).unwrap();
+        }
+
+        let stats = blueprint.stats();
+        assert_eq!((stats.aspects, stats.events, stats.transitions, stats.observers), (1, 3, 5, 1));
+        assert_eq!(stats.transitions_per_event.into_iter().collect::<Vec<_>>(), vec![(100, 1), (101, 4)]);
+        assert_eq!((stats.declarative_guards, stats.opaque_guards), (1, 4));
+        assert_eq!((stats.declarative_transfers, stats.opaque_transfers), (1, 4));
+        assert_eq!(stats.warnings, vec![ComplexityWarning::UnusedEvent(102)]);
+
+        let stats = blueprint.stats_with(ComplexityThresholds { transitions_per_event: 4, observers: 1 });
+        assert_eq!(stats.max_transitions_per_event(), 4);
+        assert_eq!(stats.warnings, vec![
+            ComplexityWarning::HotEvent { event_id: 101, transitions: 4 },
+            ComplexityWarning::TooManyObservers(1),
+            ComplexityWarning::UnusedEvent(102),
+        ]);
+        assert!(stats.to_string().ends_with("\n  警告：事件 102 未被任何转换使用"));
+    }
+}
+
+#[cfg(test)]
+mod product_tests {
+    use super::*;
+    use state_zen::StateZenError;
+    use state_zen::utils::product;
+
+    fn toggle(aspect: StateAspectId, ids: [u64; 2], events: [u64; 2]) -> StateMachineBlueprint {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(aspect)).unwrap();
+        for (i, (id, event_id)) in ids.into_iter().zip(events).enumerate() {
+            let from = i as i32;
+            blueprint.add_event(EventDef::new(event_id)).unwrap();
+            blueprint.add_transition(Transition::new(
+                id,
+                event_id,
+                StateInRange::aspect_eq(aspect, from),
+                Transfer::set(aspect, 1 - from),
+            )).unwrap();
+        }
+        blueprint
+    }
+
+    fn value(runtime: &RuntimeStateMachine, aspect: StateAspectId) -> i32 {
+        *runtime.current_state.get(&aspect).unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    #[test]
+    fn test_product_fires_synchronized_pairs_together() {
+        // 门：事件 10 开门、11 关门；警报：事件 20 解除、21 布防，开门与解除警报同步
+        let door = toggle(1, [1, 2], [10, 11]);
+        let alarm = toggle(2, [3, 4], [21, 20]);
+        let composed = product(&door, &alarm, &[(10, 20)]).unwrap();
+
+        let synced = composed.transition(5).unwrap();
+        assert_eq!(synced.event_id, 10);
+        assert_eq!(composed.transitions_for_event(20).count(), 0);
+        assert_eq!(composed.transitions().count(), 3);
+
+        let mut state = State::new();
+        state.insert(1, Arc::new(0i32));
+        state.insert(2, Arc::new(0i32));
+        let mut runtime = RuntimeStateMachine::new(composed, state);
+        // 警报未布防时无法同步开门
+        runtime.handle_event(10, None);
+        assert_eq!((value(&runtime, 1), value(&runtime, 2)), (0, 0));
+
+        runtime.handle_event(21, None);
+        runtime.handle_event(10, None);
+        assert_eq!((value(&runtime, 1), value(&runtime, 2)), (1, 0));
+        runtime.handle_event(11, None);
+        assert_eq!((value(&runtime, 1), value(&runtime, 2)), (0, 0));
+    }
+
+    #[test]
+    fn test_product_rejects_overlapping_machines() {
+        let door = toggle(1, [1, 2], [10, 11]);
+        assert_eq!(
+            product(&door, &toggle(1, [3, 4], [20, 21]), &[]).err(),
+            Some(StateZenError::DuplicateAspect(1))
+        );
+        assert_eq!(
+            product(&door, &toggle(2, [2, 3], [20, 21]), &[]).err(),
+            Some(StateZenError::DuplicateTransition(2))
+        );
+        assert_eq!(
+            product(&door, &toggle(2, [3, 4], [20, 21]), &[(10, 99)]).err(),
+            Some(StateZenError::UnknownEvent(99))
+        );
+    }
+}
+
+#[cfg(test)]
+mod projection_tests {
+    use super::*;
+
+    #[test]
+    fn test_project_keeps_parts_within_aspect_subset() {
+        let (mut blueprint, _) = create_player_blueprint();
+        blueprint.add_aspect(StateAspect::of::<i32>(2)).unwrap();
+        let walk = blueprint.transition_mut(1).unwrap();
+        walk.guard = StateInRange::aspect_eq(1, Action::Idle);
+        walk.transfer = Transfer::set(1, Action::Walk);
+        walk.ensures = Some(StateInRange::aspect_in(2, 0..));
+        // 守卫读取饥饿度，超出子集
+        let stop = blueprint.transition_mut(2).unwrap();
+        stop.guard = StateInRange::aspect_eq(1, Action::Walk).and(StateInRange::aspect_in(2, ..5));
+        stop.transfer = Transfer::set(1, Action::Idle);
+        blueprint.add_observer(StateObserver {
+            id: 2,
+            region: StateInRange::aspect_eq(1, Action::Walk),
+            ..blueprint.observer(1).unwrap().clone()
+        }).unwrap();
+        blueprint.set_final_region(StateInRange::aspect_in(2, 10..));
+
+        let projected = blueprint.project(&[1]);
+        assert_eq!(projected.aspects().map(|a| a.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(projected.transitions().map(|t| t.id).collect::<Vec<_>>(), vec![1]);
+        assert!(projected.transition(1).unwrap().ensures.is_none());
+        assert_eq!(projected.events().map(|e| e.id).collect::<Vec<_>>(), vec![100]);
+        assert_eq!(projected.observers().map(|o| o.id).collect::<Vec<_>>(), vec![2]);
+        assert!(projected.final_region().is_none());
+
+        // 投影后的状态机只需要子集内的方面
+        let mut state = State::new();
+        state.insert(1, Arc::new(Action::Idle));
+        let mut runtime = RuntimeStateMachine::new(projected, state);
+        runtime.handle_event(100, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+
+        assert_eq!(blueprint.project(&[1, 2]).transitions().count(), 2);
+    }
+}
+
+#[cfg(test)]
+mod prediction_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use state_zen::core::PredictedMachine;
+
+    fn count(state: &State) -> i32 {
+        *state.get(&1).unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    #[test]
+    fn test_reconcile_rolls_back_and_replays_unacknowledged_events() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(1)).unwrap();
+        blueprint.add_event(EventDef::new(10)).unwrap();
+        blueprint.add_transition(Transition {
+            on_tran: Some(Arc::new(move |_, _| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })),
+            ..Transition::new(1, 10, StateInRange::always(), Transfer::add(1, 1i32))
+        }).unwrap();
+        let mut state = State::new();
+        state.insert(1, Arc::new(0i32));
+        let mut machine = PredictedMachine::new(RuntimeStateMachine::new(blueprint, state));
+
+        let seqs: Vec<u64> = (0..3).map(|_| machine.apply(10, None)).collect();
+        assert_eq!(seqs, vec![0, 1, 2]);
+        assert_eq!(count(machine.current_state()), 3);
+
+        // 服务器处理了第一个本地事件，且其他来源把计数改成了 10
+        let mut authoritative = State::new();
+        authoritative.insert(1, Arc::new(10i32));
+        assert_eq!(machine.reconcile(authoritative, Some(0)), 2);
+        assert_eq!(count(machine.current_state()), 12);
+        // 重放不会再次执行回调
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        let mut authoritative = State::new();
+        authoritative.insert(1, Arc::new(12i32));
+        assert_eq!(machine.reconcile(authoritative, Some(2)), 0);
+        assert_eq!(machine.pending(), 0);
+        assert_eq!(count(machine.current_state()), 12);
+    }
+
+    #[test]
+    fn test_replay_does_not_emit_or_trace_again() {
+        use std::sync::mpsc;
+        use state_zen::core::{EventTemplate, Tracer};
+
+        #[derive(Default)]
+        struct Commits(AtomicUsize);
+
+        impl Tracer for Commits {
+            fn on_commit(&self, _prev: &State, _next: &State) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(1)).unwrap();
+        blueprint.add_event(EventDef::new(10)).unwrap();
+        blueprint.add_transition(Transition {
+            emits: vec![EventTemplate::new(300)],
+            ..Transition::new(1, 10, StateInRange::always(), Transfer::add(1, 1i32))
+        }).unwrap();
+        let mut state = State::new();
+        state.insert(1, Arc::new(0i32));
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        let (tx, rx) = mpsc::channel();
+        runtime.set_event_sink(tx);
+        let commits = Arc::new(Commits::default());
+        runtime.add_tracer(commits.clone());
+        let mut machine = PredictedMachine::new(runtime);
+
+        machine.apply(10, None);
+        machine.apply(10, None);
+        assert_eq!(rx.try_iter().count(), 2);
+
+        let mut authoritative = State::new();
+        authoritative.insert(1, Arc::new(5i32));
+        assert_eq!(machine.reconcile(authoritative, None), 2);
+        assert_eq!(count(machine.current_state()), 7);
+        // 重放只重建状态：不再发出事件，也不再通知追踪器
+        assert_eq!(rx.try_iter().count(), 0);
+        assert_eq!(commits.0.load(Ordering::Relaxed), 2);
+
+        // 重放结束后恢复正常
+        machine.apply(10, None);
+        assert_eq!(rx.try_iter().count(), 1);
+        assert_eq!(commits.0.load(Ordering::Relaxed), 3);
+    }
+}
+
+#[cfg(test)]
+mod delta_snapshot_tests {
+    use super::*;
+    use state_zen::StateZenError;
+    use state_zen::core::{StateCodec, StateDelta};
+
+    fn runtime() -> RuntimeStateMachine {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(1)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<String>(2)).unwrap();
+        blueprint.add_event(EventDef::new(10)).unwrap();
+        blueprint.add_transition(Transition::new(1, 10, StateInRange::always(), Transfer::add(1, 1i32))).unwrap();
+        let mut state = State::new();
+        state.insert(1, Arc::new(0i32));
+        state.insert(2, Arc::new("goblin".to_string()));
+        RuntimeStateMachine::new(blueprint, state)
+    }
+
+    fn codec() -> StateCodec {
+        let mut codec = StateCodec::new();
+        codec.register::<i32>(1).register::<String>(2);
+        codec
+    }
+
+    #[test]
+    fn test_delta_contains_only_changed_aspects() {
+        let mut server = runtime();
+        let base = server.snapshot();
+        server.handle_event(10, None);
+        server.handle_event(10, None);
+        let latest = server.snapshot();
+
+        let delta = latest.delta_since(&base, &codec()).unwrap();
+        assert_eq!(delta, StateDelta { changed: vec![(1, 2, "2".to_string())], removed: vec![] });
+        assert!(latest.delta_since(&latest, &codec()).unwrap().is_empty());
+
+        let client = base.apply_delta(&delta, &codec()).unwrap();
+        assert_eq!(client.state.get(&1).unwrap().downcast_ref::<i32>(), Some(&2));
+        assert_eq!(client.state.get(&2).unwrap().downcast_ref::<String>().map(String::as_str), Some("goblin"));
+        assert_eq!(client.versions, latest.versions);
+
+        // 过期的增量不会让状态倒退
+        server.handle_event(10, None);
+        let newer = server.snapshot().delta_since(&base, &codec()).unwrap();
+        let client = client.apply_delta(&newer, &codec()).unwrap().apply_delta(&delta, &codec()).unwrap();
+        assert_eq!(client.state.get(&1).unwrap().downcast_ref::<i32>(), Some(&3));
+    }
+
+    #[test]
+    fn test_delta_reports_removed_and_unencodable_aspects() {
+        let mut server = runtime();
+        let base = server.snapshot();
+        let mut state = server.current_state.clone();
+        state.remove(&2);
+        server.set_state(state);
+        let delta = server.snapshot().delta_since(&base, &codec()).unwrap();
+        assert_eq!(delta.removed, vec![2]);
+        assert!(!base.apply_delta(&delta, &codec()).unwrap().state.contains_key(&2));
+
+        server.handle_event(10, None);
+        assert_eq!(
+            server.snapshot().delta_since(&base, &StateCodec::new()).err(),
+            Some(StateZenError::CodecFailed(1))
+        );
+    }
+
+    #[test]
+    fn test_stale_delta_does_not_resurrect_removed_aspect() {
+        let mut server = runtime();
+        let base = server.snapshot();
+        let mut state = server.current_state.clone();
+        state.insert(2, Arc::new("orc".to_string()));
+        server.set_state(state.clone());
+        let renamed = server.snapshot();
+        let stale = renamed.delta_since(&base, &codec()).unwrap();
+        state.remove(&2);
+        server.set_state(state);
+        let removal = server.snapshot().delta_since(&renamed, &codec()).unwrap();
+
+        // 改名的增量在移除之后才重复到达
+        let client = base
+            .apply_delta(&stale, &codec())
+            .unwrap()
+            .apply_delta(&removal, &codec())
+            .unwrap()
+            .apply_delta(&stale, &codec())
+            .unwrap();
+        assert!(!client.state.contains_key(&2));
+        assert_eq!(client.versions.get(&2), renamed.versions.get(&2));
+    }
+}
+
+#[cfg(test)]
+mod authority_tests {
+    use super::*;
+    use state_zen::StateZenError;
+    use state_zen::core::{Authority, NetworkRole, AuthorityPolicy};
+
+    // 方面 1 为服务器权威的生命值，方面 2 为客户端权威的朝向
+    fn runtime() -> RuntimeStateMachine {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<i32>(1)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i32>(2)).unwrap();
+        blueprint.set_authority(1, Authority::Server);
+        blueprint.set_authority(2, Authority::Client);
+        blueprint.add_event(EventDef::new(10)).unwrap();
+        blueprint.add_event(EventDef::new(20)).unwrap();
+        for (id, event_id, aspect) in [(1, 10, 1), (2, 20, 2)] {
+            blueprint.add_transition(Transition::new(id, event_id, StateInRange::always(), Transfer::add(aspect, 1i32))).unwrap();
+        }
+        let mut state = State::new();
+        state.insert(1, Arc::new(100i32));
+        state.insert(2, Arc::new(0i32));
+        RuntimeStateMachine::new(blueprint, state)
+    }
+
+    fn value(runtime: &RuntimeStateMachine, aspect: StateAspectId) -> i32 {
+        *runtime.current_state.get(&aspect).unwrap().downcast_ref::<i32>().unwrap()
+    }
+
+    #[test]
+    fn test_client_rejects_writes_to_server_aspects() {
+        let mut client = runtime();
+        client.set_network_role(NetworkRole::Client, AuthorityPolicy::Reject);
+        assert_eq!(client.network_role(), NetworkRole::Client);
+
+        client.handle_event(10, None);
+        client.handle_event(20, None);
+        assert_eq!((value(&client, 1), value(&client, 2)), (100, 1));
+        assert_eq!(
+            client.take_errors(),
+            vec![StateZenError::AuthorityViolation { aspect: 1, transition: Some(1) }]
+        );
+
+        // 服务器同步过来的状态不受约束
+        let mut state = client.current_state.clone();
+        state.insert(1, Arc::new(90i32));
+        client.set_state(state);
+        assert_eq!(value(&client, 1), 90);
+        assert!(client.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_server_and_warn_policy() {
+        let mut server = runtime();
+        server.set_network_role(NetworkRole::Server, AuthorityPolicy::Warn);
+        server.handle_event(10, None);
+        server.handle_event(20, None);
+        assert_eq!((value(&server, 1), value(&server, 2)), (101, 1));
+        assert_eq!(
+            server.take_errors(),
+            vec![StateZenError::AuthorityViolation { aspect: 2, transition: Some(2) }]
+        );
+
+        let mut standalone = runtime();
+        standalone.handle_event(10, None);
+        standalone.handle_event(20, None);
+        assert_eq!((value(&standalone, 1), value(&standalone, 2)), (101, 1));
+        assert!(standalone.take_errors().is_empty());
+        assert_eq!(standalone.blueprint.authority(3), Authority::Shared);
+    }
+
+    #[test]
+    fn test_request_ignores_warnings_that_do_not_stop_the_transition() {
+        let mut server = runtime();
+        server.set_network_role(NetworkRole::Server, AuthorityPolicy::Warn);
+        // 越权警告只记录，转换照常提交，请求不算失败
+        assert!(server.request(20, None).unwrap().is_none());
+        assert_eq!(value(&server, 2), 1);
+        assert_eq!(server.take_errors().len(), 1);
+
+        let mut client = runtime();
+        client.set_network_role(NetworkRole::Client, AuthorityPolicy::Reject);
+        assert_eq!(
+            client.request(10, None).err(),
+            Some(StateZenError::AuthorityViolation { aspect: 1, transition: Some(1) })
+        );
+    }
+}
+
+#[cfg(test)]
+mod profiling_tests {
+    use super::*;
+    use std::time::Duration;
+    use state_zen::core::ProfileKey;
+
+    #[test]
+    fn test_profile_report_sorted_by_cost() {
+        let (mut blueprint, state) = create_player_blueprint();
+        blueprint.add_transition(Transition::new(
+            3,
+            100,
+            StateInRange::new(|_| {
+                std::thread::sleep(Duration::from_millis(5));
+                false
+            }),
+            Transfer::new(|s| s.clone()),
+        )).unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.handle_event(100, None);
+        assert_eq!(runtime.profile_report(), None);
+
+        runtime.set_profiling(true);
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        let report = runtime.profile_report().unwrap();
+        let slowest = report.entries[0];
+        assert_eq!((slowest.key, slowest.calls), (ProfileKey::Guard(3), 1));
+        assert!(slowest.total >= Duration::from_millis(5));
+        assert_eq!(report.get(ProfileKey::Guard(1)).unwrap().calls, 1);
+        assert_eq!(report.get(ProfileKey::Transfer(1)).unwrap().calls, 1);
+        assert_eq!(report.get(ProfileKey::Transfer(2)).unwrap().calls, 1);
+        assert!(report.get(ProfileKey::Observer(1)).is_some());
+        assert!(report.to_string().starts_with("转换 3 的守卫：1 次"));
+
+        runtime.set_profiling(true);
+        assert!(runtime.profile_report().unwrap().entries.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod state_pool_tests {
+    use super::*;
+    use state_zen::core::{AspectValue, BatchConflictPolicy, EventInstance};
+
+    #[test]
+    fn test_batches_do_not_retain_replaced_values() {
+        let (blueprint, mut state) = create_player_blueprint();
+        let marker: AspectValue = Arc::new(42i32);
+        state.insert(9, marker.clone());
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.set_batch_policy(BatchConflictPolicy::Sequential);
+        let events: Vec<EventInstance> = [100, 101, 100].into_iter().map(|e| EventInstance::new(e, None)).collect();
+        for _ in 0..3 {
+            runtime.handle_events(&events);
+        }
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+
+        // 回收的中间状态已被清空，不再持有被替换的取值
+        runtime.current_state.remove(&9);
+        assert_eq!(Arc::strong_count(&marker), 1);
+    }
+}
+
+#[cfg(test)]
+mod canonical_state_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use state_zen::core::{CanonicalStates, ObserverId};
+
+    fn counting_observer(id: ObserverId, reads: bool, evaluations: Arc<AtomicUsize>, entered: Arc<AtomicUsize>) -> StateObserver {
+        let region = StateInRange::new(move |s| {
+            evaluations.fetch_add(1, Ordering::Relaxed);
+            get_action(s) == Some(Action::Walk)
+        });
+        StateObserver {
+            on_enter: Some(Arc::new(move |_| {
+                entered.fetch_add(1, Ordering::Relaxed);
+            })),
+            ..StateObserver::new(id, if reads { region.with_reads([1]) } else { region })
+        }
+    }
+
+    fn canonical() -> CanonicalStates {
+        let states = [Action::Idle, Action::Walk]
+            .into_iter()
+            .map(|a| [(1, Arc::new(a) as state_zen::core::AspectValue)].into_iter().collect())
+            .collect();
+        CanonicalStates::by_aspect::<Action>(1, states)
+    }
+
+    #[test]
+    fn test_canonical_states_skip_region_predicates() {
+        let (mut blueprint, state) = create_player_blueprint();
+        let (evaluations, entered) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        blueprint.remove_observer(1);
+        for id in 1..=3 {
+            blueprint.add_observer(counting_observer(id, true, evaluations.clone(), entered.clone())).unwrap();
+        }
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        assert_eq!(canonical().classify(&runtime.current_state), Some(0));
+        runtime.set_canonical_states(Some(canonical()));
+        let after_build = evaluations.load(Ordering::Relaxed);
+        assert_eq!(after_build, 6);
+
+        for event in [100, 101, 100, 101, 100] {
+            runtime.handle_event(event, None);
+        }
+        assert_eq!(entered.load(Ordering::Relaxed), 9);
+        assert_eq!(evaluations.load(Ordering::Relaxed), after_build);
+    }
+
+    #[test]
+    fn test_uncovered_observers_still_evaluated() {
+        let (mut blueprint, state) = create_player_blueprint();
+        let (evaluations, entered) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let opaque = Arc::new(AtomicUsize::new(0));
+        blueprint.remove_observer(1);
+        blueprint.add_observer(counting_observer(1, true, evaluations.clone(), entered.clone())).unwrap();
+        blueprint.add_observer(counting_observer(2, false, opaque.clone(), entered.clone())).unwrap();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.set_canonical_states(Some(canonical()));
+        let after_build = evaluations.load(Ordering::Relaxed);
+
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        assert_eq!(entered.load(Ordering::Relaxed), 4);
+        assert_eq!(evaluations.load(Ordering::Relaxed), after_build);
+        assert!(opaque.load(Ordering::Relaxed) >= 3);
+        assert_eq!(canonical().classify(&State::new()), None);
+    }
+
+    #[test]
+    fn test_swapping_an_observer_refreshes_membership() {
+        let (mut blueprint, state) = create_player_blueprint();
+        let (evaluations, entered) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        blueprint.remove_observer(1);
+        for id in 1..=2 {
+            blueprint.add_observer(counting_observer(id, true, evaluations.clone(), entered.clone())).unwrap();
+        }
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+        runtime.set_canonical_states(Some(canonical()));
+
+        // 观察者数量不变，但第二个观察者换成了观察 Idle 的
+        let idle_entered = Arc::new(AtomicUsize::new(0));
+        let counter = idle_entered.clone();
+        runtime.blueprint.remove_observer(2);
+        runtime.blueprint.add_observer(StateObserver {
+            on_enter: Some(Arc::new(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })),
+            ..StateObserver::new(3, StateInRange::aspect_eq(1, Action::Idle))
+        }).unwrap();
+
+        runtime.handle_event(100, None);
+        assert_eq!(idle_entered.load(Ordering::Relaxed), 0);
+        runtime.handle_event(101, None);
+        assert_eq!(idle_entered.load(Ordering::Relaxed), 1);
+        assert_eq!(entered.load(Ordering::Relaxed), 1);
+    }
+}
+
+#[cfg(test)]
+mod compiled_machine_tests {
+    use super::*;
+
+    fn transition(id: u64, event_id: u64, priority: i32, guard: StateInRange, transfer: Transfer) -> Transition {
+        Transition {
+            priority,
+            ..Transition::new(id, event_id, guard, transfer)
+        }
+    }
+
+    // 方面 1 为动作（枚举），方面 2 为体力（只以区间出现，不能查表）
+    fn blueprint() -> StateMachineBlueprint {
+        let mut blueprint = StateMachineBlueprint::new();
+        blueprint.add_aspect(StateAspect::of::<Action>(1)).unwrap();
+        blueprint.add_aspect(StateAspect::of::<i32>(2)).unwrap();
+        for id in [100, 101, 102] {
+            blueprint.add_event(EventDef::new(id)).unwrap();
+        }
+        let idle = StateInRange::aspect_eq(1, Action::Idle);
+        let walk = StateInRange::aspect_eq(1, Action::Walk);
+        blueprint.add_transition(transition(1, 100, 0, idle.clone(), Transfer::set(1, Action::Walk))).unwrap();
+        blueprint.add_transition(transition(2, 101, 0, walk.clone(), Transfer::set(1, Action::Idle))).unwrap();
+        blueprint.add_transition(transition(3, 101, 0, idle.clone().not(), Transfer::add(2, -1i32))).unwrap();
+        // 高优先级但需要运行时求值
+        blueprint.add_transition(transition(
+            4,
+            100,
+            1,
+            walk.clone().and(StateInRange::aspect_in(2, 5i32..)),
+            Transfer::add(2, -5i32),
+        )).unwrap();
+        blueprint.add_transition(transition(5, 102, 0, idle.or(walk), Transfer::add(2, 3i32))).unwrap();
+        blueprint
+    }
+
+    fn initial() -> State {
+        let mut state = State::new();
+        state.insert(1, Arc::new(Action::Idle));
+        state.insert(2, Arc::new(10i32));
+        state
+    }
+
+    #[test]
+    fn test_compiled_machine_matches_runtime() {
+        let mut compiled = blueprint().compile(initial());
+        assert_eq!(compiled.lowered_guards(), (4, 1));
+
+        let mut runtime = RuntimeStateMachine::new(blueprint(), initial());
+        let events = [100, 100, 100, 102, 101, 101, 100, 100, 102, 100, 101, 100, 100];
+        for event in events {
+            runtime.event_happen(event, None);
+            let expected = runtime.pending_transition().map(|t| t.id);
+            runtime.transform();
+            assert_eq!(compiled.handle_event(event), expected);
+            assert_eq!(get_action(compiled.current_state()), get_action(&runtime.current_state));
+            assert_eq!(
+                compiled.current_state().get(&2).unwrap().downcast_ref::<i32>(),
+                runtime.current_state.get(&2).unwrap().downcast_ref::<i32>()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod static_machine_tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use state_zen::StateZenError;
+    use state_zen::core::{StaticBlueprint, StaticMachine, StaticTransition, StaticObserver};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Player {
+        action: Action,
+        stamina: i32,
+    }
+
+    static ENTERED: AtomicUsize = AtomicUsize::new(0);
+    static EXITED: AtomicUsize = AtomicUsize::new(0);
+
+    fn blueprint() -> StaticBlueprint<Player> {
+        let mut blueprint = StaticBlueprint::<Player>::new();
+        blueprint.add_transition(StaticTransition {
+            id: 1,
+            event_id: 100,
+            guard: |p| p.action == Action::Idle,
+            transfer: |p| Player { action: Action::Walk, ..p.clone() },
+            priority: 0,
+            on_tran: None,
+        }).unwrap();
+        blueprint.add_transition(StaticTransition {
+            id: 2,
+            event_id: 101,
+            guard: |p| p.action == Action::Walk,
+            transfer: |p| Player { action: Action::Idle, ..p.clone() },
+            priority: 0,
+            on_tran: None,
+        }).unwrap();
+        blueprint.add_transition(StaticTransition {
+            id: 3,
+            event_id: 100,
+            guard: |p| p.action == Action::Walk && p.stamina > 0,
+            transfer: |p| Player { stamina: p.stamina - 1, ..p.clone() },
+            priority: 1,
+            on_tran: None,
+        }).unwrap();
+        blueprint.add_observer(StaticObserver {
+            id: 1,
+            region: |p| p.action == Action::Walk,
+            on_enter: Some(|p| {
+                assert_eq!(p.action, Action::Walk);
+                ENTERED.fetch_add(1, Ordering::Relaxed);
+            }),
+            on_exit: Some(|p| {
+                assert_eq!(p.action, Action::Walk);
+                EXITED.fetch_add(1, Ordering::Relaxed);
+            }),
+            priority: 0,
+        }).unwrap();
+        blueprint.set_final_region(|p| p.stamina == 0);
+        blueprint
+    }
+
+    #[test]
+    fn test_static_machine_dispatch_and_observers() {
+        let mut machine = StaticMachine::new(blueprint(), Player { action: Action::Idle, stamina: 2 });
+        assert_eq!(machine.handle_event(100), Some(1));
+        assert_eq!(machine.handle_event(100), Some(3));
+        assert_eq!(machine.handle_event(100), Some(3));
+        assert!(machine.is_finished());
+        assert_eq!(machine.handle_event(100), None);
+        assert_eq!(machine.handle_event(101), Some(2));
+        assert_eq!(machine.state(), &Player { action: Action::Idle, stamina: 0 });
+        assert_eq!((ENTERED.load(Ordering::Relaxed), EXITED.load(Ordering::Relaxed)), (1, 1));
+
+        machine.set_state(Player { action: Action::Walk, stamina: 1 });
+        assert_eq!(ENTERED.load(Ordering::Relaxed), 1);
+        assert_eq!(machine.select(100).map(|t| t.id), Some(3));
+
+        let mut duplicate = blueprint();
+        assert_eq!(
+            duplicate.add_observer(*machine.blueprint().observers().next().unwrap()),
+            Err(StateZenError::DuplicateObserver(1))
+        );
+    }
+
+    static ORDER: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn test_static_machine_callback_order_matches_runtime() {
+        let mut blueprint = StaticBlueprint::<Player>::new();
+        blueprint.add_transition(StaticTransition {
+            on_tran: Some(|from, to| {
+                assert_eq!((from.action, to.action), (Action::Idle, Action::Walk));
+                ORDER.lock().unwrap().push("tran");
+            }),
+            ..StaticTransition::new(1, 100, |p| p.action == Action::Idle, |p| Player { action: Action::Walk, ..p.clone() })
+        }).unwrap();
+        blueprint.add_observer(StaticObserver {
+            on_exit: Some(|_| ORDER.lock().unwrap().push("exit idle")),
+            ..StaticObserver::new(1, |p| p.action == Action::Idle)
+        }).unwrap();
+        blueprint.add_observer(StaticObserver {
+            on_enter: Some(|_| ORDER.lock().unwrap().push("enter low")),
+            ..StaticObserver::new(2, |p| p.action == Action::Walk)
+        }).unwrap();
+        blueprint.add_observer(StaticObserver {
+            on_enter: Some(|_| ORDER.lock().unwrap().push("enter high")),
+            priority: 5,
+            ..StaticObserver::new(3, |p| p.action == Action::Walk)
+        }).unwrap();
+
+        let mut machine = StaticMachine::new(blueprint, Player { action: Action::Idle, stamina: 1 });
+        assert_eq!(machine.handle_event(100), Some(1));
+        assert_eq!(*ORDER.lock().unwrap(), ["exit idle", "tran", "enter high", "enter low"]);
+    }
+}
+
+#[cfg(test)]
+mod state_bridge_tests {
+    use super::*;
+    use state_zen::StateZenError;
+    use state_zen::core::StateBridge;
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct Hud {
+        action: Option<Action>,
+        hunger: i32,
+        // 不参与桥接
+        frame: u64,
+    }
+
+    fn bridge() -> StateBridge<Hud> {
+        StateBridge::<Hud>::new()
+            .field(1, |h: &Hud| &h.action, |h, v| h.action = v)
+            .field(2, |h: &Hud| &h.hunger, |h, v| h.hunger = v)
+    }
+
+    #[test]
+    fn test_bridge_round_trip_and_errors() {
+        let hud = Hud { action: Some(Action::Walk), hunger: 3, frame: 99 };
+        let state = bridge().to_state(&hud);
+        assert_eq!(state.len(), 2);
+        assert_eq!(bridge().from_state(&state), Ok(Hud { frame: 0, ..hud.clone() }));
+
+        let mut blueprint = StateMachineBlueprint::new();
+        bridge().register_aspects(&mut blueprint).unwrap();
+        assert_eq!(blueprint.aspect(2).map(|a| a.value_type_id), Some(TypeId::of::<i32>()));
+        assert_eq!(blueprint.validate_state(&state), Ok(()));
+
+        let mut missing = state.clone();
+        missing.remove(&2);
+        assert_eq!(bridge().from_state(&missing), Err(StateZenError::MissingAspect(2)));
+        let mut mistyped = state.clone();
+        mistyped.insert(1, Arc::new(Action::Idle));
+        assert_eq!(bridge().from_state(&mistyped), Err(StateZenError::AspectTypeMismatch(1)));
+
+        let with_base = StateBridge::with_base(|| Hud { frame: 7, ..Hud::default() })
+            .field(2, |h: &Hud| &h.hunger, |h, v| h.hunger = v);
+        assert_eq!(with_base.from_state(&state).map(|h| (h.hunger, h.frame)), Ok((3, 7)));
+    }
+}
+
+#[cfg(test)]
+mod transition_toggle_tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_transitions_and_events_are_skipped() {
+        let (blueprint, state) = create_player_blueprint();
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+
+        runtime.set_transition_enabled(1, false);
+        assert!(!runtime.is_transition_enabled(1));
+        runtime.handle_event(100, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+
+        runtime.set_transition_enabled(1, true);
+        runtime.handle_event(100, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+
+        runtime.set_event_enabled(101, false);
+        assert!(!runtime.is_event_enabled(101));
+        runtime.handle_event(101, None);
+        runtime.handle_events(&[state_zen::core::EventInstance::new(101, None)]);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Walk));
+        assert!(runtime.is_transition_enabled(2));
+
+        runtime.set_event_enabled(101, true);
+        runtime.handle_event(101, None);
+        assert_eq!(get_action(&runtime.current_state), Some(Action::Idle));
+        assert_eq!(runtime.blueprint.transitions().count(), 2);
+    }
+}
+
+#[cfg(test)]
+mod active_when_observer_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_observer_only_tracked_inside_activation_region() {
+        let (mut blueprint, initial_state) = create_player_blueprint();
+        let mode = StateAspect::of::<bool>(2);
+        blueprint.add_aspect(mode).unwrap();
+        blueprint.add_event(EventDef::new(102)).unwrap();
+        blueprint.add_transition(Transition::new(
+            3,
+            102,
+            StateInRange::always(),
+            Transfer::new(|s| {
+                let mut next = s.clone();
+                let combat = next.get(&2).and_then(|v| v.downcast_ref::<bool>()).copied().unwrap_or(false);
+                next.insert(2, Arc::new(!combat));
+                next
+            }),
+        )).unwrap();
+
+        let evaluations = Arc::new(AtomicUsize::new(0));
+        let entered = Arc::new(AtomicUsize::new(0));
+        let exited = Arc::new(AtomicUsize::new(0));
+        let (counter, on_enter, on_exit) = (evaluations.clone(), entered.clone(), exited.clone());
+        blueprint.add_observer(StateObserver {
+            on_enter: Some(Arc::new(move |_| {
+                on_enter.fetch_add(1, Ordering::SeqCst);
+            })),
+            on_exit: Some(Arc::new(move |_| {
+                on_exit.fetch_add(1, Ordering::SeqCst);
+            })),
+            active_when: Some(StateInRange::aspect_eq(2, true)),
+            ..StateObserver::new(
+                2,
+                StateInRange::new(move |s| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    s.get(&1).and_then(|v| v.downcast_ref::<Action>()) == Some(&Action::Walk)
+                }),
+            )
+        }).unwrap();
+        let mut state = initial_state;
+        state.insert(2, Arc::new(false));
+        let mut runtime = RuntimeStateMachine::new(blueprint, state);
+
+        // 未激活：进入观察区域不触发回调，区域谓词也不求值
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+        assert_eq!(evaluations.load(Ordering::SeqCst), 0);
+        assert_eq!(entered.load(Ordering::SeqCst), 0);
+
+        // 激活时已在观察区域内视为进入，离开激活区域视为退出
+        runtime.handle_event(102, None);
+        assert_eq!(entered.load(Ordering::SeqCst), 1);
+        runtime.handle_event(102, None);
+        assert_eq!(exited.load(Ordering::SeqCst), 1);
+
+        runtime.handle_event(102, None);
+        runtime.handle_event(101, None);
+        assert_eq!((entered.load(Ordering::SeqCst), exited.load(Ordering::SeqCst)), (2, 2));
+        assert!(runtime.blueprint.observers_watching(2).any(|o| o.id == 2));
+    }
+}
@@ -0,0 +1,145 @@
+//! JSON 蓝图加载测试
+
+#![cfg(feature = "json")]
+
+use state_zen::loader::json::{self, LoadError};
+
+const PLAYER: &str = include_str!("../examples/blueprints/player.json");
+
+#[test]
+fn test_load_and_run_json_blueprint() {
+    let loaded = json::load_str(PLAYER).unwrap();
+    assert_eq!(loaded.template.blueprint.version(), 1);
+    assert_eq!(loaded.event_id("press_w"), Some(100));
+    // `set` 与 `add` 声明的转移带有写集合
+    let blueprint = &loaded.template.blueprint;
+    assert_eq!(blueprint.transition(1).unwrap().transfer.writes(), Some(&[1, 2][..]));
+    assert_eq!(blueprint.transition(2).unwrap().transfer.writes(), Some(&[1][..]));
+
+    let mut runtime = loaded.instantiate().unwrap();
+    runtime.handle_event(100, None);
+    assert_eq!(
+        loaded.registry.format_state(&runtime.current_state),
+        "action=\"Walk\", stamina=2"
+    );
+
+    // 体力耗尽后守卫不再满足
+    for _ in 0..2 {
+        runtime.handle_event(101, None);
+        runtime.handle_event(100, None);
+    }
+    runtime.handle_event(101, None);
+    runtime.handle_event(100, None);
+    assert_eq!(
+        loaded.registry.format_state(&runtime.current_state),
+        "action=\"Idle\", stamina=0"
+    );
+
+    let region = loaded.parse_region(r#"{"stamina": {"max": 0}}"#).unwrap();
+    assert!(region.contains(&runtime.current_state));
+}
+
+#[test]
+fn test_load_rejects_invalid_values() {
+    let source = PLAYER.replace(r#""set": { "action": "Walk" }"#, r#""set": { "action": "Run" }"#);
+    assert!(matches!(
+        json::load_str(&source),
+        Err(LoadError::InvalidValue { aspect, .. }) if aspect == "action"
+    ));
+
+    let source = PLAYER.replace(r#""event": "press_s""#, r#""event": "press_x""#);
+    assert_eq!(json::load_str(&source).err(), Some(LoadError::UnknownEvent("press_x".to_string())));
+}
+
+#[test]
+fn test_load_with_named_factories() {
+    use state_zen::loader::{FactoryError, Registry};
+    use state_zen::StateInRange;
+
+    const STAMINA: u64 = 2;
+    let mut factories = Registry::with_builtins();
+    factories.register_guard("stamina_at_most", |n: i32| StateInRange::aspect_in(STAMINA, ..=i64::from(n)));
+
+    let source = r#"{
+      "aspects": [{ "id": 2, "name": "stamina", "type": "int", "default": 3 }],
+      "events": [{ "id": 100, "name": "rest" }],
+      "transitions": [
+        { "id": 1, "event": "rest", "guard_call": { "name": "stamina_at_most", "args": [4] },
+          "transfer_call": { "name": "add_int", "args": [2, 1] } }
+      ],
+      "observers": [{ "id": 1, "region_call": { "name": "int_at_least", "args": [2, 5] } }]
+    }"#;
+    let loaded = json::load_str_with(source, &factories).unwrap();
+    let mut runtime = loaded.instantiate().unwrap();
+    for _ in 0..3 {
+        runtime.handle_event(100, None);
+    }
+    assert_eq!(loaded.registry.format_state(&runtime.current_state), "stamina=5");
+
+    assert_eq!(
+        json::load_str(source).err(),
+        Some(LoadError::Factory(FactoryError::Unknown("stamina_at_most".to_string())))
+    );
+    let bad = source.replace("[4]", r#"["four"]"#);
+    assert!(matches!(
+        json::load_str_with(&bad, &factories),
+        Err(LoadError::Factory(FactoryError::Argument { index: 0, .. }))
+    ));
+}
+
+#[cfg(feature = "expr")]
+#[test]
+fn test_load_guard_expressions() {
+    let source = PLAYER.replace(
+        r#""guard": { "action": "Idle", "stamina": { "min": 1 } }"#,
+        r#""guard_expr": "action == 'Idle' && stamina >= 2""#,
+    );
+    let loaded = json::load_str(&source).unwrap();
+    let mut runtime = loaded.instantiate().unwrap();
+    for _ in 0..3 {
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+    }
+    assert_eq!(
+        loaded.registry.format_state(&runtime.current_state),
+        "action=\"Idle\", stamina=1"
+    );
+
+    let source = PLAYER.replace(
+        r#""guard": { "action": "Idle", "stamina": { "min": 1 } }"#,
+        r#""guard_expr": "stamina >= ""#,
+    );
+    assert!(matches!(json::load_str(&source), Err(LoadError::Expr(_))));
+}
+
+#[test]
+fn test_load_transition_postcondition() {
+    let source = r#"{
+      "aspects": [{ "id": 2, "name": "stamina", "type": "int", "default": 3 }],
+      "events": [{ "id": 100, "name": "rest" }],
+      "transitions": [
+        { "id": 1, "event": "rest", "add": { "stamina": 1 }, "ensures": { "stamina": { "max": 4 } } }
+      ]
+    }"#;
+    let loaded = json::load_str(source).unwrap();
+    let ensures = loaded.template.blueprint.transition(1).unwrap().ensures.as_ref().unwrap();
+    assert_eq!(ensures.describe_with(&loaded.registry), "stamina <= 4");
+}
+
+#[test]
+fn test_add_overflow_keeps_state() {
+    let source = r#"{
+      "aspects": [{ "id": 2, "name": "stamina", "type": "int", "default": 9223372036854775806 }],
+      "events": [{ "id": 100, "name": "rest" }],
+      "transitions": [{ "id": 1, "event": "rest", "add": { "stamina": 2 } }]
+    }"#;
+    let loaded = json::load_str(source).unwrap();
+    let mut runtime = loaded.instantiate().unwrap();
+    runtime.handle_event(100, None);
+
+    assert_eq!(loaded.registry.format_state(&runtime.current_state), "stamina=9223372036854775806");
+    assert_eq!(
+        loaded.last_error(),
+        Some(LoadError::Overflow { aspect: "stamina".to_string(), delta: 2 })
+    );
+}
@@ -0,0 +1,145 @@
+//! Lua 脚本测试
+
+#![cfg(feature = "lua")]
+
+use std::sync::Arc;
+use state_zen::State;
+use state_zen::core::AspectRegistry;
+use state_zen::loader::json::{self, LoadError};
+use state_zen::loader::lua::LuaScripts;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mood {
+    Calm,
+}
+
+fn registry() -> AspectRegistry {
+    let mut registry = AspectRegistry::new();
+    registry
+        .register::<i64>(1, "hp")
+        .register::<f64>(2, "speed")
+        .register::<String>(3, "mode")
+        .register::<Mood>(4, "mood");
+    registry
+}
+
+fn state() -> State {
+    let mut state = State::new();
+    state.insert(1, Arc::new(5i64));
+    state.insert(2, Arc::new(1.5f64));
+    state.insert(3, Arc::new("idle".to_string()));
+    state.insert(4, Arc::new(Mood::Calm));
+    state
+}
+
+#[test]
+fn test_lua_guards_and_transfers_reference_aspects_by_name() {
+    let scripts = LuaScripts::new(&registry());
+    let guard = scripts.guard("hp > 3 and mode == 'idle' and mood == 'Calm'").unwrap();
+    assert!(guard.contains(&state()));
+
+    let transfer = scripts
+        .transfer("hp = hp - 2; speed = speed * math.floor(2.5); mode = 'run'; mood = mood")
+        .unwrap();
+    let next = transfer.apply(&state());
+    assert_eq!(next.get(&1).and_then(|v| v.downcast_ref::<i64>()), Some(&3));
+    assert_eq!(next.get(&2).and_then(|v| v.downcast_ref::<f64>()), Some(&3.0));
+    assert_eq!(next.get(&3).and_then(|v| v.downcast_ref::<String>()).map(String::as_str), Some("run"));
+    assert_eq!(next.get(&4).and_then(|v| v.downcast_ref::<Mood>()), Some(&Mood::Calm));
+    assert!(!guard.contains(&next));
+
+    let remove = scripts.transfer("speed = nil").unwrap();
+    assert!(remove.apply(&state()).get(&2).is_none());
+}
+
+#[test]
+fn test_lua_runtime_errors_are_contained() {
+    let scripts = LuaScripts::new(&registry());
+    assert!(scripts.guard("hp >").is_err());
+    assert!(scripts.last_error().is_none());
+
+    let guard = scripts.guard("missing.field > 1").unwrap();
+    assert!(!guard.contains(&state()));
+    assert!(scripts.last_error().is_some());
+
+    // 非标量方面不能由脚本改写
+    let transfer = scripts.transfer("hp = 0; mood = 'Angry'").unwrap();
+    let next = transfer.apply(&state());
+    assert_eq!(next.get(&1).and_then(|v| v.downcast_ref::<i64>()), Some(&5));
+    assert!(scripts.last_error().unwrap().contains("mood"));
+}
+
+#[test]
+fn test_lua_maps_every_numeric_width() {
+    let mut registry = AspectRegistry::new();
+    registry.register::<i32>(1, "hp").register::<u8>(2, "level").register::<f32>(3, "speed");
+    let scripts = LuaScripts::new(&registry);
+    let mut state = State::new();
+    state.insert(1, Arc::new(5i32));
+    state.insert(2, Arc::new(200u8));
+    state.insert(3, Arc::new(1.5f32));
+
+    assert!(scripts.guard("hp > 3 and level == 200 and speed < 2").unwrap().contains(&state));
+    assert!(scripts.last_error().is_none());
+
+    let next = scripts.transfer("hp = hp - 10; level = level + 1; speed = speed * 2").unwrap().apply(&state);
+    assert_eq!(next.get(&1).and_then(|v| v.downcast_ref::<i32>()), Some(&-5));
+    assert_eq!(next.get(&2).and_then(|v| v.downcast_ref::<u8>()), Some(&201));
+    assert_eq!(next.get(&3).and_then(|v| v.downcast_ref::<f32>()), Some(&3.0));
+
+    // 超出类型范围的写入被拒绝，状态保持不变
+    let overflow = scripts.transfer("level = 256").unwrap().apply(&state);
+    assert_eq!(overflow.get(&2).and_then(|v| v.downcast_ref::<u8>()), Some(&200));
+    assert!(scripts.last_error().unwrap().contains("level"));
+}
+
+#[test]
+fn test_lua_snippets_are_sandboxed() {
+    let scripts = LuaScripts::new(&registry()).with_instruction_limit(100_000);
+    assert!(scripts.guard("os == nil and io == nil and require == nil and dofile == nil").unwrap().contains(&state()));
+    assert!(scripts.guard("string.len(mode) == 4 and table.concat({1, 2}) == '12'").unwrap().contains(&state()));
+
+    // 片段单独编译，不能闭合外层代码块注入语句
+    assert!(scripts.guard("true) end os.exit() --").is_err());
+    assert!(scripts.guard("true; hp = 0").is_err());
+    assert!(scripts.transfer("end hp = 0 --").is_err());
+
+    let spin = scripts.guard("(function() while true do end end)()").unwrap();
+    assert!(!spin.contains(&state()));
+    assert!(scripts.last_error().unwrap().contains("指令预算"));
+
+    let transfer = scripts.transfer("while true do end").unwrap();
+    assert_eq!(transfer.apply(&state()).get(&1).and_then(|v| v.downcast_ref::<i64>()), Some(&5));
+
+    // 预算按次重置，之后的片段正常执行
+    assert!(scripts.guard("hp == 5").unwrap().contains(&state()));
+}
+
+#[test]
+fn test_load_lua_snippets_from_json() {
+    let source = include_str!("../examples/blueprints/player.json")
+        .replace(
+            r#""guard": { "action": "Idle", "stamina": { "min": 1 } },
+      "set": { "action": "Walk" }, "add": { "stamina": -1 }"#,
+            r#""guard_lua": "action == 'Idle' and stamina >= 2",
+      "transfer_lua": "action = 'Walk'; stamina = stamina - 2",
+      "on_tran_lua": "assert(prev.stamina - stamina == 2)""#,
+        )
+        .replace(
+            r#""region": { "action": "Walk" }"#,
+            r#""region_lua": "action == 'Walk'", "on_enter_lua": "_G.entered = (_G.entered or 0) + stamina""#,
+        );
+    let loaded = json::load_str(&source).unwrap();
+    let mut runtime = loaded.instantiate().unwrap();
+    for _ in 0..2 {
+        runtime.handle_event(100, None);
+        runtime.handle_event(101, None);
+    }
+    assert_eq!(loaded.registry.format_state(&runtime.current_state), "action=\"Idle\", stamina=1");
+    // 回调写入全局表的值对其他片段可见
+    assert!(loaded.scripts.guard("_G.entered == 1").unwrap().contains(&runtime.current_state));
+    assert_eq!(loaded.scripts.last_error(), None);
+
+    let broken = source.replace("stamina >= 2", "stamina >=");
+    assert!(matches!(json::load_str(&broken), Err(LoadError::Lua(_))));
+}
@@ -0,0 +1,143 @@
+//! 蓝图结构清单测试
+
+#![cfg(feature = "json")]
+
+use std::sync::Arc;
+use std::time::Duration;
+use state_zen::core::AspectRegistry;
+use state_zen::core::event::EventTemplate;
+use state_zen::loader::json::LoadError;
+use state_zen::loader::manifest::{BlueprintManifest, GuardNode, TransferNode, ManifestValue};
+use state_zen::loader::{ArgValue, Registry};
+use state_zen::{EventDef, RuntimeStateMachine, State, StateAspect, StateInRange, StateMachineBlueprint, StateObserver, Transfer, Transition};
+
+const HP: u64 = 1;
+const MODE: u64 = 2;
+const STANCE: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stance {
+    Low,
+}
+
+fn transition(id: u64, event_id: u64, guard: StateInRange, transfer: Transfer) -> Transition {
+    Transition::new(id, event_id, guard, transfer)
+}
+
+fn names() -> AspectRegistry {
+    let mut names = AspectRegistry::new();
+    names.register::<i64>(HP, "hp").register::<String>(MODE, "mode").register::<Stance>(STANCE, "stance");
+    names
+}
+
+fn blueprint(registry: &Registry) -> StateMachineBlueprint {
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.set_version(3);
+    blueprint.add_aspect(StateAspect::of::<i64>(HP)).unwrap();
+    blueprint.add_aspect(StateAspect::of::<String>(MODE)).unwrap();
+    blueprint.add_aspect(StateAspect::of::<Stance>(STANCE)).unwrap();
+    blueprint.add_event(EventDef::new(10)).unwrap();
+    blueprint.add_event(EventDef::of::<i32>(11)).unwrap();
+    blueprint.add_transition(Transition {
+        priority: 2,
+        tag: Some("combat".to_string()),
+        emits: vec![EventTemplate::new(11)],
+        min_dwell: Some(Duration::from_millis(250)),
+        ensures: Some(StateInRange::aspect_in(HP, 0i64..)),
+        ..transition(
+            1,
+            10,
+            StateInRange::aspect_in(HP, 1i64..=5).and(StateInRange::aspect_eq(MODE, "idle".to_string())),
+            Transfer::set(MODE, "run".to_string()).then(Transfer::sub(HP, 1i64)),
+        )
+    }).unwrap();
+    blueprint.add_transition(transition(
+        2,
+        11,
+        registry.guard("int_at_most", &[ArgValue::Int(HP as i64), ArgValue::Int(3)]).unwrap().not(),
+        registry.transfer("add_int", &[ArgValue::Int(HP as i64), ArgValue::Int(2)]).unwrap(),
+    )).unwrap();
+    blueprint.add_observer(StateObserver {
+        priority: 1,
+        active_when: Some(StateInRange::aspect_in(HP, 1i64..)),
+        ..StateObserver::new(1, StateInRange::aspect_eq(MODE, "run".to_string()))
+    }).unwrap();
+    blueprint.set_final_region(StateInRange::aspect_eq(HP, 0i64));
+    blueprint.protect_aspect(MODE);
+    blueprint
+}
+
+#[test]
+fn test_manifest_round_trips_through_json() {
+    let mut registry = Registry::with_builtins();
+    registry.register_type::<Stance>("Stance");
+    let original = blueprint(&registry);
+    let manifest = original.to_manifest_with(&names());
+
+    assert_eq!(manifest.aspects[2].type_name, "Stance");
+    assert_eq!(manifest.events[1].payload.as_deref(), Some("i32"));
+    assert_eq!(
+        manifest.transitions[0].transfer,
+        TransferNode::Then(
+            Box::new(TransferNode::Set { aspect: MODE, value: ManifestValue::String("run".to_string()) }),
+            Box::new(TransferNode::Sub { aspect: HP, delta: ManifestValue::I64(1) }),
+        )
+    );
+    assert_eq!(
+        manifest.transitions[1].guard,
+        GuardNode::Not(Box::new(GuardNode::Call {
+            name: "int_at_most".to_string(),
+            args: vec![ArgValue::Int(1), ArgValue::Int(3)],
+        }))
+    );
+
+    let json = manifest.to_json();
+    assert!(json.contains("\"int_at_most\""));
+    let parsed = BlueprintManifest::from_json(&json).unwrap();
+    assert_eq!(parsed, manifest);
+
+    let mut restored = StateMachineBlueprint::from_manifest(&parsed, &registry).unwrap();
+    assert_eq!(restored.to_manifest_with(&names()), manifest);
+    assert!(original.diff(&restored).is_empty());
+    assert_eq!(restored.protected_aspects().collect::<Vec<_>>(), vec![MODE]);
+
+    // 还原的蓝图行为一致
+    let mut state = State::new();
+    state.insert(HP, Arc::new(3i64));
+    state.insert(MODE, Arc::new("idle".to_string()));
+    state.insert(STANCE, Arc::new(Stance::Low));
+    restored.enable_tags(&["combat"]);
+    let mut runtime = RuntimeStateMachine::new(restored, state);
+    runtime.tick(Duration::from_millis(300));
+    runtime.handle_event(10, None);
+    runtime.handle_event(11, None);
+    let hp = |r: &RuntimeStateMachine| *r.current_state.get(&HP).unwrap().downcast_ref::<i64>().unwrap();
+    assert_eq!(hp(&runtime), 2);
+    let mut state = runtime.current_state.clone();
+    state.insert(HP, Arc::new(4i64));
+    runtime.set_state(state);
+    runtime.handle_event(11, None);
+    assert_eq!(hp(&runtime), 6);
+}
+
+#[test]
+fn test_closures_and_unknown_types_cannot_be_restored() {
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.add_aspect(StateAspect::of::<Stance>(STANCE)).unwrap();
+    blueprint.add_event(EventDef::new(10)).unwrap();
+    blueprint.add_transition(transition(1, 10, StateInRange::aspect_eq(STANCE, Stance::Low), Transfer::new(|s| s.clone()))).unwrap();
+
+    let manifest = blueprint.to_manifest_with(&names());
+    assert_eq!(manifest.transitions[0].guard, GuardNode::Opaque("stance == Low".to_string()));
+    assert_eq!(manifest.transitions[0].transfer, TransferNode::Opaque("<closure>".to_string()));
+
+    let mut registry = Registry::new();
+    assert_eq!(
+        StateMachineBlueprint::from_manifest(&manifest, &registry).err(),
+        Some(LoadError::UnknownType("Stance".to_string()))
+    );
+    registry.register_type::<Stance>("Stance");
+    let err = StateMachineBlueprint::from_manifest(&manifest, &registry).err().unwrap();
+    assert_eq!(err, LoadError::Opaque("转换 1：stance == Low".to_string()));
+    assert_eq!(err.to_string(), "无法还原闭包构造的部分：转换 1：stance == Low");
+}
@@ -0,0 +1,57 @@
+//! 属性测试策略测试
+
+#![cfg(feature = "proptest")]
+
+use proptest::prelude::*;
+use state_zen::core::{EventDef, StateAspect, Transfer, Transition};
+use state_zen::testing::{blueprint_events, StateDomains};
+use state_zen::{RuntimeStateMachine, StateInRange, StateMachineBlueprint};
+
+const STAMINA: u64 = 1;
+const RESTING: u64 = 2;
+
+fn blueprint() -> StateMachineBlueprint {
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.add_aspect(StateAspect::of::<i32>(STAMINA).with_validator(|v: &i32| (0..=5).contains(v))).unwrap();
+    blueprint.add_aspect(StateAspect::of::<bool>(RESTING)).unwrap();
+    for id in [10, 11] {
+        blueprint.add_event(EventDef::new(id)).unwrap();
+    }
+    let transitions = [
+        (1, 10, StateInRange::aspect_in(STAMINA, 1i32..), Transfer::sub(STAMINA, 1i32)),
+        (2, 11, StateInRange::aspect_in(STAMINA, ..5i32), Transfer::add(STAMINA, 1i32).then(Transfer::set(RESTING, true))),
+    ];
+    for (id, event_id, guard, transfer) in transitions {
+        blueprint.add_transition(Transition::new(id, event_id, guard, transfer)).unwrap();
+    }
+    blueprint
+}
+
+fn domains() -> StateDomains {
+    StateDomains::new().with(STAMINA, 0..=5i32).with(RESTING, [false, true])
+}
+
+proptest! {
+    #[test]
+    fn stamina_stays_in_bounds(start in domains().strategy(), events in blueprint_events(&blueprint(), 0..32)) {
+        let mut runtime = RuntimeStateMachine::new(blueprint(), start.state);
+        for event in events {
+            runtime.handle_event(event, None);
+        }
+        prop_assert!(runtime.blueprint.validate_state(&runtime.current_state).is_ok());
+        prop_assert!(runtime.take_errors().is_empty());
+    }
+}
+
+#[test]
+fn test_generated_state_debug_shows_domain_indices() {
+    use proptest::strategy::ValueTree;
+    use proptest::test_runner::TestRunner;
+
+    let mut runner = TestRunner::deterministic();
+    let tree = domains().strategy().new_tree(&mut runner).unwrap();
+    let generated = tree.current();
+    assert_eq!(generated.state.len(), 2);
+    let debug = format!("{generated:?}");
+    assert!(debug.starts_with("{\"#1\": \"domain["), "{debug}");
+}
@@ -0,0 +1,130 @@
+//! WebSocket 远程调试测试
+
+#![cfg(feature = "remote-debug")]
+
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use state_zen::loader::json;
+use state_zen::monitor::remote::{RemoteCommand, RemoteDebugger, RemoteMessage};
+use state_zen::RuntimeStateMachine;
+use tungstenite::{Message, WebSocket};
+
+const PLAYER: &str = include_str!("../examples/blueprints/player.json");
+
+fn wait_until(mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "等待超时");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn send(client: &mut WebSocket<TcpStream>, command: &RemoteCommand) {
+    client.send(Message::Text(serde_json::to_string(command).unwrap())).unwrap();
+}
+
+fn receive(client: &mut WebSocket<TcpStream>) -> RemoteMessage {
+    loop {
+        if let Message::Text(text) = client.read().unwrap() {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+fn poll(debugger: &RemoteDebugger, runtime: &mut RuntimeStateMachine) {
+    wait_until(|| debugger.poll(runtime) > 0);
+}
+
+#[test]
+fn test_remote_debugger_streams_trace_and_runs_commands() {
+    let loaded = json::load_str(PLAYER).unwrap();
+    let mut runtime = loaded.instantiate().unwrap();
+    let debugger = RemoteDebugger::bind("127.0.0.1:0", loaded.registry.clone()).unwrap();
+    runtime.add_tracer(debugger.clone());
+
+    let stream = TcpStream::connect(debugger.local_addr()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let (mut client, _) = tungstenite::client(format!("ws://{}", debugger.local_addr()), stream).unwrap();
+    wait_until(|| debugger.client_count() == 1);
+
+    send(&mut client, &RemoteCommand::Dispatch { event: 100 });
+    poll(&debugger, &mut runtime);
+    assert_eq!(receive(&mut client), RemoteMessage::Event { event: 100, selected: Some(1) });
+    assert_eq!(receive(&mut client), RemoteMessage::Transition { transition: 1 });
+    let mut message = receive(&mut client);
+    while !matches!(message, RemoteMessage::State { .. }) {
+        message = receive(&mut client);
+    }
+    let RemoteMessage::State { state, paused } = message else { unreachable!() };
+    assert_eq!(state["action"], "\"Walk\"");
+    assert_eq!(state["stamina"], "2");
+    assert!(!paused);
+
+    send(&mut client, &RemoteCommand::Pause);
+    poll(&debugger, &mut runtime);
+    assert!(runtime.is_paused());
+    assert!(matches!(receive(&mut client), RemoteMessage::State { paused: true, .. }));
+
+    // 暂停期间分发的事件由运行时缓冲，恢复后执行
+    send(&mut client, &RemoteCommand::Dispatch { event: 101 });
+    poll(&debugger, &mut runtime);
+    assert_eq!(runtime.buffered_events(), 1);
+    assert!(matches!(receive(&mut client), RemoteMessage::State { paused: true, .. }));
+    send(&mut client, &RemoteCommand::Resume);
+    poll(&debugger, &mut runtime);
+    assert!(!runtime.is_paused());
+    assert_eq!(runtime.buffered_events(), 0);
+    let mut message = receive(&mut client);
+    while !matches!(message, RemoteMessage::State { .. }) {
+        message = receive(&mut client);
+    }
+    assert!(matches!(message, RemoteMessage::State { paused: false, .. }));
+    send(&mut client, &RemoteCommand::Pause);
+    poll(&debugger, &mut runtime);
+    assert!(matches!(receive(&mut client), RemoteMessage::State { paused: true, .. }));
+
+    send(&mut client, &RemoteCommand::Dispatch { event: 999 });
+    poll(&debugger, &mut runtime);
+    assert!(matches!(receive(&mut client), RemoteMessage::Error { .. }));
+
+    client.send(Message::Text("{\"command\": \"jump\"}".to_string())).unwrap();
+    assert!(matches!(receive(&mut client), RemoteMessage::Error { .. }));
+}
+
+fn connect(debugger: &RemoteDebugger) -> WebSocket<TcpStream> {
+    let stream = TcpStream::connect(debugger.local_addr()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    tungstenite::client(format!("ws://{}", debugger.local_addr()), stream).unwrap().0
+}
+
+#[test]
+fn test_remote_debugger_replies_to_requester_and_shuts_down() {
+    let loaded = json::load_str(PLAYER).unwrap();
+    let mut runtime = loaded.instantiate().unwrap();
+    let debugger = RemoteDebugger::bind("127.0.0.1:0", loaded.registry.clone()).unwrap();
+    let addr = debugger.local_addr();
+    let mut asking = connect(&debugger);
+    let mut other = connect(&debugger);
+    wait_until(|| debugger.client_count() == 2);
+
+    send(&mut asking, &RemoteCommand::Dump);
+    poll(&debugger, &mut runtime);
+    assert!(matches!(receive(&mut asking), RemoteMessage::State { paused: false, .. }));
+    send(&mut asking, &RemoteCommand::Dispatch { event: 999 });
+    poll(&debugger, &mut runtime);
+    assert!(matches!(receive(&mut asking), RemoteMessage::Error { .. }));
+
+    // 应答只发给发出命令的客户端
+    other.get_ref().set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+    assert!(other.read().is_err());
+
+    // 断开的客户端立即从计数中移除
+    drop(other);
+    wait_until(|| debugger.client_count() == 1);
+
+    // 停止后连接被关闭，端口被释放
+    debugger.shutdown();
+    assert_eq!(debugger.client_count(), 0);
+    assert!(!matches!(asking.read(), Ok(Message::Text(_))));
+    drop(std::net::TcpListener::bind(addr).unwrap());
+}
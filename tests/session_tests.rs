@@ -0,0 +1,72 @@
+//! 调试会话文件测试
+
+#![cfg(feature = "json")]
+
+use std::sync::Arc;
+use state_zen::core::{AspectRegistry, History, Tracer};
+use state_zen::loader::json::{self, LoadError};
+use state_zen::loader::session::{Session, SessionValue};
+use state_zen::loader::manifest::ManifestValue;
+use state_zen::{State, StateAspect, StateMachineBlueprint};
+
+const PLAYER: &str = include_str!("../examples/blueprints/player.json");
+
+#[test]
+fn test_session_round_trips_history_and_manifest() {
+    let loaded = json::load_str(PLAYER).unwrap();
+    let mut runtime = loaded.instantiate().unwrap();
+    let history = History::new(None);
+    runtime.add_tracer(history.clone());
+    runtime.handle_event(100, None);
+    runtime.handle_event(101, None);
+
+    let session = Session::capture(&runtime.blueprint, &loaded.registry, &history)
+        .with_event_names(loaded.events.iter().cloned());
+    assert_eq!(session.entries.len(), 2);
+    assert_eq!(session.event_name(100), "press_w");
+    assert_eq!(session.entries[0].transition, Some(1));
+    assert_eq!(
+        session.format_state(&session.entries[0].after),
+        loaded.registry.format_state(&history.entries()[0].after)
+    );
+
+    let path = std::env::temp_dir().join(format!("state-zen-{}.szsession", std::process::id()));
+    session.save(&path).unwrap();
+    let reopened = Session::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(reopened, session);
+    assert_eq!(reopened.manifest, runtime.blueprint.to_manifest_with(&loaded.registry));
+
+    let restored = Session::restore_state(&reopened.entries[1].after);
+    assert_eq!(loaded.registry.format_state(&restored), loaded.registry.format_state(&runtime.current_state));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mood {
+    Calm,
+}
+
+#[test]
+fn test_session_records_non_scalar_values_as_text() {
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.add_aspect(StateAspect::of::<Mood>(1)).unwrap();
+    blueprint.add_aspect(StateAspect::of::<u32>(2)).unwrap();
+    let mut names = AspectRegistry::new();
+    names.register::<Mood>(1, "mood");
+    let mut state = State::new();
+    state.insert(1, Arc::new(Mood::Calm));
+    state.insert(2, Arc::new(7u32));
+    let history = History::new(None);
+    history.on_commit(&State::new(), &state);
+
+    let mut session = Session::capture(&blueprint, &names, &history);
+    let after = &session.entries[0].after;
+    assert_eq!(after[&1], SessionValue::Text("Calm".to_string()));
+    assert_eq!(after[&2], SessionValue::Value(ManifestValue::U32(7)));
+    assert_eq!(session.format_state(after), "mood=Calm, #2=7");
+    assert_eq!(Session::restore_state(after).len(), 1);
+    assert_eq!(session.entries[0].event_id, None);
+
+    session.format = 99;
+    assert!(matches!(Session::from_json(&session.to_json()), Err(LoadError::Parse(_))));
+}
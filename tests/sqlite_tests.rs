@@ -0,0 +1,85 @@
+//! SQLite 历史存储测试
+
+#![cfg(feature = "sqlite")]
+
+use std::sync::Arc;
+use state_zen::core::{EventDef, SqliteHistoryStore, StateAspect, StateCodec, StateStore, Transfer, Transition};
+use state_zen::{RuntimeStateMachine, State, StateInRange, StateMachineBlueprint};
+
+fn counter_machine() -> RuntimeStateMachine {
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.add_aspect(StateAspect::of::<i32>(1)).unwrap();
+    blueprint.add_event(EventDef::new(10)).unwrap();
+    blueprint.add_transition(Transition::new(1, 10, StateInRange::always(), Transfer::add(1, 1i32))).unwrap();
+    let mut state = State::new();
+    state.insert(1, Arc::new(0i32));
+    RuntimeStateMachine::new(blueprint, state)
+}
+
+fn codec() -> StateCodec {
+    let mut codec = StateCodec::new();
+    codec.register::<i32>(1);
+    codec
+}
+
+fn count(state: &State) -> i32 {
+    *state.get(&1).unwrap().downcast_ref::<i32>().unwrap()
+}
+
+#[test]
+fn test_sqlite_records_and_replays_transforms() {
+    let store = Arc::new(SqliteHistoryStore::in_memory(codec()).unwrap());
+    let recorder = store.recorder(7);
+    let mut runtime = counter_machine();
+    runtime.add_tracer(recorder.clone());
+    for _ in 0..3 {
+        runtime.handle_event(10, None);
+    }
+    assert!(recorder.take_errors().is_empty());
+
+    // 序号 0 为首次记录前的完整状态，之后每次提交一行
+    let entries = store.entries(7).unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0].event_id, None);
+    assert_eq!(entries[0].diff, vec![(1, Some("0".to_string()))]);
+    assert_eq!(entries[2].event_id, Some(10));
+    assert_eq!(entries[2].transition, Some(1));
+    assert_eq!(entries[2].diff, vec![(1, Some("2".to_string()))]);
+
+    assert_eq!(count(&store.state_at(7, 1).unwrap().unwrap()), 1);
+    assert_eq!(count(&store.load(7).unwrap().unwrap()), 3);
+    assert!(store.state_at(7, 9).unwrap().is_none());
+    assert!(store.load(8).unwrap().is_none());
+}
+
+#[test]
+fn test_sqlite_machines_in_region() {
+    let store = SqliteHistoryStore::in_memory(codec()).unwrap();
+    for (id, value) in [(1, 5i32), (2, 12), (3, 20)] {
+        let mut state = State::new();
+        state.insert(1, Arc::new(value));
+        store.save(id, &state).unwrap();
+    }
+    let mut state = State::new();
+    state.insert(1, Arc::new(3i32));
+    store.save(3, &state).unwrap();
+
+    let low = StateInRange::new(|s| s.get(&1).and_then(|v| v.downcast_ref::<i32>()).is_some_and(|v| *v < 10));
+    assert_eq!(store.machines_in_region(&low).unwrap(), vec![1, 3]);
+    assert_eq!(store.machines().unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_sqlite_persists_to_file() {
+    let path = std::env::temp_dir().join(format!("state-zen-history-{}.db", std::process::id()));
+    {
+        let store = SqliteHistoryStore::open(&path, codec()).unwrap();
+        let mut state = State::new();
+        state.insert(1, Arc::new(42i32));
+        store.save(1, &state).unwrap();
+    }
+    let store = SqliteHistoryStore::open(&path, codec()).unwrap();
+    assert_eq!(count(&store.load(1).unwrap().unwrap()), 42);
+    drop(store);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,28 @@
+//! 以流驱动状态机的测试
+
+#![cfg(feature = "stream")]
+
+use std::sync::Arc;
+use futures::{StreamExt, executor, stream};
+use state_zen::core::{EventDef, EventInstance, StateAspect, Transfer, Transition};
+use state_zen::{RuntimeStateMachine, State, StateInRange, StateMachineBlueprint};
+
+#[test]
+fn test_drive_stream_yields_snapshots() {
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.add_aspect(StateAspect::of::<i32>(1)).unwrap();
+    blueprint.add_event(EventDef::new(10)).unwrap();
+    blueprint.add_transition(Transition::new(1, 10, StateInRange::always(), Transfer::add(1, 1i32))).unwrap();
+    let mut state = State::new();
+    state.insert(1, Arc::new(0i32));
+    let mut runtime = RuntimeStateMachine::new(blueprint, state);
+
+    let events = stream::iter((0..3).map(|_| EventInstance::new(10, None)));
+    let counts: Vec<i32> = executor::block_on(
+        runtime
+            .drive_stream(events)
+            .map(|snapshot| *snapshot.state.get(&1).unwrap().downcast_ref::<i32>().unwrap())
+            .collect(),
+    );
+    assert_eq!(counts, vec![1, 2, 3]);
+}
@@ -0,0 +1,60 @@
+//! 状态机任务测试
+
+#![cfg(feature = "tokio")]
+
+use std::sync::Arc;
+use state_zen::core::{
+    spawn_machine_task, EventBuffer, EventDef, EventInstance, OverflowPolicy, SendError, StateAspect, Transfer,
+    Transition,
+};
+use state_zen::{RuntimeStateMachine, State, StateInRange, StateMachineBlueprint};
+
+fn counter_machine() -> RuntimeStateMachine {
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.add_aspect(StateAspect::of::<i32>(1)).unwrap();
+    blueprint.add_event(EventDef::new(10)).unwrap();
+    blueprint.add_transition(Transition::new(1, 10, StateInRange::always(), Transfer::add(1, 1i32))).unwrap();
+    let mut state = State::new();
+    state.insert(1, Arc::new(0i32));
+    RuntimeStateMachine::new(blueprint, state)
+}
+
+fn count(state: &State) -> i32 {
+    *state.get(&1).unwrap().downcast_ref::<i32>().unwrap()
+}
+
+#[tokio::test]
+async fn test_machine_task_processes_mailbox() {
+    let (sender, mut states, handle) = spawn_machine_task(counter_machine(), EventBuffer::default());
+    assert_eq!(count(&states.current()), 0);
+
+    sender.send(10, None).unwrap();
+    assert_eq!(count(&states.changed().await.unwrap()), 1);
+
+    let other = sender.clone();
+    other.send(10, None).unwrap();
+    sender.send(10, None).unwrap();
+    drop(other);
+    drop(sender);
+
+    // 发送端全部丢弃后任务处理完剩余事件并交还状态机
+    let runtime = handle.await.unwrap();
+    assert_eq!(count(&runtime.current_state), 3);
+    assert_eq!(count(&states.current()), 3);
+}
+
+#[tokio::test]
+async fn test_bounded_mailbox_blocks_or_rejects_when_full() {
+    // 单线程运行时：任务在发送端让出之前不会取走事件
+    let (sender, _states, handle) = spawn_machine_task(counter_machine(), EventBuffer::new(Some(1), OverflowPolicy::Block));
+
+    sender.send(10, None).unwrap();
+    // 已满：同步发送无法等待，`Block` 事件被拒绝而不是超出容量
+    assert_eq!(sender.send(10, None), Err(SendError::Overflow(10)));
+
+    // 异步发送等到任务取走事件腾出空位
+    sender.send_async(EventInstance::new(10, None)).await.unwrap();
+    drop(sender);
+    let runtime = handle.await.unwrap();
+    assert_eq!(count(&runtime.current_state), 2);
+}
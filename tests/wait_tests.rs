@@ -0,0 +1,97 @@
+//! 等待进入区域的 Future 测试
+
+#![cfg(feature = "async")]
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use state_zen::core::{ContinuousTransfer, EventDef, StateAspect, Transfer, Transition};
+use state_zen::{RuntimeStateMachine, State, StateInRange, StateMachineBlueprint};
+
+fn counter(state: &State) -> i32 {
+    *state.get(&1).unwrap().downcast_ref::<i32>().unwrap()
+}
+
+/// 事件 10 使计数加一，事件 11 使计数归零
+fn counter_machine() -> RuntimeStateMachine {
+    let mut blueprint = StateMachineBlueprint::new();
+    blueprint.add_aspect(StateAspect::of::<i32>(1)).unwrap();
+    for (id, event_id, transfer) in [(1, 10, Transfer::add(1, 1i32)), (2, 11, Transfer::set(1, 0i32))] {
+        blueprint.add_event(EventDef::new(event_id)).unwrap();
+        blueprint.add_transition(Transition::new(id, event_id, StateInRange::always(), transfer)).unwrap();
+    }
+    let mut state = State::new();
+    state.insert(1, Arc::new(0i32));
+    RuntimeStateMachine::new(blueprint, state)
+}
+
+fn ready() -> StateInRange {
+    StateInRange::new(|s| counter(s) >= 2).with_reads([1])
+}
+
+#[test]
+fn test_wait_for_resolves_on_region_entry() {
+    let mut runtime = counter_machine();
+    let mut entry = pin!(runtime.wait_for(ready()));
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(entry.as_mut().poll(&mut cx).is_pending());
+
+    runtime.handle_event(10, None);
+    assert!(entry.as_mut().poll(&mut cx).is_pending());
+    runtime.handle_event(10, None);
+    match entry.as_mut().poll(&mut cx) {
+        Poll::Ready(snapshot) => {
+            assert_eq!(snapshot.event_id, Some(10));
+            assert_eq!(counter(&snapshot.state), 2);
+        }
+        Poll::Pending => panic!("进入区域后 Future 应完成"),
+    }
+}
+
+#[test]
+fn test_wait_for_requires_fresh_entry() {
+    let mut runtime = counter_machine();
+    runtime.handle_event(10, None);
+    runtime.handle_event(10, None);
+
+    // 已在区域内：等待离开后的下一次进入
+    let mut entry = pin!(runtime.wait_for(ready()));
+    let mut cx = Context::from_waker(Waker::noop());
+    runtime.handle_event(10, None);
+    assert!(entry.as_mut().poll(&mut cx).is_pending());
+
+    // 由 `tick` 推进的连续转换进入区域，快照不带事件
+    runtime.handle_event(11, None);
+    runtime.blueprint.add_continuous_transfer(ContinuousTransfer::new(StateInRange::always(), |s, _| {
+        let mut next = s.clone();
+        next.insert(1, Arc::new(counter(s) + 2));
+        next
+    }));
+    runtime.tick(Duration::from_millis(16));
+    match entry.as_mut().poll(&mut cx) {
+        Poll::Ready(snapshot) => {
+            assert_eq!(snapshot.event_id, None);
+            assert_eq!(counter(&snapshot.state), 2);
+        }
+        Poll::Pending => panic!("进入区域后 Future 应完成"),
+    }
+}
+
+#[tokio::test]
+async fn test_wait_for_wakes_task_across_threads() {
+    let mut runtime = counter_machine();
+    let entry = runtime.wait_for(ready());
+    let worker = std::thread::spawn(move || {
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(5));
+            runtime.handle_event(10, None);
+        }
+        runtime
+    });
+
+    let snapshot = entry.await;
+    assert_eq!(counter(&snapshot.state), 2);
+    assert_eq!(counter(&worker.join().unwrap().current_state), 3);
+}